@@ -7,4 +7,4 @@
 
 mod settings;
 
-pub use settings::Config;
+pub use settings::{Config, DefaultCommand, DEFAULT_BLOCKING_THREADS};
@@ -1,20 +1,77 @@
 //! Configuration settings and validation.
 
+use crate::embeddings::{EmbeddingTemplates, ExecutionProvider, LongChunkStrategy};
+use crate::storage::DistanceMetric;
+use crate::watcher::OverflowPolicy;
 use crate::{Error, Result};
+use std::collections::HashMap;
 use std::path::PathBuf;
 
+/// Default cap on threads for blocking work (Tokio's own default).
+pub const DEFAULT_BLOCKING_THREADS: usize = 512;
+
+/// What `nellie` does when run without a subcommand.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DefaultCommand {
+    /// Print usage and exit.
+    #[default]
+    Help,
+    /// Start the server, as `nellie serve` with the same flags and
+    /// environment would.
+    Serve,
+}
+
+impl DefaultCommand {
+    /// Convert command to string representation.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Help => "help",
+            Self::Serve => "serve",
+        }
+    }
+}
+
+impl std::str::FromStr for DefaultCommand {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "help" => Ok(Self::Help),
+            "serve" => Ok(Self::Serve),
+            _ => Err(format!(
+                "unknown default command '{s}' (expected 'help' or 'serve')"
+            )),
+        }
+    }
+}
+
 /// Main configuration for Nellie server.
+// Each flag mirrors an independent command-line switch
+#[allow(clippy::struct_excessive_bools)]
 #[derive(Debug, Clone)]
 pub struct Config {
     /// Directory for `SQLite` database and other data.
     pub data_dir: PathBuf,
 
+    /// Directory holding the embedding model and tokenizer.
+    ///
+    /// Defaults to `data_dir/models`. Set it to keep bundled models on a
+    /// read-only mount while the database lives on writable storage.
+    pub model_dir: Option<PathBuf>,
+
     /// Host address to bind to.
     pub host: String,
 
     /// Port to listen on.
     pub port: u16,
 
+    /// Unix domain socket to listen on instead of `host`/`port`.
+    ///
+    /// Only local processes that can open the socket file may connect, so
+    /// its permissions act as access control.
+    pub listen_socket: Option<PathBuf>,
+
     /// Log level (trace, debug, info, warn, error).
     pub log_level: String,
 
@@ -24,22 +81,250 @@ pub struct Config {
     /// Maximum number of embedding worker threads.
     pub embedding_threads: usize,
 
+    /// Tokio runtime worker threads (`None` uses one per CPU core).
+    pub worker_threads: Option<usize>,
+
+    /// Cap on threads for blocking work: directory walks, file reads and
+    /// synchronous database calls run through `spawn_blocking`.
+    pub blocking_threads: usize,
+
+    /// What to do when no subcommand is given.
+    pub default_command: DefaultCommand,
+
     /// API key for authentication. If None, authentication is disabled (dev mode).
     pub api_key: Option<String>,
+
+    /// Verify content hashes during reconciliation instead of trusting mtime/size.
+    ///
+    /// Useful on filesystems with unreliable mtimes (e.g. NFS).
+    pub verify_hashes: bool,
+
+    /// Files startup reconciliation checks per batch. Progress is saved after
+    /// each batch, so a restart resumes from the last one.
+    pub reconcile_batch_size: usize,
+
+    /// Capacity of the queue feeding reconciled, scanned and changed files
+    /// to the indexer.
+    pub reconcile_queue_capacity: usize,
+
+    /// What happens when the index queue is full: wait for space (`block`)
+    /// or evict the oldest queued file (`drop_oldest`).
+    pub index_queue_overflow: OverflowPolicy,
+
+    /// OTLP collector endpoint for trace export. If None, traces are not exported.
+    pub otlp_endpoint: Option<String>,
+
+    /// Maximum number of query embeddings to cache (0 disables the cache).
+    pub query_cache_size: usize,
+
+    /// Window in milliseconds for coalescing file change events.
+    pub debounce_ms: u64,
+
+    /// Browser origins allowed to call the API (CORS).
+    ///
+    /// Empty (the default) sends no CORS headers, so browsers block
+    /// cross-origin requests. `["*"]` allows any origin, which lets any
+    /// webpage a user visits query the server (and its indexed code) if the
+    /// API key leaks or auth is disabled; prefer listing exact origins.
+    pub cors_allowed_origins: Vec<String>,
+
+    /// Maximum HTTP request body size in bytes; larger requests get 413.
+    pub max_request_bytes: usize,
+
+    /// Fail startup if the sqlite-vec extension cannot be loaded.
+    ///
+    /// When false, the server starts without vector tables and the
+    /// semantic search tools return errors.
+    pub require_vector_search: bool,
+
+    /// Seconds without a status update before an agent is considered stale.
+    pub agent_stale_secs: i64,
+
+    /// Largest serialized checkpoint `state` accepted, in bytes.
+    pub max_checkpoint_state_bytes: usize,
+
+    /// Deepest nesting of arrays/objects accepted in checkpoint `state`.
+    pub max_checkpoint_state_depth: usize,
+
+    /// Distance metric for vector search.
+    ///
+    /// L2 suits unit-normalized embeddings such as all-MiniLM-L6-v2; use
+    /// cosine for models whose embeddings aren't normalized. Fixed once a
+    /// database has vectors.
+    pub distance_metric: DistanceMetric,
+
+    /// Similarity score (0.0-1.0) at which `add_lesson` flags an existing
+    /// lesson as a possible duplicate.
+    pub lesson_duplicate_threshold: f32,
+
+    /// Half-life, in days, of the time decay applied when `search_lessons`
+    /// or `search_checkpoints` is called with a `recency_weight`.
+    pub recency_half_life_days: u64,
+
+    /// Number of files `index_repo`, `diff_index` and `full_reindex` index concurrently.
+    pub index_concurrency: usize,
+
+    /// URL that receives a POST with the summary of each completed
+    /// `index_repo`, `diff_index` or `full_reindex` run.
+    pub index_complete_webhook: Option<String>,
+
+    /// ONNX Runtime intra-op threads per embedding session.
+    pub onnx_intra_threads: usize,
+
+    /// ONNX Runtime inter-op threads per embedding session.
+    pub onnx_inter_threads: usize,
+
+    /// ONNX Runtime execution provider (falls back to CPU if unavailable).
+    pub execution_provider: ExecutionProvider,
+
+    /// How chunks longer than the model's maximum sequence length are
+    /// embedded: truncated, or split into windows that are mean-pooled.
+    pub long_chunk_strategy: LongChunkStrategy,
+
+    /// Skip dependency lockfiles (`package-lock.json`, `Cargo.lock`, ...) and
+    /// minified `*.min.js`/`*.min.css` assets when indexing.
+    pub skip_lockfiles: bool,
+
+    /// Store each chunk's text in the database. When false only the file
+    /// path and line range are kept, and search reads the text from disk,
+    /// which roughly halves the database but needs the source to stay put.
+    /// Keyword, symbol and grep search only see chunks with stored text.
+    pub store_chunk_content: bool,
+
+    /// Deduplicate chunks by content hash at index time. A chunk whose text
+    /// is already indexed elsewhere reuses that embedding instead of being
+    /// embedded again, and `search_code` lists the copies under one result.
+    pub dedupe_chunks: bool,
+
+    /// Free space on the data directory's filesystem, in bytes, below which
+    /// `get_status` and `/health/ready` report a low-disk warning (0 disables).
+    pub min_free_disk_bytes: u64,
+
+    /// Cross-encoder ONNX model used by `search_code` with `rerank`, with its
+    /// `tokenizer.json` alongside. Loaded on the first rerank request.
+    pub reranker_model: Option<PathBuf>,
+
+    /// Normalize lesson tags on write (lowercase, trimmed, spaces/`_`/`-`
+    /// collapsed to `-`) so spelling variants share one tag.
+    pub normalize_tags: bool,
+
+    /// Lesson severities accepted in addition to `critical`, `warning` and
+    /// `info`. Stored lessons with any other severity are corrected on startup.
+    pub extra_severities: Vec<String>,
+
+    /// Largest `limit` the search tools and `list_lessons` honour; larger
+    /// requests are clamped so one client can't force an enormous scan.
+    pub max_search_limit: usize,
+
+    /// Tool calls taking at least this many milliseconds are logged at
+    /// `warn` with their embedding and database time (0 disables).
+    pub slow_query_ms: u64,
+
+    /// Seconds between refreshes of the row-count gauges on `/metrics`
+    /// (chunks, lessons, files, checkpoints, agents in progress; 0 disables).
+    pub metrics_refresh_secs: u64,
+
+    /// Crashed embedding workers replaced before the pool reports itself
+    /// degraded in `get_status`.
+    pub embedding_max_restarts: usize,
+
+    /// Milliseconds a search or indexing batch waits for an embedding before
+    /// failing with `embedding_timeout` (0 waits forever).
+    pub embedding_timeout_ms: u64,
+
+    /// MCP tools hidden from listings and refused on invocation, on every
+    /// transport.
+    pub disabled_tools: Vec<String>,
+
+    /// Disable every tool that writes to the database or starts indexing.
+    pub read_only: bool,
+
+    /// Serve a Swagger UI for the REST API at `/docs`. `/openapi.json` is
+    /// always served.
+    pub api_docs_ui: bool,
+
+    /// Templates applied to code, lesson and checkpoint text before
+    /// embedding, and to queries against each. Document and query templates
+    /// must be changed together.
+    pub embedding_templates: EmbeddingTemplates,
+
+    /// File extensions to index and the language each is tagged with,
+    /// on top of the built-in table (e.g. `tf` → `terraform`).
+    pub extra_languages: HashMap<String, String>,
+
+    /// File extensions to index without a language tag.
+    pub extra_code_extensions: Vec<String>,
+
+    /// Also index documentation (`.rst`, `.txt`, ...) and config (`.ini`,
+    /// `.cfg`, ...) files, tagged `doc` and `config` and chunked at paragraph
+    /// breaks. Markdown, YAML, JSON and TOML are indexed as code either way;
+    /// this retags them `doc` and `config` too.
+    pub index_docs: bool,
+
+    /// Only index files with these extensions; empty indexes every
+    /// supported extension.
+    pub index_extensions: Vec<String>,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             data_dir: PathBuf::from("./data"),
+            model_dir: None,
             host: "127.0.0.1".to_string(),
             port: 8080,
+            listen_socket: None,
             log_level: "info".to_string(),
             watch_dirs: Vec::new(),
             embedding_threads: std::thread::available_parallelism()
                 .map(|n| n.get().min(4))
                 .unwrap_or(4),
+            worker_threads: None,
+            blocking_threads: DEFAULT_BLOCKING_THREADS,
+            default_command: DefaultCommand::Help,
             api_key: std::env::var("NELLIE_API_KEY").ok(),
+            verify_hashes: false,
+            reconcile_batch_size: crate::watcher::DEFAULT_RECONCILE_BATCH_SIZE,
+            reconcile_queue_capacity: crate::watcher::DEFAULT_RECONCILE_QUEUE_CAPACITY,
+            index_queue_overflow: OverflowPolicy::Block,
+            otlp_endpoint: std::env::var("NELLIE_OTLP_ENDPOINT").ok(),
+            query_cache_size: 256,
+            debounce_ms: crate::watcher::DEFAULT_DEBOUNCE_MS,
+            cors_allowed_origins: Vec::new(),
+            max_request_bytes: 4 * 1024 * 1024,
+            require_vector_search: true,
+            agent_stale_secs: crate::storage::DEFAULT_AGENT_STALE_SECS,
+            max_checkpoint_state_bytes: crate::server::DEFAULT_MAX_CHECKPOINT_STATE_BYTES,
+            max_checkpoint_state_depth: crate::server::DEFAULT_MAX_CHECKPOINT_STATE_DEPTH,
+            distance_metric: DistanceMetric::default(),
+            lesson_duplicate_threshold: crate::server::DEFAULT_LESSON_DUPLICATE_THRESHOLD,
+            recency_half_life_days: crate::server::DEFAULT_RECENCY_HALF_LIFE_DAYS,
+            index_concurrency: crate::server::DEFAULT_INDEX_CONCURRENCY,
+            index_complete_webhook: None,
+            onnx_intra_threads: 1,
+            onnx_inter_threads: 1,
+            execution_provider: ExecutionProvider::default(),
+            long_chunk_strategy: LongChunkStrategy::default(),
+            skip_lockfiles: true,
+            store_chunk_content: true,
+            dedupe_chunks: false,
+            min_free_disk_bytes: crate::server::DEFAULT_MIN_FREE_DISK_BYTES,
+            reranker_model: None,
+            normalize_tags: false,
+            extra_severities: Vec::new(),
+            max_search_limit: crate::server::DEFAULT_MAX_SEARCH_LIMIT,
+            slow_query_ms: crate::server::DEFAULT_SLOW_QUERY_MS,
+            metrics_refresh_secs: crate::server::DEFAULT_METRICS_REFRESH_SECS,
+            embedding_max_restarts: crate::embeddings::DEFAULT_MAX_WORKER_RESTARTS,
+            embedding_timeout_ms: crate::embeddings::DEFAULT_EMBED_TIMEOUT_MS,
+            disabled_tools: Vec::new(),
+            read_only: false,
+            api_docs_ui: false,
+            embedding_templates: EmbeddingTemplates::default(),
+            extra_languages: HashMap::new(),
+            extra_code_extensions: Vec::new(),
+            index_docs: false,
+            index_extensions: Vec::new(),
         }
     }
 }
@@ -64,12 +349,47 @@ impl Config {
         Ok(config)
     }
 
+    /// Build the Tokio runtime sized by `worker_threads` and
+    /// `blocking_threads`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either count is 0 or the runtime can't start.
+    pub fn build_runtime(&self) -> Result<tokio::runtime::Runtime> {
+        self.validate_runtime()?;
+
+        let mut builder = tokio::runtime::Builder::new_multi_thread();
+        builder
+            .enable_all()
+            .max_blocking_threads(self.blocking_threads);
+        if let Some(worker_threads) = self.worker_threads {
+            builder.worker_threads(worker_threads);
+        }
+        Ok(builder.build()?)
+    }
+
+    /// Validate the runtime thread counts, which Tokio rejects by panicking.
+    fn validate_runtime(&self) -> Result<()> {
+        if self.worker_threads == Some(0) {
+            return Err(Error::config("worker_threads cannot be 0"));
+        }
+        if self.blocking_threads == 0 {
+            return Err(Error::config("blocking_threads cannot be 0"));
+        }
+        Ok(())
+    }
+
     /// Validate configuration values.
     ///
     /// # Errors
     ///
     /// Returns an error if any configuration value is invalid.
     pub fn validate(&self) -> Result<()> {
+        validate_dir("data_dir", &self.data_dir)?;
+        if let Some(model_dir) = &self.model_dir {
+            validate_dir("model_dir", model_dir)?;
+        }
+
         // Validate port
         if self.port == 0 {
             return Err(Error::config("port cannot be 0"));
@@ -96,11 +416,135 @@ impl Config {
             ));
         }
 
+        self.validate_runtime()?;
+
         // Validate host is not empty
         if self.host.is_empty() {
             return Err(Error::config("host cannot be empty"));
         }
 
+        if let Some(socket) = &self.listen_socket {
+            if socket.as_os_str().is_empty() {
+                return Err(Error::config("listen_socket cannot be empty"));
+            }
+            if cfg!(not(unix)) {
+                return Err(Error::config(
+                    "listen_socket is only supported on Unix platforms",
+                ));
+            }
+        }
+
+        if self.max_checkpoint_state_bytes == 0 || self.max_checkpoint_state_depth == 0 {
+            return Err(Error::config(
+                "max_checkpoint_state_bytes and max_checkpoint_state_depth cannot be 0",
+            ));
+        }
+
+        if self.agent_stale_secs <= 0 {
+            return Err(Error::config("agent_stale_secs must be positive"));
+        }
+
+        if self.recency_half_life_days == 0 {
+            return Err(Error::config("recency_half_life_days cannot be 0"));
+        }
+
+        if self.index_concurrency == 0 {
+            return Err(Error::config("index_concurrency cannot be 0"));
+        }
+
+        if self.reconcile_batch_size == 0 || self.reconcile_queue_capacity == 0 {
+            return Err(Error::config(
+                "reconcile_batch_size and reconcile_queue_capacity cannot be 0",
+            ));
+        }
+
+        if self.onnx_intra_threads == 0 || self.onnx_inter_threads == 0 {
+            return Err(Error::config(
+                "onnx_intra_threads and onnx_inter_threads cannot be 0",
+            ));
+        }
+
+        if let Some(url) = &self.index_complete_webhook {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                return Err(Error::config(format!(
+                    "index_complete_webhook must be an http(s) URL, got '{url}'"
+                )));
+            }
+        }
+
+        if !(0.0..=1.0).contains(&self.lesson_duplicate_threshold) {
+            return Err(Error::config(
+                "lesson_duplicate_threshold must be between 0.0 and 1.0",
+            ));
+        }
+
+        if self.max_request_bytes == 0 {
+            return Err(Error::config("max_request_bytes cannot be 0"));
+        }
+
+        if self.cors_allowed_origins.len() > 1 && self.cors_allowed_origins.iter().any(|o| o == "*")
+        {
+            return Err(Error::config(
+                "cors_allowed_origins: '*' cannot be combined with other origins",
+            ));
+        }
+
+        let tools = crate::server::get_tools();
+        if let Some(unknown) = self
+            .disabled_tools
+            .iter()
+            .find(|name| !tools.iter().any(|t| &t.name == *name))
+        {
+            return Err(Error::config(format!(
+                "disabled_tools: unknown tool '{unknown}'"
+            )));
+        }
+
+        self.embedding_templates.validate().map_err(Error::config)?;
+
+        if self.max_search_limit == 0 {
+            return Err(Error::config("max_search_limit cannot be 0"));
+        }
+
+        if let Some(bad) = self.extra_severities.iter().find(|s| {
+            s.is_empty()
+                || !s
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+        }) {
+            return Err(Error::config(format!(
+                "extra_severities: '{bad}' must be lowercase letters, digits, '-' or '_'"
+            )));
+        }
+
+        if let Some(bad) = self
+            .extra_languages
+            .keys()
+            .chain(&self.extra_code_extensions)
+            .find(|ext| !is_extension(ext))
+        {
+            return Err(Error::config(format!(
+                "extra file extension '{bad}' must be letters, digits, '-' or '_', with an optional leading '.'"
+            )));
+        }
+
+        if let Some(bad) = self.index_extensions.iter().find(|ext| !is_extension(ext)) {
+            return Err(Error::config(format!(
+                "index_extensions: '{bad}' must be letters, digits, '-' or '_', with an optional leading '.'"
+            )));
+        }
+
+        if let Some((ext, _)) = self.extra_languages.iter().find(|(_, lang)| {
+            lang.is_empty()
+                || !lang
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+        }) {
+            return Err(Error::config(format!(
+                "extra_languages: language for '{ext}' must be lowercase letters, digits, '-' or '_'"
+            )));
+        }
+
         Ok(())
     }
 
@@ -110,11 +554,50 @@ impl Config {
         self.data_dir.join("nellie.db")
     }
 
+    /// Get the directory the embedding model is loaded from.
+    #[must_use]
+    pub fn models_path(&self) -> PathBuf {
+        self.model_dir
+            .clone()
+            .unwrap_or_else(|| self.data_dir.join("models"))
+    }
+
     /// Get the server address as a string.
+    ///
+    /// This is the socket path when listening on a Unix domain socket.
     #[must_use]
     pub fn server_addr(&self) -> String {
-        format!("{}:{}", self.host, self.port)
+        self.listen_socket.as_ref().map_or_else(
+            || format!("{}:{}", self.host, self.port),
+            |socket| socket.display().to_string(),
+        )
+    }
+}
+
+/// Check that a configured directory is non-empty and, if it already
+/// exists, is a directory. Missing directories are fine; `data_dir` is
+/// created on startup.
+fn validate_dir(name: &str, path: &std::path::Path) -> Result<()> {
+    if path.as_os_str().is_empty() {
+        return Err(Error::config(format!("{name} cannot be empty")));
+    }
+    if path.exists() && !path.is_dir() {
+        return Err(Error::config(format!(
+            "{name} '{}' is not a directory",
+            path.display()
+        )));
     }
+    Ok(())
+}
+
+/// Check a configured file extension, e.g. `tf` or `.bzl`. Only the last
+/// extension of a file name is matched, so inner dots are rejected.
+fn is_extension(ext: &str) -> bool {
+    let ext = ext.strip_prefix('.').unwrap_or(ext);
+    !ext.is_empty()
+        && ext
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
 }
 
 #[cfg(test)]
@@ -175,6 +658,33 @@ mod tests {
         assert!(err.to_string().contains("32"));
     }
 
+    #[test]
+    fn test_runtime_thread_budget() {
+        let config = Config {
+            worker_threads: Some(2),
+            blocking_threads: 4,
+            ..Default::default()
+        };
+        config.validate().unwrap();
+        let runtime = config.build_runtime().unwrap();
+        assert_eq!(runtime.metrics().num_workers(), 2);
+        assert_eq!(runtime.block_on(async { 1 + 1 }), 2);
+
+        let config = Config {
+            worker_threads: Some(0),
+            ..Default::default()
+        };
+        let err = config.build_runtime().unwrap_err();
+        assert!(err.to_string().contains("worker_threads"));
+
+        let config = Config {
+            blocking_threads: 0,
+            ..Default::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("blocking_threads"));
+    }
+
     #[test]
     fn test_validate_empty_host() {
         let config = Config {
@@ -197,6 +707,60 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_models_path() {
+        let config = Config {
+            data_dir: PathBuf::from("/var/lib/nellie"),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.models_path(),
+            PathBuf::from("/var/lib/nellie/models")
+        );
+
+        let config = Config {
+            model_dir: Some(PathBuf::from("/opt/nellie/models")),
+            ..config
+        };
+        assert_eq!(config.models_path(), PathBuf::from("/opt/nellie/models"));
+        assert_eq!(
+            config.database_path(),
+            PathBuf::from("/var/lib/nellie/nellie.db")
+        );
+    }
+
+    #[test]
+    fn test_validate_dirs() {
+        let config = Config {
+            data_dir: PathBuf::new(),
+            ..Default::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("data_dir"));
+
+        let config = Config {
+            model_dir: Some(PathBuf::new()),
+            ..Default::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("model_dir"));
+
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let config = Config {
+            model_dir: Some(file.path().to_path_buf()),
+            ..Default::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("not a directory"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            model_dir: Some(dir.path().to_path_buf()),
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+    }
+
     #[test]
     fn test_server_addr() {
         let config = Config {
@@ -251,4 +815,367 @@ mod tests {
         assert!(config.validate().is_ok());
         assert_eq!(config.api_key, None);
     }
+
+    #[test]
+    fn test_skip_lockfiles_defaults_on() {
+        assert!(Config::default().skip_lockfiles);
+    }
+
+    #[test]
+    fn test_store_chunk_content_defaults_on() {
+        assert!(Config::default().store_chunk_content);
+    }
+
+    #[test]
+    fn test_dedupe_chunks_defaults_off() {
+        assert!(!Config::default().dedupe_chunks);
+    }
+
+    #[test]
+    fn test_verify_hashes_defaults_off() {
+        let config = Config::default();
+        assert!(!config.verify_hashes);
+    }
+
+    #[test]
+    fn test_query_cache_size_default() {
+        let config = Config::default();
+        assert_eq!(config.query_cache_size, 256);
+    }
+
+    #[test]
+    fn test_debounce_default() {
+        let config = Config::default();
+        assert_eq!(config.debounce_ms, 500);
+    }
+
+    #[test]
+    fn test_distance_metric_default() {
+        assert_eq!(Config::default().distance_metric, DistanceMetric::L2);
+    }
+
+    #[test]
+    fn test_recency_half_life_days() {
+        assert_eq!(Config::default().recency_half_life_days, 90);
+
+        let config = Config {
+            recency_half_life_days: 0,
+            ..Default::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("recency_half_life_days"));
+    }
+
+    #[test]
+    fn test_min_free_disk_bytes_default() {
+        assert_eq!(Config::default().min_free_disk_bytes, 1024 * 1024 * 1024);
+    }
+
+    #[test]
+    fn test_index_concurrency() {
+        assert_eq!(Config::default().index_concurrency, 4);
+
+        let config = Config {
+            index_concurrency: 0,
+            ..Default::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("index_concurrency"));
+    }
+
+    #[test]
+    fn test_index_complete_webhook() {
+        assert!(Config::default().index_complete_webhook.is_none());
+
+        let config = Config {
+            index_complete_webhook: Some("https://ci.example.com/nellie".to_string()),
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+
+        let config = Config {
+            index_complete_webhook: Some("ci.example.com/nellie".to_string()),
+            ..Default::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("index_complete_webhook"));
+    }
+
+    #[test]
+    fn test_onnx_settings() {
+        let config = Config::default();
+        assert_eq!(config.onnx_intra_threads, 1);
+        assert_eq!(config.onnx_inter_threads, 1);
+        assert_eq!(config.execution_provider, ExecutionProvider::Cpu);
+
+        let config = Config {
+            onnx_inter_threads: 0,
+            ..Default::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("onnx_inter_threads"));
+    }
+
+    #[test]
+    fn test_long_chunk_strategy_default() {
+        assert_eq!(
+            Config::default().long_chunk_strategy,
+            LongChunkStrategy::Truncate
+        );
+    }
+
+    #[test]
+    fn test_default_command_parsing() {
+        assert_eq!(Config::default().default_command, DefaultCommand::Help);
+        assert_eq!("serve".parse(), Ok(DefaultCommand::Serve));
+        assert_eq!("HELP".parse(), Ok(DefaultCommand::Help));
+        assert_eq!(DefaultCommand::Serve.as_str(), "serve");
+        assert!("run".parse::<DefaultCommand>().is_err());
+    }
+
+    #[test]
+    fn test_index_queue_overflow_default() {
+        assert_eq!(
+            Config::default().index_queue_overflow,
+            OverflowPolicy::Block
+        );
+    }
+
+    #[test]
+    fn test_disabled_tools() {
+        let config = Config::default();
+        assert!(config.disabled_tools.is_empty());
+        assert!(!config.read_only);
+        assert!(!config.api_docs_ui);
+
+        let config = Config {
+            disabled_tools: vec!["delete_lesson".to_string(), "full_reindex".to_string()],
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+
+        let config = Config {
+            disabled_tools: vec!["delete_everything".to_string()],
+            ..Default::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("delete_everything"));
+    }
+
+    #[test]
+    fn test_embedding_templates() {
+        let config = Config {
+            embedding_templates: EmbeddingTemplates {
+                code: "search_document: {file_path}\\n{content}".to_string(),
+                ..EmbeddingTemplates::default()
+            },
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+
+        let config = Config {
+            embedding_templates: EmbeddingTemplates {
+                checkpoint_query: "{text}".to_string(),
+                ..EmbeddingTemplates::default()
+            },
+            ..Default::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("checkpoint_query_template"));
+    }
+
+    #[test]
+    fn test_embedding_max_restarts_default() {
+        assert_eq!(Config::default().embedding_max_restarts, 5);
+    }
+
+    #[test]
+    fn test_embedding_timeout_default() {
+        assert_eq!(Config::default().embedding_timeout_ms, 30_000);
+    }
+
+    #[test]
+    fn test_lesson_duplicate_threshold() {
+        let config = Config::default();
+        assert!((config.lesson_duplicate_threshold - 0.9).abs() < f32::EPSILON);
+
+        let config = Config {
+            lesson_duplicate_threshold: 1.5,
+            ..Default::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("lesson_duplicate_threshold"));
+    }
+
+    #[test]
+    fn test_checkpoint_state_limits() {
+        let config = Config::default();
+        assert_eq!(config.max_checkpoint_state_bytes, 256 * 1024);
+        assert_eq!(config.max_checkpoint_state_depth, 32);
+
+        let config = Config {
+            max_checkpoint_state_depth: 0,
+            ..Default::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("max_checkpoint_state_depth"));
+    }
+
+    #[test]
+    fn test_agent_stale_secs() {
+        let config = Config::default();
+        assert_eq!(config.agent_stale_secs, 3600);
+
+        let config = Config {
+            agent_stale_secs: 0,
+            ..Default::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("agent_stale_secs"));
+    }
+
+    #[test]
+    fn test_require_vector_search_default() {
+        assert!(Config::default().require_vector_search);
+    }
+
+    #[test]
+    fn test_max_request_bytes() {
+        let config = Config::default();
+        assert_eq!(config.max_request_bytes, 4 * 1024 * 1024);
+
+        let config = Config {
+            max_request_bytes: 0,
+            ..Default::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("max_request_bytes"));
+    }
+
+    #[test]
+    fn test_cors_origins() {
+        let config = Config::default();
+        assert!(config.cors_allowed_origins.is_empty());
+
+        let config = Config {
+            cors_allowed_origins: vec!["*".to_string()],
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+
+        let config = Config {
+            cors_allowed_origins: vec!["*".to_string(), "https://app.example.com".to_string()],
+            ..Default::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("cors_allowed_origins"));
+    }
+
+    #[test]
+    fn test_extra_severities() {
+        let config = Config::default();
+        assert!(config.extra_severities.is_empty());
+
+        let config = Config {
+            extra_severities: vec!["security".to_string(), "perf-2".to_string()],
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+
+        for bad in ["", "Security", "needs review"] {
+            let config = Config {
+                extra_severities: vec![bad.to_string()],
+                ..Default::default()
+            };
+            let err = config.validate().unwrap_err();
+            assert!(err.to_string().contains("extra_severities"));
+        }
+    }
+
+    #[test]
+    fn test_extra_languages() {
+        let config = Config::default();
+        assert!(config.extra_languages.is_empty());
+        assert!(config.extra_code_extensions.is_empty());
+
+        let config = Config {
+            extra_languages: HashMap::from([
+                ("tf".to_string(), "terraform".to_string()),
+                (".bzl".to_string(), "starlark".to_string()),
+            ]),
+            extra_code_extensions: vec!["rbi".to_string()],
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+
+        for bad in ["", ".", "d.ts", "a b"] {
+            let config = Config {
+                extra_code_extensions: vec![bad.to_string()],
+                ..Default::default()
+            };
+            let err = config.validate().unwrap_err();
+            assert!(err.to_string().contains("extra file extension"));
+        }
+
+        let config = Config {
+            extra_languages: HashMap::from([("tf".to_string(), "Terraform".to_string())]),
+            ..Default::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("extra_languages"));
+    }
+
+    #[test]
+    fn test_index_docs() {
+        let config = Config::default();
+        assert!(!config.index_docs);
+        assert!(config.index_extensions.is_empty());
+
+        let config = Config {
+            index_docs: true,
+            index_extensions: vec!["md".to_string(), ".rs".to_string()],
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+
+        let config = Config {
+            index_extensions: vec!["d.ts".to_string()],
+            ..Default::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("index_extensions"));
+    }
+
+    #[test]
+    fn test_max_search_limit() {
+        let config = Config::default();
+        assert_eq!(config.max_search_limit, 100);
+        assert_eq!(config.slow_query_ms, 1000);
+        assert_eq!(config.metrics_refresh_secs, 15);
+
+        let config = Config {
+            max_search_limit: 0,
+            ..Default::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("max_search_limit"));
+    }
+
+    #[test]
+    fn test_listen_socket() {
+        let config = Config {
+            listen_socket: Some(PathBuf::from("/tmp/nellie.sock")),
+            ..Default::default()
+        };
+        assert_eq!(config.server_addr(), "/tmp/nellie.sock");
+        #[cfg(unix)]
+        assert!(config.validate().is_ok());
+
+        let config = Config {
+            listen_socket: Some(PathBuf::new()),
+            ..Default::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("listen_socket"));
+    }
 }
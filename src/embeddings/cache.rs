@@ -0,0 +1,138 @@
+//! LRU cache for query embeddings.
+//!
+//! Agents tend to repeat the same queries within a session. Embeddings are
+//! deterministic, so memoizing them by exact query string skips ONNX
+//! inference without changing results.
+
+use std::collections::HashMap;
+
+use parking_lot::Mutex;
+
+/// Bounded least-recently-used cache of query embeddings.
+///
+/// Thread-safe; shared across handlers via the owning `EmbeddingService`.
+#[derive(Debug)]
+pub struct QueryCache {
+    capacity: usize,
+    inner: Mutex<CacheInner>,
+}
+
+#[derive(Debug, Default)]
+struct CacheInner {
+    entries: HashMap<String, CacheEntry>,
+    tick: u64,
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    embedding: Vec<f32>,
+    last_used: u64,
+}
+
+impl QueryCache {
+    /// Create a cache holding at most `capacity` embeddings.
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Mutex::new(CacheInner::default()),
+        }
+    }
+
+    /// Look up an embedding, marking it as recently used.
+    pub fn get(&self, query: &str) -> Option<Vec<f32>> {
+        let mut inner = self.inner.lock();
+        inner.tick += 1;
+        let tick = inner.tick;
+        inner.entries.get_mut(query).map(|entry| {
+            entry.last_used = tick;
+            entry.embedding.clone()
+        })
+    }
+
+    /// Insert an embedding, evicting the least recently used entry if full.
+    pub fn insert(&self, query: String, embedding: Vec<f32>) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut inner = self.inner.lock();
+        inner.tick += 1;
+        let tick = inner.tick;
+
+        if !inner.entries.contains_key(&query) && inner.entries.len() >= self.capacity {
+            let oldest = inner
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(key) = oldest {
+                inner.entries.remove(&key);
+            }
+        }
+
+        inner.entries.insert(
+            query,
+            CacheEntry {
+                embedding,
+                last_used: tick,
+            },
+        );
+    }
+
+    /// Number of cached embeddings.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.inner.lock().entries.len()
+    }
+
+    /// Check if the cache is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Maximum number of cached embeddings.
+    #[must_use]
+    pub const fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_and_insert() {
+        let cache = QueryCache::new(2);
+        assert!(cache.get("a").is_none());
+
+        cache.insert("a".to_string(), vec![1.0]);
+        assert_eq!(cache.get("a"), Some(vec![1.0]));
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let cache = QueryCache::new(2);
+        cache.insert("a".to_string(), vec![1.0]);
+        cache.insert("b".to_string(), vec![2.0]);
+
+        // Touch "a" so "b" becomes the eviction candidate
+        assert!(cache.get("a").is_some());
+        cache.insert("c".to_string(), vec![3.0]);
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get("a").is_some());
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("c").is_some());
+    }
+
+    #[test]
+    fn test_zero_capacity_stores_nothing() {
+        let cache = QueryCache::new(0);
+        cache.insert("a".to_string(), vec![1.0]);
+        assert!(cache.is_empty());
+    }
+}
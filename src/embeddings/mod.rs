@@ -4,16 +4,29 @@
 //! - ONNX Runtime integration via the `ort` crate
 //! - Dedicated thread pool for embedding generation
 //! - Async API using channels for non-blocking operation
+//! - Optional cross-encoder reranking of search results
 
+mod cache;
 mod model;
+mod reranker;
 mod service;
+mod templates;
 mod worker;
 
+pub use cache::QueryCache;
 pub use model::{
-    is_runtime_available, EmbeddingModel, DEFAULT_MODEL_NAME, EMBEDDING_DIM, MAX_SEQ_LENGTH,
+    is_runtime_available, EmbeddingModel, ExecutionProvider, OnnxOptions, DEFAULT_MODEL_NAME,
+    EMBEDDING_DIM, MAX_SEQ_LENGTH,
+};
+pub use reranker::{Reranker, RerankerConfig, RerankerService, RERANK_MAX_SEQ_LENGTH};
+pub use service::{
+    placeholder_embedding, EmbeddingConfig, EmbeddingService, InitState, DEFAULT_EMBED_TIMEOUT_MS,
+};
+pub use templates::EmbeddingTemplates;
+pub use worker::{
+    l2_normalize, load_tokenizer, EmbeddingWorker, LongChunkStrategy, WorkerHealth,
+    DEFAULT_MAX_WORKER_RESTARTS,
 };
-pub use service::{placeholder_embedding, EmbeddingConfig, EmbeddingService};
-pub use worker::{load_tokenizer, EmbeddingWorker};
 
 /// Initialize embeddings module.
 pub fn init() {
@@ -5,8 +5,14 @@
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
+#[cfg(feature = "coreml")]
+use ort::ep::CoreML;
+#[cfg(any(feature = "coreml", feature = "cuda"))]
+use ort::ep::ExecutionProvider as _;
+#[cfg(feature = "cuda")]
+use ort::ep::CUDA;
 use ort::session::builder::GraphOptimizationLevel;
-use ort::session::builder::SessionBuilder;
+use ort::session::builder::{BuilderResult, SessionBuilder};
 use ort::session::Session;
 
 use crate::error::EmbeddingError;
@@ -21,6 +27,84 @@ pub const EMBEDDING_DIM: usize = 384;
 /// Maximum sequence length for the model.
 pub const MAX_SEQ_LENGTH: usize = 256;
 
+/// ONNX Runtime execution provider used to run the model.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExecutionProvider {
+    /// Default CPU provider, always available.
+    #[default]
+    Cpu,
+    /// Apple `CoreML`, for Apple Silicon.
+    CoreMl,
+    /// NVIDIA CUDA.
+    Cuda,
+}
+
+impl ExecutionProvider {
+    /// Convert provider to string representation.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Cpu => "cpu",
+            Self::CoreMl => "coreml",
+            Self::Cuda => "cuda",
+        }
+    }
+
+    /// Whether this build was compiled with the provider and the loaded
+    /// ONNX Runtime library can run it.
+    #[cfg_attr(
+        not(any(feature = "coreml", feature = "cuda")),
+        allow(clippy::missing_const_for_fn)
+    )]
+    fn is_available(self) -> bool {
+        match self {
+            Self::Cpu => true,
+            #[cfg(feature = "coreml")]
+            Self::CoreMl => matches!(CoreML::default().is_available(), Ok(true)),
+            #[cfg(feature = "cuda")]
+            Self::Cuda => matches!(CUDA::default().is_available(), Ok(true)),
+            #[allow(unreachable_patterns)]
+            _ => false,
+        }
+    }
+}
+
+impl std::str::FromStr for ExecutionProvider {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "cpu" => Ok(Self::Cpu),
+            "coreml" => Ok(Self::CoreMl),
+            "cuda" => Ok(Self::Cuda),
+            _ => Err(format!(
+                "unknown execution provider '{s}' (expected 'cpu', 'coreml' or 'cuda')"
+            )),
+        }
+    }
+}
+
+/// ONNX Runtime session settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OnnxOptions {
+    /// Threads used to parallelize work within an operator.
+    pub intra_threads: usize,
+    /// Threads used to run independent operators in parallel.
+    pub inter_threads: usize,
+    /// Requested execution provider; falls back to CPU if unavailable.
+    pub execution_provider: ExecutionProvider,
+}
+
+impl Default for OnnxOptions {
+    fn default() -> Self {
+        Self {
+            intra_threads: 1,
+            inter_threads: 1,
+            execution_provider: ExecutionProvider::Cpu,
+        }
+    }
+}
+
 /// ONNX embedding model wrapper.
 pub struct EmbeddingModel {
     session: Arc<Session>,
@@ -34,6 +118,19 @@ impl EmbeddingModel {
     ///
     /// Returns an error if the model cannot be loaded.
     pub fn load(model_path: impl AsRef<Path>) -> Result<Self> {
+        Self::load_with_options(model_path, &OnnxOptions::default())
+    }
+
+    /// Load an ONNX embedding model with explicit session settings.
+    ///
+    /// A requested execution provider that this binary wasn't built with
+    /// (the `cuda` and `coreml` features) or that the loaded ONNX Runtime
+    /// library doesn't support is replaced by CPU with a warning.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the model cannot be loaded.
+    pub fn load_with_options(model_path: impl AsRef<Path>, options: &OnnxOptions) -> Result<Self> {
         let model_path = model_path.as_ref().to_path_buf();
 
         if !model_path.exists() {
@@ -44,14 +141,54 @@ impl EmbeddingModel {
             .into());
         }
 
-        tracing::info!(path = %model_path.display(), "Loading ONNX embedding model");
+        let provider = if options.execution_provider.is_available() {
+            options.execution_provider
+        } else {
+            tracing::warn!(
+                requested = options.execution_provider.as_str(),
+                "Execution provider not built in or not supported by ONNX Runtime, falling back to cpu"
+            );
+            ExecutionProvider::Cpu
+        };
+
+        tracing::info!(
+            path = %model_path.display(),
+            provider = provider.as_str(),
+            intra_threads = options.intra_threads,
+            inter_threads = options.inter_threads,
+            "Loading ONNX embedding model"
+        );
 
-        let session = SessionBuilder::new()
+        let builder = SessionBuilder::new()
             .map_err(|e| EmbeddingError::Runtime(format!("failed to create session builder: {e}")))?
             .with_optimization_level(GraphOptimizationLevel::Level3)
             .map_err(|e| EmbeddingError::Runtime(format!("failed to set optimization level: {e}")))?
-            .with_intra_threads(1)
+            .with_intra_threads(options.intra_threads.max(1))
+            .map_err(|e| EmbeddingError::Runtime(format!("failed to set threads: {e}")))?
+            .with_inter_threads(options.inter_threads.max(1))
             .map_err(|e| EmbeddingError::Runtime(format!("failed to set threads: {e}")))?
+            // Inter-op threads only take effect with parallel execution
+            .with_parallel_execution(options.inter_threads > 1)
+            .map_err(|e| EmbeddingError::Runtime(format!("failed to set execution mode: {e}")))?;
+
+        let registered: BuilderResult = match provider {
+            #[cfg(feature = "coreml")]
+            ExecutionProvider::CoreMl => {
+                builder.with_execution_providers([CoreML::default().build()])
+            }
+            #[cfg(feature = "cuda")]
+            ExecutionProvider::Cuda => builder.with_execution_providers([CUDA::default().build()]),
+            // `is_available` already mapped providers missing from this build to CPU
+            _ => Ok(builder),
+        };
+        let mut builder = registered.map_err(|e| {
+            EmbeddingError::Runtime(format!(
+                "failed to register {} execution provider: {e}",
+                provider.as_str()
+            ))
+        })?;
+
+        let session = builder
             .commit_from_file(&model_path)
             .map_err(|e| EmbeddingError::ModelLoad(format!("failed to load model: {e}")))?;
 
@@ -165,6 +302,45 @@ mod tests {
         assert!(err.to_string().contains("not found"));
     }
 
+    #[test]
+    fn test_execution_provider_from_str() {
+        assert_eq!(
+            "cpu".parse::<ExecutionProvider>(),
+            Ok(ExecutionProvider::Cpu)
+        );
+        assert_eq!(
+            "CoreML".parse::<ExecutionProvider>(),
+            Ok(ExecutionProvider::CoreMl)
+        );
+        assert_eq!(
+            "cuda".parse::<ExecutionProvider>(),
+            Ok(ExecutionProvider::Cuda)
+        );
+        assert!("tpu".parse::<ExecutionProvider>().is_err());
+        assert_eq!(ExecutionProvider::default(), ExecutionProvider::Cpu);
+        assert_eq!(ExecutionProvider::CoreMl.as_str(), "coreml");
+    }
+
+    #[test]
+    fn test_onnx_options_default() {
+        let options = OnnxOptions::default();
+        assert_eq!(options.intra_threads, 1);
+        assert_eq!(options.inter_threads, 1);
+        assert_eq!(options.execution_provider, ExecutionProvider::Cpu);
+    }
+
+    #[test]
+    fn test_load_nonexistent_model_with_options() {
+        let options = OnnxOptions {
+            intra_threads: 4,
+            inter_threads: 2,
+            execution_provider: ExecutionProvider::Cuda,
+        };
+        let err =
+            EmbeddingModel::load_with_options("/nonexistent/model.onnx", &options).unwrap_err();
+        assert!(err.to_string().contains("not found"));
+    }
+
     #[test]
     fn test_is_runtime_available() {
         // This may return true or false depending on system setup
@@ -0,0 +1,305 @@
+//! Cross-encoder reranking.
+//!
+//! A cross-encoder scores a `(query, document)` pair jointly, which orders
+//! the top of a result list more precisely than comparing independently
+//! computed embeddings. It is too slow to run over a whole index, so it is
+//! only applied to the best vector search candidates.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use ort::session::Session;
+use ort::value::Value;
+use parking_lot::Mutex;
+use tokenizers::{PaddingParams, Tokenizer, TruncationParams};
+
+use super::model::{EmbeddingModel, OnnxOptions};
+use crate::error::EmbeddingError;
+use crate::Result;
+
+/// Longest `(query, document)` token sequence scored; longer pairs are truncated.
+pub const RERANK_MAX_SEQ_LENGTH: usize = 512;
+
+/// Reranker configuration.
+#[derive(Debug, Clone)]
+pub struct RerankerConfig {
+    /// Path to the cross-encoder ONNX model file.
+    pub model_path: PathBuf,
+
+    /// Path to the model's tokenizer.json file.
+    pub tokenizer_path: PathBuf,
+
+    /// ONNX Runtime threading and execution provider.
+    pub onnx: OnnxOptions,
+}
+
+impl RerankerConfig {
+    /// Create config for a cross-encoder model.
+    ///
+    /// Expects `tokenizer.json` in the same directory as the model.
+    #[must_use]
+    pub fn from_model_path(model_path: impl AsRef<Path>) -> Self {
+        let model_path = model_path.as_ref().to_path_buf();
+        let tokenizer_path = model_path.with_file_name("tokenizer.json");
+        Self {
+            model_path,
+            tokenizer_path,
+            onnx: OnnxOptions::default(),
+        }
+    }
+
+    /// Set the ONNX Runtime session options.
+    #[must_use]
+    pub const fn with_onnx_options(mut self, onnx: OnnxOptions) -> Self {
+        self.onnx = onnx;
+        self
+    }
+}
+
+/// A loaded cross-encoder model.
+pub struct Reranker {
+    session: Mutex<Session>,
+    tokenizer: Tokenizer,
+}
+
+impl Reranker {
+    /// Load the cross-encoder model and tokenizer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the model or tokenizer cannot be loaded.
+    pub fn load(config: &RerankerConfig) -> Result<Self> {
+        let model = EmbeddingModel::load_with_options(&config.model_path, &config.onnx)?;
+        let session = model.session();
+        drop(model);
+        let session = Arc::try_unwrap(session).map_err(|_| {
+            EmbeddingError::ModelLoad("reranker session has multiple owners".to_string())
+        })?;
+
+        let mut tokenizer = super::worker::load_tokenizer(&config.tokenizer_path)?;
+        tokenizer
+            .with_truncation(Some(TruncationParams {
+                max_length: RERANK_MAX_SEQ_LENGTH,
+                ..TruncationParams::default()
+            }))
+            .map_err(|e| {
+                EmbeddingError::Tokenization(format!("failed to configure truncation: {e}"))
+            })?
+            .with_padding(Some(PaddingParams::default()));
+
+        Ok(Self {
+            session: Mutex::new(session),
+            tokenizer,
+        })
+    }
+
+    /// Score how relevant each document is to `query`, from 0.0 to 1.0.
+    ///
+    /// Scores are returned in the same order as `documents`. This runs
+    /// inference on the calling thread.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if tokenization or inference fails.
+    pub fn score(&self, query: &str, documents: &[String]) -> Result<Vec<f32>> {
+        if documents.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let pairs: Vec<(String, String)> = documents
+            .iter()
+            .map(|doc| (query.to_string(), doc.clone()))
+            .collect();
+        let encodings = self
+            .tokenizer
+            .encode_batch(pairs, true)
+            .map_err(|e| EmbeddingError::Tokenization(format!("failed to tokenize: {e}")))?;
+
+        let batch_size = encodings.len();
+        let seq_len = encodings.first().map_or(0, tokenizers::Encoding::len);
+        let mut input_ids = Vec::with_capacity(batch_size * seq_len);
+        let mut attention_mask = Vec::with_capacity(batch_size * seq_len);
+        let mut token_type_ids = Vec::with_capacity(batch_size * seq_len);
+        for encoding in &encodings {
+            input_ids.extend(encoding.get_ids().iter().map(|&id| i64::from(id)));
+            attention_mask.extend(encoding.get_attention_mask().iter().map(|&m| i64::from(m)));
+            token_type_ids.extend(encoding.get_type_ids().iter().map(|&t| i64::from(t)));
+        }
+
+        #[allow(clippy::cast_possible_wrap)]
+        let shape = vec![batch_size as i64, seq_len as i64];
+        let input_ids = Value::from_array((shape.as_slice(), input_ids))
+            .map_err(|e| EmbeddingError::Runtime(format!("failed to create input_ids: {e}")))?;
+        let attention_mask =
+            Value::from_array((shape.as_slice(), attention_mask)).map_err(|e| {
+                EmbeddingError::Runtime(format!("failed to create attention_mask: {e}"))
+            })?;
+        let token_type_ids =
+            Value::from_array((shape.as_slice(), token_type_ids)).map_err(|e| {
+                EmbeddingError::Runtime(format!("failed to create token_type_ids: {e}"))
+            })?;
+
+        let mut session = self.session.lock();
+        let outputs = session
+            .run(ort::inputs![
+                "input_ids" => input_ids,
+                "attention_mask" => attention_mask,
+                "token_type_ids" => token_type_ids,
+            ])
+            .map_err(|e| EmbeddingError::Runtime(format!("ONNX inference failed: {e}")))?;
+
+        // Logits: [batch_size, num_labels]
+        let (out_shape, logits) = outputs[0]
+            .try_extract_tensor::<f32>()
+            .map_err(|e| EmbeddingError::Runtime(format!("failed to extract logits: {e}")))?;
+        #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+        let num_labels = (*out_shape.last().unwrap_or(&1)).max(1) as usize;
+        let scores = relevance_scores(logits, num_labels);
+
+        drop(outputs);
+        drop(session);
+        Ok(scores)
+    }
+}
+
+impl std::fmt::Debug for Reranker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Reranker")
+            .field("session", &"<Mutex<Session>>")
+            .finish_non_exhaustive()
+    }
+}
+
+/// Turn per-pair logits into 0.0-1.0 relevance scores.
+///
+/// Single-label models output one relevance logit per pair; for
+/// multi-label models the last label is taken as "relevant".
+fn relevance_scores(logits: &[f32], num_labels: usize) -> Vec<f32> {
+    logits
+        .chunks_exact(num_labels)
+        .map(|row| sigmoid(row[num_labels - 1]))
+        .collect()
+}
+
+fn sigmoid(x: f32) -> f32 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+/// Lazily loaded cross-encoder.
+///
+/// The model is only loaded on the first rerank request, so configuring a
+/// reranker costs nothing until a search asks for it. Clone is cheap.
+#[derive(Clone)]
+pub struct RerankerService {
+    inner: Arc<RerankerServiceInner>,
+}
+
+struct RerankerServiceInner {
+    config: RerankerConfig,
+    reranker: tokio::sync::OnceCell<Arc<Reranker>>,
+}
+
+impl RerankerService {
+    /// Create a reranker service without loading the model.
+    #[must_use]
+    pub fn new(config: RerankerConfig) -> Self {
+        Self {
+            inner: Arc::new(RerankerServiceInner {
+                config,
+                reranker: tokio::sync::OnceCell::new(),
+            }),
+        }
+    }
+
+    /// Path of the configured cross-encoder model.
+    #[must_use]
+    pub fn model_path(&self) -> &Path {
+        &self.inner.config.model_path
+    }
+
+    /// Whether the model has been loaded.
+    #[must_use]
+    pub fn is_loaded(&self) -> bool {
+        self.inner.reranker.initialized()
+    }
+
+    /// Score documents against `query`, loading the model on first use.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the model cannot be loaded or inference fails.
+    pub async fn score(&self, query: &str, documents: Vec<String>) -> Result<Vec<f32>> {
+        let reranker = self
+            .inner
+            .reranker
+            .get_or_try_init(|| async {
+                let config = self.inner.config.clone();
+                tracing::info!(path = %config.model_path.display(), "Loading reranker model");
+                tokio::task::spawn_blocking(move || Reranker::load(&config))
+                    .await
+                    .map_err(|e| {
+                        EmbeddingError::ModelLoad(format!("reranker load task failed: {e}"))
+                    })?
+                    .map(Arc::new)
+            })
+            .await?;
+
+        let reranker = Arc::clone(reranker);
+        let query = query.to_string();
+        tokio::task::spawn_blocking(move || reranker.score(&query, &documents))
+            .await
+            .map_err(|e| EmbeddingError::Runtime(format!("reranker task failed: {e}")))?
+    }
+}
+
+impl std::fmt::Debug for RerankerService {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RerankerService")
+            .field("model_path", &self.inner.config.model_path)
+            .field("loaded", &self.is_loaded())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_from_model_path() {
+        let config = RerankerConfig::from_model_path("/models/reranker/ms-marco.onnx");
+        assert_eq!(
+            config.tokenizer_path,
+            PathBuf::from("/models/reranker/tokenizer.json")
+        );
+        assert_eq!(config.onnx, OnnxOptions::default());
+    }
+
+    #[test]
+    fn test_relevance_scores() {
+        let scores = relevance_scores(&[0.0, 4.0, -4.0], 1);
+        assert_eq!(scores.len(), 3);
+        assert!((scores[0] - 0.5).abs() < 1e-6);
+        assert!(scores[1] > 0.98);
+        assert!(scores[2] < 0.02);
+
+        // Two-label models use the last label
+        let scores = relevance_scores(&[3.0, -3.0, -3.0, 3.0], 2);
+        assert!(scores[0] < scores[1]);
+    }
+
+    #[tokio::test]
+    async fn test_service_loads_lazily() {
+        let service = RerankerService::new(RerankerConfig::from_model_path(
+            "/nonexistent/reranker.onnx",
+        ));
+        assert!(!service.is_loaded());
+
+        let err = service
+            .score("query", vec!["doc".to_string()])
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("model file not found"));
+        assert!(!service.is_loaded());
+    }
+}
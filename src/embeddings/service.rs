@@ -2,17 +2,28 @@
 //!
 //! Provides a convenient async API for generating embeddings.
 
+use std::future::Future;
 use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 
+use serde::Serialize;
 use tokenizers::Tokenizer;
 use tokio::sync::RwLock;
 
-use super::model::EmbeddingModel;
-use super::worker::EmbeddingWorker;
+use super::cache::QueryCache;
+use super::model::{EmbeddingModel, OnnxOptions, EMBEDDING_DIM};
+use super::templates::EmbeddingTemplates;
+use super::worker::{
+    EmbeddingWorker, LongChunkStrategy, WorkerHealth, DEFAULT_MAX_WORKER_RESTARTS,
+};
 use crate::error::EmbeddingError;
+use crate::storage::EmbeddingModelInfo;
 use crate::Result;
 
+/// Default time to wait for an embedding before failing the request.
+pub const DEFAULT_EMBED_TIMEOUT_MS: u64 = 30_000;
+
 /// Embedding service configuration.
 #[derive(Debug, Clone)]
 pub struct EmbeddingConfig {
@@ -24,6 +35,25 @@ pub struct EmbeddingConfig {
 
     /// Number of worker threads.
     pub num_workers: usize,
+
+    /// Maximum number of query embeddings to cache (0 disables the cache).
+    pub query_cache_size: usize,
+
+    /// ONNX Runtime threading and execution provider.
+    pub onnx: OnnxOptions,
+
+    /// How inputs longer than the model's sequence length are embedded.
+    pub long_chunk_strategy: LongChunkStrategy,
+
+    /// Crashed workers replaced before the pool reports itself degraded.
+    pub max_worker_restarts: usize,
+
+    /// Milliseconds to wait for an embedding before failing the request
+    /// (0 waits forever).
+    pub embed_timeout_ms: u64,
+
+    /// Templates shaping code, lesson and checkpoint text before embedding.
+    pub templates: EmbeddingTemplates,
 }
 
 impl EmbeddingConfig {
@@ -33,11 +63,131 @@ impl EmbeddingConfig {
     /// and tokenizer at `{data_dir}/models/tokenizer.json`.
     #[must_use]
     pub fn from_data_dir(data_dir: impl AsRef<Path>, num_workers: usize) -> Self {
-        let models_dir = data_dir.as_ref().join("models");
+        Self::from_model_dir(data_dir.as_ref().join("models"), num_workers)
+    }
+
+    /// Create config from a model directory.
+    ///
+    /// Expects `all-MiniLM-L6-v2.onnx` and `tokenizer.json` in `model_dir`,
+    /// which may be read-only.
+    #[must_use]
+    pub fn from_model_dir(model_dir: impl AsRef<Path>, num_workers: usize) -> Self {
+        let model_dir = model_dir.as_ref();
         Self {
-            model_path: models_dir.join("all-MiniLM-L6-v2.onnx"),
-            tokenizer_path: models_dir.join("tokenizer.json"),
+            model_path: model_dir.join("all-MiniLM-L6-v2.onnx"),
+            tokenizer_path: model_dir.join("tokenizer.json"),
             num_workers,
+            query_cache_size: 0,
+            onnx: OnnxOptions::default(),
+            long_chunk_strategy: LongChunkStrategy::default(),
+            max_worker_restarts: DEFAULT_MAX_WORKER_RESTARTS,
+            embed_timeout_ms: DEFAULT_EMBED_TIMEOUT_MS,
+            templates: EmbeddingTemplates::default(),
+        }
+    }
+
+    /// Name and dimension of the configured model.
+    ///
+    /// The name is the model file name without its extension.
+    #[must_use]
+    pub fn model_info(&self) -> EmbeddingModelInfo {
+        let name = self.model_path.file_stem().map_or_else(
+            || self.model_path.to_string_lossy().into_owned(),
+            |stem| stem.to_string_lossy().into_owned(),
+        );
+
+        EmbeddingModelInfo {
+            name,
+            dimension: EMBEDDING_DIM,
+        }
+    }
+
+    /// Set the query embedding cache size (0 disables the cache).
+    #[must_use]
+    pub const fn with_query_cache_size(mut self, size: usize) -> Self {
+        self.query_cache_size = size;
+        self
+    }
+
+    /// Set the ONNX Runtime session options.
+    #[must_use]
+    pub const fn with_onnx_options(mut self, onnx: OnnxOptions) -> Self {
+        self.onnx = onnx;
+        self
+    }
+
+    /// Set how inputs longer than the model's sequence length are embedded.
+    #[must_use]
+    pub const fn with_long_chunk_strategy(mut self, strategy: LongChunkStrategy) -> Self {
+        self.long_chunk_strategy = strategy;
+        self
+    }
+
+    /// Set how many crashed workers are replaced before the pool is degraded.
+    #[must_use]
+    pub const fn with_max_worker_restarts(mut self, max_restarts: usize) -> Self {
+        self.max_worker_restarts = max_restarts;
+        self
+    }
+
+    /// Set how long to wait for an embedding (0 waits forever).
+    #[must_use]
+    pub const fn with_embed_timeout_ms(mut self, timeout_ms: u64) -> Self {
+        self.embed_timeout_ms = timeout_ms;
+        self
+    }
+
+    /// Set the input templates for each record type.
+    #[must_use]
+    pub fn with_templates(mut self, templates: EmbeddingTemplates) -> Self {
+        self.templates = templates;
+        self
+    }
+}
+
+/// Progress of loading the embedding service, as reported by
+/// [`EmbeddingService::init_state`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, utoipa::ToSchema)]
+#[serde(tag = "state", content = "reason", rename_all = "snake_case")]
+pub enum InitState {
+    /// `init()` has not been called, or the service was shut down.
+    NotStarted,
+    /// Loading the ONNX model.
+    LoadingModel,
+    /// Loading the tokenizer.
+    LoadingTokenizer,
+    /// Workers are running; waiting for the warmup inference to succeed.
+    WarmingUp,
+    /// Loaded and warmed up.
+    Ready,
+    /// Loading or warmup failed with the given reason.
+    Failed(String),
+}
+
+impl InitState {
+    /// Short name of the state, as used in status output.
+    #[must_use]
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::NotStarted => "not_started",
+            Self::LoadingModel => "loading_model",
+            Self::LoadingTokenizer => "loading_tokenizer",
+            Self::WarmingUp => "warming_up",
+            Self::Ready => "ready",
+            Self::Failed(_) => "failed",
+        }
+    }
+
+    /// Encode the state for the atomic phase; the failure reason is kept
+    /// alongside it.
+    const fn phase(&self) -> u8 {
+        match self {
+            Self::NotStarted => 0,
+            Self::LoadingModel => 1,
+            Self::LoadingTokenizer => 2,
+            Self::WarmingUp => 3,
+            Self::Ready => 4,
+            Self::Failed(_) => 5,
         }
     }
 }
@@ -53,20 +203,31 @@ pub struct EmbeddingService {
 struct EmbeddingServiceInner {
     worker: RwLock<Option<EmbeddingWorker>>,
     config: EmbeddingConfig,
-    initialized: std::sync::atomic::AtomicBool,
+    /// Current [`InitState`], encoded by [`InitState::phase`].
+    init_phase: std::sync::atomic::AtomicU8,
+    /// Reason for the last failed `init()` or `warmup()`.
+    init_failure: parking_lot::Mutex<Option<String>>,
+    /// Signaled when the service becomes ready or fails to load.
+    ready: tokio::sync::Notify,
+    query_cache: Option<QueryCache>,
 }
 
 impl EmbeddingService {
     /// Create a new embedding service.
     ///
-    /// The service is created but not initialized. Call `init()` to start workers.
+    /// The service is created but not initialized. Call `init()` to start workers
+    /// and `warmup()` to mark it ready.
     #[must_use]
     pub fn new(config: EmbeddingConfig) -> Self {
         Self {
             inner: Arc::new(EmbeddingServiceInner {
                 worker: RwLock::new(None),
+                query_cache: (config.query_cache_size > 0)
+                    .then(|| QueryCache::new(config.query_cache_size)),
                 config,
-                initialized: std::sync::atomic::AtomicBool::new(false),
+                init_phase: std::sync::atomic::AtomicU8::new(InitState::NotStarted.phase()),
+                init_failure: parking_lot::Mutex::new(None),
+                ready: tokio::sync::Notify::new(),
             }),
         }
     }
@@ -77,8 +238,18 @@ impl EmbeddingService {
     ///
     /// # Errors
     ///
-    /// Returns an error if initialization fails.
+    /// Returns an error if initialization fails; the failure is also
+    /// reported by [`init_state`](Self::init_state).
     pub async fn init(&self) -> Result<()> {
+        let result = self.load().await;
+        if let Err(e) = &result {
+            self.set_init_state(InitState::Failed(e.to_string()));
+        }
+        result
+    }
+
+    /// Load the model and tokenizer and start the worker pool.
+    async fn load(&self) -> Result<()> {
         {
             let mut worker_guard = self.inner.worker.write().await;
 
@@ -87,55 +258,265 @@ impl EmbeddingService {
             }
 
             tracing::info!("Initializing embedding service");
+            self.set_init_state(InitState::LoadingModel);
 
             // Load model and extract session (drop model so Arc refcount = 1
             // for try_unwrap in the worker pool)
-            let model = EmbeddingModel::load(&self.inner.config.model_path)?;
+            let model = EmbeddingModel::load_with_options(
+                &self.inner.config.model_path,
+                &self.inner.config.onnx,
+            )?;
             let session = model.session();
             drop(model);
 
             // Load tokenizer
-            let tokenizer =
+            self.set_init_state(InitState::LoadingTokenizer);
+            let mut tokenizer =
                 Tokenizer::from_file(&self.inner.config.tokenizer_path).map_err(|e| {
                     EmbeddingError::Tokenization(format!("failed to load tokenizer: {e}"))
                 })?;
 
+            // The worker pads batches and applies the long chunk strategy
+            // itself, so it needs the full untruncated token sequence
+            tokenizer
+                .with_truncation(None)
+                .map_err(|e| {
+                    EmbeddingError::Tokenization(format!("failed to disable truncation: {e}"))
+                })?
+                .with_padding(None);
+
             // Create worker pool
-            let worker =
-                EmbeddingWorker::new(session, Arc::new(tokenizer), self.inner.config.num_workers)?;
+            let worker = EmbeddingWorker::new(
+                session,
+                Arc::new(tokenizer),
+                self.inner.config.num_workers,
+                self.inner.config.long_chunk_strategy,
+                self.inner.config.max_worker_restarts,
+            )?;
 
             *worker_guard = Some(worker);
+            drop(worker_guard);
+            self.set_init_state(InitState::WarmingUp);
         }
-        self.inner
-            .initialized
-            .store(true, std::sync::atomic::Ordering::Release);
 
         tracing::info!("Embedding service initialized");
         Ok(())
     }
 
-    /// Check if the service is initialized.
-    #[must_use]
-    pub fn is_initialized(&self) -> bool {
+    /// Warm up the model with a dummy inference.
+    ///
+    /// The first inference after loading pays for graph optimization and
+    /// buffer allocation. Running it here keeps that cost off the first real
+    /// query. The service only reports itself as initialized once warmup
+    /// succeeds.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the service has not been loaded or inference fails.
+    pub async fn warmup(&self) -> Result<()> {
+        let start = std::time::Instant::now();
+        if let Err(e) = self.embed_one("warmup").await {
+            self.set_init_state(InitState::Failed(format!("warmup failed: {e}")));
+            return Err(e);
+        }
+
+        self.mark_ready();
+
+        tracing::info!(
+            latency_ms = start.elapsed().as_millis(),
+            "Embedding model warmed up"
+        );
+        Ok(())
+    }
+
+    /// Mark the service ready and wake anyone in `wait_until_ready`.
+    fn mark_ready(&self) {
+        self.set_init_state(InitState::Ready);
+    }
+
+    /// Record a new [`InitState`], waking `wait_until_ready` callers when
+    /// loading has finished one way or the other.
+    fn set_init_state(&self, state: InitState) {
+        let phase = state.phase();
+        let settled = matches!(state, InitState::Ready | InitState::Failed(_));
+        if let InitState::Failed(reason) = state {
+            tracing::warn!(reason = %reason, "Embedding service failed to load");
+            *self.inner.init_failure.lock() = Some(reason);
+        }
         self.inner
-            .initialized
+            .init_phase
+            .store(phase, std::sync::atomic::Ordering::Release);
+        if settled {
+            self.inner.ready.notify_waiters();
+        }
+    }
+
+    /// How far loading has got.
+    ///
+    /// Distinguishes a model that is still loading from one that failed, which
+    /// `is_initialized` alone can't.
+    #[must_use]
+    pub fn init_state(&self) -> InitState {
+        match self
+            .inner
+            .init_phase
             .load(std::sync::atomic::Ordering::Acquire)
+        {
+            0 => InitState::NotStarted,
+            1 => InitState::LoadingModel,
+            2 => InitState::LoadingTokenizer,
+            3 => InitState::WarmingUp,
+            4 => InitState::Ready,
+            _ => InitState::Failed(self.inner.init_failure.lock().clone().unwrap_or_default()),
+        }
+    }
+
+    /// Wait up to `timeout` for the service to finish loading and warming up.
+    ///
+    /// Returns `true` once the service is ready, or `false` on timeout or if
+    /// loading failed.
+    pub async fn wait_until_ready(&self, timeout: std::time::Duration) -> bool {
+        let ready = async {
+            loop {
+                // Register before checking the state so a concurrent
+                // `mark_ready` can't slip between the check and the wait
+                let notified = self.inner.ready.notified();
+                tokio::pin!(notified);
+                notified.as_mut().enable();
+
+                match self.init_state() {
+                    InitState::Ready => return true,
+                    InitState::Failed(_) => return false,
+                    _ => notified.await,
+                }
+            }
+        };
+
+        tokio::time::timeout(timeout, ready).await.unwrap_or(false)
+    }
+
+    /// Shut down the worker pool.
+    ///
+    /// Waits for in-flight embedding calls to complete, lets workers drain
+    /// their queue, and joins the worker threads. The service reports itself
+    /// as uninitialized afterwards.
+    pub async fn shutdown(&self) {
+        let worker = self.inner.worker.write().await.take();
+        self.set_init_state(InitState::NotStarted);
+
+        if let Some(worker) = worker {
+            match tokio::task::spawn_blocking(move || worker.shutdown()).await {
+                Ok(stopped) => tracing::info!(workers = stopped, "Embedding workers stopped"),
+                Err(e) => tracing::warn!(error = %e, "Embedding worker shutdown failed"),
+            }
+        }
+    }
+
+    /// Health of the worker pool, or `None` when it isn't running (or is
+    /// being started or stopped).
+    #[must_use]
+    pub fn worker_health(&self) -> Option<WorkerHealth> {
+        self.inner
+            .worker
+            .try_read()
+            .ok()?
+            .as_ref()
+            .map(EmbeddingWorker::health)
+    }
+
+    /// Check if the service is initialized and warmed up.
+    #[must_use]
+    pub fn is_initialized(&self) -> bool {
+        self.init_state() == InitState::Ready
     }
 
     /// Generate embedding for a single text.
     ///
+    /// Consults the query cache first when one is configured.
+    ///
     /// # Errors
     ///
     /// Returns an error if not initialized or embedding fails.
     #[allow(clippy::significant_drop_tightening)]
     pub async fn embed_one(&self, text: impl Into<String>) -> Result<Vec<f32>> {
-        {
+        let text = text.into();
+
+        if let Some(cache) = &self.inner.query_cache {
+            if let Some(embedding) = cache.get(&text) {
+                crate::server::QUERY_CACHE_HITS.inc();
+                return Ok(embedding);
+            }
+            crate::server::QUERY_CACHE_MISSES.inc();
+        }
+
+        let started = std::time::Instant::now();
+        let embedding = {
             let worker_guard = self.inner.worker.read().await;
             let worker = worker_guard
                 .as_ref()
                 .ok_or_else(|| EmbeddingError::WorkerPool("service not initialized".to_string()))?;
-            worker.embed_one(text.into()).await
+            self.with_timeout(worker.embed_one(text.clone())).await?
+        };
+        crate::server::observability::timings::record_embedding(started.elapsed());
+
+        if let Some(cache) = &self.inner.query_cache {
+            cache.insert(text, embedding.clone());
         }
+
+        Ok(embedding)
+    }
+
+    /// Await `embedding`, failing with `EmbeddingError::Timeout` once the
+    /// configured timeout expires.
+    ///
+    /// The worker still finishes the abandoned job; only the caller stops
+    /// waiting, so a wedged model can't hang searches indefinitely.
+    async fn with_timeout<T>(&self, embedding: impl Future<Output = Result<T>>) -> Result<T> {
+        let timeout_ms = self.inner.config.embed_timeout_ms;
+        if timeout_ms == 0 {
+            return embedding.await;
+        }
+
+        tokio::time::timeout(Duration::from_millis(timeout_ms), embedding)
+            .await
+            .unwrap_or_else(|_| {
+                tracing::warn!(timeout_ms, "Embedding request timed out");
+                Err(EmbeddingError::Timeout(timeout_ms).into())
+            })
+    }
+
+    /// Number of embedding requests waiting for a worker.
+    ///
+    /// Zero when the worker pool isn't running. Also updates the
+    /// `nellie_embedding_queue_depth` gauge.
+    pub async fn queue_depth(&self) -> usize {
+        let depth = self
+            .inner
+            .worker
+            .read()
+            .await
+            .as_ref()
+            .map_or(0, EmbeddingWorker::queue_depth);
+        crate::server::EMBEDDING_QUEUE_DEPTH.set(i64::try_from(depth).unwrap_or(i64::MAX));
+        depth
+    }
+
+    /// Name and dimension of the model this service loads.
+    #[must_use]
+    pub fn model_info(&self) -> EmbeddingModelInfo {
+        self.inner.config.model_info()
+    }
+
+    /// Templates applied to inputs before embedding.
+    #[must_use]
+    pub fn templates(&self) -> &EmbeddingTemplates {
+        &self.inner.config.templates
+    }
+
+    /// Get the query embedding cache, if enabled.
+    #[must_use]
+    pub fn query_cache(&self) -> Option<&QueryCache> {
+        self.inner.query_cache.as_ref()
     }
 
     /// Generate embeddings for multiple texts.
@@ -145,13 +526,16 @@ impl EmbeddingService {
     /// Returns an error if not initialized or embedding fails.
     #[allow(clippy::significant_drop_tightening)]
     pub async fn embed_batch(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
-        {
+        let started = std::time::Instant::now();
+        let embeddings = {
             let worker_guard = self.inner.worker.read().await;
             let worker = worker_guard
                 .as_ref()
                 .ok_or_else(|| EmbeddingError::WorkerPool("service not initialized".to_string()))?;
-            worker.embed(texts).await
-        }
+            self.with_timeout(worker.embed(texts)).await
+        };
+        crate::server::observability::timings::record_embedding(started.elapsed());
+        embeddings
     }
 
     /// Generate embeddings for texts, returning results paired with original texts.
@@ -168,7 +552,7 @@ impl EmbeddingService {
 impl std::fmt::Debug for EmbeddingService {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("EmbeddingService")
-            .field("initialized", &self.is_initialized())
+            .field("init_state", &self.init_state())
             .field("config", &self.inner.config)
             .finish()
     }
@@ -196,14 +580,7 @@ pub fn placeholder_embedding(text: &str) -> Vec<f32> {
         embedding.push(value);
     }
 
-    // L2 normalize
-    let norm: f32 = embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
-    if norm > 0.0 {
-        for v in &mut embedding {
-            *v /= norm;
-        }
-    }
-
+    super::worker::l2_normalize(&mut embedding);
     embedding
 }
 
@@ -223,6 +600,57 @@ mod tests {
             "/var/lib/nellie/models/tokenizer.json"
         );
         assert_eq!(config.num_workers, 4);
+        assert_eq!(config.query_cache_size, 0);
+        assert_eq!(
+            EmbeddingConfig::from_model_dir("/opt/nellie/models", 4)
+                .model_path
+                .to_string_lossy(),
+            "/opt/nellie/models/all-MiniLM-L6-v2.onnx"
+        );
+        assert_eq!(config.onnx, OnnxOptions::default());
+        assert_eq!(config.long_chunk_strategy, LongChunkStrategy::Truncate);
+        assert_eq!(
+            config
+                .clone()
+                .with_long_chunk_strategy(LongChunkStrategy::MeanPool)
+                .long_chunk_strategy,
+            LongChunkStrategy::MeanPool
+        );
+        assert_eq!(config.max_worker_restarts, DEFAULT_MAX_WORKER_RESTARTS);
+        assert_eq!(
+            config
+                .clone()
+                .with_max_worker_restarts(0)
+                .max_worker_restarts,
+            0
+        );
+        assert_eq!(config.embed_timeout_ms, DEFAULT_EMBED_TIMEOUT_MS);
+        assert_eq!(config.clone().with_embed_timeout_ms(0).embed_timeout_ms, 0);
+
+        let onnx = OnnxOptions {
+            intra_threads: 4,
+            inter_threads: 2,
+            execution_provider: super::super::ExecutionProvider::CoreMl,
+        };
+        assert_eq!(config.with_onnx_options(onnx).onnx, onnx);
+    }
+
+    #[test]
+    fn test_model_info() {
+        let service = EmbeddingService::new(EmbeddingConfig::from_data_dir("/tmp", 1));
+        let info = service.model_info();
+        assert_eq!(info.name, "all-MiniLM-L6-v2");
+        assert_eq!(info.dimension, 384);
+    }
+
+    #[test]
+    fn test_query_cache_optional() {
+        let service = EmbeddingService::new(EmbeddingConfig::from_data_dir("/tmp", 1));
+        assert!(service.query_cache().is_none());
+
+        let config = EmbeddingConfig::from_data_dir("/tmp", 1).with_query_cache_size(16);
+        let service = EmbeddingService::new(config);
+        assert_eq!(service.query_cache().map(QueryCache::capacity), Some(16));
     }
 
     #[test]
@@ -230,6 +658,7 @@ mod tests {
         let config = EmbeddingConfig::from_data_dir("/tmp", 1);
         let service = EmbeddingService::new(config);
         assert!(!service.is_initialized());
+        assert!(service.worker_health().is_none());
     }
 
     #[test]
@@ -261,4 +690,120 @@ mod tests {
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("not initialized"));
     }
+
+    #[tokio::test]
+    async fn test_embed_times_out_on_slow_worker() {
+        let config = EmbeddingConfig::from_data_dir("/tmp", 1).with_embed_timeout_ms(20);
+        let service = EmbeddingService::new(config);
+        let processor: super::super::worker::Processor = Arc::new(|texts: &[String]| {
+            std::thread::sleep(Duration::from_millis(300));
+            Ok(vec![vec![1.0, 0.0]; texts.len()])
+        });
+        *service.inner.worker.write().await =
+            Some(EmbeddingWorker::start(processor, 1, 0).unwrap());
+
+        let err = service.embed_one("slow").await.unwrap_err();
+        assert!(matches!(
+            err,
+            crate::Error::Embedding(EmbeddingError::Timeout(20))
+        ));
+        assert_eq!(
+            err.to_string(),
+            "embedding error: embedding timed out after 20 ms"
+        );
+        assert!(service.embed_batch(vec!["slow".to_string()]).await.is_err());
+
+        service.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_without_init() {
+        let config = EmbeddingConfig::from_data_dir("/tmp", 1);
+        let service = EmbeddingService::new(config);
+
+        service.shutdown().await;
+        assert!(!service.is_initialized());
+    }
+
+    #[tokio::test]
+    async fn test_warmup_without_init() {
+        let config = EmbeddingConfig::from_data_dir("/tmp", 1);
+        let service = EmbeddingService::new(config);
+
+        assert!(service.warmup().await.is_err());
+        assert!(!service.is_initialized());
+        assert!(matches!(service.init_state(), InitState::Failed(_)));
+    }
+
+    #[tokio::test]
+    async fn test_init_state_reports_load_failure() {
+        let service = EmbeddingService::new(EmbeddingConfig::from_data_dir("/nonexistent", 1));
+        assert_eq!(service.init_state(), InitState::NotStarted);
+
+        let err = service.init().await.unwrap_err();
+        let InitState::Failed(reason) = service.init_state() else {
+            panic!("expected failed state, got {:?}", service.init_state());
+        };
+        assert_eq!(reason, err.to_string());
+        assert!(!service.is_initialized());
+
+        // Waiters give up as soon as loading fails instead of timing out
+        assert!(
+            !service
+                .wait_until_ready(std::time::Duration::from_secs(60))
+                .await
+        );
+
+        assert_eq!(
+            serde_json::to_value(service.init_state()).unwrap(),
+            serde_json::json!({"state": "failed", "reason": reason})
+        );
+        assert_eq!(
+            serde_json::to_value(InitState::WarmingUp).unwrap(),
+            serde_json::json!({"state": "warming_up"})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_init_state_after_warmup_and_shutdown() {
+        let service = EmbeddingService::new(EmbeddingConfig::from_data_dir("/tmp", 1));
+        let processor: super::super::worker::Processor =
+            Arc::new(|texts: &[String]| Ok(vec![vec![1.0, 0.0]; texts.len()]));
+        *service.inner.worker.write().await =
+            Some(EmbeddingWorker::start(processor, 1, 0).unwrap());
+        service.set_init_state(InitState::WarmingUp);
+        assert_eq!(service.init_state().as_str(), "warming_up");
+
+        service.warmup().await.unwrap();
+        assert_eq!(service.init_state(), InitState::Ready);
+        assert!(service.is_initialized());
+
+        service.shutdown().await;
+        assert_eq!(service.init_state(), InitState::NotStarted);
+    }
+
+    #[tokio::test]
+    async fn test_wait_until_ready() {
+        let service = EmbeddingService::new(EmbeddingConfig::from_data_dir("/tmp", 1));
+        assert!(
+            !service
+                .wait_until_ready(std::time::Duration::from_millis(10))
+                .await
+        );
+
+        let waiter = {
+            let service = service.clone();
+            tokio::spawn(async move {
+                service
+                    .wait_until_ready(std::time::Duration::from_secs(5))
+                    .await
+            })
+        };
+        tokio::task::yield_now().await;
+        service.mark_ready();
+        assert!(waiter.await.unwrap());
+
+        // Already ready: returns immediately
+        assert!(service.wait_until_ready(std::time::Duration::ZERO).await);
+    }
 }
@@ -0,0 +1,256 @@
+//! Input templates applied to text before it is embedded.
+//!
+//! Instruction-tuned embedding models expect inputs shaped a particular way,
+//! e.g. `search_document: ` before stored text and `search_query: ` before
+//! queries. Each record type has a document template, used when storing, and
+//! a query template, used when searching it. The two must be kept consistent:
+//! vectors built with one template only compare well with queries built with
+//! its counterpart.
+
+/// Placeholders a code template may use. The first is required.
+const CODE_FIELDS: &[&str] = &["content", "language", "file_path"];
+
+/// Placeholders a lesson template may use. The first is required.
+const LESSON_FIELDS: &[&str] = &["content", "title"];
+
+/// Placeholders a checkpoint template may use. The first is required.
+const CHECKPOINT_FIELDS: &[&str] = &["working_on"];
+
+/// Placeholders a query template may use. The first is required.
+const QUERY_FIELDS: &[&str] = &["query"];
+
+/// Templates for each record type, with `{name}` placeholders.
+///
+/// A literal `\n` in a template is rendered as a newline, so templates can
+/// be given on one line in flags and environment variables. The defaults
+/// reproduce the untemplated inputs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmbeddingTemplates {
+    /// Code chunks: `{content}`, `{language}`, `{file_path}`.
+    pub code: String,
+    /// `search_code` queries: `{query}`.
+    pub code_query: String,
+    /// Lessons: `{title}`, `{content}`.
+    pub lesson: String,
+    /// `search_lessons` queries: `{query}`.
+    pub lesson_query: String,
+    /// Checkpoints: `{working_on}`.
+    pub checkpoint: String,
+    /// `search_checkpoints` queries: `{query}`.
+    pub checkpoint_query: String,
+}
+
+impl Default for EmbeddingTemplates {
+    fn default() -> Self {
+        Self {
+            code: "{content}".to_string(),
+            code_query: "{query}".to_string(),
+            lesson: "{title}\n{content}".to_string(),
+            lesson_query: "{query}".to_string(),
+            checkpoint: "{working_on}".to_string(),
+            checkpoint_query: "{query}".to_string(),
+        }
+    }
+}
+
+impl EmbeddingTemplates {
+    /// Check every template uses only its own placeholders and includes
+    /// the one carrying the text.
+    ///
+    /// # Errors
+    ///
+    /// Returns a message naming the offending template.
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        let templates = [
+            ("code_template", &self.code, CODE_FIELDS),
+            ("code_query_template", &self.code_query, QUERY_FIELDS),
+            ("lesson_template", &self.lesson, LESSON_FIELDS),
+            ("lesson_query_template", &self.lesson_query, QUERY_FIELDS),
+            ("checkpoint_template", &self.checkpoint, CHECKPOINT_FIELDS),
+            (
+                "checkpoint_query_template",
+                &self.checkpoint_query,
+                QUERY_FIELDS,
+            ),
+        ];
+        for (name, template, fields) in templates {
+            let placeholders = placeholders(template);
+            if let Some(unknown) = placeholders.iter().find(|p| !fields.contains(p)) {
+                return Err(format!(
+                    "{name}: unknown placeholder {{{unknown}}} (expected {})",
+                    fields
+                        .iter()
+                        .map(|f| format!("{{{f}}}"))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ));
+            }
+            if !placeholders.contains(&fields[0]) {
+                return Err(format!("{name}: must include {{{}}}", fields[0]));
+            }
+        }
+        Ok(())
+    }
+
+    /// Text to embed for a code chunk.
+    #[must_use]
+    pub fn code(&self, content: &str, language: Option<&str>, file_path: &str) -> String {
+        render(
+            &self.code,
+            &[
+                ("content", content),
+                ("language", language.unwrap_or_default()),
+                ("file_path", file_path),
+            ],
+        )
+    }
+
+    /// Text to embed for a code search query.
+    #[must_use]
+    pub fn code_query(&self, query: &str) -> String {
+        render(&self.code_query, &[("query", query)])
+    }
+
+    /// Text to embed for a lesson.
+    #[must_use]
+    pub fn lesson(&self, title: &str, content: &str) -> String {
+        render(&self.lesson, &[("title", title), ("content", content)])
+    }
+
+    /// Text to embed for a lesson search query.
+    #[must_use]
+    pub fn lesson_query(&self, query: &str) -> String {
+        render(&self.lesson_query, &[("query", query)])
+    }
+
+    /// Text to embed for a checkpoint.
+    #[must_use]
+    pub fn checkpoint(&self, working_on: &str) -> String {
+        render(&self.checkpoint, &[("working_on", working_on)])
+    }
+
+    /// Text to embed for a checkpoint search query.
+    #[must_use]
+    pub fn checkpoint_query(&self, query: &str) -> String {
+        render(&self.checkpoint_query, &[("query", query)])
+    }
+}
+
+/// Names of the `{name}` placeholders in `template`.
+fn placeholders(template: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rest = &rest[start + 1..];
+        if let Some((name, after)) = rest.split_once('}') {
+            if is_placeholder_name(name) {
+                names.push(name);
+                rest = after;
+            }
+        }
+    }
+    names
+}
+
+/// Whether `name` looks like a placeholder rather than literal braces.
+fn is_placeholder_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Substitute `fields` into `template` and expand `\n` escapes.
+///
+/// Substituted values are inserted verbatim; placeholders without a value
+/// are left as written.
+fn render(template: &str, fields: &[(&str, &str)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(i) = rest.find(['{', '\\']) {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+
+        if let Some(after) = rest.strip_prefix("\\n") {
+            out.push('\n');
+            rest = after;
+            continue;
+        }
+        let substituted = rest
+            .strip_prefix('{')
+            .and_then(|inner| inner.split_once('}'))
+            .and_then(|(name, after)| {
+                fields
+                    .iter()
+                    .find(|(field, _)| *field == name)
+                    .map(|(_, value)| (*value, after))
+            });
+        if let Some((value, after)) = substituted {
+            out.push_str(value);
+            rest = after;
+        } else {
+            out.push_str(&rest[..1]);
+            rest = &rest[1..];
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_match_untemplated_inputs() {
+        let templates = EmbeddingTemplates::default();
+        assert!(templates.validate().is_ok());
+        assert_eq!(
+            templates.code("fn a() {}", Some("rust"), "/a.rs"),
+            "fn a() {}"
+        );
+        assert_eq!(templates.code_query("retry"), "retry");
+        assert_eq!(templates.lesson("Title", "Body"), "Title\nBody");
+        assert_eq!(templates.checkpoint("Refactor"), "Refactor");
+    }
+
+    #[test]
+    fn test_render_fields_and_escapes() {
+        let templates = EmbeddingTemplates {
+            code: "search_document: {file_path} ({language})\\n{content}".to_string(),
+            code_query: "search_query: {query}".to_string(),
+            ..EmbeddingTemplates::default()
+        };
+        assert!(templates.validate().is_ok());
+        assert_eq!(
+            templates.code("fn a() { {x} }", Some("rust"), "/src/a.rs"),
+            "search_document: /src/a.rs (rust)\nfn a() { {x} }"
+        );
+        assert_eq!(templates.code("x", None, "/a"), "search_document: /a ()\nx");
+        assert_eq!(render("\\t{query}", &[("query", "q")]), "\\tq");
+        assert_eq!(templates.code_query("retry"), "search_query: retry");
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_placeholders() {
+        let templates = EmbeddingTemplates {
+            lesson: "{title}\n{content}\n{tags}".to_string(),
+            ..EmbeddingTemplates::default()
+        };
+        let err = templates.validate().unwrap_err();
+        assert!(err.starts_with("lesson_template: unknown placeholder {tags}"));
+
+        let templates = EmbeddingTemplates {
+            code_query: "search_query: ".to_string(),
+            ..EmbeddingTemplates::default()
+        };
+        assert_eq!(
+            templates.validate().unwrap_err(),
+            "code_query_template: must include {query}"
+        );
+
+        // Literal braces that aren't placeholder names are fine
+        let templates = EmbeddingTemplates {
+            code: "{ {content} }".to_string(),
+            ..EmbeddingTemplates::default()
+        };
+        assert!(templates.validate().is_ok());
+    }
+}
@@ -1,19 +1,62 @@
 //! Embedding worker thread pool.
 //!
 //! Runs ONNX inference in a dedicated thread pool to avoid blocking the async runtime.
+//! A supervisor thread replaces workers that panic, up to a restart budget.
 
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 
-use crossbeam_channel::{bounded, Receiver, Sender};
+use crossbeam_channel::{bounded, unbounded, Receiver, Sender};
 use ort::session::Session;
 use ort::value::Value;
 use parking_lot::Mutex;
+use serde::Serialize;
 use tokenizers::Tokenizer;
 
 use super::model::{EMBEDDING_DIM, MAX_SEQ_LENGTH};
 use crate::error::EmbeddingError;
 use crate::Result;
 
+/// How inputs longer than `MAX_SEQ_LENGTH` tokens are embedded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LongChunkStrategy {
+    /// Embed only the first `MAX_SEQ_LENGTH` tokens.
+    #[default]
+    Truncate,
+    /// Embed consecutive windows of the input and average them, so the
+    /// whole input is represented at the cost of extra inference.
+    MeanPool,
+}
+
+impl LongChunkStrategy {
+    /// Convert strategy to string representation.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Truncate => "truncate",
+            Self::MeanPool => "mean_pool",
+        }
+    }
+}
+
+impl std::str::FromStr for LongChunkStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "truncate" => Ok(Self::Truncate),
+            "mean_pool" => Ok(Self::MeanPool),
+            _ => Err(format!(
+                "unknown long chunk strategy '{s}' (expected 'truncate' or 'mean_pool')"
+            )),
+        }
+    }
+}
+
+/// Default number of crashed workers the pool replaces before it reports
+/// itself degraded.
+pub const DEFAULT_MAX_WORKER_RESTARTS: usize = 5;
+
 /// Request to generate embeddings.
 struct EmbeddingRequest {
     /// Texts to embed.
@@ -22,10 +65,69 @@ struct EmbeddingRequest {
     response_tx: tokio::sync::oneshot::Sender<Result<Vec<Vec<f32>>>>,
 }
 
+/// Turns a batch of texts into embeddings on a worker thread.
+pub(super) type Processor = Arc<dyn Fn(&[String]) -> Result<Vec<Vec<f32>>> + Send + Sync>;
+
+/// Health of the worker pool, as reported by `get_status`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct WorkerHealth {
+    /// Workers the pool was started with
+    pub workers: usize,
+    /// Workers currently running
+    pub alive: usize,
+    /// Crashed workers replaced so far
+    pub restarts: usize,
+    /// Replacements allowed before the pool gives up
+    pub max_restarts: usize,
+    /// A crashed worker could not be replaced, so the pool is short of
+    /// capacity (or, with no workers alive, failing every request)
+    pub degraded: bool,
+}
+
+/// Counters behind [`WorkerHealth`], updated by the supervisor.
+#[derive(Debug, Default)]
+struct PoolHealth {
+    workers: usize,
+    max_restarts: usize,
+    alive: AtomicUsize,
+    restarts: AtomicUsize,
+    degraded: AtomicBool,
+}
+
+/// Everything needed to spawn a worker, kept by the supervisor for restarts.
+struct WorkerContext {
+    processor: Processor,
+    request_rx: Arc<Mutex<Receiver<EmbeddingRequest>>>,
+    exit_tx: Sender<WorkerExit>,
+}
+
+/// Sent to the supervisor when a worker thread ends.
+struct WorkerExit {
+    index: usize,
+    panicked: bool,
+}
+
+/// Reports its worker's exit on drop, so a panicking worker is reported
+/// while it unwinds.
+struct ExitGuard {
+    index: usize,
+    exit_tx: Sender<WorkerExit>,
+}
+
+impl Drop for ExitGuard {
+    fn drop(&mut self) {
+        let _ = self.exit_tx.send(WorkerExit {
+            index: self.index,
+            panicked: std::thread::panicking(),
+        });
+    }
+}
+
 /// Worker pool for embedding generation.
 pub struct EmbeddingWorker {
     request_tx: Sender<EmbeddingRequest>,
-    _workers: Vec<std::thread::JoinHandle<()>>,
+    supervisor: std::thread::JoinHandle<usize>,
+    health: Arc<PoolHealth>,
 }
 
 impl EmbeddingWorker {
@@ -36,53 +138,118 @@ impl EmbeddingWorker {
     /// * `session` - ONNX session for inference
     /// * `tokenizer` - Tokenizer for text processing
     /// * `num_workers` - Number of worker threads
+    /// * `strategy` - How inputs longer than `MAX_SEQ_LENGTH` are embedded
+    /// * `max_restarts` - Crashed workers replaced before the pool is degraded
     ///
     /// # Errors
     ///
     /// Returns an error if worker creation fails.
-    #[allow(clippy::needless_pass_by_value)]
     pub fn new(
         session: Arc<Session>,
         tokenizer: Arc<Tokenizer>,
         num_workers: usize,
+        strategy: LongChunkStrategy,
+        max_restarts: usize,
     ) -> Result<Self> {
-        let (request_tx, request_rx): (Sender<EmbeddingRequest>, Receiver<EmbeddingRequest>) =
-            bounded(100);
-
         // Unwrap Arc<Session> to get owned Session for mutable access in run().
         let session = Arc::try_unwrap(session).map_err(|_| {
             EmbeddingError::WorkerPool(
                 "session has multiple owners; cannot unwrap for worker pool".to_string(),
             )
         })?;
+        let session = Mutex::new(session);
 
-        let request_rx = Arc::new(Mutex::new(request_rx));
-        let session = Arc::new(Mutex::new(session));
-        let mut workers = Vec::with_capacity(num_workers);
+        let processor: Processor = Arc::new(move |texts: &[String]| {
+            process_request(&session, &tokenizer, strategy, texts)
+        });
+        let pool = Self::start(processor, num_workers, max_restarts)?;
 
-        for i in 0..num_workers {
-            let session = Arc::clone(&session);
-            let tokenizer = Arc::clone(&tokenizer);
-            let rx = Arc::clone(&request_rx);
-
-            let handle = std::thread::Builder::new()
-                .name(format!("embedding-worker-{i}"))
-                .spawn(move || {
-                    worker_loop(session, tokenizer, rx);
-                })
-                .map_err(|e| EmbeddingError::WorkerPool(format!("failed to spawn worker: {e}")))?;
+        tracing::info!(
+            num_workers,
+            long_chunk_strategy = strategy.as_str(),
+            max_restarts,
+            "Embedding worker pool started"
+        );
+
+        Ok(pool)
+    }
 
-            workers.push(handle);
+    /// Start `num_workers` threads running `processor`, plus their supervisor.
+    pub(super) fn start(
+        processor: Processor,
+        num_workers: usize,
+        max_restarts: usize,
+    ) -> Result<Self> {
+        let (request_tx, request_rx): (Sender<EmbeddingRequest>, Receiver<EmbeddingRequest>) =
+            bounded(100);
+        let (exit_tx, exit_rx) = unbounded();
+
+        let context = WorkerContext {
+            processor,
+            request_rx: Arc::new(Mutex::new(request_rx)),
+            exit_tx,
+        };
+        let health = Arc::new(PoolHealth {
+            workers: num_workers,
+            max_restarts,
+            ..PoolHealth::default()
+        });
+
+        let mut workers = Vec::with_capacity(num_workers);
+        for i in 0..num_workers {
+            workers.push(Some(spawn_worker(&context, i)?));
+            health.alive.fetch_add(1, Ordering::Relaxed);
         }
 
-        tracing::info!(num_workers, "Embedding worker pool started");
+        let supervisor_health = Arc::clone(&health);
+        let supervisor = std::thread::Builder::new()
+            .name("embedding-supervisor".to_string())
+            .spawn(move || supervise(&context, workers, &exit_rx, &supervisor_health))
+            .map_err(|e| EmbeddingError::WorkerPool(format!("failed to spawn supervisor: {e}")))?;
 
         Ok(Self {
             request_tx,
-            _workers: workers,
+            supervisor,
+            health,
         })
     }
 
+    /// Stop the worker pool.
+    ///
+    /// Closes the request channel so workers finish any queued requests and
+    /// exit, then joins their threads. Blocks until all workers have stopped.
+    ///
+    /// Returns the number of workers that exited cleanly.
+    #[must_use]
+    pub fn shutdown(self) -> usize {
+        let Self {
+            request_tx,
+            supervisor,
+            ..
+        } = self;
+        drop(request_tx);
+
+        supervisor.join().unwrap_or(0)
+    }
+
+    /// Current worker counts and restart budget.
+    #[must_use]
+    pub fn health(&self) -> WorkerHealth {
+        WorkerHealth {
+            workers: self.health.workers,
+            alive: self.health.alive.load(Ordering::Relaxed),
+            restarts: self.health.restarts.load(Ordering::Relaxed),
+            max_restarts: self.health.max_restarts,
+            degraded: self.health.degraded.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Number of requests waiting for a free worker.
+    #[must_use]
+    pub fn queue_depth(&self) -> usize {
+        self.request_tx.len()
+    }
+
     /// Generate embeddings for texts asynchronously.
     ///
     /// # Errors
@@ -97,9 +264,16 @@ impl EmbeddingWorker {
             .send(request)
             .map_err(|_| EmbeddingError::WorkerPool("worker pool closed".to_string()))?;
 
-        response_rx
+        let mut embeddings = response_rx
             .await
-            .map_err(|_| EmbeddingError::WorkerPool("worker dropped response".to_string()))?
+            .map_err(|_| EmbeddingError::WorkerPool("worker dropped response".to_string()))??;
+
+        // Scores assume unit-length vectors; this is a no-op for pooled output
+        for embedding in &mut embeddings {
+            l2_normalize(embedding);
+        }
+
+        Ok(embeddings)
     }
 
     /// Generate embedding for a single text.
@@ -116,13 +290,115 @@ impl EmbeddingWorker {
     }
 }
 
+/// Spawn worker thread `index`.
+fn spawn_worker(context: &WorkerContext, index: usize) -> Result<std::thread::JoinHandle<()>> {
+    let processor = Arc::clone(&context.processor);
+    let rx = Arc::clone(&context.request_rx);
+    let exit_tx = context.exit_tx.clone();
+
+    std::thread::Builder::new()
+        .name(format!("embedding-worker-{index}"))
+        .spawn(move || {
+            let guard = ExitGuard { index, exit_tx };
+            worker_loop(&processor, &rx);
+            drop(guard);
+        })
+        .map_err(|e| EmbeddingError::WorkerPool(format!("failed to spawn worker: {e}")).into())
+}
+
+/// Supervisor loop: joins workers as they exit and replaces those that
+/// panicked while the restart budget lasts.
+///
+/// Once no workers are left it fails queued and later requests until the
+/// pool shuts down, so callers get an error instead of waiting forever.
+/// Returns the number of workers that exited cleanly.
+fn supervise(
+    context: &WorkerContext,
+    mut workers: Vec<Option<std::thread::JoinHandle<()>>>,
+    exit_rx: &Receiver<WorkerExit>,
+    health: &PoolHealth,
+) -> usize {
+    let mut running = workers.len();
+    let mut clean = 0;
+
+    while running > 0 {
+        // The context holds a sender, so this only fails if it was dropped
+        let Ok(exit) = exit_rx.recv() else { break };
+        let joined = workers[exit.index]
+            .take()
+            .map(std::thread::JoinHandle::join);
+        health.alive.fetch_sub(1, Ordering::Relaxed);
+
+        if !exit.panicked {
+            clean += 1;
+            running -= 1;
+            continue;
+        }
+
+        let reason = joined.and_then(std::result::Result::err).map_or_else(
+            || "unknown panic".to_string(),
+            |payload| panic_message(payload.as_ref()),
+        );
+        tracing::error!(worker = exit.index, panic = %reason, "Embedding worker crashed");
+
+        if health.restarts.load(Ordering::Relaxed) < health.max_restarts {
+            match spawn_worker(context, exit.index) {
+                Ok(handle) => {
+                    workers[exit.index] = Some(handle);
+                    health.alive.fetch_add(1, Ordering::Relaxed);
+                    let restarts = health.restarts.fetch_add(1, Ordering::Relaxed) + 1;
+                    tracing::warn!(
+                        worker = exit.index,
+                        restarts,
+                        max_restarts = health.max_restarts,
+                        "Restarted embedding worker"
+                    );
+                    continue;
+                }
+                Err(e) => tracing::error!(error = %e, "Failed to restart embedding worker"),
+            }
+        }
+
+        running -= 1;
+        health.degraded.store(true, Ordering::Relaxed);
+        tracing::error!(
+            alive = health.alive.load(Ordering::Relaxed),
+            max_restarts = health.max_restarts,
+            "Embedding worker pool degraded: crashed worker not replaced"
+        );
+    }
+
+    // Drain until the pool shuts down; on a clean shutdown the queue is
+    // already empty and this returns at once
+    loop {
+        let request = {
+            let rx = context.request_rx.lock();
+            if let Ok(req) = rx.recv() {
+                req
+            } else {
+                break;
+            }
+        };
+        let _ = request.response_tx.send(Err(EmbeddingError::WorkerPool(
+            "embedding worker pool degraded: all workers crashed".to_string(),
+        )
+        .into()));
+    }
+
+    clean
+}
+
+/// Text of a panic payload, if it carries one.
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| (*s).to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "non-string panic payload".to_string())
+}
+
 /// Worker loop that processes embedding requests.
-#[allow(clippy::needless_pass_by_value)]
-fn worker_loop(
-    session: Arc<Mutex<Session>>,
-    tokenizer: Arc<Tokenizer>,
-    request_rx: Arc<Mutex<Receiver<EmbeddingRequest>>>,
-) {
+fn worker_loop(processor: &Processor, request_rx: &Mutex<Receiver<EmbeddingRequest>>) {
     loop {
         let request = {
             let rx = request_rx.lock();
@@ -134,7 +410,9 @@ fn worker_loop(
             }
         };
 
-        let result = process_request(&session, &tokenizer, &request.texts);
+        // A panic here drops the request, failing its caller, and is
+        // reported to the supervisor by the worker's exit guard
+        let result = processor(&request.texts);
 
         // Send response (ignore error if receiver dropped)
         let _ = request.response_tx.send(result);
@@ -143,8 +421,9 @@ fn worker_loop(
 
 /// Process a batch of texts and generate embeddings via ONNX inference.
 fn process_request(
-    session: &Arc<Mutex<Session>>,
+    session: &Mutex<Session>,
     tokenizer: &Tokenizer,
+    strategy: LongChunkStrategy,
     texts: &[String],
 ) -> Result<Vec<Vec<f32>>> {
     if texts.is_empty() {
@@ -156,30 +435,109 @@ fn process_request(
         .encode_batch(texts.to_vec(), true)
         .map_err(|e| EmbeddingError::Tokenization(format!("failed to tokenize: {e}")))?;
 
-    let batch_size = encodings.len();
-    let max_len = encodings
-        .iter()
-        .map(|e| e.get_ids().len())
-        .max()
-        .unwrap_or(0)
-        .min(MAX_SEQ_LENGTH);
+    // Split each text into model-sized segments, remembering which text
+    // each segment came from
+    let mut segments: Vec<Vec<i64>> = Vec::with_capacity(encodings.len());
+    let mut owners: Vec<usize> = Vec::with_capacity(encodings.len());
+    for (i, encoding) in encodings.iter().enumerate() {
+        let ids = encoding.get_ids();
+        if ids.len() > MAX_SEQ_LENGTH {
+            match strategy {
+                LongChunkStrategy::Truncate => {
+                    crate::server::EMBEDDINGS_TRUNCATED_TOTAL.inc();
+                    tracing::debug!(
+                        tokens = ids.len(),
+                        max = MAX_SEQ_LENGTH,
+                        "Truncating input to the model's sequence length"
+                    );
+                }
+                LongChunkStrategy::MeanPool => {
+                    tracing::debug!(
+                        tokens = ids.len(),
+                        max = MAX_SEQ_LENGTH,
+                        "Mean-pooling long input over multiple windows"
+                    );
+                }
+            }
+        }
+        for segment in split_tokens(ids, strategy) {
+            segments.push(segment);
+            owners.push(i);
+        }
+    }
+
+    let segment_embeddings = run_segments(session, &segments)?;
+
+    // Average the segments of each text; single-segment texts pass through
+    let mut embeddings = vec![Vec::new(); encodings.len()];
+    let mut counts = vec![0.0f32; encodings.len()];
+    for (embedding, owner) in segment_embeddings.into_iter().zip(owners) {
+        let target = &mut embeddings[owner];
+        if target.is_empty() {
+            *target = embedding;
+        } else {
+            for (t, v) in target.iter_mut().zip(embedding) {
+                *t += v;
+            }
+        }
+        counts[owner] += 1.0;
+    }
+    for (embedding, count) in embeddings.iter_mut().zip(counts) {
+        if count > 1.0 {
+            for v in embedding.iter_mut() {
+                *v /= count;
+            }
+            l2_normalize(embedding);
+        }
+    }
+
+    Ok(embeddings)
+}
+
+/// Split a tokenized input into sequences the model accepts.
+///
+/// Inputs within `MAX_SEQ_LENGTH` are returned whole. Longer inputs are cut
+/// to the first `MAX_SEQ_LENGTH` tokens under `Truncate`, or, under
+/// `MeanPool`, split into consecutive windows that each keep the leading
+/// `[CLS]` and trailing `[SEP]` tokens.
+fn split_tokens(ids: &[u32], strategy: LongChunkStrategy) -> Vec<Vec<i64>> {
+    let to_i64 = |ids: &[u32]| ids.iter().copied().map(i64::from).collect::<Vec<_>>();
+
+    if ids.len() <= MAX_SEQ_LENGTH {
+        return vec![to_i64(ids)];
+    }
+
+    match strategy {
+        LongChunkStrategy::Truncate => vec![to_i64(&ids[..MAX_SEQ_LENGTH])],
+        LongChunkStrategy::MeanPool => {
+            let (cls, body, sep) = (ids[0], &ids[1..ids.len() - 1], ids[ids.len() - 1]);
+            body.chunks(MAX_SEQ_LENGTH - 2)
+                .map(|window| {
+                    let mut segment = Vec::with_capacity(window.len() + 2);
+                    segment.push(i64::from(cls));
+                    segment.extend(window.iter().copied().map(i64::from));
+                    segment.push(i64::from(sep));
+                    segment
+                })
+                .collect()
+        }
+    }
+}
+
+/// Run ONNX inference over token sequences and mean-pool each one.
+fn run_segments(session: &Mutex<Session>, segments: &[Vec<i64>]) -> Result<Vec<Vec<f32>>> {
+    let batch_size = segments.len();
+    let max_len = segments.iter().map(Vec::len).max().unwrap_or(0);
 
     // Create padded input vectors (i64 is standard for BERT-like models)
     let mut input_ids_vec: Vec<i64> = vec![0; batch_size * max_len];
     let mut attention_mask_vec: Vec<i64> = vec![0; batch_size * max_len];
-    let mut token_type_ids_vec: Vec<i64> = vec![0; batch_size * max_len];
+    let token_type_ids_vec: Vec<i64> = vec![0; batch_size * max_len];
 
-    for (i, encoding) in encodings.iter().enumerate() {
-        let ids = encoding.get_ids();
-        let mask = encoding.get_attention_mask();
-        let types = encoding.get_type_ids();
-
-        let len = ids.len().min(max_len);
-        for j in 0..len {
-            input_ids_vec[i * max_len + j] = i64::from(ids[j]);
-            attention_mask_vec[i * max_len + j] = i64::from(mask[j]);
-            token_type_ids_vec[i * max_len + j] = i64::from(types[j]);
-        }
+    for (i, segment) in segments.iter().enumerate() {
+        let row = i * max_len;
+        input_ids_vec[row..row + segment.len()].copy_from_slice(segment);
+        attention_mask_vec[row..row + segment.len()].fill(1);
     }
 
     // Build input tensors
@@ -210,8 +568,10 @@ fn process_request(
         .try_extract_tensor::<f32>()
         .map_err(|e| EmbeddingError::Runtime(format!("failed to extract output tensor: {e}")))?;
 
-    #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
-    let hidden_size = *out_shape.last().unwrap_or(&(EMBEDDING_DIM as i64)) as usize;
+    let hidden_size = out_shape
+        .last()
+        .and_then(|&size| usize::try_from(size).ok())
+        .unwrap_or(EMBEDDING_DIM);
 
     // Copy data out before dropping the session lock
     let hidden_data = hidden_data.to_vec();
@@ -260,15 +620,21 @@ fn mean_pool_embedding(
         }
     }
 
-    // L2 normalize
-    let norm: f32 = sum.iter().map(|x| x * x).sum::<f32>().sqrt();
-    if norm > 0.0 {
-        for s in &mut sum {
-            *s /= norm;
+    l2_normalize(&mut sum);
+    sum
+}
+
+/// Scale an embedding to unit L2 length in place.
+///
+/// Vectors that are already unit length (within floating point tolerance)
+/// and zero vectors are left untouched, so repeated calls are no-ops.
+pub fn l2_normalize(embedding: &mut [f32]) {
+    let norm: f32 = embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 && (norm - 1.0).abs() > 1e-6 {
+        for v in embedding.iter_mut() {
+            *v /= norm;
         }
     }
-
-    sum
 }
 
 /// Load tokenizer from file.
@@ -316,4 +682,116 @@ mod tests {
         // All zeros when mask is empty
         assert!(result.iter().all(|&x| x == 0.0));
     }
+
+    #[test]
+    fn test_split_tokens_short_input() {
+        let ids = [101, 7, 8, 102];
+        for strategy in [LongChunkStrategy::Truncate, LongChunkStrategy::MeanPool] {
+            assert_eq!(split_tokens(&ids, strategy), vec![vec![101, 7, 8, 102]]);
+        }
+    }
+
+    #[test]
+    fn test_split_tokens_long_input() {
+        // [CLS] + 300 body tokens + [SEP]
+        let mut ids: Vec<u32> = vec![101];
+        ids.extend(1..=300);
+        ids.push(102);
+
+        let truncated = split_tokens(&ids, LongChunkStrategy::Truncate);
+        assert_eq!(truncated.len(), 1);
+        assert_eq!(truncated[0].len(), MAX_SEQ_LENGTH);
+        assert_eq!(truncated[0][0], 101);
+
+        let windows = split_tokens(&ids, LongChunkStrategy::MeanPool);
+        assert_eq!(windows.len(), 2);
+        assert_eq!(windows[0].len(), MAX_SEQ_LENGTH);
+        assert_eq!(windows[1].len(), 300 - (MAX_SEQ_LENGTH - 2) + 2);
+        for window in &windows {
+            assert_eq!(window[0], 101);
+            assert_eq!(*window.last().unwrap(), 102);
+        }
+        // Every body token lands in exactly one window
+        let body: Vec<i64> = windows
+            .iter()
+            .flat_map(|w| w[1..w.len() - 1].iter().copied())
+            .collect();
+        assert_eq!(body, (1..=300).collect::<Vec<i64>>());
+    }
+
+    #[test]
+    fn test_long_chunk_strategy_from_str() {
+        assert_eq!(
+            "truncate".parse::<LongChunkStrategy>(),
+            Ok(LongChunkStrategy::Truncate)
+        );
+        assert_eq!(
+            "mean_pool".parse::<LongChunkStrategy>(),
+            Ok(LongChunkStrategy::MeanPool)
+        );
+        assert!("average".parse::<LongChunkStrategy>().is_err());
+        assert_eq!(LongChunkStrategy::default(), LongChunkStrategy::Truncate);
+    }
+
+    #[tokio::test]
+    async fn test_pool_recovers_from_panicking_job() {
+        let processor: Processor = Arc::new(|texts: &[String]| {
+            if texts.iter().any(|t| t == "panic") {
+                panic!("injected embedding failure");
+            }
+            Ok(texts.iter().map(|_| vec![1.0, 0.0]).collect())
+        });
+        let pool = EmbeddingWorker::start(processor, 1, 2).unwrap();
+
+        // The crashing job fails, and a replacement worker serves the next
+        assert!(pool.embed_one("panic".to_string()).await.is_err());
+        assert_eq!(
+            pool.embed_one("ok".to_string()).await.unwrap(),
+            vec![1.0, 0.0]
+        );
+        let health = pool.health();
+        assert_eq!(health.restarts, 1);
+        assert_eq!(health.alive, 1);
+        assert!(!health.degraded);
+
+        // With the budget spent, later requests fail rather than hang
+        assert!(pool.embed_one("panic".to_string()).await.is_err());
+        assert!(pool.embed_one("panic".to_string()).await.is_err());
+        let err = pool.embed_one("ok".to_string()).await.unwrap_err();
+        assert!(err.to_string().contains("degraded"));
+        let health = pool.health();
+        assert_eq!(health.restarts, 2);
+        assert_eq!(health.alive, 0);
+        assert!(health.degraded);
+
+        assert_eq!(pool.shutdown(), 0);
+    }
+
+    #[test]
+    fn test_shutdown_counts_clean_exits() {
+        let processor: Processor = Arc::new(|texts: &[String]| Ok(vec![Vec::new(); texts.len()]));
+        let pool = EmbeddingWorker::start(processor, 3, 0).unwrap();
+        assert_eq!(pool.health().alive, 3);
+        assert_eq!(pool.shutdown(), 3);
+    }
+
+    #[test]
+    fn test_l2_normalize() {
+        let mut embedding = vec![3.0, 4.0, -12.0];
+        l2_normalize(&mut embedding);
+
+        let norm: f32 = embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
+        assert!((norm - 1.0).abs() < 1e-6);
+        assert!((embedding[0] - 3.0 / 13.0).abs() < 1e-6);
+
+        // Normalizing again leaves the vector unchanged
+        let before = embedding.clone();
+        l2_normalize(&mut embedding);
+        assert_eq!(embedding, before);
+
+        // Zero vectors stay zero
+        let mut zero = vec![0.0; 3];
+        l2_normalize(&mut zero);
+        assert!(zero.iter().all(|&x| x == 0.0));
+    }
 }
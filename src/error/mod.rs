@@ -78,6 +78,10 @@ pub enum EmbeddingError {
     /// Worker pool error.
     #[error("worker pool error: {0}")]
     WorkerPool(String),
+
+    /// No embedding came back within the configured timeout.
+    #[error("embedding timed out after {0} ms")]
+    Timeout(u64),
 }
 
 /// File watcher errors.
@@ -121,6 +121,12 @@ mod tests {
         assert_eq!(err.to_string(), "worker pool error: worker thread panicked");
     }
 
+    #[test]
+    fn test_embedding_error_timeout() {
+        let err = EmbeddingError::Timeout(30_000);
+        assert_eq!(err.to_string(), "embedding timed out after 30000 ms");
+    }
+
     #[test]
     fn test_watcher_error_process_failed() {
         let err = WatcherError::ProcessFailed {
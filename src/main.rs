@@ -6,10 +6,18 @@
 #![warn(clippy::pedantic)]
 #![allow(clippy::module_name_repetitions)]
 
-use clap::{Parser, Subcommand};
-use nellie::server::{init_metrics, init_tracing, App, ServerConfig};
-use nellie::watcher::{FileFilter, FileWatcher, IndexRequest, Indexer, WatcherConfig};
-use nellie::storage::{init_storage, Database};
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use nellie::config::DefaultCommand;
+use nellie::embeddings::{EmbeddingTemplates, ExecutionProvider, LongChunkStrategy};
+use nellie::server::{
+    init_metrics, init_tracing, invoke_tool_direct, shutdown_tracing, App, McpState, ServerConfig,
+    ToolRequest,
+};
+use nellie::watcher::{
+    detect_repo, index_queue, reconcile_from_db, FileFilter, FileTypes, FileWatcher,
+    IndexRequest, Indexer, OverflowPolicy, ReconcileOptions, WatcherConfig,
+};
+use nellie::storage::{init_storage, init_storage_with, Database, DistanceMetric};
 use nellie::{Config, Result};
 use std::path::PathBuf;
 use std::time::Duration;
@@ -34,6 +42,10 @@ struct Cli {
     )]
     data_dir: PathBuf,
 
+    /// Directory holding the embedding model and tokenizer [default: <data-dir>/models]
+    #[arg(long, env = "NELLIE_MODEL_DIR", global = true)]
+    model_dir: Option<PathBuf>,
+
     /// Log level (trace, debug, info, warn, error)
     #[arg(long, env = "NELLIE_LOG_LEVEL", default_value = "info", global = true)]
     log_level: String,
@@ -46,10 +58,167 @@ struct Cli {
     #[arg(long, env = "NELLIE_API_KEY", global = true)]
     api_key: Option<String>,
 
+    /// OTLP collector endpoint for trace export (e.g. <http://localhost:4317>)
+    #[arg(long, env = "NELLIE_OTLP_ENDPOINT", global = true)]
+    otlp_endpoint: Option<String>,
+
+    /// Tokio runtime worker threads [default: one per CPU core]
+    #[arg(long, env = "NELLIE_WORKER_THREADS", global = true)]
+    worker_threads: Option<usize>,
+
+    /// Cap on threads for blocking work (directory walks, file reads, database calls)
+    #[arg(
+        long,
+        env = "NELLIE_BLOCKING_THREADS",
+        default_value = "512",
+        global = true
+    )]
+    blocking_threads: usize,
+
+    /// What to do when no subcommand is given: help (print usage) or serve
+    /// (start the server with the serve flags and NELLIE_* settings)
+    #[arg(
+        long,
+        env = "NELLIE_DEFAULT_COMMAND",
+        default_value = "help",
+        global = true
+    )]
+    default_command: DefaultCommand,
+
+    #[command(flatten)]
+    templates: TemplateArgs,
+
+    #[command(flatten)]
+    languages: LanguageArgs,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
 
+/// Input templates applied before embedding.
+///
+/// Global so `serve` and `index` embed code the same way. Changing a
+/// document template only affects records embedded afterwards.
+#[derive(Args, Debug)]
+struct TemplateArgs {
+    /// Template for code chunks ({content}, {language}, {file_path}; \n is a newline)
+    #[arg(
+        long,
+        env = "NELLIE_CODE_TEMPLATE",
+        default_value = "{content}",
+        global = true
+    )]
+    code_template: String,
+
+    /// Template for `search_code` queries ({query})
+    #[arg(
+        long,
+        env = "NELLIE_CODE_QUERY_TEMPLATE",
+        default_value = "{query}",
+        global = true
+    )]
+    code_query_template: String,
+
+    /// Template for lessons ({title}, {content})
+    #[arg(
+        long,
+        env = "NELLIE_LESSON_TEMPLATE",
+        default_value = "{title}\\n{content}",
+        global = true
+    )]
+    lesson_template: String,
+
+    /// Template for `search_lessons` queries ({query})
+    #[arg(
+        long,
+        env = "NELLIE_LESSON_QUERY_TEMPLATE",
+        default_value = "{query}",
+        global = true
+    )]
+    lesson_query_template: String,
+
+    /// Template for checkpoints ({working_on})
+    #[arg(
+        long,
+        env = "NELLIE_CHECKPOINT_TEMPLATE",
+        default_value = "{working_on}",
+        global = true
+    )]
+    checkpoint_template: String,
+
+    /// Template for `search_checkpoints` queries ({query})
+    #[arg(
+        long,
+        env = "NELLIE_CHECKPOINT_QUERY_TEMPLATE",
+        default_value = "{query}",
+        global = true
+    )]
+    checkpoint_query_template: String,
+}
+
+impl TemplateArgs {
+    fn into_templates(self) -> EmbeddingTemplates {
+        EmbeddingTemplates {
+            code: self.code_template,
+            code_query: self.code_query_template,
+            lesson: self.lesson_template,
+            lesson_query: self.lesson_query_template,
+            checkpoint: self.checkpoint_template,
+            checkpoint_query: self.checkpoint_query_template,
+        }
+    }
+}
+
+/// File extensions indexed beyond the built-in table.
+///
+/// Global so `serve` and `index` pick up the same files.
+#[derive(Args, Debug)]
+struct LanguageArgs {
+    /// Extensions to index with a language tag, as ext=language pairs
+    /// (comma-separated), e.g. tf=terraform,bzl=starlark
+    #[arg(
+        long,
+        env = "NELLIE_EXTRA_LANGUAGES",
+        value_delimiter = ',',
+        value_parser = parse_language_mapping,
+        global = true
+    )]
+    extra_languages: Vec<(String, String)>,
+
+    /// Extensions to index without a language tag (comma-separated)
+    #[arg(
+        long,
+        env = "NELLIE_EXTRA_CODE_EXTENSIONS",
+        value_delimiter = ',',
+        global = true
+    )]
+    extra_code_extensions: Vec<String>,
+
+    /// Also index documentation and config files (.rst, .txt, .ini, ...),
+    /// tagged doc and config; .md, .yaml, .json and .toml are retagged
+    #[arg(long, env = "NELLIE_INDEX_DOCS", global = true)]
+    index_docs: bool,
+
+    /// Only index files with these extensions (comma-separated)
+    #[arg(
+        long,
+        env = "NELLIE_INDEX_EXTENSIONS",
+        value_delimiter = ',',
+        global = true
+    )]
+    index_extensions: Vec<String>,
+}
+
+/// Parse an `ext=language` pair for `--extra-languages`.
+fn parse_language_mapping(s: &str) -> std::result::Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((ext, lang)) if !ext.trim().is_empty() && !lang.trim().is_empty() => {
+            Ok((ext.trim().to_string(), lang.trim().to_string()))
+        }
+        _ => Err(format!("expected ext=language, got '{s}'")),
+    }
+}
+
 #[derive(Subcommand, Debug)]
 enum Commands {
     /// Start the Nellie server
@@ -66,6 +235,11 @@ enum Commands {
         #[arg(short, long, env = "NELLIE_PORT", default_value = "8080")]
         port: u16,
 
+        /// Listen on this Unix domain socket instead of a TCP port
+        /// (the socket is created with mode 0600)
+        #[arg(long, env = "NELLIE_SOCKET", conflicts_with_all = ["host", "port"])]
+        socket: Option<PathBuf>,
+
         /// Directories to watch for code changes (comma-separated)
         #[arg(short, long, env = "NELLIE_WATCH_DIRS", value_delimiter = ',')]
         watch: Vec<PathBuf>,
@@ -77,20 +251,208 @@ enum Commands {
         /// Disable embedding service (semantic search will not work)
         #[arg(long, env = "NELLIE_DISABLE_EMBEDDINGS")]
         disable_embeddings: bool,
+
+        /// Compare content hashes during startup reconciliation (for unreliable mtimes, e.g. NFS)
+        #[arg(long, env = "NELLIE_VERIFY_HASHES")]
+        verify_hashes: bool,
+
+        /// Files startup reconciliation checks per batch; progress is saved after each
+        #[arg(long, env = "NELLIE_RECONCILE_BATCH_SIZE", default_value = "500")]
+        reconcile_batch_size: usize,
+
+        /// Capacity of the queue feeding reconciled, scanned and changed files
+        /// to the indexer
+        #[arg(long, env = "NELLIE_RECONCILE_QUEUE_CAPACITY", default_value = "1000")]
+        reconcile_queue_capacity: usize,
+
+        /// What to do when the index queue is full: block (wait for space) or
+        /// drop_oldest (evict the oldest queued file)
+        #[arg(long, env = "NELLIE_INDEX_QUEUE_OVERFLOW", default_value = "block")]
+        index_queue_overflow: OverflowPolicy,
+
+        /// Number of query embeddings to cache (0 disables the cache)
+        #[arg(long, env = "NELLIE_QUERY_CACHE_SIZE", default_value = "256")]
+        query_cache_size: usize,
+
+        /// Milliseconds to coalesce rapid file changes before reindexing
+        #[arg(long, env = "NELLIE_DEBOUNCE_MS", default_value = "500")]
+        debounce_ms: u64,
+
+        /// Browser origins allowed to call the API (comma-separated, `*` for any).
+        /// Empty disables CORS
+        #[arg(long, env = "NELLIE_CORS_ORIGINS", value_delimiter = ',')]
+        cors_origins: Vec<String>,
+
+        /// Maximum HTTP request body size in bytes (larger requests get 413)
+        #[arg(long, env = "NELLIE_MAX_REQUEST_BYTES", default_value = "4194304")]
+        max_request_bytes: usize,
+
+        /// Start without semantic search if the sqlite-vec extension fails to load
+        #[arg(long, env = "NELLIE_ALLOW_MISSING_VECTOR_SEARCH")]
+        allow_missing_vector_search: bool,
+
+        /// Seconds without a status update before an agent is reported stale
+        #[arg(long, env = "NELLIE_AGENT_STALE_SECS", default_value = "3600")]
+        agent_stale_secs: i64,
+
+        /// Largest serialized checkpoint state accepted, in bytes
+        #[arg(
+            long,
+            env = "NELLIE_MAX_CHECKPOINT_STATE_BYTES",
+            default_value = "262144"
+        )]
+        max_checkpoint_state_bytes: usize,
+
+        /// Deepest nesting of arrays/objects accepted in checkpoint state
+        #[arg(long, env = "NELLIE_MAX_CHECKPOINT_STATE_DEPTH", default_value = "32")]
+        max_checkpoint_state_depth: usize,
+
+        /// Vector distance metric: l2 (normalized embeddings) or cosine.
+        /// Fixed once the database has vectors
+        #[arg(long, env = "NELLIE_DISTANCE_METRIC", default_value = "l2")]
+        distance_metric: DistanceMetric,
+
+        /// Similarity (0.0-1.0) at which add_lesson reports a possible duplicate
+        #[arg(long, env = "NELLIE_LESSON_DUPLICATE_THRESHOLD", default_value = "0.9")]
+        lesson_duplicate_threshold: f32,
+
+        /// Half-life in days for recency-weighted lesson/checkpoint search
+        #[arg(long, env = "NELLIE_RECENCY_HALF_LIFE_DAYS", default_value = "90")]
+        recency_half_life_days: u64,
+
+        /// Number of files index_repo, diff_index and full_reindex index concurrently
+        #[arg(long, env = "NELLIE_INDEX_CONCURRENCY", default_value = "4")]
+        index_concurrency: usize,
+
+        /// URL that receives a POST with the summary of each completed
+        /// index_repo, diff_index or full_reindex run
+        #[arg(long, env = "NELLIE_INDEX_COMPLETE_WEBHOOK")]
+        index_complete_webhook: Option<String>,
+
+        /// ONNX Runtime intra-op threads per embedding session
+        #[arg(long, env = "NELLIE_ONNX_INTRA_THREADS", default_value = "1")]
+        onnx_intra_threads: usize,
+
+        /// ONNX Runtime inter-op threads per embedding session
+        #[arg(long, env = "NELLIE_ONNX_INTER_THREADS", default_value = "1")]
+        onnx_inter_threads: usize,
+
+        /// ONNX Runtime execution provider: cpu, coreml or cuda
+        /// (falls back to cpu if the runtime doesn't support it)
+        #[arg(long, env = "NELLIE_EXECUTION_PROVIDER", default_value = "cpu")]
+        execution_provider: ExecutionProvider,
+
+        /// How chunks longer than the model's sequence length are embedded:
+        /// truncate, or mean_pool over multiple windows
+        #[arg(long, env = "NELLIE_LONG_CHUNK_STRATEGY", default_value = "truncate")]
+        long_chunk_strategy: LongChunkStrategy,
+
+        /// Index dependency lockfiles and minified *.min.js/*.min.css assets
+        /// (skipped by default)
+        #[arg(long, env = "NELLIE_INDEX_LOCKFILES")]
+        index_lockfiles: bool,
+
+        /// Keep only each chunk's file path and line range in the database
+        /// and read its text from disk at search time
+        #[arg(long, env = "NELLIE_NO_CHUNK_CONTENT")]
+        no_chunk_content: bool,
+
+        /// Store chunks whose text is already indexed as references to the
+        /// first copy instead of embedding them again
+        #[arg(long, env = "NELLIE_DEDUPE_CHUNKS")]
+        dedupe_chunks: bool,
+
+        /// Free bytes on the data directory's filesystem below which status
+        /// reports a low-disk warning (0 disables)
+        #[arg(long, env = "NELLIE_MIN_FREE_DISK_BYTES", default_value = "1073741824")]
+        min_free_disk_bytes: u64,
+
+        /// Cross-encoder ONNX model for search_code's rerank option
+        /// (tokenizer.json must sit next to it; loaded on first use)
+        #[arg(long, env = "NELLIE_RERANKER_MODEL")]
+        reranker_model: Option<PathBuf>,
+
+        /// Normalize lesson tags on write (lowercase, spaces/_/- collapsed
+        /// to -) so spelling variants share one tag
+        #[arg(long, env = "NELLIE_NORMALIZE_TAGS")]
+        normalize_tags: bool,
+
+        /// Lesson severities accepted besides critical, warning and info
+        /// (comma-separated). Lessons with other severities are corrected
+        /// on startup
+        #[arg(long, env = "NELLIE_EXTRA_SEVERITIES", value_delimiter = ',')]
+        extra_severities: Vec<String>,
+
+        /// Largest limit the search tools and list_lessons honour; larger
+        /// requests are clamped
+        #[arg(long, env = "NELLIE_MAX_SEARCH_LIMIT", default_value = "100")]
+        max_search_limit: usize,
+
+        /// Log tool calls taking at least this many milliseconds at warn,
+        /// with their embedding and database time (0 disables)
+        #[arg(long, env = "NELLIE_SLOW_QUERY_MS", default_value = "1000")]
+        slow_query_ms: u64,
+
+        /// Seconds between refreshes of the row-count gauges on /metrics
+        /// (0 disables)
+        #[arg(long, env = "NELLIE_METRICS_REFRESH_SECS", default_value = "15")]
+        metrics_refresh_secs: u64,
+
+        /// Crashed embedding workers to replace before the pool reports
+        /// itself degraded
+        #[arg(long, env = "NELLIE_EMBEDDING_MAX_RESTARTS", default_value = "5")]
+        embedding_max_restarts: usize,
+
+        /// Milliseconds to wait for an embedding before failing the request
+        /// (0 waits forever)
+        #[arg(long, env = "NELLIE_EMBEDDING_TIMEOUT_MS", default_value = "30000")]
+        embedding_timeout_ms: u64,
+
+        /// MCP tools to hide and refuse on every transport (comma-separated)
+        #[arg(long, env = "NELLIE_DISABLED_TOOLS", value_delimiter = ',')]
+        disabled_tools: Vec<String>,
+
+        /// Disable every tool that writes lessons or checkpoints or starts
+        /// indexing
+        #[arg(long, env = "NELLIE_READ_ONLY")]
+        read_only: bool,
+
+        /// Serve a Swagger UI for the REST API at /docs
+        #[arg(long, env = "NELLIE_API_DOCS_UI")]
+        api_docs_ui: bool,
     },
 
     /// Manually index a directory
     ///
     /// Triggers immediate indexing of one or more directories.
     /// Useful for forcing re-indexing without waiting for file watcher.
+    /// Doesn't need a running server; unchanged files are skipped.
     Index {
-        /// Path(s) to index (comma-separated)
+        /// Path(s) to index
         #[arg(value_name = "PATH")]
         paths: Vec<PathBuf>,
 
         /// Number of embedding worker threads
         #[arg(long, env = "NELLIE_EMBEDDING_THREADS", default_value = "4")]
         embedding_threads: usize,
+
+        /// Index without embeddings (chunks are only findable by text and
+        /// symbol search until re-embedded)
+        #[arg(long)]
+        disable_embeddings: bool,
+
+        /// Keep only each chunk's file path and line range in the database
+        #[arg(long, env = "NELLIE_NO_CHUNK_CONTENT")]
+        no_chunk_content: bool,
+
+        /// Store chunks whose text is already indexed as references to the
+        /// first copy
+        #[arg(long, env = "NELLIE_DEDUPE_CHUNKS")]
+        dedupe_chunks: bool,
+
+        /// Output format (text or json)
+        #[arg(long, default_value = "text")]
+        format: String,
     },
 
     /// Search for code semantically
@@ -98,9 +460,18 @@ enum Commands {
     /// Performs a semantic search across indexed code.
     /// Requires the server to be running in another terminal.
     Search {
-        /// Search query (natural language or code keywords)
-        #[arg(value_name = "QUERY")]
-        query: String,
+        /// Search query (natural language or code keywords); `-` reads
+        /// newline-separated queries from stdin
+        #[arg(value_name = "QUERY", required_unless_present = "stdin")]
+        query: Option<String>,
+
+        /// Read newline-separated queries from stdin and run each one
+        #[arg(long, conflicts_with = "query")]
+        stdin: bool,
+
+        /// Output format: text, or json (the REST response, one line per query)
+        #[arg(long, default_value = "text")]
+        format: String,
 
         /// Maximum number of results
         #[arg(short, long, default_value = "10")]
@@ -128,14 +499,110 @@ enum Commands {
         #[arg(long, default_value = "text")]
         format: String,
     },
+
+    /// List indexed repositories
+    ///
+    /// Shows each repository with its chunk and file counts, languages,
+    /// and when it was last indexed.
+    Repos {
+        /// Output format (text or json)
+        #[arg(long, default_value = "text")]
+        format: String,
+    },
+
+    /// Compact the vector tables
+    ///
+    /// Rebuilds the chunk, lesson and checkpoint embedding tables in one
+    /// transaction to reclaim space left by deletes and re-indexes. Heavy:
+    /// a running server's database access waits until it finishes.
+    Optimize {
+        /// Output format (text or json)
+        #[arg(long, default_value = "text")]
+        format: String,
+    },
+
+    /// Database maintenance
+    Db {
+        #[command(subcommand)]
+        command: DbCommands,
+    },
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let cli = Cli::parse();
+/// `nellie db` subcommands.
+#[derive(Subcommand, Debug)]
+enum DbCommands {
+    /// Fill in the repository of chunks indexed without one
+    ///
+    /// Infers each file's repository from the nearest enclosing git root on
+    /// disk and tags its chunks in place, so repo-scoped search covers data
+    /// indexed before chunks recorded their repository, without a reindex.
+    BackfillRepo {
+        /// Output format (text or json)
+        #[arg(long, default_value = "text")]
+        format: String,
+    },
+}
+
+fn main() -> Result<()> {
+    let Some((cli, command)) = parse_args(std::env::args_os().collect()).unwrap_or_else(|e| e.exit())
+    else {
+        Cli::command().print_help()?;
+        return Ok(());
+    };
 
+    // Built by hand so operators can bound the process's thread footprint
+    let runtime = Config {
+        worker_threads: cli.worker_threads,
+        blocking_threads: cli.blocking_threads,
+        ..Config::default()
+    }
+    .build_runtime()?;
+    runtime.block_on(run(cli, command))
+}
+
+/// Parse the command line, resolving a missing subcommand.
+///
+/// Without a subcommand, `--default-command serve` (or
+/// `NELLIE_DEFAULT_COMMAND=serve`) parses the arguments again as
+/// `nellie serve ...`, so serve flags given without the subcommand
+/// (`nellie --port 9000`) apply exactly as for an explicit `serve`.
+/// Returns `None` when help should be printed instead.
+fn parse_args(
+    args: Vec<std::ffi::OsString>,
+) -> std::result::Result<Option<(Cli, Commands)>, clap::Error> {
+    let first = match Cli::try_parse_from(&args) {
+        Ok(mut cli) => match cli.command.take() {
+            Some(command) => return Ok(Some((cli, command))),
+            None => Ok(()),
+        },
+        // Serve flags are unknown until `serve` is inserted
+        Err(e) if e.kind() == clap::error::ErrorKind::UnknownArgument => Err(e),
+        Err(e) => return Err(e),
+    };
+
+    if let Ok(mut cli) = Cli::try_parse_from(with_subcommand(args, "serve")) {
+        if cli.default_command == DefaultCommand::Serve {
+            if let Some(command) = cli.command.take() {
+                return Ok(Some((cli, command)));
+            }
+        }
+    }
+    first.map(|()| None)
+}
+
+/// Insert `subcommand` after the program name in `args`.
+fn with_subcommand(
+    args: impl IntoIterator<Item = std::ffi::OsString>,
+    subcommand: &str,
+) -> Vec<std::ffi::OsString> {
+    let mut args: Vec<_> = args.into_iter().collect();
+    args.insert(args.len().min(1), subcommand.into());
+    args
+}
+
+async fn run(cli: Cli, command: Commands) -> Result<()> {
     // Initialize tracing with configuration
-    init_tracing(&cli.log_level, cli.log_json);
+    init_tracing(&cli.log_level, cli.log_json, cli.otlp_endpoint.as_deref());
 
     tracing::info!(
         "Nellie Production v{} - Semantic code memory system",
@@ -143,65 +610,227 @@ async fn main() -> Result<()> {
     );
 
     // Route to appropriate command handler
-    match cli.command {
-        Some(Commands::Serve {
+    let result = match command {
+        Commands::Serve {
             host,
             port,
+            socket,
             watch,
             embedding_threads,
             disable_embeddings,
-        }) => {
+            verify_hashes,
+            reconcile_batch_size,
+            reconcile_queue_capacity,
+            index_queue_overflow,
+            query_cache_size,
+            debounce_ms,
+            cors_origins,
+            max_request_bytes,
+            allow_missing_vector_search,
+            agent_stale_secs,
+            max_checkpoint_state_bytes,
+            max_checkpoint_state_depth,
+            distance_metric,
+            lesson_duplicate_threshold,
+            recency_half_life_days,
+            index_concurrency,
+            index_complete_webhook,
+            onnx_intra_threads,
+            onnx_inter_threads,
+            execution_provider,
+            long_chunk_strategy,
+            index_lockfiles,
+            no_chunk_content,
+            dedupe_chunks,
+            min_free_disk_bytes,
+            reranker_model,
+            normalize_tags,
+            extra_severities,
+            max_search_limit,
+            slow_query_ms,
+            metrics_refresh_secs,
+            embedding_max_restarts,
+            embedding_timeout_ms,
+            disabled_tools,
+            read_only,
+            api_docs_ui,
+        } => {
             serve_command(ServeCommandArgs {
                 data_dir: cli.data_dir,
+                model_dir: cli.model_dir,
                 host,
                 port,
+                socket,
                 watch,
                 embedding_threads,
                 log_level: cli.log_level,
                 api_key: cli.api_key,
                 disable_embeddings,
+                verify_hashes,
+                reconcile_batch_size,
+                reconcile_queue_capacity,
+                index_queue_overflow,
+                query_cache_size,
+                debounce_ms,
+                cors_origins,
+                max_request_bytes,
+                allow_missing_vector_search,
+                agent_stale_secs,
+                max_checkpoint_state_bytes,
+                max_checkpoint_state_depth,
+                distance_metric,
+                lesson_duplicate_threshold,
+                recency_half_life_days,
+                index_concurrency,
+                index_complete_webhook,
+                onnx_intra_threads,
+                onnx_inter_threads,
+                execution_provider,
+                long_chunk_strategy,
+                index_lockfiles,
+                no_chunk_content,
+                dedupe_chunks,
+                min_free_disk_bytes,
+                reranker_model,
+                normalize_tags,
+                extra_severities,
+                max_search_limit,
+                slow_query_ms,
+                metrics_refresh_secs,
+                embedding_max_restarts,
+                embedding_timeout_ms,
+                disabled_tools,
+                read_only,
+                api_docs_ui,
+                embedding_templates: cli.templates.into_templates(),
+                extra_languages: cli.languages.extra_languages.into_iter().collect(),
+                extra_code_extensions: cli.languages.extra_code_extensions,
+                index_docs: cli.languages.index_docs,
+                index_extensions: cli.languages.index_extensions,
+                otlp_endpoint: cli.otlp_endpoint,
+                worker_threads: cli.worker_threads,
+                blocking_threads: cli.blocking_threads,
+                default_command: cli.default_command,
             })
             .await
         }
-        Some(Commands::Index {
+        Commands::Index {
             paths,
             embedding_threads,
-        }) => index_command(cli.data_dir, paths, embedding_threads),
-        Some(Commands::Search {
+            disable_embeddings,
+            no_chunk_content,
+            dedupe_chunks,
+            format,
+        } => {
+            let config = Config {
+                data_dir: cli.data_dir,
+                model_dir: cli.model_dir,
+                store_chunk_content: !no_chunk_content,
+                dedupe_chunks,
+                embedding_templates: cli.templates.into_templates(),
+                extra_languages: cli.languages.extra_languages.into_iter().collect(),
+                extra_code_extensions: cli.languages.extra_code_extensions,
+                index_docs: cli.languages.index_docs,
+                index_extensions: cli.languages.index_extensions,
+                ..Config::default()
+            };
+            index_command(
+                config,
+                paths,
+                embedding_threads,
+                disable_embeddings,
+                &format,
+            )
+            .await
+        }
+        Commands::Search {
             query,
+            stdin,
+            format,
             limit,
             threshold,
             server,
-        }) => search_command(query, limit, threshold, server),
-        Some(Commands::Status { server, format }) => status_command(server, format),
-        None => {
-            // Default to serve command for backward compatibility
-            tracing::info!("No command specified, starting server (use 'serve' explicitly)");
-            serve_command(ServeCommandArgs {
-                data_dir: cli.data_dir,
-                host: "127.0.0.1".to_string(),
-                port: 8080,
-                watch: vec![],
-                embedding_threads: 4,
-                log_level: cli.log_level,
-                api_key: cli.api_key,
-                disable_embeddings: false,
-            })
+        } => {
+            search_command(
+                query.as_deref(),
+                stdin,
+                &format,
+                limit,
+                threshold,
+                &server,
+                cli.api_key.as_deref(),
+            )
             .await
         }
-    }
+        Commands::Status { server, format } => status_command(server, format),
+        Commands::Repos { format } => repos_command(cli.data_dir, &format),
+        Commands::Optimize { format } => optimize_command(cli.data_dir, &format),
+        Commands::Db {
+            command: DbCommands::BackfillRepo { format },
+        } => backfill_repo_command(cli.data_dir, &format),
+    };
+
+    shutdown_tracing();
+    result
 }
 
 /// Command arguments for serve subcommand.
 struct ServeCommandArgs {
     data_dir: PathBuf,
+    model_dir: Option<PathBuf>,
     host: String,
     port: u16,
+    socket: Option<PathBuf>,
     watch: Vec<PathBuf>,
     embedding_threads: usize,
     log_level: String,
     api_key: Option<String>,
     disable_embeddings: bool,
+    verify_hashes: bool,
+    reconcile_batch_size: usize,
+    reconcile_queue_capacity: usize,
+    index_queue_overflow: OverflowPolicy,
+    query_cache_size: usize,
+    debounce_ms: u64,
+    cors_origins: Vec<String>,
+    max_request_bytes: usize,
+    allow_missing_vector_search: bool,
+    agent_stale_secs: i64,
+    max_checkpoint_state_bytes: usize,
+    max_checkpoint_state_depth: usize,
+    distance_metric: DistanceMetric,
+    lesson_duplicate_threshold: f32,
+    recency_half_life_days: u64,
+    index_concurrency: usize,
+    index_complete_webhook: Option<String>,
+    onnx_intra_threads: usize,
+    onnx_inter_threads: usize,
+    execution_provider: ExecutionProvider,
+    long_chunk_strategy: LongChunkStrategy,
+    index_lockfiles: bool,
+    no_chunk_content: bool,
+    dedupe_chunks: bool,
+    min_free_disk_bytes: u64,
+    reranker_model: Option<PathBuf>,
+    normalize_tags: bool,
+    extra_severities: Vec<String>,
+    max_search_limit: usize,
+    slow_query_ms: u64,
+    metrics_refresh_secs: u64,
+    embedding_max_restarts: usize,
+    embedding_timeout_ms: u64,
+    disabled_tools: Vec<String>,
+    read_only: bool,
+    api_docs_ui: bool,
+    embedding_templates: EmbeddingTemplates,
+    extra_languages: std::collections::HashMap<String, String>,
+    extra_code_extensions: Vec<String>,
+    index_docs: bool,
+    index_extensions: Vec<String>,
+    otlp_endpoint: Option<String>,
+    worker_threads: Option<usize>,
+    blocking_threads: usize,
+    default_command: DefaultCommand,
 }
 
 /// Serve command: Start the Nellie server
@@ -211,23 +840,70 @@ async fn serve_command(args: ServeCommandArgs) -> Result<()> {
     // Build config from CLI arguments
     let config = Config {
         data_dir: args.data_dir.clone(),
+        model_dir: args.model_dir,
         host: args.host.clone(),
         port: args.port,
+        listen_socket: args.socket.clone(),
         log_level: args.log_level,
         watch_dirs: args.watch.clone(),
         embedding_threads: args.embedding_threads,
         api_key: args.api_key.clone(),
+        verify_hashes: args.verify_hashes,
+        reconcile_batch_size: args.reconcile_batch_size,
+        reconcile_queue_capacity: args.reconcile_queue_capacity,
+        index_queue_overflow: args.index_queue_overflow,
+        otlp_endpoint: args.otlp_endpoint,
+        worker_threads: args.worker_threads,
+        blocking_threads: args.blocking_threads,
+        default_command: args.default_command,
+        query_cache_size: args.query_cache_size,
+        debounce_ms: args.debounce_ms,
+        cors_allowed_origins: args.cors_origins,
+        max_request_bytes: args.max_request_bytes,
+        require_vector_search: !args.allow_missing_vector_search,
+        agent_stale_secs: args.agent_stale_secs,
+        max_checkpoint_state_bytes: args.max_checkpoint_state_bytes,
+        max_checkpoint_state_depth: args.max_checkpoint_state_depth,
+        distance_metric: args.distance_metric,
+        lesson_duplicate_threshold: args.lesson_duplicate_threshold,
+        recency_half_life_days: args.recency_half_life_days,
+        index_concurrency: args.index_concurrency,
+        index_complete_webhook: args.index_complete_webhook,
+        onnx_intra_threads: args.onnx_intra_threads,
+        onnx_inter_threads: args.onnx_inter_threads,
+        execution_provider: args.execution_provider,
+        long_chunk_strategy: args.long_chunk_strategy,
+        skip_lockfiles: !args.index_lockfiles,
+        store_chunk_content: !args.no_chunk_content,
+        dedupe_chunks: args.dedupe_chunks,
+        min_free_disk_bytes: args.min_free_disk_bytes,
+        reranker_model: args.reranker_model,
+        normalize_tags: args.normalize_tags,
+        extra_severities: args.extra_severities,
+        max_search_limit: args.max_search_limit,
+        slow_query_ms: args.slow_query_ms,
+        metrics_refresh_secs: args.metrics_refresh_secs,
+        embedding_max_restarts: args.embedding_max_restarts,
+        embedding_timeout_ms: args.embedding_timeout_ms,
+        disabled_tools: args.disabled_tools,
+        read_only: args.read_only,
+        api_docs_ui: args.api_docs_ui,
+        embedding_templates: args.embedding_templates,
+        extra_languages: args.extra_languages,
+        extra_code_extensions: args.extra_code_extensions,
+        index_docs: args.index_docs,
+        index_extensions: args.index_extensions,
     };
 
     tracing::debug!(?config, "Configuration loaded");
 
     // Validate config
     config.validate()?;
+    let file_types = FileTypes::from_config(&config);
 
     tracing::info!(
-        "Server binding to {}:{}, data directory: {:?}",
-        args.host,
-        args.port,
+        "Server binding to {}, data directory: {:?}",
+        config.server_addr(),
         config.data_dir
     );
 
@@ -254,7 +930,11 @@ async fn serve_command(args: ServeCommandArgs) -> Result<()> {
 
     // Initialize database
     let db = Database::open(config.database_path())?;
-    init_storage(&db)?;
+    let vector_search = init_storage_with(
+        &db,
+        config.require_vector_search,
+        Some(config.distance_metric),
+    )?;
 
     // Initialize metrics
     init_metrics();
@@ -263,12 +943,49 @@ async fn serve_command(args: ServeCommandArgs) -> Result<()> {
     let server_config = ServerConfig {
         host: args.host,
         port: args.port,
+        listen_socket: config.listen_socket.clone(),
         shutdown_timeout: Duration::from_secs(30),
         api_key: args.api_key,
+        model_dir: Some(config.models_path()),
         data_dir: config.data_dir,
         embedding_threads: args.embedding_threads,
+        query_cache_size: config.query_cache_size,
         enable_embeddings: !args.disable_embeddings,
         watch_dirs: args.watch.clone(),
+        cors_allowed_origins: config.cors_allowed_origins.clone(),
+        max_request_bytes: config.max_request_bytes,
+        vector_search,
+        agent_stale_secs: config.agent_stale_secs,
+        max_checkpoint_state_bytes: config.max_checkpoint_state_bytes,
+        max_checkpoint_state_depth: config.max_checkpoint_state_depth,
+        lesson_duplicate_threshold: config.lesson_duplicate_threshold,
+        recency_half_life_days: config.recency_half_life_days,
+        index_concurrency: config.index_concurrency,
+        index_complete_webhook: config.index_complete_webhook.clone(),
+        onnx_intra_threads: config.onnx_intra_threads,
+        onnx_inter_threads: config.onnx_inter_threads,
+        execution_provider: config.execution_provider,
+        long_chunk_strategy: config.long_chunk_strategy,
+        skip_noise_files: config.skip_lockfiles,
+        debounce_ms: config.debounce_ms,
+        store_chunk_content: config.store_chunk_content,
+        dedupe_chunks: config.dedupe_chunks,
+        index_queue_capacity: config.reconcile_queue_capacity,
+        index_queue_overflow: config.index_queue_overflow,
+        min_free_disk_bytes: config.min_free_disk_bytes,
+        reranker_model: config.reranker_model.clone(),
+        normalize_tags: config.normalize_tags,
+        extra_severities: config.extra_severities.clone(),
+        max_search_limit: config.max_search_limit,
+        slow_query_ms: config.slow_query_ms,
+        metrics_refresh_secs: config.metrics_refresh_secs,
+        embedding_max_restarts: config.embedding_max_restarts,
+        embedding_timeout_ms: config.embedding_timeout_ms,
+        disabled_tools: config.disabled_tools.clone(),
+        read_only: config.read_only,
+        api_docs_ui: config.api_docs_ui,
+        embedding_templates: config.embedding_templates.clone(),
+        file_types: file_types.clone(),
     };
 
     // Clone db for the indexer before giving it to the App
@@ -279,13 +996,27 @@ async fn serve_command(args: ServeCommandArgs) -> Result<()> {
     // Wire up file watcher and indexer if watch dirs specified
     if !args.watch.is_empty() {
         let embeddings = if !args.disable_embeddings {
-            let embed_config = nellie::embeddings::EmbeddingConfig::from_data_dir(
-                &server_config.data_dir,
+            let embed_config = nellie::embeddings::EmbeddingConfig::from_model_dir(
+                server_config.models_path(),
                 args.embedding_threads,
-            );
+            )
+            .with_onnx_options(server_config.onnx_options())
+            .with_long_chunk_strategy(server_config.long_chunk_strategy)
+            .with_max_worker_restarts(server_config.embedding_max_restarts)
+            .with_embed_timeout_ms(server_config.embedding_timeout_ms)
+            .with_templates(server_config.embedding_templates.clone());
             let svc = nellie::embeddings::EmbeddingService::new(embed_config);
             match svc.init().await {
-                Ok(()) => Some(svc),
+                Ok(()) => match svc.warmup().await {
+                    Ok(()) => {
+                        app.track_embeddings(svc.clone());
+                        Some(svc)
+                    }
+                    Err(e) => {
+                        tracing::warn!("Indexer embeddings failed to warm up: {e}");
+                        None
+                    }
+                },
                 Err(e) => {
                     tracing::warn!("Indexer embeddings failed to init: {e}");
                     None
@@ -296,47 +1027,118 @@ async fn serve_command(args: ServeCommandArgs) -> Result<()> {
         };
 
         let scan_db = indexer_db.clone();
-        let indexer = std::sync::Arc::new(Indexer::new(indexer_db, embeddings));
-        let (index_tx, index_rx) = tokio::sync::mpsc::channel::<IndexRequest>(1000);
+        let indexer = std::sync::Arc::new(
+            Indexer::new(indexer_db, embeddings)
+                .with_store_content(config.store_chunk_content)
+                .with_dedupe(config.dedupe_chunks),
+        );
+        let (index_tx, index_rx) =
+            index_queue(config.reconcile_queue_capacity, config.index_queue_overflow);
         let (delete_tx, delete_rx) = tokio::sync::mpsc::channel(100);
+        app.track_index_queue(&index_tx);
 
         // Start the indexer loop
         let indexer_clone = std::sync::Arc::clone(&indexer);
-        tokio::spawn(async move {
-            indexer_clone.run(index_rx, delete_rx).await;
-        });
+        let indexer_shutdown = app.shutdown_token();
+        app.track_task(
+            "indexer",
+            tokio::spawn(async move {
+                indexer_clone
+                    .run(index_rx, delete_rx, indexer_shutdown)
+                    .await;
+            }),
+        );
         // Startup reconciliation: iterate file_state DB instead of walking NFS tree.
         // For each known file, stat() it — if gone, delete from index; if changed, re-index.
         // New files are discovered by the watcher (FSEvents).
         let index_tx_scan = index_tx.clone();
         let delete_tx_scan = delete_tx.clone();
-        std::thread::spawn(move || {
-            reconcile_from_db(&scan_db, &index_tx_scan, &delete_tx_scan);
-        });
+        let reconcile_options = ReconcileOptions {
+            verify_hashes: config.verify_hashes,
+            batch_size: config.reconcile_batch_size,
+            file_types: file_types.clone(),
+        };
+        let reconcile_status = app.reconcile_status();
+        let reconcile_shutdown = app.shutdown_token();
+        app.track_task(
+            "reconcile",
+            tokio::spawn(async move {
+                // Failures are logged and reported by get_status
+                let _ = reconcile_from_db(
+                    &scan_db,
+                    &index_tx_scan,
+                    &delete_tx_scan,
+                    reconcile_options,
+                    &reconcile_status,
+                    &reconcile_shutdown,
+                )
+                .await;
+            }),
+        );
 
         // Start file watcher for ongoing changes — uses direct indexer calls
         // to bypass the scan channel and get immediate indexing of new/changed files
         let watcher_watch_dirs = args.watch.clone();
         let watcher_indexer = std::sync::Arc::clone(&indexer);
         let watcher_delete_tx = delete_tx.clone();
-        tokio::spawn(async move {
+        let watcher_shutdown = app.shutdown_token();
+        let debounce_ms = config.debounce_ms;
+        let skip_lockfiles = config.skip_lockfiles;
+        let watcher_handle = tokio::spawn(async move {
             let watcher_config = WatcherConfig {
                 watch_dirs: watcher_watch_dirs,
                 ..Default::default()
-            };
+            }
+            .with_debounce_ms(debounce_ms)
+            .with_skip_noise_files(skip_lockfiles)
+            .with_file_types(file_types);
+            // Events get the same ignore rules as the walker
+            let filters: Vec<(PathBuf, FileFilter)> = watcher_config
+                .watch_dirs
+                .iter()
+                .map(|root| {
+                    let filter = FileFilter::new(root)
+                        .with_skip_dirs(watcher_config.skip_dirs.clone())
+                        .with_skip_noise(watcher_config.skip_noise_files)
+                        .with_file_types(watcher_config.file_types.clone());
+                    (root.clone(), filter)
+                })
+                .collect();
             match FileWatcher::new(&watcher_config) {
                 Ok(mut watcher) => {
                     tracing::info!("File watcher started");
-                    while let Some(batch) = watcher.recv().await {
+                    loop {
+                        let mut batch = tokio::select! {
+                            () = watcher_shutdown.cancelled() => break,
+                            batch = watcher.recv() => match batch {
+                                Some(batch) => batch,
+                                None => break,
+                            },
+                        };
+                        watcher_indexer.resolve_renames(&mut batch).await;
                         let total = batch.modified.len() + batch.deleted.len();
                         tracing::info!(events = total, "Processing file change batch");
 
+                        let mut skipped = 0usize;
                         for path in batch.modified {
-                            if FileFilter::is_code_file(&path)
-                                && !is_default_ignored_path(&path)
-                            {
-                                let language = FileFilter::detect_language(&path).map(String::from);
-                                let request = IndexRequest { path: path.clone(), language };
+                            // Finish the current file but don't start new ones
+                            if watcher_shutdown.is_cancelled() {
+                                skipped += 1;
+                                continue;
+                            }
+
+                            let filter = filters
+                                .iter()
+                                .find(|(root, _)| path.starts_with(root))
+                                .map(|(_, filter)| filter)
+                                .filter(|filter| filter.should_index(&path));
+                            if let Some(filter) = filter {
+                                let language = filter.detect_language(&path).map(String::from);
+                                let request = IndexRequest {
+                                    path: path.clone(),
+                                    language,
+                                    repo: None,
+                                };
                                 match watcher_indexer.index_file(&request).await {
                                     Ok(chunks) => {
                                         if chunks > 0 {
@@ -357,6 +1159,10 @@ async fn serve_command(args: ServeCommandArgs) -> Result<()> {
                                 }
                             }
                         }
+                        if skipped > 0 {
+                            tracing::info!(skipped, "Shutdown: abandoned pending watcher changes");
+                            break;
+                        }
                         for path in batch.deleted {
                             let _ = watcher_delete_tx.send(path).await;
                         }
@@ -367,146 +1173,51 @@ async fn serve_command(args: ServeCommandArgs) -> Result<()> {
                 }
             }
         });
+        app.track_task("watcher", watcher_handle);
     }
 
     app.run().await
 }
 
-/// Reconcile index state from DB on startup (no filesystem walk).
-///
-/// Instead of recursively walking NFS directories (which hangs on slow mounts),
-/// iterate the `file_state` table and check each known file's metadata.
-/// - If file is gone: delete from index
-/// - If mtime or size changed: queue for re-indexing
-/// - If unchanged: skip (fast path)
-///
-/// New files are discovered by the watcher (FSEvents), not the startup scan.
-fn reconcile_from_db(
-    db: &Database,
-    index_tx: &tokio::sync::mpsc::Sender<IndexRequest>,
-    delete_tx: &tokio::sync::mpsc::Sender<std::path::PathBuf>,
-) {
-    tracing::info!("Starting DB-first reconciliation (no filesystem walk)");
-
-    let paths = match db.with_conn(|conn| {
-        nellie::storage::list_file_paths(conn)
-    }) {
-        Ok(p) => p,
-        Err(e) => {
-            tracing::error!(error = %e, "Failed to list file paths from DB");
-            return;
-        }
-    };
-
-    let total = paths.len();
-    tracing::info!(tracked_files = total, "Reconciling file states");
-
-    let mut unchanged = 0u64;
-    let mut requeued = 0u64;
-    let mut deleted = 0u64;
-    let mut errors = 0u64;
-
-    for (i, path_str) in paths.iter().enumerate() {
-        let path = std::path::PathBuf::from(path_str);
-
-        match std::fs::metadata(&path) {
-            Ok(metadata) => {
-                #[allow(clippy::cast_possible_wrap)]
-                let mtime = metadata
-                    .modified()
-                    .map(|t| {
-                        t.duration_since(std::time::UNIX_EPOCH)
-                            .unwrap_or_default()
-                            .as_secs() as i64
-                    })
-                    .unwrap_or(0);
-                #[allow(clippy::cast_possible_wrap)]
-                let size = metadata.len() as i64;
-
-                let needs_index = db
-                    .with_conn(|conn| {
-                        nellie::storage::needs_reindex_by_metadata(conn, path_str, mtime, size)
-                    })
-                    .unwrap_or(true);
-
-                if needs_index {
-                    let language = FileFilter::detect_language(&path).map(String::from);
-                    if index_tx.blocking_send(IndexRequest { path, language }).is_err() {
-                        tracing::warn!("Index channel closed during reconciliation");
-                        return;
-                    }
-                    requeued += 1;
-                } else {
-                    unchanged += 1;
-                }
-            }
-            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
-                if delete_tx.blocking_send(path).is_err() {
-                    tracing::warn!("Delete channel closed during reconciliation");
-                    return;
-                }
-                deleted += 1;
-            }
-            Err(_) => {
-                errors += 1;
-            }
-        }
-
-        if (i + 1) % 10000 == 0 {
-            tracing::info!(
-                progress = i + 1,
-                total,
-                unchanged,
-                requeued,
-                deleted,
-                errors,
-                "Reconciliation progress..."
-            );
-        }
-    }
-
-    tracing::info!(
-        total,
-        unchanged,
-        requeued,
-        deleted,
-        errors,
-        "Reconciliation complete"
-    );
+/// Counters reported at the end of a manual `nellie index` run.
+#[derive(Debug, Default, serde::Serialize)]
+struct IndexSummary {
+    paths_requested: usize,
+    paths_indexed: usize,
+    paths_missing: Vec<PathBuf>,
+    paths_failed: Vec<PathBuf>,
+    files_indexed: u64,
+    files_unchanged: u64,
+    files_skipped: u64,
+    chunks_created: u64,
+    errors: u64,
+    embeddings: bool,
 }
 
-/// Check if a path should be ignored (simplified version for scan).
-fn is_default_ignored_path(path: &std::path::Path) -> bool {
-    let path_str = path.to_string_lossy();
-
-    // Dotdir heuristic
-    for component in path_str.split('/') {
-        if component.starts_with('.')
-            && component.len() > 1
-            && component != ".github"
-            && component != ".gitignore"
-        {
-            return true;
-        }
-    }
-
-    let ignored_dirs = [
-        "node_modules", "target", "build", "dist", "__pycache__",
-        "venv", "vendor", "obj", "bin", "coverage", "egg-info",
-    ];
-
-    if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-        if ignored_dirs.contains(&name) {
-            return true;
-        }
+impl IndexSummary {
+    /// Add the counters from an `index_repo` result.
+    fn add_run(&mut self, result: &serde_json::Value) {
+        let count = |key: &str| result[key].as_u64().unwrap_or(0);
+        self.paths_indexed += 1;
+        self.files_indexed += count("files_indexed");
+        self.files_unchanged += count("files_unchanged");
+        self.files_skipped += count("files_skipped");
+        self.chunks_created += count("chunks_created");
+        self.errors += count("errors");
     }
-
-    false
 }
 
 /// Index command: Manually index directories
-#[allow(clippy::unnecessary_wraps)]
-fn index_command(_data_dir: PathBuf, paths: Vec<PathBuf>, embedding_threads: usize) -> Result<()> {
+///
+/// Runs the same walk-and-index job as the `index_repo` tool against the
+/// local database, loading the embedding model from `model_dir` itself.
+async fn index_command(
+    config: Config,
+    paths: Vec<PathBuf>,
+    embedding_threads: usize,
+    disable_embeddings: bool,
+    format: &str,
+) -> Result<()> {
     if paths.is_empty() {
         return Err(nellie::Error::internal(
             "at least one path must be specified",
@@ -520,69 +1231,234 @@ fn index_command(_data_dir: PathBuf, paths: Vec<PathBuf>, embedding_threads: usi
     );
 
     // Initialize database
-    let db = Database::open(Config::default().database_path())?;
-    init_storage(&db)?;
+    config.validate()?;
+    let db = Database::open(config.database_path())?;
+    let vector_search = init_storage_with(&db, false, None)?;
 
     // Initialize metrics
     init_metrics();
 
+    let embeddings = if disable_embeddings || !vector_search {
+        tracing::warn!("Indexing without embeddings - semantic search won't find these chunks");
+        None
+    } else {
+        let embed_config = nellie::embeddings::EmbeddingConfig::from_model_dir(
+            config.models_path(),
+            embedding_threads,
+        )
+        .with_templates(config.embedding_templates.clone());
+        let service = nellie::embeddings::EmbeddingService::new(embed_config);
+        match service.init().await {
+            Ok(()) => {
+                let model = service.model_info();
+                if let Err(e) =
+                    db.with_conn(|conn| nellie::storage::record_embedding_model(conn, &model))
+                {
+                    tracing::warn!("Failed to record embedding model: {e}");
+                }
+                Some(service)
+            }
+            // Indexing without vectors would record the files as indexed,
+            // so later runs would skip them and they'd never be embedded
+            Err(e) => {
+                return Err(nellie::Error::internal(format!(
+                    "failed to initialize embeddings: {e} \
+                     (pass --disable-embeddings to index without them)"
+                )));
+            }
+        }
+    };
+
+    let state = match embeddings {
+        Some(ref service) => McpState::with_embeddings(db, service.clone()),
+        None => McpState::new(db),
+    }
+    .with_vector_search(vector_search)
+    .with_skip_noise_files(config.skip_lockfiles)
+    .with_file_types(FileTypes::from_config(&config))
+    .with_store_chunk_content(config.store_chunk_content)
+    .with_dedupe_chunks(config.dedupe_chunks);
+
+    let mut summary = IndexSummary {
+        paths_requested: paths.len(),
+        embeddings: embeddings.is_some(),
+        ..IndexSummary::default()
+    };
+
     for path in paths {
-        if !path.exists() {
+        let Ok(root) = path.canonicalize() else {
             tracing::warn!("Path does not exist: {:?}", path);
+            summary.paths_missing.push(path);
             continue;
+        };
+
+        tracing::info!("Indexing: {:?}", root);
+        let response = invoke_tool_direct(
+            &state,
+            ToolRequest {
+                name: "index_repo".to_string(),
+                arguments: serde_json::json!({ "path": root.to_string_lossy() }),
+            },
+        )
+        .await;
+
+        match response.error {
+            None => summary.add_run(&response.content),
+            Some(e) => {
+                tracing::error!("Failed to index {:?}: {e}", root);
+                summary.paths_failed.push(path);
+            }
         }
+    }
 
-        tracing::info!("Indexing: {:?}", path);
-        // TODO: Implement directory indexing
-        // This will be called from watcher module with actual indexing logic
+    if let Some(service) = embeddings {
+        service.shutdown().await;
     }
 
     tracing::info!("Indexing complete");
-    Ok(())
+
+    if format == "json" {
+        let json_str = serde_json::to_string_pretty(&summary)
+            .map_err(|e| nellie::Error::internal(format!("JSON serialization error: {e}")))?;
+        println!("{json_str}");
+    } else {
+        println!(
+            "Indexed {} of {} paths: {} files ({} chunks), {} unchanged, {} skipped, {} errors",
+            summary.paths_indexed,
+            summary.paths_requested,
+            summary.files_indexed,
+            summary.chunks_created,
+            summary.files_unchanged,
+            summary.files_skipped,
+            summary.errors
+        );
+        for path in &summary.paths_missing {
+            println!("  Missing: {}", path.display());
+        }
+        for path in &summary.paths_failed {
+            println!("  Failed:  {}", path.display());
+        }
+    }
+
+    if summary.paths_failed.is_empty() {
+        Ok(())
+    } else {
+        Err(nellie::Error::internal(format!(
+            "failed to index {} of {} paths",
+            summary.paths_failed.len(),
+            summary.paths_requested
+        )))
+    }
 }
 
-/// Search command: Perform semantic search
-#[allow(clippy::needless_pass_by_value)]
-fn search_command(query: String, limit: usize, threshold: f32, server: String) -> Result<()> {
-    tracing::info!(
-        "Searching for: '{}' (limit={}, threshold={})",
-        query,
-        limit,
-        threshold
-    );
+/// Search command: Run one or more queries against the server's REST search endpoint
+async fn search_command(
+    query: Option<&str>,
+    stdin: bool,
+    format: &str,
+    limit: usize,
+    threshold: f32,
+    server: &str,
+    api_key: Option<&str>,
+) -> Result<()> {
+    let queries = resolve_queries(query, stdin, std::io::stdin().lock())?;
+    let url = format!("{}/api/v1/search/code", server.trim_end_matches('/'));
+    let client = reqwest::Client::new();
+    let batch = queries.len() > 1;
+
+    for (i, query) in queries.iter().enumerate() {
+        tracing::info!(query, limit, threshold, "Searching");
+
+        let mut request = client.post(&url).json(&serde_json::json!({
+            "query": query,
+            "limit": limit,
+            "min_score": threshold,
+        }));
+        if let Some(key) = api_key {
+            request = request.bearer_auth(key);
+        }
 
-    // Open database directly and get statistics
-    let db = Database::open(Config::default().database_path())?;
+        let response = request
+            .send()
+            .await
+            .map_err(|e| nellie::Error::internal(format!("failed to reach {url}: {e}")))?;
+        let status = response.status();
+        let body: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| nellie::Error::internal(format!("invalid response from {url}: {e}")))?;
+        if !status.is_success() {
+            let message = body["error"].as_str().unwrap_or("unknown error");
+            return Err(nellie::Error::internal(format!(
+                "search failed ({status}): {message}"
+            )));
+        }
 
-    // Initialize storage schema if needed
-    init_storage(&db)?;
+        if format == "json" {
+            // One compact document per query, so batches stream as NDJSON
+            println!("{body}");
+            continue;
+        }
+        if batch {
+            if i > 0 {
+                println!();
+            }
+            println!("== {query} ==");
+        }
+        print_search_results(&body);
+    }
 
-    let chunk_count = db.with_conn(nellie::storage::count_chunks)?;
+    Ok(())
+}
 
-    if chunk_count == 0 {
-        println!("No indexed chunks found in database.");
-        println!("Please index code first using: nellie index <path>");
-        return Ok(());
+/// Work out which queries `nellie search` should run.
+///
+/// A `-` query or `--stdin` reads one query per non-blank line from `input`;
+/// otherwise the positional query is run on its own.
+fn resolve_queries(
+    query: Option<&str>,
+    stdin: bool,
+    input: impl std::io::BufRead,
+) -> Result<Vec<String>> {
+    if !stdin && query != Some("-") {
+        let query = query.ok_or_else(|| nellie::Error::internal("a query is required"))?;
+        return Ok(vec![query.to_string()]);
     }
 
-    // For semantic search, we would need embeddings. Since search requires the
-    // embedding worker (which needs async context and the server running),
-    // we direct the user to use the server's search API.
-    println!("Semantic code search for: {query}");
-    println!("  Limit: {limit}");
-    println!("  Threshold: {threshold}");
-    println!("  Server: {server}");
-    println!();
-    println!("Note: Semantic search requires the server to be running.");
-    println!("Start the server with: nellie serve");
-    println!();
-    println!("Then query it via the MCP API or REST endpoint:");
-    println!("  - MCP Tool: search_code");
-    println!("  - REST: POST {server}/api/v1/search/code");
-    println!();
-    println!("Database contains {chunk_count} indexed chunks ready for search.");
+    let mut queries = Vec::new();
+    for line in input.lines() {
+        let line = line?;
+        let line = line.trim();
+        if !line.is_empty() {
+            queries.push(line.to_string());
+        }
+    }
 
-    Ok(())
+    if queries.is_empty() {
+        return Err(nellie::Error::internal("no queries read from stdin"));
+    }
+    Ok(queries)
+}
+
+/// Print `search_code` results, one location and score per line.
+fn print_search_results(body: &serde_json::Value) {
+    let results = body["results"].as_array().map_or(&[][..], Vec::as_slice);
+    if results.is_empty() {
+        println!("No results.");
+        return;
+    }
+
+    for result in results {
+        let score = result["score"]
+            .as_f64()
+            .map_or_else(|| "-".to_string(), |s| format!("{s:.3}"));
+        println!(
+            "{score}  {}:{}-{}",
+            result["file_path"].as_str().unwrap_or("?"),
+            result["start_line"],
+            result["end_line"],
+        );
+    }
 }
 
 /// Status command: Show server status
@@ -597,6 +1473,10 @@ fn status_command(_server: String, format: String) -> Result<()> {
     let chunk_count = db.with_conn(nellie::storage::count_chunks)?;
     let lesson_count = db.with_conn(nellie::storage::count_lessons)?;
     let file_count = db.with_conn(nellie::storage::count_tracked_files)?;
+    let chunks_by_language = db.with_conn(nellie::storage::count_chunks_by_language)?;
+    let lessons_by_severity = db.with_conn(nellie::storage::count_lessons_by_severity)?;
+    let last_indexed_at = db.with_conn(nellie::storage::latest_indexed_at)?;
+    let embedding_model = db.with_conn(nellie::storage::get_embedding_model)?;
 
     tracing::info!(
         "Status: {} chunks, {} lessons, {} tracked files",
@@ -609,10 +1489,21 @@ fn status_command(_server: String, format: String) -> Result<()> {
         // JSON output
         let json = serde_json::json!({
             "version": env!("CARGO_PKG_VERSION"),
+            "schema_version": nellie::storage::SCHEMA_VERSION,
+            "embedding_model": embedding_model,
             "stats": {
                 "indexed_chunks": chunk_count,
                 "lessons": lesson_count,
-                "tracked_files": file_count
+                "tracked_files": file_count,
+                "chunks_by_language": chunks_by_language
+                    .iter()
+                    .map(|(lang, count)| (lang.clone(), serde_json::json!(count)))
+                    .collect::<serde_json::Map<_, _>>(),
+                "lessons_by_severity": lessons_by_severity
+                    .iter()
+                    .map(|(severity, count)| (severity.clone(), serde_json::json!(count)))
+                    .collect::<serde_json::Map<_, _>>(),
+                "last_indexed_at": last_indexed_at
             }
         });
         let json_str = serde_json::to_string_pretty(&json)
@@ -626,6 +1517,135 @@ fn status_command(_server: String, format: String) -> Result<()> {
         println!("  Indexed chunks:  {chunk_count}");
         println!("  Lessons:         {lesson_count}");
         println!("  Tracked files:   {file_count}");
+        if let Some(ts) = last_indexed_at {
+            println!("  Last indexed:    {ts}");
+        }
+        println!("  Schema version:  {}", nellie::storage::SCHEMA_VERSION);
+        match &embedding_model {
+            Some(model) => println!("  Embedding model: {} ({}d)", model.name, model.dimension),
+            None => println!("  Embedding model: (not recorded)"),
+        }
+        if !chunks_by_language.is_empty() {
+            println!();
+            println!("Chunks by language:");
+            for (lang, count) in &chunks_by_language {
+                println!("  {lang:<16} {count}");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Repos command: List indexed repositories
+fn repos_command(data_dir: PathBuf, format: &str) -> Result<()> {
+    let config = Config {
+        data_dir,
+        ..Config::default()
+    };
+    let db = Database::open(config.database_path())?;
+    init_storage(&db)?;
+
+    let repos = db.with_conn(nellie::storage::list_repos)?;
+
+    if format == "json" {
+        let json_str = serde_json::to_string_pretty(&repos)
+            .map_err(|e| nellie::Error::internal(format!("JSON serialization error: {e}")))?;
+        println!("{json_str}");
+    } else if repos.is_empty() {
+        println!("No repositories indexed yet.");
+    } else {
+        println!(
+            "{:<24} {:>8} {:>8}  {:<12} LANGUAGES",
+            "REPO", "CHUNKS", "FILES", "LAST INDEXED"
+        );
+        for repo in &repos {
+            println!(
+                "{:<24} {:>8} {:>8}  {:<12} {}",
+                repo.repo.as_deref().unwrap_or("(none)"),
+                repo.chunk_count,
+                repo.file_count,
+                repo.last_indexed_at,
+                repo.languages.join(", ")
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Optimize command: Rebuild the vector tables
+fn optimize_command(data_dir: PathBuf, format: &str) -> Result<()> {
+    let config = Config {
+        data_dir,
+        ..Config::default()
+    };
+    let db = Database::open(config.database_path())?;
+    init_storage(&db)?;
+
+    let started = std::time::Instant::now();
+    let tables = db.with_transaction(nellie::storage::rebuild_vector_tables)?;
+    let elapsed = started.elapsed();
+
+    if format == "json" {
+        let json = serde_json::json!({
+            "tables": tables,
+            "elapsed_ms": elapsed.as_millis(),
+        });
+        let json_str = serde_json::to_string_pretty(&json)
+            .map_err(|e| nellie::Error::internal(format!("JSON serialization error: {e}")))?;
+        println!("{json_str}");
+    } else {
+        println!("{:<24} {:>10} {:>10}", "TABLE", "BEFORE", "AFTER");
+        for table in &tables {
+            println!(
+                "{:<24} {:>10} {:>10}",
+                table.table, table.rows_before, table.rows_after
+            );
+        }
+        println!("Optimized in {:.1}s", elapsed.as_secs_f64());
+    }
+
+    Ok(())
+}
+
+/// Db backfill-repo command: Infer the repository of unattributed chunks
+fn backfill_repo_command(data_dir: PathBuf, format: &str) -> Result<()> {
+    let config = Config {
+        data_dir,
+        ..Config::default()
+    };
+    let db = Database::open(config.database_path())?;
+    init_storage(&db)?;
+
+    // Files in one directory share a repository; look each one up once
+    let mut repos_by_dir: std::collections::HashMap<PathBuf, Option<String>> =
+        std::collections::HashMap::new();
+    let report = db.with_transaction(|conn| {
+        nellie::storage::backfill_chunk_repos(conn, |file_path| {
+            let dir = std::path::Path::new(file_path).parent()?;
+            repos_by_dir
+                .entry(dir.to_path_buf())
+                .or_insert_with(|| detect_repo(dir))
+                .clone()
+        })
+    })?;
+
+    if format == "json" {
+        let json_str = serde_json::to_string_pretty(&report)
+            .map_err(|e| nellie::Error::internal(format!("JSON serialization error: {e}")))?;
+        println!("{json_str}");
+    } else {
+        println!(
+            "Updated {} chunks in {} files",
+            report.chunks_updated, report.files_updated
+        );
+        if report.files_unattributed > 0 {
+            println!(
+                "Could not attribute {} chunks in {} files (no git repository found)",
+                report.chunks_unattributed, report.files_unattributed
+            );
+        }
     }
 
     Ok(())
@@ -644,21 +1664,152 @@ mod tests {
         if let Some(Commands::Serve {
             host,
             port,
+            socket,
             watch,
             embedding_threads,
             disable_embeddings,
+            verify_hashes,
+            reconcile_batch_size,
+            reconcile_queue_capacity,
+            index_queue_overflow,
+            query_cache_size,
+            debounce_ms,
+            cors_origins,
+            max_request_bytes,
+            allow_missing_vector_search,
+            agent_stale_secs,
+            max_checkpoint_state_bytes,
+            max_checkpoint_state_depth,
+            distance_metric,
+            lesson_duplicate_threshold,
+            recency_half_life_days,
+            index_concurrency,
+            index_complete_webhook,
+            onnx_intra_threads,
+            onnx_inter_threads,
+            execution_provider,
+            long_chunk_strategy,
+            index_lockfiles,
+            no_chunk_content,
+            dedupe_chunks,
+            min_free_disk_bytes,
+            reranker_model,
+            normalize_tags,
+            extra_severities,
+            max_search_limit,
+            slow_query_ms,
+            metrics_refresh_secs,
+            embedding_max_restarts,
+            embedding_timeout_ms,
+            disabled_tools,
+            read_only,
+            api_docs_ui,
         }) = cli.command
         {
             assert_eq!(host, "0.0.0.0");
             assert_eq!(port, 9000);
+            assert!(socket.is_none());
             assert!(watch.is_empty());
             assert_eq!(embedding_threads, 4);
             assert!(!disable_embeddings);
+            assert!(!verify_hashes);
+            assert_eq!(reconcile_batch_size, 500);
+            assert_eq!(reconcile_queue_capacity, 1000);
+            assert_eq!(index_queue_overflow, OverflowPolicy::Block);
+            assert_eq!(query_cache_size, 256);
+            assert_eq!(debounce_ms, 500);
+            assert!(cors_origins.is_empty());
+            assert_eq!(max_request_bytes, 4 * 1024 * 1024);
+            assert!(!allow_missing_vector_search);
+            assert_eq!(agent_stale_secs, 3600);
+            assert_eq!(max_checkpoint_state_bytes, 256 * 1024);
+            assert_eq!(max_checkpoint_state_depth, 32);
+            assert_eq!(distance_metric, DistanceMetric::L2);
+            assert!((lesson_duplicate_threshold - 0.9).abs() < f32::EPSILON);
+            assert_eq!(recency_half_life_days, 90);
+            assert_eq!(index_concurrency, 4);
+            assert!(index_complete_webhook.is_none());
+            assert_eq!(onnx_intra_threads, 1);
+            assert_eq!(onnx_inter_threads, 1);
+            assert_eq!(execution_provider, ExecutionProvider::Cpu);
+            assert_eq!(long_chunk_strategy, LongChunkStrategy::Truncate);
+            assert!(!index_lockfiles);
+            assert!(!no_chunk_content);
+            assert!(!dedupe_chunks);
+            assert_eq!(min_free_disk_bytes, 1024 * 1024 * 1024);
+            assert!(reranker_model.is_none());
+            assert!(!normalize_tags);
+            assert!(extra_severities.is_empty());
+            assert_eq!(max_search_limit, 100);
+            assert_eq!(slow_query_ms, 1000);
+            assert_eq!(metrics_refresh_secs, 15);
+            assert_eq!(embedding_max_restarts, 5);
+            assert_eq!(embedding_timeout_ms, 30_000);
+            assert!(disabled_tools.is_empty());
+            assert!(!read_only);
+            assert!(!api_docs_ui);
         } else {
             panic!("Expected Serve command");
         }
     }
 
+    #[test]
+    fn test_cli_parsing_default_command() {
+        let parse = |args: &[&str]| parse_args(args.iter().map(Into::into).collect());
+
+        // Help by default: nothing to run, and serve flags are rejected
+        assert!(parse(&["nellie", "--data-dir", "/srv/nellie"])
+            .unwrap()
+            .is_none());
+        assert!(parse(&["nellie", "--port", "9000"]).is_err());
+
+        // The serve fallback re-parses with `serve` inserted, so serve flags
+        // and global flags given without a subcommand both apply
+        let (cli, command) = parse(&[
+            "nellie",
+            "--default-command",
+            "serve",
+            "--data-dir",
+            "/srv/nellie",
+            "--port",
+            "9000",
+        ])
+        .unwrap()
+        .unwrap();
+        assert_eq!(cli.default_command, DefaultCommand::Serve);
+        assert_eq!(cli.data_dir, PathBuf::from("/srv/nellie"));
+        assert!(matches!(command, Commands::Serve { port: 9000, .. }));
+
+        // An explicit subcommand wins over the default
+        let (_, command) = parse(&["nellie", "--default-command", "serve", "repos"])
+            .unwrap()
+            .unwrap();
+        assert!(matches!(command, Commands::Repos { .. }));
+
+        assert!(parse(&["nellie", "--default-command", "run"]).is_err());
+    }
+
+    #[test]
+    fn test_cli_parsing_serve_socket() {
+        let cli = Cli::try_parse_from(["nellie", "serve", "--socket", "/tmp/nellie.sock"]).unwrap();
+        if let Some(Commands::Serve { socket, .. }) = cli.command {
+            assert_eq!(socket, Some(PathBuf::from("/tmp/nellie.sock")));
+        } else {
+            panic!("Expected Serve command");
+        }
+
+        // A socket replaces host/port, so combining them is an error
+        let args = [
+            "nellie",
+            "serve",
+            "--socket",
+            "/tmp/nellie.sock",
+            "--port",
+            "9000",
+        ];
+        assert!(Cli::try_parse_from(args).is_err());
+    }
+
     #[test]
     fn test_cli_parsing_index() {
         let args = vec!["nellie", "index", "/path/to/code"];
@@ -668,15 +1819,54 @@ mod tests {
         if let Some(Commands::Index {
             paths,
             embedding_threads,
+            disable_embeddings,
+            no_chunk_content,
+            dedupe_chunks,
+            format,
         }) = cli.command
         {
             assert_eq!(paths.len(), 1);
             assert_eq!(embedding_threads, 4);
+            assert!(!disable_embeddings);
+            assert!(!no_chunk_content);
+            assert!(!dedupe_chunks);
+            assert_eq!(format, "text");
         } else {
             panic!("Expected Index command");
         }
     }
 
+    #[test]
+    fn test_cli_parsing_templates() {
+        let cli = Cli::try_parse_from(vec!["nellie", "index", "/path/to/code"]).unwrap();
+        let templates = cli.templates.into_templates();
+        assert_eq!(templates.code, "{content}");
+        // The escaped default renders like the built-in one
+        assert_eq!(
+            templates.lesson("Title", "Body"),
+            EmbeddingTemplates::default().lesson("Title", "Body")
+        );
+
+        let args = vec![
+            "nellie",
+            "index",
+            "/path/to/code",
+            "--code-template",
+            "search_document: {content}",
+            "--code-query-template",
+            "search_query: {query}",
+        ];
+        let templates = Cli::try_parse_from(args)
+            .unwrap()
+            .templates
+            .into_templates();
+        assert_eq!(
+            templates.code("fn a() {}", None, "/a.rs"),
+            "search_document: fn a() {}"
+        );
+        assert_eq!(templates.code_query("retry"), "search_query: retry");
+    }
+
     #[test]
     fn test_cli_parsing_search() {
         let args = vec!["nellie", "search", "find auth handler"];
@@ -685,12 +1875,16 @@ mod tests {
         let cli = cli.unwrap();
         if let Some(Commands::Search {
             query,
+            stdin,
+            format,
             limit,
             threshold,
             server,
         }) = cli.command
         {
-            assert_eq!(query, "find auth handler");
+            assert_eq!(query.as_deref(), Some("find auth handler"));
+            assert!(!stdin);
+            assert_eq!(format, "text");
             assert_eq!(limit, 10);
             assert_eq!(threshold, 0.5);
             assert_eq!(server, "http://127.0.0.1:8080");
@@ -699,6 +1893,73 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_cli_parsing_search_stdin() {
+        let cli = Cli::try_parse_from(["nellie", "search", "--stdin"]).unwrap();
+        if let Some(Commands::Search { query, stdin, .. }) = cli.command {
+            assert!(query.is_none());
+            assert!(stdin);
+        } else {
+            panic!("Expected Search command");
+        }
+
+        let cli = Cli::try_parse_from(["nellie", "search", "-"]).unwrap();
+        if let Some(Commands::Search { query, stdin, .. }) = cli.command {
+            assert_eq!(query.as_deref(), Some("-"));
+            assert!(!stdin);
+        } else {
+            panic!("Expected Search command");
+        }
+
+        let cli = Cli::try_parse_from(["nellie", "search", "-", "--format", "json"]).unwrap();
+        assert!(matches!(cli.command, Some(Commands::Search { format, .. }) if format == "json"));
+
+        // A query is required unless reading from stdin
+        assert!(Cli::try_parse_from(["nellie", "search"]).is_err());
+        assert!(Cli::try_parse_from(["nellie", "search", "q", "--stdin"]).is_err());
+    }
+
+    #[test]
+    fn test_index_summary_json() {
+        let cli = Cli::try_parse_from(["nellie", "index", "/code", "--format", "json"]).unwrap();
+        assert!(matches!(cli.command, Some(Commands::Index { format, .. }) if format == "json"));
+
+        let mut summary = IndexSummary {
+            paths_requested: 2,
+            paths_missing: vec![PathBuf::from("/missing")],
+            ..IndexSummary::default()
+        };
+        summary.add_run(&serde_json::json!({
+            "files_indexed": 3,
+            "files_unchanged": 1,
+            "chunks_created": 12,
+            "errors": 0
+        }));
+        let json: serde_json::Value =
+            serde_json::from_str(&serde_json::to_string_pretty(&summary).unwrap()).unwrap();
+        assert_eq!(json["paths_requested"], 2);
+        assert_eq!(json["paths_indexed"], 1);
+        assert_eq!(json["paths_missing"], serde_json::json!(["/missing"]));
+        assert_eq!(json["files_indexed"], 3);
+        assert_eq!(json["files_unchanged"], 1);
+        assert_eq!(json["chunks_created"], 12);
+        assert_eq!(json["errors"], 0);
+    }
+
+    #[test]
+    fn test_resolve_queries() {
+        let input = std::io::Cursor::new("find auth handler\n\n  parse config  \n");
+        let queries = resolve_queries(Some("-"), false, input.clone()).unwrap();
+        assert_eq!(queries, vec!["find auth handler", "parse config"]);
+        assert_eq!(resolve_queries(None, true, input).unwrap(), queries);
+
+        // A positional query ignores stdin
+        let queries = resolve_queries(Some("one query"), false, std::io::Cursor::new("x")).unwrap();
+        assert_eq!(queries, vec!["one query"]);
+
+        assert!(resolve_queries(None, true, std::io::Cursor::new(" \n")).is_err());
+    }
+
     #[test]
     fn test_cli_parsing_status() {
         let args = vec!["nellie", "status"];
@@ -713,6 +1974,40 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_cli_parsing_repos() {
+        let cli = Cli::try_parse_from(["nellie", "repos", "--format", "json"]).unwrap();
+        if let Some(Commands::Repos { format }) = cli.command {
+            assert_eq!(format, "json");
+        } else {
+            panic!("Expected Repos command");
+        }
+    }
+
+    #[test]
+    fn test_cli_parsing_optimize() {
+        let cli = Cli::try_parse_from(["nellie", "optimize"]).unwrap();
+        if let Some(Commands::Optimize { format }) = cli.command {
+            assert_eq!(format, "text");
+        } else {
+            panic!("Expected Optimize command");
+        }
+    }
+
+    #[test]
+    fn test_cli_parsing_db_backfill_repo() {
+        let cli =
+            Cli::try_parse_from(["nellie", "db", "backfill-repo", "--format", "json"]).unwrap();
+        if let Some(Commands::Db {
+            command: DbCommands::BackfillRepo { format },
+        }) = cli.command
+        {
+            assert_eq!(format, "json");
+        } else {
+            panic!("Expected Db backfill-repo command");
+        }
+    }
+
     #[test]
     fn test_cli_global_options() {
         let args = vec![
@@ -728,6 +2023,75 @@ mod tests {
         let cli = cli.unwrap();
         assert_eq!(cli.data_dir, PathBuf::from("/custom/data"));
         assert_eq!(cli.log_level, "debug");
+        assert_eq!(cli.model_dir, None);
+        assert_eq!(cli.worker_threads, None);
+        assert_eq!(cli.blocking_threads, 512);
+
+        let cli = Cli::try_parse_from([
+            "nellie",
+            "index",
+            "/src",
+            "--worker-threads",
+            "2",
+            "--blocking-threads",
+            "8",
+        ])
+        .unwrap();
+        assert_eq!(cli.worker_threads, Some(2));
+        assert_eq!(cli.blocking_threads, 8);
+    }
+
+    #[test]
+    fn test_cli_extra_languages() {
+        let cli = Cli::try_parse_from([
+            "nellie",
+            "index",
+            "/repo",
+            "--extra-languages",
+            "tf=terraform, bzl = starlark",
+            "--extra-code-extensions",
+            "rbi,.hcl",
+        ])
+        .unwrap();
+        assert_eq!(
+            cli.languages.extra_languages,
+            vec![
+                ("tf".to_string(), "terraform".to_string()),
+                ("bzl".to_string(), "starlark".to_string()),
+            ]
+        );
+        assert_eq!(cli.languages.extra_code_extensions, vec!["rbi", ".hcl"]);
+        assert!(!cli.languages.index_docs);
+        assert!(cli.languages.index_extensions.is_empty());
+
+        let cli = Cli::try_parse_from([
+            "nellie",
+            "serve",
+            "--index-docs",
+            "--index-extensions",
+            "md,rs",
+        ])
+        .unwrap();
+        assert!(cli.languages.index_docs);
+        assert_eq!(cli.languages.index_extensions, vec!["md", "rs"]);
+
+        assert!(Cli::try_parse_from(["nellie", "--extra-languages", "tf", "serve"]).is_err());
+        assert!(Cli::try_parse_from(["nellie", "--extra-languages", "=hcl", "serve"]).is_err());
+    }
+
+    #[test]
+    fn test_cli_model_dir() {
+        let cli = Cli::try_parse_from([
+            "nellie",
+            "serve",
+            "--data-dir",
+            "/var/lib/nellie-rs",
+            "--model-dir",
+            "/opt/nellie/models",
+        ])
+        .unwrap();
+        assert_eq!(cli.data_dir, PathBuf::from("/var/lib/nellie-rs"));
+        assert_eq!(cli.model_dir, Some(PathBuf::from("/opt/nellie/models")));
     }
 
     #[test]
@@ -757,12 +2121,16 @@ mod tests {
         let cli = cli.unwrap();
         if let Some(Commands::Search {
             query,
+            stdin,
+            format,
             limit,
             threshold,
             server,
         }) = cli.command
         {
-            assert_eq!(query, "database query");
+            assert_eq!(query, Some("database query".to_string()));
+            assert!(!stdin);
+            assert_eq!(format, "text");
             assert_eq!(limit, 20);
             assert_eq!(threshold, 0.7);
             assert_eq!(server, "http://custom.server:9000");
@@ -787,6 +2155,17 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_cli_verify_hashes() {
+        let args = vec!["nellie", "serve", "--verify-hashes"];
+        let cli = Cli::try_parse_from(args).unwrap();
+        if let Some(Commands::Serve { verify_hashes, .. }) = cli.command {
+            assert!(verify_hashes);
+        } else {
+            panic!("Expected Serve command");
+        }
+    }
+
     #[test]
     fn test_cli_help_message() {
         // Test that help parsing doesn't crash
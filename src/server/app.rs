@@ -8,47 +8,130 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use axum::{
-    extract::Request,
-    http::StatusCode,
-    middleware::{self, Next},
-    response::{IntoResponse, Response},
-    Router,
+    extract::DefaultBodyLimit,
+    http::{header, HeaderName, HeaderValue, Method},
+    middleware, Router,
 };
+use parking_lot::Mutex;
 use tokio::signal;
 use tokio::sync::mpsc;
-use tower_http::cors::{Any, CorsLayer};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tower_http::limit::RequestBodyLimitLayer;
 use tower_http::trace::TraceLayer;
 
-use super::auth::ApiKeyConfig;
+use super::auth::{auth_middleware_wrapper, ApiKeyConfig};
 use super::mcp::{create_mcp_router, McpState};
+use super::openapi::create_openapi_router;
 use super::rest::create_rest_router;
 use super::sse::create_sse_router;
-use crate::embeddings::{EmbeddingConfig, EmbeddingService};
+use crate::embeddings::{
+    EmbeddingConfig, EmbeddingService, EmbeddingTemplates, ExecutionProvider, LongChunkStrategy,
+    OnnxOptions, RerankerConfig, RerankerService,
+};
 use crate::storage::Database;
 use crate::watcher::{
-    EventHandler, FileWatcher, HandlerConfig, Indexer, WatcherConfig, WatcherStats,
+    index_queue, EventHandler, FileTypes, FileWatcher, HandlerConfig, IndexSender, OverflowPolicy,
+    ScanStats, WatcherConfig,
 };
 use crate::Result;
 
 /// Server configuration.
+// Each flag mirrors an independent command-line switch
+#[allow(clippy::struct_excessive_bools)]
 #[derive(Debug, Clone)]
 pub struct ServerConfig {
     /// Host address to bind to
     pub host: String,
     /// Port to listen on
     pub port: u16,
+    /// Unix domain socket to listen on instead of `host`/`port`
+    pub listen_socket: Option<std::path::PathBuf>,
     /// Shutdown timeout duration
     pub shutdown_timeout: Duration,
     /// API key for authentication (None = disabled)
     pub api_key: Option<String>,
     /// Data directory for models and embeddings
     pub data_dir: std::path::PathBuf,
+    /// Embedding model directory (None = `data_dir/models`)
+    pub model_dir: Option<std::path::PathBuf>,
     /// Number of embedding worker threads
     pub embedding_threads: usize,
+    /// Query embedding cache size (0 = disabled)
+    pub query_cache_size: usize,
     /// Enable embedding service (semantic search)
     pub enable_embeddings: bool,
     /// Directories to watch for code changes
     pub watch_dirs: Vec<std::path::PathBuf>,
+    /// Allowed CORS origins (empty = no CORS, `["*"]` = any origin)
+    pub cors_allowed_origins: Vec<String>,
+    /// Maximum request body size in bytes
+    pub max_request_bytes: usize,
+    /// Whether sqlite-vec loaded (false = semantic tools return errors)
+    pub vector_search: bool,
+    /// Seconds without a status update before an agent is reported stale
+    pub agent_stale_secs: i64,
+    /// Largest serialized checkpoint `state` accepted, in bytes
+    pub max_checkpoint_state_bytes: usize,
+    /// Deepest nesting accepted in checkpoint `state`
+    pub max_checkpoint_state_depth: usize,
+    /// Similarity at which `add_lesson` reports a possible duplicate
+    pub lesson_duplicate_threshold: f32,
+    /// Half-life in days for recency-weighted lesson/checkpoint search
+    pub recency_half_life_days: u64,
+    /// Files the indexing tools index at once
+    pub index_concurrency: usize,
+    /// URL that receives a POST when an indexing run completes
+    pub index_complete_webhook: Option<String>,
+    /// ONNX Runtime intra-op threads per embedding session
+    pub onnx_intra_threads: usize,
+    /// ONNX Runtime inter-op threads per embedding session
+    pub onnx_inter_threads: usize,
+    /// Requested ONNX Runtime execution provider
+    pub execution_provider: ExecutionProvider,
+    /// How inputs longer than the model's sequence length are embedded
+    pub long_chunk_strategy: LongChunkStrategy,
+    /// Skip dependency lockfiles and minified assets when indexing
+    pub skip_noise_files: bool,
+    /// Debounce window for watcher events, in milliseconds
+    pub debounce_ms: u64,
+    /// Store chunk text in the database (false = read it from disk)
+    pub store_chunk_content: bool,
+    /// Reuse embeddings of chunks whose text is already indexed
+    pub dedupe_chunks: bool,
+    /// Capacity of the queue feeding scanned and changed files to the indexer
+    pub index_queue_capacity: usize,
+    /// What senders do when the index queue is full
+    pub index_queue_overflow: OverflowPolicy,
+    /// Free disk bytes below which status reports a low-disk warning
+    pub min_free_disk_bytes: u64,
+    /// Cross-encoder model for `search_code` reranking (None = disabled)
+    pub reranker_model: Option<std::path::PathBuf>,
+    /// Normalize lesson tags on write
+    pub normalize_tags: bool,
+    /// Lesson severities accepted besides `critical`, `warning` and `info`
+    pub extra_severities: Vec<String>,
+    /// Largest `limit` the search tools and `list_lessons` honour
+    pub max_search_limit: usize,
+    /// Tool latency in milliseconds at which a call is logged as slow
+    pub slow_query_ms: u64,
+    /// Seconds between refreshes of the row-count metrics gauges (0 = never)
+    pub metrics_refresh_secs: u64,
+    /// Crashed embedding workers replaced before the pool is degraded
+    pub embedding_max_restarts: usize,
+    /// Milliseconds to wait for an embedding (0 waits forever)
+    pub embedding_timeout_ms: u64,
+    /// MCP tools hidden and refused on every transport
+    pub disabled_tools: Vec<String>,
+    /// Disable every mutating MCP tool
+    pub read_only: bool,
+    /// Serve the Swagger UI at `/docs`
+    pub api_docs_ui: bool,
+    /// Templates applied to code, lesson and checkpoint text before embedding
+    pub embedding_templates: EmbeddingTemplates,
+    /// Which extensions are indexed and their languages
+    pub file_types: FileTypes,
 }
 
 impl Default for ServerConfig {
@@ -56,20 +139,92 @@ impl Default for ServerConfig {
         Self {
             host: "127.0.0.1".to_string(),
             port: 8080,
+            listen_socket: None,
             shutdown_timeout: Duration::from_secs(30),
             api_key: None,
             data_dir: std::path::PathBuf::from("./data"),
+            model_dir: None,
             embedding_threads: 4,
+            query_cache_size: 256,
             enable_embeddings: true,
             watch_dirs: Vec::new(),
+            cors_allowed_origins: Vec::new(),
+            max_request_bytes: 4 * 1024 * 1024,
+            vector_search: true,
+            agent_stale_secs: crate::storage::DEFAULT_AGENT_STALE_SECS,
+            max_checkpoint_state_bytes: super::mcp::DEFAULT_MAX_CHECKPOINT_STATE_BYTES,
+            max_checkpoint_state_depth: super::mcp::DEFAULT_MAX_CHECKPOINT_STATE_DEPTH,
+            lesson_duplicate_threshold: super::mcp::DEFAULT_LESSON_DUPLICATE_THRESHOLD,
+            recency_half_life_days: super::mcp::DEFAULT_RECENCY_HALF_LIFE_DAYS,
+            index_concurrency: super::indexing::DEFAULT_INDEX_CONCURRENCY,
+            index_complete_webhook: None,
+            onnx_intra_threads: 1,
+            onnx_inter_threads: 1,
+            execution_provider: ExecutionProvider::Cpu,
+            long_chunk_strategy: LongChunkStrategy::Truncate,
+            skip_noise_files: true,
+            debounce_ms: crate::watcher::DEFAULT_DEBOUNCE_MS,
+            store_chunk_content: true,
+            dedupe_chunks: false,
+            index_queue_capacity: crate::watcher::DEFAULT_RECONCILE_QUEUE_CAPACITY,
+            index_queue_overflow: OverflowPolicy::Block,
+            min_free_disk_bytes: super::mcp::DEFAULT_MIN_FREE_DISK_BYTES,
+            reranker_model: None,
+            normalize_tags: false,
+            extra_severities: Vec::new(),
+            max_search_limit: super::mcp::DEFAULT_MAX_SEARCH_LIMIT,
+            slow_query_ms: super::mcp::DEFAULT_SLOW_QUERY_MS,
+            metrics_refresh_secs: super::metrics::DEFAULT_METRICS_REFRESH_SECS,
+            embedding_max_restarts: crate::embeddings::DEFAULT_MAX_WORKER_RESTARTS,
+            embedding_timeout_ms: crate::embeddings::DEFAULT_EMBED_TIMEOUT_MS,
+            disabled_tools: Vec::new(),
+            read_only: false,
+            api_docs_ui: false,
+            embedding_templates: EmbeddingTemplates::default(),
+            file_types: FileTypes::default(),
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Directory the embedding model is loaded from.
+    #[must_use]
+    pub fn models_path(&self) -> std::path::PathBuf {
+        self.model_dir
+            .clone()
+            .unwrap_or_else(|| self.data_dir.join("models"))
+    }
+
+    /// ONNX Runtime session options for the embedding model.
+    #[must_use]
+    pub const fn onnx_options(&self) -> OnnxOptions {
+        OnnxOptions {
+            intra_threads: self.onnx_intra_threads,
+            inter_threads: self.onnx_inter_threads,
+            execution_provider: self.execution_provider,
         }
     }
+
+    /// Reranker for `search_code`, if a model is configured.
+    ///
+    /// The model itself is loaded on the first rerank request.
+    #[must_use]
+    pub fn reranker(&self) -> Option<RerankerService> {
+        self.reranker_model.as_ref().map(|path| {
+            RerankerService::new(
+                RerankerConfig::from_model_path(path).with_onnx_options(self.onnx_options()),
+            )
+        })
+    }
 }
 
 /// Application server.
 pub struct App {
     config: ServerConfig,
     state: Arc<McpState>,
+    shutdown: CancellationToken,
+    tasks: Mutex<Vec<(String, JoinHandle<()>)>>,
+    embedding_services: Mutex<Vec<EmbeddingService>>,
 }
 
 impl App {
@@ -91,36 +246,110 @@ impl App {
     ///
     /// Returns an error if the database operations fail.
     pub async fn new(config: ServerConfig, db: Database) -> Result<Self> {
-        let state = if config.enable_embeddings {
+        Self::normalize_severities(&db, &config.extra_severities);
+
+        let state = if !config.vector_search {
+            // Embeddings could not be stored or searched without sqlite-vec
+            tracing::warn!("Vector search unavailable - skipping embedding service");
+            McpState::with_api_key(db, config.api_key.clone())
+        } else if config.enable_embeddings {
             // Try to initialize embedding service
             match Self::init_embeddings(&config).await {
                 Ok(embedding_service) => {
                     tracing::info!("Embedding service initialized successfully");
-                    Arc::new(McpState::with_embeddings_and_api_key(
+                    Self::record_embedding_model(&db, &embedding_service);
+                    McpState::with_embeddings_and_api_key(
                         db,
                         embedding_service,
                         config.api_key.clone(),
-                    ))
+                    )
                 }
                 Err(e) => {
                     tracing::warn!(
                         "Failed to initialize embeddings: {}. Semantic search disabled.",
                         e
                     );
-                    Arc::new(McpState::with_api_key(db, config.api_key.clone()))
+                    McpState::with_api_key(db, config.api_key.clone())
                 }
             }
         } else {
             tracing::warn!("Embeddings disabled via configuration - semantic search will not work");
-            Arc::new(McpState::with_api_key(db, config.api_key.clone()))
+            McpState::with_api_key(db, config.api_key.clone())
         };
+        let state = Arc::new(
+            state
+                .with_vector_search(config.vector_search)
+                .with_agent_stale_secs(config.agent_stale_secs)
+                .with_checkpoint_state_limits(
+                    config.max_checkpoint_state_bytes,
+                    config.max_checkpoint_state_depth,
+                )
+                .with_lesson_duplicate_threshold(config.lesson_duplicate_threshold)
+                .with_recency_half_life_days(config.recency_half_life_days)
+                .with_index_concurrency(config.index_concurrency)
+                .with_index_complete_webhook(config.index_complete_webhook.clone())
+                .with_skip_noise_files(config.skip_noise_files)
+                .with_file_types(config.file_types.clone())
+                .with_store_chunk_content(config.store_chunk_content)
+                .with_dedupe_chunks(config.dedupe_chunks)
+                .with_min_free_disk_bytes(config.min_free_disk_bytes)
+                .with_reranker(config.reranker())
+                .with_normalize_tags(config.normalize_tags)
+                .with_extra_severities(config.extra_severities.clone())
+                .with_max_search_limit(config.max_search_limit)
+                .with_slow_query_ms(config.slow_query_ms)
+                .with_disabled_tools(config.disabled_tools.clone())
+                .with_read_only(config.read_only),
+        );
+
+        Ok(Self {
+            config,
+            state,
+            shutdown: CancellationToken::new(),
+            tasks: Mutex::new(Vec::new()),
+            embedding_services: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Get the token cancelled when the server begins shutting down.
+    ///
+    /// Background tasks should stop accepting new work once it fires.
+    #[must_use]
+    pub fn shutdown_token(&self) -> CancellationToken {
+        self.shutdown.clone()
+    }
 
-        Ok(Self { config, state })
+    /// Get the progress tracker reported as `reconciliation` by `get_status`.
+    #[must_use]
+    pub fn reconcile_status(&self) -> Arc<crate::watcher::ReconcileStatus> {
+        Arc::clone(&self.state.reconcile)
+    }
+
+    /// Report `index_tx`'s queue as `index_queue` in `get_status`.
+    pub fn track_index_queue(&self, index_tx: &IndexSender) {
+        self.state.set_index_queue(index_tx.monitor());
+    }
+
+    /// Register a background task to be drained on shutdown.
+    ///
+    /// After the HTTP server stops, `run` waits for tracked tasks (bounded by
+    /// `shutdown_timeout`) and aborts any that haven't finished.
+    pub fn track_task(&self, name: impl Into<String>, handle: JoinHandle<()>) {
+        self.tasks.lock().push((name.into(), handle));
+    }
+
+    /// Register an additional embedding service to stop on shutdown.
+    ///
+    /// The app's own service is always stopped; this is for services owned by
+    /// background tasks such as the indexer.
+    pub fn track_embeddings(&self, service: EmbeddingService) {
+        self.embedding_services.lock().push(service);
     }
 
     /// Initialize the embedding service.
     ///
-    /// Loads the ONNX model and starts worker threads.
+    /// Loads the ONNX model, starts worker threads, and runs a warmup
+    /// inference so the first real query doesn't pay the cold-start cost.
     ///
     /// # Arguments
     ///
@@ -135,33 +364,110 @@ impl App {
     /// Returns an error if model loading fails.
     async fn init_embeddings(config: &ServerConfig) -> Result<EmbeddingService> {
         let embedding_config =
-            EmbeddingConfig::from_data_dir(&config.data_dir, config.embedding_threads);
+            EmbeddingConfig::from_model_dir(config.models_path(), config.embedding_threads)
+                .with_query_cache_size(config.query_cache_size)
+                .with_onnx_options(config.onnx_options())
+                .with_long_chunk_strategy(config.long_chunk_strategy)
+                .with_max_worker_restarts(config.embedding_max_restarts)
+                .with_embed_timeout_ms(config.embedding_timeout_ms)
+                .with_templates(config.embedding_templates.clone());
 
         let service = EmbeddingService::new(embedding_config);
         service.init().await?;
+        service.warmup().await?;
 
         Ok(service)
     }
 
+    /// Record the embedding model in the database on first use.
+    ///
+    /// Warns when the loaded model differs from the one that produced the
+    /// stored vectors, since vectors from different models aren't comparable.
+    fn record_embedding_model(db: &Database, service: &EmbeddingService) {
+        let model = service.model_info();
+        match db.with_conn(|conn| crate::storage::record_embedding_model(conn, &model)) {
+            Ok(recorded) if recorded != model => {
+                tracing::warn!(
+                    recorded = %recorded.name,
+                    loaded = %model.name,
+                    "Embedding model differs from the one recorded for this database - \
+                     search results will mix incompatible vectors"
+                );
+            }
+            Ok(_) => {}
+            Err(e) => tracing::warn!("Failed to record embedding model: {e}"),
+        }
+    }
+
+    /// Correct stored lesson severities outside the allowed set, so severity
+    /// filters see every lesson.
+    fn normalize_severities(db: &Database, extra: &[String]) {
+        match db.with_conn(|conn| crate::storage::normalize_lesson_severities(conn, extra)) {
+            Ok(0) => {}
+            Ok(corrected) => {
+                tracing::info!(corrected, "Corrected lessons with unknown severities");
+            }
+            Err(e) => tracing::warn!("Failed to normalize lesson severities: {e}"),
+        }
+    }
+
+    /// Keep the row-count gauges on `/metrics` current by recounting every
+    /// `metrics_refresh_secs` until shutdown.
+    fn start_metrics_refresh(&self) {
+        if self.config.metrics_refresh_secs == 0 {
+            return;
+        }
+
+        let db = self.state.db.clone();
+        let shutdown = self.shutdown.clone();
+        let period = Duration::from_secs(self.config.metrics_refresh_secs);
+        let handle = tokio::spawn(async move {
+            let mut timer = tokio::time::interval(period);
+            timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                tokio::select! {
+                    biased;
+                    () = shutdown.cancelled() => break,
+                    _ = timer.tick() => {
+                        // The count queries block on SQLite, so keep them off
+                        // the runtime's worker threads
+                        let db = db.clone();
+                        match tokio::task::spawn_blocking(move || {
+                            super::metrics::refresh_db_gauges(&db)
+                        })
+                        .await
+                        {
+                            Ok(Ok(())) => {}
+                            Ok(Err(e)) => tracing::warn!("Failed to refresh metrics gauges: {e}"),
+                            Err(e) => tracing::warn!("Metrics refresh task panicked: {e}"),
+                        }
+                    }
+                }
+            }
+        });
+        self.track_task("metrics-refresh", handle);
+    }
+
     /// Get the API key configuration for this app.
     fn api_key_config(&self) -> Arc<ApiKeyConfig> {
         Arc::new(ApiKeyConfig::new(self.config.api_key.clone()))
     }
 
-    /// Start the file watcher and indexer pipeline.
-    ///
-    /// Spawns watcher setup and initial indexing in background tasks so the
     /// Get a clone of the embedding service if available.
     pub fn embeddings(&self) -> Option<EmbeddingService> {
         self.state.embeddings.clone()
     }
 
-    /// server can start immediately. Returns handles to spawned tasks.
+    /// Start the file watcher and indexer pipeline.
+    ///
+    /// Spawns watcher setup and initial indexing in background tasks so the
+    /// server can start immediately. Returns handles to spawned tasks. Must be
+    /// called from within a Tokio runtime.
     ///
     /// # Errors
     ///
     /// Returns an error only for critical failures (none currently - all errors logged).
-    pub async fn start_watcher(
+    pub fn start_watcher(
         &self,
         watch_dirs: Vec<std::path::PathBuf>,
     ) -> Result<Option<(tokio::task::JoinHandle<()>, tokio::task::JoinHandle<()>)>> {
@@ -173,24 +479,36 @@ impl App {
         tracing::info!(?watch_dirs, "Starting file watcher (background)");
 
         // Create channels
-        let (index_tx, index_rx) = mpsc::channel(1000);
+        let (index_tx, index_rx) = index_queue(
+            self.config.index_queue_capacity,
+            self.config.index_queue_overflow,
+        );
         let (delete_tx, delete_rx) = mpsc::channel(100);
+        self.track_index_queue(&index_tx);
 
-        // Create indexer
-        let indexer = Arc::new(Indexer::new(
-            self.state.db().clone(),
-            self.state.embedding_service(),
-        ));
+        // Share the tools' indexer so its stats cover all indexing
+        let indexer = self.state.indexer();
 
         // Spawn indexer task (runs immediately)
         let indexer_clone = Arc::clone(&indexer);
+        let indexer_shutdown = self.shutdown.clone();
         let indexer_handle = tokio::spawn(async move {
-            indexer_clone.run(index_rx, delete_rx).await;
+            indexer_clone
+                .run(index_rx, delete_rx, indexer_shutdown)
+                .await;
         });
 
         // Clone data for background task
         let watch_dirs_for_task = watch_dirs.clone();
         let index_tx_for_task = index_tx.clone();
+        let watcher_shutdown = self.shutdown.clone();
+        let watcher_indexer = Arc::clone(&indexer);
+        let skip_noise_files = self.config.skip_noise_files;
+        let file_types = self.config.file_types.clone();
+        let debounce_ms = self.config.debounce_ms;
+        let scan_db = self.state.db().clone();
+        let watcher_stats = Arc::clone(&self.state.watcher_stats);
+        let scan_stats = Arc::clone(&self.state.scan_stats);
 
         // Spawn watcher setup and initial scan in background
         // This allows server to start immediately while indexing happens
@@ -199,7 +517,11 @@ impl App {
             let watcher_config = WatcherConfig {
                 watch_dirs: watch_dirs_for_task.clone(),
                 ..Default::default()
-            };
+            }
+            .with_debounce_ms(debounce_ms)
+            .with_skip_noise_files(skip_noise_files)
+            .with_file_types(file_types);
+            let scan_config = watcher_config.clone();
 
             // FileWatcher::new() uses blocking walkdir, so run in spawn_blocking
             let watcher_result = tokio::task::spawn_blocking(move || {
@@ -221,16 +543,18 @@ impl App {
             tracing::info!("File watcher initialized successfully");
 
             // Create event handlers
-            let stats = WatcherStats::new();
             let mut handlers = Vec::new();
             for dir in &watch_dirs_for_task {
                 let handler_config = HandlerConfig {
                     base_path: dir.clone(),
                     ignore_patterns: vec![],
+                    skip_dirs: scan_config.skip_dirs.clone(),
+                    skip_noise_files,
+                    file_types: scan_config.file_types.clone(),
                 };
                 match EventHandler::new(
                     &handler_config,
-                    Arc::clone(&stats),
+                    Arc::clone(&watcher_stats),
                     index_tx_for_task.clone(),
                     delete_tx.clone(),
                 ) {
@@ -242,14 +566,31 @@ impl App {
             // Do initial scan
             tracing::info!("Starting initial scan of watch directories");
             for dir in &watch_dirs_for_task {
-                if let Err(e) = Self::do_initial_scan(dir, &index_tx_for_task).await {
-                    tracing::error!("Initial scan failed for {:?}: {}", dir, e);
+                match Self::do_initial_scan(dir, &scan_config, &index_tx_for_task, &scan_stats)
+                    .await
+                {
+                    Ok(files) => {
+                        if let Err(e) =
+                            scan_db.with_conn(|conn| crate::storage::record_scan(conn, files))
+                        {
+                            tracing::warn!("Failed to record scan of {:?}: {}", dir, e);
+                        }
+                    }
+                    Err(e) => tracing::error!("Initial scan failed for {:?}: {}", dir, e),
                 }
             }
             tracing::info!("Initial scan complete");
 
-            // Run watcher event loop
-            while let Some(batch) = watcher.recv().await {
+            // Run watcher event loop until shutdown
+            loop {
+                let mut batch = tokio::select! {
+                    () = watcher_shutdown.cancelled() => break,
+                    batch = watcher.recv() => match batch {
+                        Some(batch) => batch,
+                        None => break,
+                    },
+                };
+                watcher_indexer.resolve_renames(&mut batch).await;
                 for (base_path, handler) in &handlers {
                     let filtered_batch = crate::watcher::EventBatch {
                         modified: batch
@@ -264,6 +605,7 @@ impl App {
                             .filter(|p| p.starts_with(base_path))
                             .cloned()
                             .collect(),
+                        renamed: Vec::new(),
                     };
                     if !filtered_batch.is_empty() {
                         handler.process_batch(filtered_batch).await;
@@ -277,53 +619,121 @@ impl App {
     }
 
     /// Perform initial scan of a directory (static helper for background task).
+    ///
+    /// Returns the number of files queued for indexing. Running totals
+    /// across all directories accumulate in `stats`.
     async fn do_initial_scan(
         dir: &std::path::Path,
-        index_tx: &mpsc::Sender<crate::watcher::IndexRequest>,
-    ) -> Result<()> {
-        use crate::watcher::{FileFilter, IndexRequest};
-
-        let filter = FileFilter::new(dir);
-        let mut count = 0;
-
-        for entry in walkdir::WalkDir::new(dir)
-            .follow_links(false)
-            .into_iter()
-            .filter_map(std::result::Result::ok)
-        {
-            let path = entry.path();
-            if path.is_file() && filter.should_index(path) {
-                let language = FileFilter::detect_language(path).map(String::from);
-                let request = IndexRequest {
-                    path: path.to_path_buf(),
-                    language,
-                };
-                if index_tx.send(request).await.is_err() {
-                    tracing::warn!("Index channel closed during initial scan");
-                    break;
+        config: &WatcherConfig,
+        index_tx: &IndexSender,
+        stats: &ScanStats,
+    ) -> Result<u64> {
+        use crate::watcher::{build_walker, FileFilter, IndexRequest};
+        use std::sync::atomic::Ordering;
+
+        let mut count = 0u64;
+        let mut skipped_noise = 0u64;
+
+        for entry in build_walker(dir, config) {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    tracing::debug!(error = %e, "Error walking directory");
+                    stats.errors.fetch_add(1, Ordering::Relaxed);
+                    continue;
                 }
-                count += 1;
+            };
+            let path = entry.path();
+            if !path.is_file() {
+                continue;
+            }
+            stats.files_found.fetch_add(1, Ordering::Relaxed);
+            if !config.file_types.is_code_file(path) {
+                stats.files_skipped.fetch_add(1, Ordering::Relaxed);
+                continue;
             }
+            if config.skip_noise_files && FileFilter::is_noise_file(path) {
+                skipped_noise += 1;
+                stats.files_skipped_noise.fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+            let language = config.file_types.detect_language(path).map(String::from);
+            let request = IndexRequest {
+                path: path.to_path_buf(),
+                language,
+                repo: None,
+            };
+            if index_tx.send(request).await.is_err() {
+                tracing::warn!("Index queue closed during initial scan; indexer has stopped");
+                break;
+            }
+            stats.files_queued.fetch_add(1, Ordering::Relaxed);
+            count += 1;
         }
 
-        tracing::info!(dir = %dir.display(), files = count, "Directory scan complete");
-        Ok(())
+        tracing::info!(
+            dir = %dir.display(),
+            files = count,
+            files_skipped_noise = skipped_noise,
+            "Directory scan complete"
+        );
+        Ok(count)
+    }
+
+    /// Build the CORS layer from the configured origins.
+    ///
+    /// Returns `None` when no origins are configured, so no CORS headers are
+    /// sent and browsers block cross-origin requests.
+    fn cors_layer(&self) -> Option<CorsLayer> {
+        let origins = &self.config.cors_allowed_origins;
+        if origins.is_empty() {
+            return None;
+        }
+
+        let allow_origin = if origins.iter().any(|o| o == "*") {
+            tracing::warn!("CORS allows any origin; any webpage can call this server");
+            AllowOrigin::any()
+        } else {
+            let values: Vec<HeaderValue> = origins
+                .iter()
+                .filter_map(|origin| match HeaderValue::from_str(origin) {
+                    Ok(value) => Some(value),
+                    Err(_) => {
+                        tracing::warn!(origin, "Ignoring invalid CORS origin");
+                        None
+                    }
+                })
+                .collect();
+            AllowOrigin::list(values)
+        };
+
+        Some(
+            CorsLayer::new()
+                .allow_origin(allow_origin)
+                .allow_methods([Method::GET, Method::POST, Method::OPTIONS])
+                .allow_headers([
+                    header::AUTHORIZATION,
+                    header::CONTENT_TYPE,
+                    header::ACCEPT,
+                    HeaderName::from_static("x-api-key"),
+                    HeaderName::from_static("x-request-id"),
+                ]),
+        )
     }
 
     /// Build the router with all endpoints.
     fn router(&self) -> Router {
-        let cors = CorsLayer::new()
-            .allow_origin(Any)
-            .allow_methods(Any)
-            .allow_headers(Any);
-
         let api_key_config = self.api_key_config();
 
-        Router::new()
+        let router = Router::new()
             .merge(create_mcp_router(Arc::clone(&self.state)))
             .merge(create_rest_router(Arc::clone(&self.state)))
             .merge(create_sse_router(Arc::clone(&self.state)))
+            .merge(create_openapi_router(self.config.api_docs_ui))
             .layer(middleware::from_fn(auth_middleware_wrapper(api_key_config)))
+            // Replace axum's fixed 2 MB extractor limit with the configured one
+            .layer(DefaultBodyLimit::disable())
+            .layer(RequestBodyLimitLayer::new(self.config.max_request_bytes))
             .layer(
                 TraceLayer::new_for_http()
                     .make_span_with(|request: &axum::http::Request<_>| {
@@ -340,120 +750,155 @@ impl App {
                             method = %method,
                             uri = %uri,
                             request_id = %request_id,
+                            status = tracing::field::Empty,
+                            latency_ms = tracing::field::Empty,
                         )
                     })
                     .on_response(
                         |response: &axum::response::Response,
-                         _latency: std::time::Duration,
-                         _span: &tracing::Span| {
+                         latency: std::time::Duration,
+                         span: &tracing::Span| {
+                            #[allow(clippy::cast_possible_truncation)]
+                            let latency_ms = latency.as_millis() as u64;
+                            span.record("status", response.status().as_u16());
+                            span.record("latency_ms", latency_ms);
                             tracing::info!(
                                 status = %response.status(),
+                                latency_ms,
                                 "Request completed"
                             );
                         },
                     ),
-            )
-            .layer(cors)
+            );
+
+        match self.cors_layer() {
+            Some(cors) => router.layer(cors),
+            None => router,
+        }
     }
 
     /// Run the server until shutdown signal.
     ///
     /// The server listens for SIGTERM (Unix) and Ctrl+C signals,
-    /// then gracefully shuts down all connections.
+    /// then gracefully shuts down all connections. Once HTTP traffic has
+    /// drained, the shutdown token is cancelled, tracked background tasks are
+    /// awaited, and embedding workers are stopped, all within
+    /// `shutdown_timeout`.
     ///
     /// # Errors
     ///
     /// Returns an error if the server cannot start or encounters
     /// a fatal error during execution.
     pub async fn run(self) -> Result<()> {
-        let addr: SocketAddr = format!("{}:{}", self.config.host, self.config.port)
-            .parse()
-            .map_err(|e| crate::Error::config(format!("invalid address: {e}")))?;
-
-        let listener = tokio::net::TcpListener::bind(addr).await.map_err(|e| {
-            crate::error::ServerError::BindFailed {
-                address: addr.to_string(),
-                reason: e.to_string(),
+        self.start_metrics_refresh();
+
+        let shutdown = self.shutdown.clone();
+        let graceful = async move {
+            tokio::select! {
+                () = shutdown_signal() => {}
+                () = shutdown.cancelled() => {}
             }
-        })?;
+        };
 
-        tracing::info!(%addr, "Server listening");
+        let serve_result = if let Some(ref socket) = self.config.listen_socket {
+            self.serve_unix(socket, graceful).await?
+        } else {
+            let addr: SocketAddr = format!("{}:{}", self.config.host, self.config.port)
+                .parse()
+                .map_err(|e| crate::Error::config(format!("invalid address: {e}")))?;
+
+            let listener = tokio::net::TcpListener::bind(addr).await.map_err(|e| {
+                crate::error::ServerError::BindFailed {
+                    address: addr.to_string(),
+                    reason: e.to_string(),
+                }
+            })?;
 
-        axum::serve(listener, self.router())
-            .with_graceful_shutdown(shutdown_signal())
-            .await
-            .map_err(|e| crate::error::ServerError::Request(e.to_string()))?;
+            tracing::info!(%addr, "Server listening");
+            axum::serve(listener, self.router())
+                .with_graceful_shutdown(graceful)
+                .await
+        };
+
+        self.shutdown.cancel();
+        self.drain_background().await;
+
+        serve_result.map_err(|e| crate::error::ServerError::Request(e.to_string()))?;
 
         tracing::info!("Server shut down gracefully");
         Ok(())
     }
-}
 
-/// Create an authentication middleware function.
-fn auth_middleware_wrapper(
-    config: Arc<ApiKeyConfig>,
-) -> impl Fn(Request, Next) -> std::pin::Pin<Box<dyn std::future::Future<Output = Response> + Send>>
-       + Clone
-       + Send
-       + 'static {
-    move |request: Request, next: Next| {
-        let config = Arc::clone(&config);
-        Box::pin(async move {
-            // Allow /health endpoint without authentication (needed for load balancers)
-            if request.uri().path() == "/health" {
-                return next.run(request).await;
-            }
-
-            // If authentication is disabled, allow the request
-            if !config.is_enabled() {
-                return next.run(request).await;
-            }
+    /// Serve on a Unix domain socket, removing the socket file on shutdown.
+    #[cfg(unix)]
+    async fn serve_unix(
+        &self,
+        socket: &std::path::Path,
+        graceful: impl std::future::Future<Output = ()> + Send + 'static,
+    ) -> Result<std::io::Result<()>> {
+        let listener = bind_unix_socket(socket)?;
+        tracing::info!(socket = %socket.display(), "Server listening");
+
+        let result = axum::serve(listener, self.router())
+            .with_graceful_shutdown(graceful)
+            .await;
+        let _ = std::fs::remove_file(socket);
+        Ok(result)
+    }
 
-            // Extract API key from headers
-            let api_key = extract_api_key_from_headers(request.headers());
+    #[cfg(not(unix))]
+    async fn serve_unix(
+        &self,
+        _socket: &std::path::Path,
+        _graceful: impl std::future::Future<Output = ()> + Send + 'static,
+    ) -> Result<std::io::Result<()>> {
+        Err(crate::Error::config(
+            "listen_socket is only supported on Unix platforms",
+        ))
+    }
 
-            // Validate the key
-            if let Some(key) = api_key {
-                if config.validate(&key) {
-                    return next.run(request).await;
-                }
+    /// Wait for background tasks and embedding workers to stop.
+    ///
+    /// Everything shares a single deadline of `shutdown_timeout`; tasks still
+    /// running at the deadline are aborted and reported as abandoned.
+    async fn drain_background(&self) {
+        let deadline = tokio::time::Instant::now() + self.config.shutdown_timeout;
+        let mut drained = Vec::new();
+        let mut abandoned = Vec::new();
+
+        let tasks = std::mem::take(&mut *self.tasks.lock());
+        for (name, mut handle) in tasks {
+            if tokio::time::timeout_at(deadline, &mut handle).await.is_ok() {
+                drained.push(name);
+            } else {
+                handle.abort();
+                abandoned.push(name);
             }
+        }
 
-            // Authentication failed
-            tracing::warn!(
-                path = %request.uri(),
-                method = %request.method(),
-                "Authentication failed - invalid or missing API key"
-            );
-
-            (
-                StatusCode::UNAUTHORIZED,
-                "Unauthorized - invalid or missing API key",
-            )
-                .into_response()
-        })
-    }
-}
-
-/// Extract API key from request headers.
-fn extract_api_key_from_headers(headers: &axum::http::HeaderMap) -> Option<String> {
-    // Check Authorization header (Bearer scheme)
-    if let Some(auth_header) = headers.get("authorization") {
-        if let Ok(auth_str) = auth_header.to_str() {
-            if let Some(key) = auth_str.strip_prefix("Bearer ") {
-                return Some(key.to_string());
+        let mut services = std::mem::take(&mut *self.embedding_services.lock());
+        services.extend(self.state.embedding_service());
+        for service in services {
+            if tokio::time::timeout_at(deadline, service.shutdown())
+                .await
+                .is_ok()
+            {
+                drained.push("embedding workers".to_string());
+            } else {
+                abandoned.push("embedding workers".to_string());
             }
         }
-    }
 
-    // Check X-API-Key header
-    if let Some(key_header) = headers.get("x-api-key") {
-        if let Ok(key_str) = key_header.to_str() {
-            return Some(key_str.to_string());
+        if abandoned.is_empty() {
+            tracing::info!(?drained, "Background tasks drained");
+        } else {
+            tracing::warn!(
+                ?drained,
+                ?abandoned,
+                "Shutdown timeout reached, abandoned tasks"
+            );
         }
     }
-
-    None
 }
 
 /// Wait for shutdown signal (SIGTERM or Ctrl+C).
@@ -492,12 +937,42 @@ async fn shutdown_signal() {
     }
 }
 
+/// Bind a Unix domain socket readable and writable only by its owner.
+///
+/// A leftover socket file from a previous run is replaced, but one that a
+/// live server still accepts connections on is an error.
+#[cfg(unix)]
+fn bind_unix_socket(path: &std::path::Path) -> Result<tokio::net::UnixListener> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    let bind_failed = |reason: String| crate::error::ServerError::BindFailed {
+        address: path.display().to_string(),
+        reason,
+    };
+
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            return Err(bind_failed("path exists and is not a socket".to_string()).into());
+        }
+        if std::os::unix::net::UnixStream::connect(path).is_ok() {
+            return Err(bind_failed("socket is in use by another process".to_string()).into());
+        }
+        std::fs::remove_file(path).map_err(|e| bind_failed(e.to_string()))?;
+    }
+
+    let listener = tokio::net::UnixListener::bind(path).map_err(|e| bind_failed(e.to_string()))?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+        .map_err(|e| bind_failed(e.to_string()))?;
+    Ok(listener)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::storage::migrate;
     use axum::body::Body;
-    use axum::http::Request;
+    use axum::http::{Request, StatusCode};
+    use axum::response::Response;
     use tower::ServiceExt;
 
     #[test]
@@ -508,9 +983,14 @@ mod tests {
         assert_eq!(config.shutdown_timeout, Duration::from_secs(30));
         assert_eq!(config.api_key, None);
         assert_eq!(config.data_dir, std::path::PathBuf::from("./data"));
+        assert_eq!(
+            config.models_path(),
+            std::path::PathBuf::from("./data/models")
+        );
         assert_eq!(config.embedding_threads, 4);
         assert!(config.enable_embeddings);
         assert!(config.watch_dirs.is_empty());
+        assert!(config.vector_search);
     }
 
     #[test]
@@ -518,21 +998,209 @@ mod tests {
         let config = ServerConfig {
             host: "0.0.0.0".to_string(),
             port: 9000,
+            listen_socket: Some(std::path::PathBuf::from("/tmp/nellie.sock")),
             shutdown_timeout: Duration::from_secs(60),
             api_key: Some("test-key".to_string()),
             data_dir: std::path::PathBuf::from("/custom/data"),
+            model_dir: Some(std::path::PathBuf::from("/opt/nellie/models")),
             embedding_threads: 8,
+            query_cache_size: 0,
             enable_embeddings: false,
             watch_dirs: vec![std::path::PathBuf::from("/some/dir")],
+            cors_allowed_origins: vec!["https://app.example.com".to_string()],
+            max_request_bytes: 1024,
+            vector_search: false,
+            agent_stale_secs: 600,
+            max_checkpoint_state_bytes: 2048,
+            max_checkpoint_state_depth: 8,
+            lesson_duplicate_threshold: 0.8,
+            recency_half_life_days: 30,
+            index_concurrency: 2,
+            index_complete_webhook: Some("http://ci.local/hook".to_string()),
+            onnx_intra_threads: 4,
+            onnx_inter_threads: 2,
+            execution_provider: ExecutionProvider::CoreMl,
+            long_chunk_strategy: LongChunkStrategy::MeanPool,
+            skip_noise_files: false,
+            debounce_ms: 250,
+            store_chunk_content: false,
+            dedupe_chunks: true,
+            index_queue_capacity: 50,
+            index_queue_overflow: OverflowPolicy::DropOldest,
+            min_free_disk_bytes: 0,
+            reranker_model: Some(std::path::PathBuf::from("/models/reranker/model.onnx")),
+            normalize_tags: true,
+            extra_severities: vec!["security".to_string()],
+            max_search_limit: 25,
+            slow_query_ms: 250,
+            metrics_refresh_secs: 60,
+            embedding_max_restarts: 1,
+            embedding_timeout_ms: 500,
+            disabled_tools: vec!["delete_lesson".to_string()],
+            read_only: true,
+            api_docs_ui: true,
+            embedding_templates: EmbeddingTemplates {
+                code_query: "search_query: {query}".to_string(),
+                ..EmbeddingTemplates::default()
+            },
+            file_types: FileTypes::default().with_docs(true),
         };
         assert_eq!(config.host, "0.0.0.0");
         assert_eq!(config.port, 9000);
+        assert_eq!(
+            config.listen_socket,
+            Some(std::path::PathBuf::from("/tmp/nellie.sock"))
+        );
         assert_eq!(config.shutdown_timeout, Duration::from_secs(60));
         assert_eq!(config.api_key, Some("test-key".to_string()));
         assert_eq!(config.data_dir, std::path::PathBuf::from("/custom/data"));
+        assert_eq!(
+            config.models_path(),
+            std::path::PathBuf::from("/opt/nellie/models")
+        );
         assert_eq!(config.embedding_threads, 8);
+        assert_eq!(config.query_cache_size, 0);
         assert!(!config.enable_embeddings);
         assert_eq!(config.watch_dirs.len(), 1);
+        assert_eq!(config.cors_allowed_origins.len(), 1);
+        assert_eq!(config.max_request_bytes, 1024);
+        assert!(!config.vector_search);
+        assert_eq!(config.agent_stale_secs, 600);
+        assert_eq!(config.max_checkpoint_state_bytes, 2048);
+        assert_eq!(config.max_checkpoint_state_depth, 8);
+        assert!((config.lesson_duplicate_threshold - 0.8).abs() < f32::EPSILON);
+        assert_eq!(config.recency_half_life_days, 30);
+        assert_eq!(config.index_concurrency, 2);
+        assert_eq!(
+            config.index_complete_webhook.as_deref(),
+            Some("http://ci.local/hook")
+        );
+        assert_eq!(config.onnx_options().intra_threads, 4);
+        assert_eq!(config.onnx_options().inter_threads, 2);
+        assert_eq!(config.execution_provider, ExecutionProvider::CoreMl);
+        assert_eq!(config.long_chunk_strategy, LongChunkStrategy::MeanPool);
+        assert!(!config.skip_noise_files);
+        assert_eq!(config.debounce_ms, 250);
+        assert!(!config.store_chunk_content);
+        assert!(config.dedupe_chunks);
+        assert_eq!(config.index_queue_capacity, 50);
+        assert_eq!(config.index_queue_overflow, OverflowPolicy::DropOldest);
+        assert_eq!(config.min_free_disk_bytes, 0);
+        let reranker = config.reranker().unwrap();
+        assert_eq!(
+            reranker.model_path(),
+            std::path::Path::new("/models/reranker/model.onnx")
+        );
+        assert!(!reranker.is_loaded());
+        assert!(config.normalize_tags);
+        assert_eq!(config.extra_severities, vec!["security".to_string()]);
+        assert_eq!(config.max_search_limit, 25);
+        assert_eq!(config.slow_query_ms, 250);
+        assert_eq!(config.metrics_refresh_secs, 60);
+        assert_eq!(config.embedding_max_restarts, 1);
+        assert_eq!(config.embedding_timeout_ms, 500);
+        assert_eq!(config.disabled_tools, vec!["delete_lesson".to_string()]);
+        assert!(config.read_only);
+        assert!(config.api_docs_ui);
+        assert_eq!(
+            config.embedding_templates.code_query,
+            "search_query: {query}"
+        );
+        assert_eq!(config.file_types, FileTypes::default().with_docs(true));
+    }
+
+    #[tokio::test]
+    async fn test_oversized_body_rejected() {
+        let config = ServerConfig {
+            enable_embeddings: false,
+            max_request_bytes: 1024,
+            ..Default::default()
+        };
+        let db = Database::open_in_memory().unwrap();
+        db.with_conn(|conn| migrate(conn)).unwrap();
+        let app = App::new(config, db).await.unwrap();
+
+        let body = serde_json::json!({
+            "name": "add_lesson",
+            "arguments": {
+                "title": "Big",
+                "content": "x".repeat(4096),
+                "tags": []
+            }
+        })
+        .to_string();
+
+        let response = app
+            .router()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/mcp/invoke")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    /// Send a CORS preflight for `/health` from `origin`.
+    async fn cors_preflight(origins: Vec<String>, origin: &str) -> Response {
+        let config = ServerConfig {
+            enable_embeddings: false,
+            cors_allowed_origins: origins,
+            ..Default::default()
+        };
+        let db = Database::open_in_memory().unwrap();
+        db.with_conn(|conn| migrate(conn)).unwrap();
+        let app = App::new(config, db).await.unwrap();
+
+        app.router()
+            .oneshot(
+                Request::builder()
+                    .method("OPTIONS")
+                    .uri("/health")
+                    .header("origin", origin)
+                    .header("access-control-request-method", "POST")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_cors_disabled_by_default() {
+        let response = cors_preflight(Vec::new(), "https://evil.example.com").await;
+        assert!(response
+            .headers()
+            .get("access-control-allow-origin")
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cors_allowed_origin_list() {
+        let origins = vec!["https://app.example.com".to_string()];
+
+        let response = cors_preflight(origins.clone(), "https://app.example.com").await;
+        assert_eq!(
+            response.headers()["access-control-allow-origin"],
+            "https://app.example.com"
+        );
+
+        let response = cors_preflight(origins, "https://evil.example.com").await;
+        assert!(response
+            .headers()
+            .get("access-control-allow-origin")
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cors_wildcard() {
+        let response = cors_preflight(vec!["*".to_string()], "https://any.example.com").await;
+        assert_eq!(response.headers()["access-control-allow-origin"], "*");
     }
 
     #[tokio::test]
@@ -589,6 +1257,33 @@ mod tests {
         assert_eq!(response.status(), StatusCode::OK);
     }
 
+    #[tokio::test]
+    async fn test_api_docs_without_auth() {
+        let config = ServerConfig {
+            enable_embeddings: false,
+            api_key: Some("secret".to_string()),
+            api_docs_ui: true,
+            ..Default::default()
+        };
+        let db = Database::open_in_memory().unwrap();
+        db.with_conn(|conn| migrate(conn)).unwrap();
+        let app = App::new(config, db).await.unwrap();
+
+        for (uri, expected) in [
+            ("/openapi.json", StatusCode::OK),
+            ("/docs/", StatusCode::OK),
+            ("/docs/swagger-ui.css", StatusCode::OK),
+            ("/api/v1/status", StatusCode::UNAUTHORIZED),
+        ] {
+            let response = app
+                .router()
+                .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), expected, "{uri}");
+        }
+    }
+
     #[tokio::test]
     async fn test_metrics_without_api_key_auth_disabled() {
         let config = ServerConfig {
@@ -720,4 +1415,136 @@ mod tests {
 
         assert_eq!(response.status(), StatusCode::OK);
     }
+
+    #[tokio::test]
+    async fn test_ignored_files_are_not_indexed() {
+        use crate::server::indexing::{run_index_job, IndexMode};
+        use crate::watcher::{EventBatch, WatcherStats};
+
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        std::fs::write(root.join(".gitignore"), "generated/\n*.gen.rs\n").unwrap();
+        for dir in ["src", "generated", "node_modules/pkg"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        std::fs::write(root.join("src/main.rs"), "fn main() {}\n").unwrap();
+        std::fs::write(root.join("src/schema.gen.rs"), "fn schema() {}\n").unwrap();
+        std::fs::write(root.join("generated/api.rs"), "fn api() {}\n").unwrap();
+        std::fs::write(root.join("node_modules/pkg/index.js"), "var a;\n").unwrap();
+        let files = [
+            "src/main.rs",
+            "src/schema.gen.rs",
+            "generated/api.rs",
+            "node_modules/pkg/index.js",
+        ];
+
+        // Initial scan
+        let config = WatcherConfig {
+            watch_dirs: vec![root.to_path_buf()],
+            ..Default::default()
+        };
+        let (index_tx, index_rx) = index_queue(16, OverflowPolicy::Block);
+        let queued = App::do_initial_scan(root, &config, &index_tx, &ScanStats::new())
+            .await
+            .unwrap();
+        assert_eq!(queued, 1);
+        assert!(index_rx.try_recv().unwrap().path.ends_with("src/main.rs"));
+
+        // Watcher events
+        let handler_config = HandlerConfig {
+            base_path: root.to_path_buf(),
+            ignore_patterns: vec![],
+            skip_dirs: config.skip_dirs.clone(),
+            skip_noise_files: true,
+            file_types: config.file_types.clone(),
+        };
+        let (delete_tx, _delete_rx) = mpsc::channel(16);
+        let handler =
+            EventHandler::new(&handler_config, WatcherStats::new(), index_tx, delete_tx).unwrap();
+        let mut batch = EventBatch::new();
+        batch.modified = files.iter().map(|f| root.join(f)).collect();
+        handler.process_batch(batch).await;
+        assert!(index_rx.try_recv().unwrap().path.ends_with("src/main.rs"));
+        assert!(index_rx.try_recv().is_none());
+
+        // Indexing tools
+        let db = Database::open_in_memory().unwrap();
+        db.with_conn(|conn| migrate(conn)).unwrap();
+        let state = McpState::new(db);
+        let report = run_index_job(
+            &state,
+            &root.to_string_lossy(),
+            IndexMode::Full {
+                repo: None,
+                skip_unchanged: false,
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.files_indexed, 1);
+        let paths: Vec<String> = state
+            .db()
+            .with_conn(|conn| {
+                let mut stmt = conn.prepare("SELECT DISTINCT file_path FROM chunks").unwrap();
+                let rows = stmt.query_map([], |row| row.get(0)).unwrap();
+                Ok(rows.map(std::result::Result::unwrap).collect())
+            })
+            .unwrap();
+        assert_eq!(paths.len(), 1);
+        assert!(paths[0].ends_with("src/main.rs"));
+    }
+
+    #[tokio::test]
+    async fn test_drain_background_aborts_after_timeout() {
+        let config = ServerConfig {
+            enable_embeddings: false,
+            shutdown_timeout: Duration::from_millis(100),
+            ..Default::default()
+        };
+        let db = Database::open_in_memory().unwrap();
+        db.with_conn(|conn| migrate(conn)).unwrap();
+
+        let app = App::new(config, db).await.unwrap();
+
+        let token = app.shutdown_token();
+        let cooperative = tokio::spawn(async move { token.cancelled().await });
+        app.track_task("cooperative", cooperative);
+
+        // Never finishes; must be abandoned at the deadline
+        let stuck = tokio::spawn(std::future::pending::<()>());
+        app.track_task("stuck", stuck);
+
+        app.shutdown.cancel();
+        let start = std::time::Instant::now();
+        app.drain_background().await;
+
+        assert!(app.tasks.lock().is_empty());
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_bind_unix_socket() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let _guard = rt.enter();
+
+        // Non-socket files are never clobbered
+        let file = dir.path().join("notes.txt");
+        std::fs::write(&file, "keep me").unwrap();
+        assert!(bind_unix_socket(&file).is_err());
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "keep me");
+
+        let socket = dir.path().join("nellie.sock");
+        let listener = bind_unix_socket(&socket).unwrap();
+        let mode = std::fs::metadata(&socket).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // A live socket is refused; a stale one is replaced
+        assert!(bind_unix_socket(&socket).is_err());
+        drop(listener);
+        assert!(bind_unix_socket(&socket).is_ok());
+    }
 }
@@ -3,7 +3,16 @@
 //! Provides middleware for validating API key authentication on protected endpoints.
 //! Supports both `Authorization: Bearer <key>` and `X-API-Key: <key>` headers.
 
-use axum::http::HeaderMap;
+use std::sync::Arc;
+
+use axum::{
+    extract::Request,
+    http::{HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use super::openapi::is_public_doc_path;
 
 /// API key authentication configuration.
 #[derive(Debug, Clone)]
@@ -37,10 +46,60 @@ impl ApiKeyConfig {
     }
 }
 
+/// Create an authentication middleware function.
+///
+/// Shared by the main router and the standalone MCP transport, so every
+/// endpoint checks the same key the same way.
+pub(super) fn auth_middleware_wrapper(
+    config: Arc<ApiKeyConfig>,
+) -> impl Fn(Request, Next) -> std::pin::Pin<Box<dyn std::future::Future<Output = Response> + Send>>
+       + Clone
+       + Send
+       + 'static {
+    move |request: Request, next: Next| {
+        let config = Arc::clone(&config);
+        Box::pin(async move {
+            // Allow /health endpoint without authentication (needed for load balancers),
+            // and the API description so the docs page can load it
+            let path = request.uri().path();
+            if path == "/health" || is_public_doc_path(path) {
+                return next.run(request).await;
+            }
+
+            // If authentication is disabled, allow the request
+            if !config.is_enabled() {
+                return next.run(request).await;
+            }
+
+            // Extract API key from headers
+            let api_key = extract_api_key(request.headers());
+
+            // Validate the key
+            if let Some(key) = api_key {
+                if config.validate(&key) {
+                    return next.run(request).await;
+                }
+            }
+
+            // Authentication failed
+            tracing::warn!(
+                path = %request.uri(),
+                method = %request.method(),
+                "Authentication failed - invalid or missing API key"
+            );
+
+            (
+                StatusCode::UNAUTHORIZED,
+                "Unauthorized - invalid or missing API key",
+            )
+                .into_response()
+        })
+    }
+}
+
 /// Extract API key from request headers.
 ///
 /// Checks both `Authorization: Bearer <key>` and `X-API-Key: <key>` headers.
-#[allow(dead_code)]
 fn extract_api_key(headers: &HeaderMap) -> Option<String> {
     // Check Authorization header (Bearer scheme)
     if let Some(auth_header) = headers.get("authorization") {
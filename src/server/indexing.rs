@@ -0,0 +1,819 @@
+//! Walk-and-index job shared by the bulk indexing tools.
+//!
+//! `index_repo`, `diff_index` and `full_reindex` differ only in what they do
+//! before the walk and which files they pass to the indexer, so all three go
+//! through [`run_index_job`] with an [`IndexMode`].
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::StreamExt;
+
+use super::mcp::McpState;
+use crate::watcher::{
+    build_walker, is_skipped_dir_name, FileFilter, IndexRequest, Indexer, WatcherConfig,
+};
+
+/// Default number of files an indexing job indexes concurrently.
+pub const DEFAULT_INDEX_CONCURRENCY: usize = 4;
+
+/// Completed-run summaries buffered for slow SSE subscribers.
+pub(super) const INDEX_EVENT_CAPACITY: usize = 16;
+
+/// How long a webhook delivery may take before it is abandoned.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Number of paths `index_repo` lists in a dry run.
+const DRY_RUN_SAMPLE_PATHS: usize = 20;
+
+/// Files processed between progress log lines.
+const PROGRESS_INTERVAL: usize = 100;
+
+/// What an indexing job does with the files it walks.
+#[derive(Debug, Clone)]
+pub(super) enum IndexMode {
+    /// Index every file found, picking up new files. With `skip_unchanged`,
+    /// files whose mtime/size match the stored state are skipped without
+    /// being read; otherwise the indexer still skips files whose content hash
+    /// is unchanged.
+    Full {
+        /// Repository name to tag chunks with (detected when `None`).
+        repo: Option<String>,
+        /// Check stored file state before reading each file.
+        skip_unchanged: bool,
+    },
+    /// Only index files whose mtime/size changed, falling back to a content
+    /// hash comparison when ambiguous (or always, with `verify_hashes`), and
+    /// drop files that no longer exist.
+    Diff {
+        /// Always compare content hashes, for unreliable mtimes.
+        verify_hashes: bool,
+    },
+    /// Clear all chunks and file state under the path, then index everything.
+    FullReindex,
+}
+
+impl IndexMode {
+    /// Tool name used in logs and completion events.
+    const fn operation(&self) -> &'static str {
+        match self {
+            Self::Full { .. } => "index_repo",
+            Self::Diff { .. } => "diff_index",
+            Self::FullReindex => "full_reindex",
+        }
+    }
+}
+
+/// Tallies from a completed indexing job.
+#[derive(Debug, Default)]
+pub(super) struct IndexJobReport {
+    /// Files the walk found (after skipping lockfiles and minified assets).
+    pub files_found: usize,
+    /// Files that produced new chunks.
+    pub files_indexed: u64,
+    /// Files left alone because they hadn't changed.
+    pub files_unchanged: u64,
+    /// Previously indexed files that no longer exist (`Diff` only).
+    pub files_deleted: u64,
+    /// Lockfiles and minified assets left out of the walk.
+    pub files_skipped_noise: u64,
+    /// Chunks stored across all indexed files.
+    pub chunks_created: u64,
+    /// Chunks removed before indexing (`FullReindex` only).
+    pub chunks_cleared: usize,
+    /// File states removed before indexing (`FullReindex` only).
+    pub files_cleared: usize,
+    /// Files that failed to index or be removed.
+    pub errors: u64,
+    /// Wall-clock duration of the job.
+    pub elapsed: Duration,
+}
+
+impl IndexJobReport {
+    /// Walked files that were neither indexed, unchanged nor failed.
+    #[must_use]
+    pub const fn files_skipped(&self) -> u64 {
+        (self.files_found as u64)
+            .saturating_sub(self.files_indexed + self.files_unchanged + self.errors)
+    }
+}
+
+/// Files collected by a walk.
+struct WalkedFiles {
+    paths: Vec<PathBuf>,
+    skipped_noise: u64,
+    is_network: bool,
+}
+
+/// Walk `path` and index its files according to `mode`.
+///
+/// Files are indexed `index_concurrency` at a time through the state's
+/// shared indexer, and a summary is published to SSE subscribers and the
+/// completion webhook when the job finishes.
+///
+/// # Errors
+///
+/// Returns an error if `path` is not an existing directory, the walk task
+/// fails, or existing index data can't be read or cleared.
+pub(super) async fn run_index_job(
+    state: &McpState,
+    path: &str,
+    mode: IndexMode,
+) -> std::result::Result<IndexJobReport, String> {
+    let root = validate_index_root(path)?;
+    let operation = mode.operation();
+    let start_time = Instant::now();
+    let mut report = IndexJobReport::default();
+
+    if matches!(mode, IndexMode::FullReindex) {
+        clear_index_data(state, path, &mut report)?;
+    }
+
+    // Files indexed under this path before the walk, to detect deletions
+    let existing_files: HashSet<String> = if matches!(mode, IndexMode::Diff { .. }) {
+        state
+            .db
+            .with_conn(|conn| crate::storage::list_file_paths_by_prefix(conn, path))
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .collect()
+    } else {
+        HashSet::new()
+    };
+
+    let walked = walk_index_root(state, &root, operation).await?;
+    report.files_found = walked.paths.len();
+    report.files_skipped_noise = walked.skipped_noise;
+    if let Err(e) = state
+        .db
+        .with_conn(|conn| crate::storage::record_scan(conn, report.files_found as u64))
+    {
+        tracing::warn!(path, error = %e, "Failed to record scan");
+    }
+    let seen_files: HashSet<String> = if matches!(mode, IndexMode::Diff { .. }) {
+        walked
+            .paths
+            .iter()
+            .map(|p| p.to_string_lossy().to_string())
+            .collect()
+    } else {
+        HashSet::new()
+    };
+
+    // Whether to check stored state before indexing, and with verify_hashes
+    let (repo, diff_check) = match &mode {
+        IndexMode::Full {
+            repo,
+            skip_unchanged,
+        } => (repo.clone(), skip_unchanged.then_some(false)),
+        IndexMode::Diff { verify_hashes } => (None, Some(*verify_hashes)),
+        IndexMode::FullReindex => (None, None),
+    };
+
+    let job = FileJob {
+        state,
+        indexer: state.indexer(),
+        repo,
+        diff_check,
+        operation,
+        path,
+        total_files: report.files_found,
+    };
+    let tallies = FileTallies::default();
+
+    // Index several files at once so they feed the embedding worker pool
+    // together; database writes still serialize on the connection lock
+    futures::stream::iter(walked.paths)
+        .map(|entry_path| job.index_file(entry_path, &tallies))
+        .buffer_unordered(state.index_concurrency.max(1))
+        .for_each(|()| async {})
+        .await;
+
+    report.files_indexed = tallies.files_indexed.into_inner();
+    report.files_unchanged = tallies.files_unchanged.into_inner();
+    report.chunks_created = tallies.chunks_created.into_inner();
+    report.errors = tallies.errors.into_inner();
+
+    remove_stale_files(state, existing_files.difference(&seen_files), &mut report);
+
+    report.elapsed = start_time.elapsed();
+    if let Err(e) = job.indexer.flush_stats() {
+        tracing::warn!(error = %e, "Failed to persist indexer stats");
+    }
+
+    tracing::info!(
+        operation,
+        path,
+        files_indexed = report.files_indexed,
+        files_unchanged = report.files_unchanged,
+        files_deleted = report.files_deleted,
+        files_skipped_noise = report.files_skipped_noise,
+        chunks_created = report.chunks_created,
+        errors = report.errors,
+        elapsed_ms = report.elapsed.as_millis(),
+        "Indexing job complete"
+    );
+
+    publish_index_complete(
+        state,
+        index_complete_payload(
+            operation,
+            path,
+            report.files_indexed,
+            report.chunks_created,
+            report.errors,
+            report.elapsed,
+        ),
+    );
+
+    Ok(report)
+}
+
+/// Clear all chunks and file state under `path` for `FullReindex`.
+fn clear_index_data(
+    state: &McpState,
+    path: &str,
+    report: &mut IndexJobReport,
+) -> std::result::Result<(), String> {
+    report.chunks_cleared = state
+        .db
+        .with_conn(|conn| crate::storage::delete_chunks_by_path_prefix(conn, path))
+        .map_err(|e| format!("Failed to clear chunks: {e}"))?;
+    report.files_cleared = state
+        .db
+        .with_conn(|conn| crate::storage::delete_file_state_by_prefix(conn, path))
+        .map_err(|e| format!("Failed to clear file state: {e}"))?;
+    tracing::info!(
+        path,
+        chunks_deleted = report.chunks_cleared,
+        files_cleared = report.files_cleared,
+        "Cleared existing index data"
+    );
+    Ok(())
+}
+
+/// Remove the chunks and file state of files that disappeared since the
+/// last run.
+fn remove_stale_files<'a>(
+    state: &McpState,
+    stale_files: impl Iterator<Item = &'a String>,
+    report: &mut IndexJobReport,
+) {
+    for old_file in stale_files {
+        if let Err(e) = state
+            .db
+            .with_conn(|conn| crate::storage::delete_chunks_by_file(conn, old_file))
+        {
+            tracing::warn!(path = old_file, error = %e, "Failed to delete stale chunks");
+            report.errors += 1;
+        } else {
+            let _ = state
+                .db
+                .with_conn(|conn| crate::storage::delete_file_state(conn, old_file));
+            report.files_deleted += 1;
+            tracing::debug!(path = old_file, "Removed deleted file from index");
+        }
+    }
+}
+
+/// Running counts shared by the files of an indexing job.
+#[derive(Default)]
+struct FileTallies {
+    files_indexed: AtomicU64,
+    files_unchanged: AtomicU64,
+    chunks_created: AtomicU64,
+    errors: AtomicU64,
+    processed: AtomicUsize,
+}
+
+/// What every file of an indexing job is indexed with.
+struct FileJob<'a> {
+    state: &'a McpState,
+    indexer: Arc<Indexer>,
+    /// Repository name to tag chunks with (detected when `None`).
+    repo: Option<String>,
+    /// Check stored file state first, with this `verify_hashes`.
+    diff_check: Option<bool>,
+    operation: &'a str,
+    path: &'a str,
+    total_files: usize,
+}
+
+impl FileJob<'_> {
+    /// Index one walked file, counting the outcome in `tallies`.
+    async fn index_file(&self, entry_path: PathBuf, tallies: &FileTallies) {
+        let changed = match self.diff_check {
+            Some(verify_hashes) => file_changed(self.state, &entry_path, verify_hashes).await,
+            None => Ok(true),
+        };
+
+        match changed {
+            Ok(false) => {
+                tallies.files_unchanged.fetch_add(1, Ordering::Relaxed);
+            }
+            Ok(true) => {
+                let request = IndexRequest {
+                    language: self
+                        .state
+                        .file_types
+                        .detect_language(&entry_path)
+                        .map(String::from),
+                    path: entry_path,
+                    repo: self.repo.clone(),
+                };
+                match self.indexer.index_file(&request).await {
+                    Ok(0) => {
+                        tallies.files_unchanged.fetch_add(1, Ordering::Relaxed);
+                    }
+                    Ok(chunks) => {
+                        tallies.files_indexed.fetch_add(1, Ordering::Relaxed);
+                        tallies
+                            .chunks_created
+                            .fetch_add(chunks as u64, Ordering::Relaxed);
+                    }
+                    Err(e) => {
+                        tracing::warn!(
+                            path = %request.path.display(),
+                            error = %e,
+                            "Failed to index file"
+                        );
+                        tallies.errors.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            }
+            Err(e) => {
+                tracing::warn!(
+                    path = %entry_path.display(),
+                    error = %e,
+                    "Failed to read file metadata"
+                );
+                tallies.errors.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let done = tallies.processed.fetch_add(1, Ordering::Relaxed) + 1;
+        if done % PROGRESS_INTERVAL == 0 {
+            tracing::info!(
+                operation = self.operation,
+                path = self.path,
+                progress = format!("{done}/{}", self.total_files),
+                files_indexed = tallies.files_indexed.load(Ordering::Relaxed),
+                files_unchanged = tallies.files_unchanged.load(Ordering::Relaxed),
+                chunks_created = tallies.chunks_created.load(Ordering::Relaxed),
+                "Indexing progress"
+            );
+        }
+    }
+}
+
+/// Walk `path` and summarize what `index_repo` would index, without
+/// embedding or storing anything.
+///
+/// # Errors
+///
+/// Returns an error if `path` is not an existing directory or the walk task
+/// fails.
+pub(super) async fn dry_run_index_job(
+    state: &McpState,
+    path: &str,
+) -> std::result::Result<serde_json::Value, String> {
+    let root = validate_index_root(path)?;
+    let start_time = Instant::now();
+    let walked = walk_index_root(state, &root, "index_repo").await?;
+
+    let mut languages: BTreeMap<&str, u64> = BTreeMap::new();
+    for p in &walked.paths {
+        let language = state.file_types.detect_language(p).unwrap_or("unknown");
+        *languages.entry(language).or_insert(0) += 1;
+    }
+
+    let sample_paths: Vec<String> = walked
+        .paths
+        .iter()
+        .take(DRY_RUN_SAMPLE_PATHS)
+        .map(|p| p.to_string_lossy().to_string())
+        .collect();
+
+    Ok(serde_json::json!({
+        "status": "dry_run",
+        "path": path,
+        "walker": if walked.is_network { "fast" } else { "gitignore" },
+        "total_files": walked.paths.len(),
+        "languages": languages,
+        "files_skipped_noise": walked.skipped_noise,
+        "sample_paths": sample_paths,
+        "elapsed_ms": start_time.elapsed().as_millis(),
+        "message": format!(
+            "Would index {} files in {} languages from {}",
+            walked.paths.len(),
+            languages.len(),
+            path
+        )
+    }))
+}
+
+/// Check that `path` is an existing directory.
+fn validate_index_root(path: &str) -> std::result::Result<PathBuf, String> {
+    let root = PathBuf::from(path);
+    if !root.exists() {
+        return Err(format!("Path does not exist: {path}"));
+    }
+    if !root.is_dir() {
+        return Err(format!(
+            "Path is not a directory: {path}. Use trigger_reindex for single files."
+        ));
+    }
+    Ok(root)
+}
+
+/// Collect the files under `root` in a blocking task (handles slow NFS/SMB).
+async fn walk_index_root(
+    state: &McpState,
+    root: &Path,
+    operation: &str,
+) -> std::result::Result<WalkedFiles, String> {
+    let is_network = is_network_path(root);
+    tracing::info!(
+        operation,
+        path = %root.display(),
+        is_network,
+        "Collecting files to index"
+    );
+
+    let root_for_walk = root.to_path_buf();
+    let config = state.walker_config();
+    let (paths, skipped_noise) = tokio::task::spawn_blocking(move || {
+        collect_index_files(&root_for_walk, is_network, &config)
+    })
+    .await
+    .map_err(|e| format!("Directory walk failed: {e}"))?;
+
+    tracing::info!(
+        operation,
+        path = %root.display(),
+        total_files = paths.len(),
+        files_skipped_noise = skipped_noise,
+        "Found files to index"
+    );
+
+    Ok(WalkedFiles {
+        paths,
+        skipped_noise,
+        is_network,
+    })
+}
+
+/// Check a file's mtime/size (and, when ambiguous, content hash) against
+/// the stored file state.
+#[allow(clippy::cast_possible_wrap)]
+async fn file_changed(state: &McpState, path: &Path, verify_hashes: bool) -> std::io::Result<bool> {
+    let metadata = tokio::fs::metadata(path).await?;
+    let mtime = metadata
+        .modified()
+        .map(|t| {
+            t.duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs() as i64
+        })
+        .unwrap_or(0);
+    let size = metadata.len() as i64;
+    let path_str = path.to_string_lossy();
+
+    Ok(
+        crate::storage::needs_reindex_by_hash(&state.db, &path_str, mtime, size, verify_hashes)
+            .unwrap_or(true),
+    )
+}
+
+/// Check if a path is on a network mount (NFS, SMB, CIFS, etc.)
+/// This is used to choose between fast walker (network) and gitignore-aware walker (local).
+fn is_network_path(path: &Path) -> bool {
+    let path_str = path.to_string_lossy();
+
+    // macOS: /Volumes/ paths that aren't the main disk
+    if path_str.starts_with("/Volumes/") && !path_str.starts_with("/Volumes/Macintosh") {
+        return true;
+    }
+
+    // Linux: common network mount points
+    if path_str.starts_with("/mnt/")
+        || path_str.starts_with("/media/")
+        || path_str.starts_with("/net/")
+        || path_str.starts_with("/nfs/")
+        || path_str.starts_with("/smb/")
+        || path_str.starts_with("/cifs/")
+    {
+        return true;
+    }
+
+    // Check /proc/mounts on Linux for NFS/CIFS mounts
+    #[cfg(target_os = "linux")]
+    {
+        if let Ok(mounts) = std::fs::read_to_string("/proc/mounts") {
+            for line in mounts.lines() {
+                let parts: Vec<&str> = line.split_whitespace().collect();
+                if parts.len() >= 3 {
+                    let mount_point = parts[1];
+                    let fs_type = parts[2];
+                    if path_str.starts_with(mount_point)
+                        && (fs_type == "nfs"
+                            || fs_type == "nfs4"
+                            || fs_type == "cifs"
+                            || fs_type == "smb")
+                    {
+                        return true;
+                    }
+                }
+            }
+        }
+    }
+
+    false
+}
+
+/// Fast directory walker for network mounts.
+/// Skips gitignore parsing (expensive over network) and uses a simple skip list.
+fn fast_walk_directory(root: &Path, config: &WatcherConfig) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut stack = vec![root.to_path_buf()];
+    let mut dirs_walked = 0u64;
+    let skip_dirs = &config.skip_dirs;
+
+    while let Some(dir) = stack.pop() {
+        dirs_walked += 1;
+
+        // Log progress every 100 directories
+        if dirs_walked % 100 == 0 {
+            tracing::debug!(dirs_walked, "fast_walk progress");
+        }
+
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(e) => e,
+            Err(e) => {
+                tracing::warn!(path = %dir.display(), error = %e, "Failed to read directory");
+                continue;
+            }
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let file_name = entry.file_name();
+            let name = file_name.to_string_lossy();
+
+            // Skip hidden files/dirs
+            if name.starts_with('.') && name != "." && name != ".." {
+                continue;
+            }
+
+            // Skip known junk directories
+            if is_skipped_dir_name(skip_dirs, &name) {
+                continue;
+            }
+
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+
+            if file_type.is_dir() {
+                stack.push(path);
+            } else if file_type.is_file() && config.file_types.is_code_file(&path) {
+                files.push(path);
+            }
+        }
+    }
+
+    tracing::info!(total_files = files.len(), dirs_walked, "fast_walk complete");
+    files
+}
+
+/// Collect the code files in `config.file_types` under `root`.
+///
+/// Network mounts use `fast_walk_directory`; local paths get the full
+/// gitignore-aware walker. Lockfiles and minified assets are dropped when
+/// `config.skip_noise_files` is set; the number dropped is returned with the
+/// files.
+fn collect_index_files(
+    root: &Path,
+    is_network: bool,
+    config: &WatcherConfig,
+) -> (Vec<PathBuf>, u64) {
+    let mut paths: Vec<PathBuf> = if is_network {
+        // Fast walker for network mounts - skip gitignore parsing
+        fast_walk_directory(root, config)
+    } else {
+        // Full walker with gitignore support for local paths
+        build_walker(root, config)
+            .filter_map(std::result::Result::ok)
+            .map(ignore::DirEntry::into_path)
+            .filter(|p| p.is_file() && config.file_types.is_code_file(p))
+            .collect()
+    };
+
+    let found = paths.len();
+    if config.skip_noise_files {
+        paths.retain(|p| !FileFilter::is_noise_file(p));
+    }
+    let skipped_noise = (found - paths.len()) as u64;
+    (paths, skipped_noise)
+}
+
+/// Build the summary published when an indexing run completes.
+fn index_complete_payload(
+    operation: &str,
+    path: &str,
+    files_indexed: u64,
+    chunks_created: u64,
+    errors: u64,
+    elapsed: Duration,
+) -> serde_json::Value {
+    serde_json::json!({
+        "event": "index_complete",
+        "operation": operation,
+        "path": path,
+        "files_indexed": files_indexed,
+        "chunks_created": chunks_created,
+        "errors": errors,
+        "duration_ms": elapsed.as_millis(),
+        "completed_at": chrono::Utc::now().timestamp(),
+    })
+}
+
+/// Publish an indexing summary to SSE subscribers and the configured webhook.
+///
+/// Delivery runs in the background; failures are logged and never fail the
+/// indexing run that produced the summary.
+fn publish_index_complete(state: &McpState, payload: serde_json::Value) {
+    // No subscribers is not an error
+    let _ = state.index_events.send(payload.clone());
+
+    let Some(url) = state.index_complete_webhook.clone() else {
+        return;
+    };
+    tokio::spawn(async move {
+        let result = reqwest::Client::new()
+            .post(&url)
+            .timeout(WEBHOOK_TIMEOUT)
+            .json(&payload)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status);
+        match result {
+            Ok(_) => tracing::debug!(url, "Delivered index_complete webhook"),
+            Err(e) => tracing::warn!(url, error = %e, "Failed to deliver index_complete webhook"),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{migrate, Database};
+
+    fn test_state() -> McpState {
+        let db = Database::open_in_memory().unwrap();
+        db.with_conn(|conn| migrate(conn)).unwrap();
+        McpState::new(db)
+    }
+
+    #[test]
+    fn test_collect_index_files_counts_noise() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("main.rs"), "fn main() {}\n").unwrap();
+        std::fs::write(dir.path().join("Cargo.lock"), "version = 3\n").unwrap();
+        std::fs::write(dir.path().join("app.min.js"), "var a=1;\n").unwrap();
+        std::fs::write(dir.path().join("notes.bin"), "not code").unwrap();
+
+        let config = WatcherConfig::default();
+        let (paths, skipped_noise) = collect_index_files(dir.path(), false, &config);
+        assert_eq!(paths.len(), 1);
+        assert!(paths[0].ends_with("main.rs"));
+        assert_eq!(skipped_noise, 1);
+
+        let config = config.with_skip_noise_files(false);
+        let (paths, skipped_noise) = collect_index_files(dir.path(), false, &config);
+        assert_eq!(paths.len(), 2);
+        assert_eq!(skipped_noise, 0);
+    }
+
+    #[test]
+    fn test_collect_index_files_uses_file_types() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("main.rs"), "fn main() {}\n").unwrap();
+        std::fs::write(dir.path().join("main.tf"), "resource {}\n").unwrap();
+
+        let types = crate::watcher::FileTypes::default()
+            .with_extensions(&std::collections::HashMap::new(), &["tf".to_string()]);
+        for is_network in [false, true] {
+            let config = WatcherConfig::default().with_file_types(types.clone());
+            let (mut paths, _) = collect_index_files(dir.path(), is_network, &config);
+            paths.sort();
+            assert_eq!(paths.len(), 2);
+            assert!(paths[1].ends_with("main.tf"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_index_job_rejects_missing_path() {
+        let state = test_state();
+        let err = run_index_job(&state, "/nonexistent/nellie/path", IndexMode::FullReindex)
+            .await
+            .unwrap_err();
+        assert!(err.contains("does not exist"));
+    }
+
+    #[tokio::test]
+    async fn test_diff_job_removes_deleted_files() {
+        let state = test_state();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("keep.rs"), "fn keep() {}\n").unwrap();
+        std::fs::write(dir.path().join("gone.rs"), "fn gone() {}\n").unwrap();
+        let path = dir.path().to_string_lossy().to_string();
+
+        let first = run_index_job(
+            &state,
+            &path,
+            IndexMode::Full {
+                repo: None,
+                skip_unchanged: false,
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(first.files_indexed, 2);
+
+        std::fs::remove_file(dir.path().join("gone.rs")).unwrap();
+        let diff = run_index_job(
+            &state,
+            &path,
+            IndexMode::Diff {
+                verify_hashes: false,
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(diff.files_found, 1);
+        assert_eq!(diff.files_indexed, 0);
+        assert_eq!(diff.files_unchanged, 1);
+        assert_eq!(diff.files_deleted, 1);
+        assert_eq!(diff.files_skipped(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_full_reindex_job_clears_then_indexes() {
+        let state = test_state();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("lib.rs"), "pub fn lib() {}\n").unwrap();
+        let path = dir.path().to_string_lossy().to_string();
+
+        let first = run_index_job(
+            &state,
+            &path,
+            IndexMode::Full {
+                repo: None,
+                skip_unchanged: false,
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(first.files_indexed, 1);
+
+        let again = run_index_job(&state, &path, IndexMode::FullReindex)
+            .await
+            .unwrap();
+        assert_eq!(again.chunks_cleared as u64, first.chunks_created);
+        assert_eq!(again.files_cleared, 1);
+        assert_eq!(again.files_indexed, 1);
+        assert_eq!(again.chunks_created, first.chunks_created);
+    }
+
+    #[tokio::test]
+    async fn test_full_job_skips_unchanged_files() {
+        let state = test_state();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.rs"), "fn a() {}\n").unwrap();
+        std::fs::write(dir.path().join("b.rs"), "fn b() {}\n").unwrap();
+        let path = dir.path().to_string_lossy().to_string();
+        let mode = IndexMode::Full {
+            repo: None,
+            skip_unchanged: true,
+        };
+
+        let first = run_index_job(&state, &path, mode.clone()).await.unwrap();
+        assert_eq!(first.files_indexed, 2);
+
+        let again = run_index_job(&state, &path, mode.clone()).await.unwrap();
+        assert_eq!(again.files_indexed, 0);
+        assert_eq!(again.files_unchanged, 2);
+
+        // Changed and new files are still picked up
+        std::fs::write(dir.path().join("a.rs"), "fn a() { changed(); }\n").unwrap();
+        std::fs::write(dir.path().join("c.rs"), "fn c() {}\n").unwrap();
+        let third = run_index_job(&state, &path, mode).await.unwrap();
+        assert_eq!(third.files_found, 3);
+        assert_eq!(third.files_indexed, 2);
+        assert_eq!(third.files_unchanged, 1);
+    }
+}
@@ -6,445 +6,68 @@
 use std::sync::Arc;
 
 use rmcp::{
-    handler::server::{router::tool::ToolRouter, wrapper::Parameters},
-    model::{ServerCapabilities, ServerInfo},
-    schemars, tool, tool_router, ServerHandler,
+    model::{CallToolResult, Content, JsonObject, ServerCapabilities, ServerInfo, Tool},
+    ServerHandler,
 };
-use serde::Deserialize;
-use serde_json::Value;
-
-use crate::embeddings::EmbeddingService;
-use crate::storage::Database;
-
-// ==================== Request Types ====================
-
-#[derive(Debug, Deserialize, schemars::JsonSchema)]
-pub struct SearchCodeRequest {
-    #[schemars(description = "Natural language query to search for relevant code")]
-    pub query: String,
-    #[schemars(description = "Maximum number of results (default: 10)")]
-    pub limit: Option<i32>,
-    #[schemars(description = "Filter by programming language")]
-    pub language: Option<String>,
-}
-
-#[derive(Debug, Deserialize, schemars::JsonSchema)]
-pub struct SearchLessonsRequest {
-    #[schemars(description = "Natural language query to search lessons")]
-    pub query: String,
-    #[schemars(description = "Maximum lessons to return (default: 5)")]
-    pub limit: Option<i32>,
-}
 
-#[derive(Debug, Deserialize, schemars::JsonSchema)]
-pub struct ListLessonsRequest {
-    #[schemars(description = "Filter by severity level (critical, warning, info)")]
-    pub severity: Option<String>,
-    #[schemars(description = "Filter by repository name")]
-    pub repo: Option<String>,
-    #[schemars(description = "Maximum lessons to return (default: 50)")]
-    pub limit: Option<i32>,
-}
-
-#[derive(Debug, Deserialize, schemars::JsonSchema)]
-pub struct AddLessonRequest {
-    #[schemars(description = "Brief title for the lesson")]
-    pub title: String,
-    #[schemars(description = "Full description of the lesson learned")]
-    pub content: String,
-    #[schemars(description = "Tags for categorization")]
-    pub tags: Vec<String>,
-    #[schemars(description = "Importance level (critical, warning, info)")]
-    pub severity: Option<String>,
-    #[schemars(description = "Repository name (e.g., mike-github/whag)")]
-    pub repo: Option<String>,
-}
-
-#[derive(Debug, Deserialize, schemars::JsonSchema)]
-pub struct DeleteLessonRequest {
-    #[schemars(description = "Lesson ID to delete")]
-    pub id: String,
-}
-
-#[derive(Debug, Deserialize, schemars::JsonSchema)]
-pub struct AddCheckpointRequest {
-    #[schemars(description = "Agent identifier")]
-    pub agent: String,
-    #[schemars(description = "Current task description")]
-    pub working_on: String,
-    #[schemars(description = "State object to persist")]
-    pub state: Value,
-}
-
-#[derive(Debug, Deserialize, schemars::JsonSchema)]
-pub struct GetCheckpointsRequest {
-    #[schemars(description = "Agent identifier")]
-    pub agent: String,
-    #[schemars(description = "Maximum checkpoints to return (default: 5)")]
-    pub limit: Option<i32>,
-}
-
-#[derive(Debug, Deserialize, schemars::JsonSchema)]
-pub struct SearchCheckpointsRequest {
-    #[schemars(description = "Query text to search checkpoints")]
-    pub query: String,
-    #[schemars(description = "Optional agent filter")]
-    pub agent: Option<String>,
-    #[schemars(description = "Maximum checkpoints to return (default: 5)")]
-    pub limit: Option<i32>,
-}
-
-#[derive(Debug, Deserialize, schemars::JsonSchema)]
-pub struct GetAgentStatusRequest {
-    #[schemars(description = "Agent identifier")]
-    pub agent: String,
-}
-
-#[derive(Debug, Deserialize, schemars::JsonSchema)]
-pub struct TriggerReindexRequest {
-    #[schemars(description = "File or directory path to re-index (optional)")]
-    pub path: Option<String>,
-}
+use super::auth::{auth_middleware_wrapper, ApiKeyConfig};
+use super::mcp::{invoke_tool_direct, McpState, ToolInfo, ToolRequest};
 
 // ==================== MCP Handler ====================
 
 /// MCP server handler for Nellie.
+///
+/// Tool listing and invocation delegate to the same table and dispatcher as
+/// `/mcp/invoke`, so both transports expose identical tools (and hide the
+/// same disabled ones).
 #[derive(Clone)]
 pub struct NellieMcpHandler {
-    db: Database,
-    embeddings: Option<EmbeddingService>,
-    tool_router: ToolRouter<Self>,
+    state: Arc<McpState>,
 }
 
 impl NellieMcpHandler {
     /// Create a new MCP handler.
-    pub fn new(db: Database, embeddings: Option<EmbeddingService>) -> Self {
-        Self {
-            db,
-            embeddings,
-            tool_router: Self::tool_router(),
-        }
+    #[must_use]
+    pub const fn new(state: Arc<McpState>) -> Self {
+        Self { state }
     }
-}
-
-#[tool_router]
-impl NellieMcpHandler {
-    #[tool(description = "Search indexed code repositories for relevant code snippets")]
-    fn search_code(&self, Parameters(req): Parameters<SearchCodeRequest>) -> String {
-        let limit = req.limit.unwrap_or(10) as usize;
-
-        let Some(ref embeddings) = self.embeddings else {
-            return serde_json::json!({"error": "Embedding service not initialized"}).to_string();
-        };
-
-        if !embeddings.is_initialized() {
-            return serde_json::json!({"error": "Embedding service not fully initialized"}).to_string();
-        }
-
-        // Generate embedding using a dedicated runtime to avoid blocking tokio
-        let query_text = req.query.clone();
-        let embeddings_clone = embeddings.clone();
-        let embedding = match std::thread::spawn(move || {
-            let rt = tokio::runtime::Runtime::new().expect("Failed to create runtime");
-            rt.block_on(async { embeddings_clone.embed_one(query_text).await })
-        }).join() {
-            Ok(Ok(e)) => e,
-            Ok(Err(e)) => return serde_json::json!({"error": format!("Embedding failed: {}", e)}).to_string(),
-            Err(_) => return serde_json::json!({"error": "Embedding thread panicked"}).to_string(),
-        };
-
-        let mut search_opts = crate::storage::SearchOptions::new(limit);
-        if let Some(lang) = req.language.as_ref() {
-            search_opts = search_opts.with_language(lang);
-        }
 
-        match self.db.with_conn(|conn| crate::storage::search_chunks(conn, &embedding, &search_opts)) {
-            Ok(results) => {
-                let formatted: Vec<Value> = results
-                    .iter()
-                    .map(|r| {
-                        serde_json::json!({
-                            "file_path": r.record.file_path,
-                            "chunk_index": r.record.chunk_index,
-                            "start_line": r.record.start_line,
-                            "end_line": r.record.end_line,
-                            "content": r.record.content,
-                            "language": r.record.language,
-                            "score": r.score,
-                        })
-                    })
-                    .collect();
-
-                serde_json::json!({
-                    "results": formatted,
-                    "query": req.query,
-                    "count": formatted.len(),
-                }).to_string()
-            }
-            Err(e) => serde_json::json!({"error": e.to_string()}).to_string(),
-        }
+    /// Tools advertised over this transport.
+    fn tools(&self) -> Vec<Tool> {
+        self.state.tools().into_iter().map(to_rmcp_tool).collect()
     }
 
-    #[tool(description = "Search previously recorded lessons learned")]
-    fn search_lessons(&self, Parameters(req): Parameters<SearchLessonsRequest>) -> String {
-        let limit = req.limit.unwrap_or(5) as usize;
-
-        let Some(ref embeddings) = self.embeddings else {
-            return serde_json::json!({"error": "Embedding service not initialized"}).to_string();
-        };
-
-        if !embeddings.is_initialized() {
-            return serde_json::json!({"error": "Embedding service not fully initialized"}).to_string();
-        }
-
-        let query_text = req.query.clone();
-        let embeddings_clone = embeddings.clone();
-        let embedding = match std::thread::spawn(move || {
-            let rt = tokio::runtime::Runtime::new().expect("Failed to create runtime");
-            rt.block_on(async { embeddings_clone.embed_one(query_text).await })
-        }).join() {
-            Ok(Ok(e)) => e,
-            Ok(Err(e)) => return serde_json::json!({"error": format!("Embedding failed: {}", e)}).to_string(),
-            Err(_) => return serde_json::json!({"error": "Embedding thread panicked"}).to_string(),
+    /// Run a tool through the shared dispatcher.
+    ///
+    /// Failures come back as an error result carrying the same `error` and
+    /// `error_code` fields as the HTTP transport.
+    async fn call(&self, name: &str, arguments: Option<JsonObject>) -> CallToolResult {
+        let request = ToolRequest {
+            name: name.to_string(),
+            arguments: arguments.map_or(serde_json::Value::Null, serde_json::Value::Object),
         };
+        let response = invoke_tool_direct(&self.state, request).await;
 
-        match self.db.with_conn(|conn| crate::storage::search_lessons_by_embedding(conn, &embedding, limit)) {
-            Ok(lessons) => serde_json::to_string(&lessons).unwrap_or_else(|_| "[]".to_string()),
-            Err(e) => serde_json::json!({"error": e.to_string()}).to_string(),
-        }
-    }
-
-    #[tool(description = "List all recorded lessons learned with optional filters")]
-    fn list_lessons(&self, Parameters(req): Parameters<ListLessonsRequest>) -> String {
-        let limit = req.limit.unwrap_or(50) as usize;
-
-        let lessons = if let Some(sev) = req.severity.as_ref() {
-            self.db.with_conn(|conn| crate::storage::list_lessons_by_severity(conn, sev))
-        } else {
-            self.db.with_conn(|conn| crate::storage::list_lessons(conn))
-        };
-
-        match lessons {
-            Ok(list) => {
-                // Filter by repo if specified
-                let filtered: Vec<_> = if let Some(ref repo) = req.repo {
-                    list.into_iter()
-                        .filter(|l| l.repo.as_ref() == Some(repo))
-                        .take(limit)
-                        .collect()
-                } else {
-                    list.into_iter().take(limit).collect()
-                };
+        match response.error {
+            None => CallToolResult::success(vec![Content::text(response.content.to_string())]),
+            Some(error) => CallToolResult::error(vec![Content::text(
                 serde_json::json!({
-                    "lessons": filtered,
-                    "count": filtered.len(),
-                }).to_string()
-            }
-            Err(e) => serde_json::json!({"error": e.to_string()}).to_string(),
-        }
-    }
-
-    #[tool(description = "Record a lesson learned during development")]
-    fn add_lesson(&self, Parameters(req): Parameters<AddLessonRequest>) -> String {
-        let severity = req.severity.as_deref().unwrap_or("info");
-        let mut lesson = crate::storage::LessonRecord::new(&req.title, &req.content, req.tags.clone())
-            .with_severity(severity);
-        if let Some(ref repo) = req.repo {
-            lesson = lesson.with_repo(repo);
-        }
-        let id = lesson.id.clone();
-
-        if let Err(e) = self.db.with_conn(|conn| crate::storage::insert_lesson(conn, &lesson)) {
-            return serde_json::json!({"error": e.to_string()}).to_string();
-        }
-
-        // Generate and store embedding if available
-        if let Some(ref embeddings) = self.embeddings {
-            if embeddings.is_initialized() {
-                let text = format!("{}\n{}", lesson.title, lesson.content);
-                let text_clone = text.clone();
-                let embeddings_clone = embeddings.clone();
-                let lesson_id = lesson.id.clone();
-                let db = self.db.clone();
-                std::thread::spawn(move || {
-                    if let Ok(rt) = tokio::runtime::Runtime::new() {
-                        if let Ok(embedding) = rt.block_on(async { embeddings_clone.embed_one(text_clone).await }) {
-                            let _ = db.with_conn(|conn| {
-                                crate::storage::store_lesson_embedding(conn, &lesson_id, &embedding)
-                            });
-                        }
-                    }
-                });
-            }
-        }
-
-        serde_json::json!({
-            "id": id,
-            "message": "Lesson recorded successfully"
-        }).to_string()
-    }
-
-    #[tool(description = "Delete a lesson by ID")]
-    fn delete_lesson(&self, Parameters(req): Parameters<DeleteLessonRequest>) -> String {
-        match self.db.with_conn(|conn| crate::storage::delete_lesson(conn, &req.id)) {
-            Ok(_) => serde_json::json!({
-                "id": req.id,
-                "message": "Lesson deleted successfully"
-            }).to_string(),
-            Err(e) => serde_json::json!({"error": e.to_string()}).to_string(),
-        }
-    }
-
-    #[tool(description = "Store an agent checkpoint for context recovery")]
-    fn add_checkpoint(&self, Parameters(req): Parameters<AddCheckpointRequest>) -> String {
-        let checkpoint = crate::storage::CheckpointRecord::new(&req.agent, &req.working_on, req.state);
-        let id = checkpoint.id.clone();
-
-        if let Err(e) = self.db.with_conn(|conn| crate::storage::insert_checkpoint(conn, &checkpoint)) {
-            return serde_json::json!({"error": e.to_string()}).to_string();
-        }
-
-        // Generate and store embedding if available
-        if let Some(ref embeddings) = self.embeddings {
-            if embeddings.is_initialized() {
-                let text_clone = checkpoint.working_on.clone();
-                let embeddings_clone = embeddings.clone();
-                let checkpoint_id = checkpoint.id.clone();
-                let db = self.db.clone();
-                std::thread::spawn(move || {
-                    if let Ok(rt) = tokio::runtime::Runtime::new() {
-                        if let Ok(embedding) = rt.block_on(async { embeddings_clone.embed_one(text_clone).await }) {
-                            let _ = db.with_conn(|conn| {
-                                crate::storage::store_checkpoint_embedding(conn, &checkpoint_id, &embedding)
-                            });
-                        }
-                    }
-                });
-            }
-        }
-
-        serde_json::json!({
-            "id": id,
-            "message": "Checkpoint saved successfully"
-        }).to_string()
-    }
-
-    #[tool(description = "Retrieve recent checkpoints for an agent")]
-    fn get_recent_checkpoints(&self, Parameters(req): Parameters<GetCheckpointsRequest>) -> String {
-        let limit = req.limit.unwrap_or(5) as usize;
-
-        match self.db.with_conn(|conn| crate::storage::get_recent_checkpoints(conn, &req.agent, limit)) {
-            Ok(checkpoints) => serde_json::to_string(&checkpoints).unwrap_or_else(|_| "[]".to_string()),
-            Err(e) => serde_json::json!({"error": e.to_string()}).to_string(),
-        }
-    }
-
-    #[tool(description = "Search checkpoints semantically by query text")]
-    fn search_checkpoints(&self, Parameters(req): Parameters<SearchCheckpointsRequest>) -> String {
-        let limit = req.limit.unwrap_or(5) as usize;
-
-        let Some(ref embeddings) = self.embeddings else {
-            return serde_json::json!({"error": "Embedding service not initialized"}).to_string();
-        };
-
-        if !embeddings.is_initialized() {
-            return serde_json::json!({"error": "Embedding service not fully initialized"}).to_string();
-        }
-
-        let query_text = req.query.clone();
-        let embeddings_clone = embeddings.clone();
-        let embedding = match std::thread::spawn(move || {
-            let rt = tokio::runtime::Runtime::new().expect("Failed to create runtime");
-            rt.block_on(async { embeddings_clone.embed_one(query_text).await })
-        }).join() {
-            Ok(Ok(e)) => e,
-            Ok(Err(e)) => return serde_json::json!({"error": format!("Embedding failed: {}", e)}).to_string(),
-            Err(_) => return serde_json::json!({"error": "Embedding thread panicked"}).to_string(),
-        };
-
-        match self.db.with_conn(|conn| crate::storage::search_checkpoints_by_embedding(conn, &embedding, limit)) {
-            Ok(results) => {
-                let checkpoints: Vec<_> = if let Some(ref agent_filter) = req.agent {
-                    results
-                        .into_iter()
-                        .filter(|cp| cp.record.agent == *agent_filter)
-                        .map(|cp| cp.record)
-                        .collect()
-                } else {
-                    results.into_iter().map(|cp| cp.record).collect()
-                };
-
-                serde_json::json!({
-                    "checkpoints": checkpoints,
-                    "count": checkpoints.len(),
-                    "query": req.query,
-                }).to_string()
-            }
-            Err(e) => serde_json::json!({"error": e.to_string()}).to_string(),
-        }
-    }
-
-    #[tool(description = "Get quick status for an agent (idle/in_progress, current task)")]
-    fn get_agent_status(&self, Parameters(req): Parameters<GetAgentStatusRequest>) -> String {
-        match self.db.with_conn(|conn| crate::storage::get_agent_status(conn, &req.agent)) {
-            Ok(status) => serde_json::json!({
-                "agent": status.agent,
-                "status": status.status.as_str(),
-                "current_task": status.current_task,
-                "last_updated": status.last_updated,
-                "checkpoint_count": status.checkpoint_count,
-            }).to_string(),
-            Err(e) => serde_json::json!({"error": e.to_string()}).to_string(),
-        }
-    }
-
-    #[tool(description = "Trigger manual re-indexing of specified paths")]
-    fn trigger_reindex(&self, Parameters(req): Parameters<TriggerReindexRequest>) -> String {
-        if let Some(target_path) = req.path.as_ref() {
-            match self.db.with_conn(|conn| {
-                crate::storage::delete_chunks_by_file(conn, target_path)?;
-                crate::storage::delete_file_state(conn, target_path)?;
-                Ok::<_, crate::Error>(())
-            }) {
-                Ok(_) => serde_json::json!({
-                    "status": "reindex_scheduled",
-                    "path": target_path,
-                }).to_string(),
-                Err(e) => serde_json::json!({"error": e.to_string()}).to_string(),
-            }
-        } else {
-            match self.db.with_conn(|conn| {
-                let paths = crate::storage::list_file_paths(conn)?;
-                for file_path in paths {
-                    crate::storage::delete_file_state(conn, &file_path)?;
-                }
-                Ok::<_, crate::Error>(())
-            }) {
-                Ok(_) => serde_json::json!({
-                    "status": "reindex_scheduled",
-                    "path": "all",
-                }).to_string(),
-                Err(e) => serde_json::json!({"error": e.to_string()}).to_string(),
-            }
+                    "error": error,
+                    "error_code": response.error_code,
+                })
+                .to_string(),
+            )]),
         }
     }
+}
 
-    #[tool(description = "Get Nellie server status and statistics")]
-    fn get_status(&self) -> String {
-        let chunk_count = self.db.with_conn(|conn| crate::storage::count_chunks(conn)).unwrap_or(0);
-        let lesson_count = self.db.with_conn(|conn| crate::storage::count_lessons(conn)).unwrap_or(0);
-        let file_count = self.db.with_conn(|conn| crate::storage::count_tracked_files(conn)).unwrap_or(0);
-
-        serde_json::json!({
-            "status": "ok",
-            "version": env!("CARGO_PKG_VERSION"),
-            "stats": {
-                "chunks": chunk_count,
-                "lessons": lesson_count,
-                "files": file_count,
-            }
-        }).to_string()
-    }
+/// Convert a shared tool definition into its rmcp form.
+fn to_rmcp_tool(info: ToolInfo) -> Tool {
+    let schema = match info.input_schema {
+        serde_json::Value::Object(map) => map,
+        _ => JsonObject::new(),
+    };
+    Tool::new(info.name, info.description.unwrap_or_default(), schema)
 }
 
 impl ServerHandler for NellieMcpHandler {
@@ -465,10 +88,12 @@ impl ServerHandler for NellieMcpHandler {
         &self,
         _request: Option<rmcp::model::PaginatedRequestParams>,
         _context: rmcp::service::RequestContext<rmcp::RoleServer>,
-    ) -> impl std::future::Future<Output = Result<rmcp::model::ListToolsResult, rmcp::ErrorData>> + Send + '_ {
+    ) -> impl std::future::Future<Output = Result<rmcp::model::ListToolsResult, rmcp::ErrorData>>
+           + Send
+           + '_ {
         std::future::ready(Ok(rmcp::model::ListToolsResult {
             meta: None,
-            tools: self.tool_router.list_all(),
+            tools: self.tools(),
             next_cursor: None,
         }))
     }
@@ -476,9 +101,10 @@ impl ServerHandler for NellieMcpHandler {
     fn call_tool(
         &self,
         request: rmcp::model::CallToolRequestParams,
-        context: rmcp::service::RequestContext<rmcp::RoleServer>,
-    ) -> impl std::future::Future<Output = Result<rmcp::model::CallToolResult, rmcp::ErrorData>> + Send + '_ {
-        self.tool_router.call(rmcp::handler::server::tool::ToolCallContext::new(self, request, context))
+        _context: rmcp::service::RequestContext<rmcp::RoleServer>,
+    ) -> impl std::future::Future<Output = Result<CallToolResult, rmcp::ErrorData>> + Send + '_
+    {
+        async move { Ok(self.call(&request.name, request.arguments).await) }
     }
 }
 
@@ -489,6 +115,8 @@ pub struct McpTransportConfig {
     pub host: String,
     /// Port for MCP server
     pub port: u16,
+    /// API key required on every request (None = disabled)
+    pub api_key: Option<String>,
 }
 
 impl Default for McpTransportConfig {
@@ -496,36 +124,22 @@ impl Default for McpTransportConfig {
         Self {
             host: "127.0.0.1".to_string(),
             port: 8766,
+            api_key: None,
         }
     }
 }
 
-/// Start the MCP HTTP server using StreamableHttpService.
+/// Build the router serving the StreamableHttp MCP endpoint at `/mcp`.
 ///
-/// This starts a server that speaks the MCP protocol,
-/// allowing Claude Code, mcporter, and other MCP clients to connect.
-pub async fn start_mcp_server(
-    config: McpTransportConfig,
-    db: Database,
-    embeddings: Option<EmbeddingService>,
-) -> crate::Result<tokio::task::JoinHandle<()>> {
+/// Requests go through the same API key check as the main server.
+fn transport_router(
+    state: Arc<McpState>,
+    api_key: Option<String>,
+    ct: &tokio_util::sync::CancellationToken,
+) -> axum::Router {
     use rmcp::transport::streamable_http_server::{
         session::local::LocalSessionManager, StreamableHttpServerConfig, StreamableHttpService,
     };
-    use std::net::SocketAddr;
-    use tokio_util::sync::CancellationToken;
-
-    let addr: SocketAddr = format!("{}:{}", config.host, config.port)
-        .parse()
-        .map_err(|e| crate::Error::config(format!("Invalid MCP address: {e}")))?;
-
-    tracing::info!(%addr, "Starting MCP HTTP server");
-
-    let ct = CancellationToken::new();
-
-    // Create the StreamableHttpService with a factory function
-    let db_clone = db.clone();
-    let embeddings_clone = embeddings.clone();
 
     let mcp_config = StreamableHttpServerConfig {
         stateful_mode: true,
@@ -535,13 +149,42 @@ pub async fn start_mcp_server(
 
     let service: StreamableHttpService<NellieMcpHandler, LocalSessionManager> =
         StreamableHttpService::new(
-            move || Ok(NellieMcpHandler::new(db_clone.clone(), embeddings_clone.clone())),
+            move || Ok(NellieMcpHandler::new(Arc::clone(&state))),
             Arc::new(LocalSessionManager::default()),
             mcp_config,
         );
 
-    // Create router with MCP service
-    let router = axum::Router::new().nest_service("/mcp", service);
+    axum::Router::new()
+        .nest_service("/mcp", service)
+        .layer(axum::middleware::from_fn(auth_middleware_wrapper(
+            Arc::new(ApiKeyConfig::new(api_key)),
+        )))
+}
+
+/// Start the MCP HTTP server using StreamableHttpService.
+///
+/// This starts a server that speaks the MCP protocol,
+/// allowing Claude Code, mcporter, and other MCP clients to connect.
+/// When `config.api_key` is set, requests without it are rejected with 401.
+///
+/// # Errors
+///
+/// Returns an error if the configured address is invalid or can't be bound.
+pub async fn start_mcp_server(
+    config: McpTransportConfig,
+    state: Arc<McpState>,
+) -> crate::Result<tokio::task::JoinHandle<()>> {
+    use std::net::SocketAddr;
+    use tokio_util::sync::CancellationToken;
+
+    let addr: SocketAddr = format!("{}:{}", config.host, config.port)
+        .parse()
+        .map_err(|e| crate::Error::config(format!("Invalid MCP address: {e}")))?;
+
+    tracing::info!(%addr, "Starting MCP HTTP server");
+
+    let ct = CancellationToken::new();
+    let router = transport_router(state, config.api_key, &ct);
 
     // Create TCP listener
     let listener = tokio::net::TcpListener::bind(addr)
@@ -563,3 +206,151 @@ pub async fn start_mcp_server(
 
     Ok(handle)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::get_tools;
+    use crate::storage::Database;
+
+    fn test_handler() -> NellieMcpHandler {
+        let db = Database::open_in_memory().expect("Failed to create in-memory database");
+        db.with_conn(|conn| crate::storage::migrate(conn))
+            .expect("Failed to migrate");
+        NellieMcpHandler::new(Arc::new(McpState::new(db)))
+    }
+
+    #[test]
+    fn test_tool_names_match_http_transport() {
+        let http: std::collections::BTreeSet<String> =
+            get_tools().into_iter().map(|t| t.name).collect();
+        let rmcp: std::collections::BTreeSet<String> = test_handler()
+            .tools()
+            .into_iter()
+            .map(|t| t.name.to_string())
+            .collect();
+
+        assert_eq!(http, rmcp);
+        for name in [
+            "index_repo",
+            "diff_index",
+            "full_reindex",
+            "trigger_reindex",
+        ] {
+            assert!(rmcp.contains(name), "missing {name}");
+        }
+    }
+
+    #[test]
+    fn test_tool_schemas_are_objects() {
+        for tool in test_handler().tools() {
+            assert_eq!(
+                tool.input_schema.get("type"),
+                Some(&serde_json::json!("object")),
+                "{} schema",
+                tool.name
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_call_delegates_to_shared_handlers() {
+        let handler = test_handler();
+
+        let mut args = JsonObject::new();
+        args.insert("title".into(), serde_json::json!("Scoped"));
+        args.insert(
+            "content".into(),
+            serde_json::json!("Kept on both transports"),
+        );
+        args.insert("tags".into(), serde_json::json!([]));
+        args.insert("repo".into(), serde_json::json!("payments-api"));
+        let result = handler.call("add_lesson", Some(args)).await;
+        assert_ne!(result.is_error, Some(true));
+
+        let lessons = handler
+            .state
+            .db
+            .with_conn(|conn| crate::storage::list_lessons(conn))
+            .unwrap();
+        assert_eq!(lessons.len(), 1);
+        assert_eq!(lessons[0].repo.as_deref(), Some("payments-api"));
+    }
+
+    #[tokio::test]
+    async fn test_call_reports_errors() {
+        let handler = test_handler();
+
+        let result = handler.call("no_such_tool", None).await;
+        assert_eq!(result.is_error, Some(true));
+
+        let result = handler.call("delete_lesson", None).await;
+        assert_eq!(result.is_error, Some(true));
+    }
+
+    #[tokio::test]
+    async fn test_disabled_tools_hidden_and_refused() {
+        let db = Database::open_in_memory().expect("Failed to create in-memory database");
+        db.with_conn(|conn| crate::storage::migrate(conn))
+            .expect("Failed to migrate");
+        let state = McpState::new(db).with_read_only(true);
+        let handler = NellieMcpHandler::new(Arc::new(state));
+
+        assert!(!handler.tools().iter().any(|t| t.name == "delete_lesson"));
+
+        let mut args = JsonObject::new();
+        args.insert("id".into(), serde_json::json!("lesson-1"));
+        let result = handler.call("delete_lesson", Some(args)).await;
+        assert_eq!(result.is_error, Some(true));
+        let text = serde_json::to_string(&result.content).unwrap();
+        assert!(text.contains("tool_disabled"));
+    }
+
+    #[tokio::test]
+    async fn test_transport_requires_api_key() {
+        use axum::body::Body;
+        use axum::http::{Request, StatusCode};
+        use tower::ServiceExt;
+
+        let ct = tokio_util::sync::CancellationToken::new();
+        let router = transport_router(
+            Arc::clone(&test_handler().state),
+            Some("secret".to_string()),
+            &ct,
+        );
+        let initialize = |key: Option<&str>| {
+            let mut request = Request::builder()
+                .method("POST")
+                .uri("/mcp")
+                .header("content-type", "application/json")
+                .header("accept", "application/json, text/event-stream");
+            if let Some(key) = key {
+                request = request.header("authorization", format!("Bearer {key}"));
+            }
+            request
+                .body(Body::from(
+                    serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "id": 1,
+                        "method": "initialize",
+                        "params": {
+                            "protocolVersion": "2025-03-26",
+                            "capabilities": {},
+                            "clientInfo": { "name": "test", "version": "0" },
+                        },
+                    })
+                    .to_string(),
+                ))
+                .unwrap()
+        };
+
+        for key in [None, Some("wrong")] {
+            let response = router.clone().oneshot(initialize(key)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "{key:?}");
+        }
+
+        let response = router.oneshot(initialize(Some("secret"))).await.unwrap();
+        assert_ne!(response.status(), StatusCode::UNAUTHORIZED);
+        ct.cancel();
+    }
+}
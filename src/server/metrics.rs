@@ -2,10 +2,16 @@
 
 use once_cell::sync::Lazy;
 use prometheus::{
-    register_histogram_vec, register_int_counter_vec, register_int_gauge, HistogramVec,
-    IntCounterVec, IntGauge,
+    register_histogram_vec, register_int_counter, register_int_counter_vec, register_int_gauge,
+    HistogramVec, IntCounter, IntCounterVec, IntGauge,
 };
 
+use crate::storage::Database;
+use crate::Result;
+
+/// Default seconds between refreshes of the database-backed gauges.
+pub const DEFAULT_METRICS_REFRESH_SECS: u64 = 15;
+
 /// Total chunks indexed.
 pub static CHUNKS_TOTAL: Lazy<IntGauge> = Lazy::new(|| {
     register_int_gauge!("nellie_chunks_total", "Total number of indexed code chunks").unwrap()
@@ -21,6 +27,24 @@ pub static FILES_TOTAL: Lazy<IntGauge> = Lazy::new(|| {
     register_int_gauge!("nellie_files_total", "Total number of tracked files").unwrap()
 });
 
+/// Total checkpoints stored.
+pub static CHECKPOINTS_TOTAL: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "nellie_checkpoints_total",
+        "Total number of checkpoints stored"
+    )
+    .unwrap()
+});
+
+/// Agents with work in progress.
+pub static AGENTS_IN_PROGRESS: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "nellie_agents_in_progress",
+        "Number of agents with work in progress"
+    )
+    .unwrap()
+});
+
 /// Request latency histogram.
 pub static REQUEST_LATENCY: Lazy<HistogramVec> = Lazy::new(|| {
     register_histogram_vec!(
@@ -51,19 +75,98 @@ pub static EMBEDDING_QUEUE_DEPTH: Lazy<IntGauge> = Lazy::new(|| {
     .unwrap()
 });
 
+/// Query embedding cache hits.
+pub static QUERY_CACHE_HITS: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "nellie_query_cache_hits_total",
+        "Number of query embeddings served from cache"
+    )
+    .unwrap()
+});
+
+/// Query embedding cache misses.
+pub static QUERY_CACHE_MISSES: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "nellie_query_cache_misses_total",
+        "Number of query embeddings computed after a cache miss"
+    )
+    .unwrap()
+});
+
+/// Embedding inputs cut to the model's maximum sequence length.
+pub static EMBEDDINGS_TRUNCATED_TOTAL: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "nellie_embeddings_truncated_total",
+        "Number of embedding inputs truncated to the model's maximum sequence length"
+    )
+    .unwrap()
+});
+
+/// Index request queue depth.
+pub static INDEX_QUEUE_DEPTH: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "nellie_index_queue_depth",
+        "Number of files waiting in the index request queue"
+    )
+    .unwrap()
+});
+
+/// Index requests evicted from a full queue.
+pub static INDEX_QUEUE_DROPPED_TOTAL: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "nellie_index_queue_dropped_total",
+        "Number of index requests dropped because the queue was full"
+    )
+    .unwrap()
+});
+
+/// Index request sends that waited for queue space.
+pub static INDEX_QUEUE_BLOCKED_SENDS_TOTAL: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "nellie_index_queue_blocked_sends_total",
+        "Number of index requests that waited for space in a full queue"
+    )
+    .unwrap()
+});
+
 /// Initialize all metrics (call once at startup).
 pub fn init_metrics() {
     // Access lazy statics to register them
     let _ = &*CHUNKS_TOTAL;
     let _ = &*LESSONS_TOTAL;
     let _ = &*FILES_TOTAL;
+    let _ = &*CHECKPOINTS_TOTAL;
+    let _ = &*AGENTS_IN_PROGRESS;
     let _ = &*REQUEST_LATENCY;
     let _ = &*REQUEST_COUNT;
     let _ = &*EMBEDDING_QUEUE_DEPTH;
+    let _ = &*QUERY_CACHE_HITS;
+    let _ = &*QUERY_CACHE_MISSES;
+    let _ = &*EMBEDDINGS_TRUNCATED_TOTAL;
+    let _ = &*INDEX_QUEUE_DEPTH;
+    let _ = &*INDEX_QUEUE_DROPPED_TOTAL;
+    let _ = &*INDEX_QUEUE_BLOCKED_SENDS_TOTAL;
 
     tracing::debug!("Prometheus metrics initialized");
 }
 
+/// Set the database-backed gauges from current row counts.
+///
+/// # Errors
+///
+/// Returns an error if any count query fails; gauges counted before the
+/// failure keep their new values.
+pub fn refresh_db_gauges(db: &Database) -> Result<()> {
+    db.with_conn(|conn| {
+        CHUNKS_TOTAL.set(crate::storage::count_chunks(conn)?);
+        LESSONS_TOTAL.set(crate::storage::count_lessons(conn)?);
+        FILES_TOTAL.set(crate::storage::count_tracked_files(conn)?);
+        CHECKPOINTS_TOTAL.set(crate::storage::count_all_checkpoints(conn)?);
+        AGENTS_IN_PROGRESS.set(crate::storage::count_agents_in_progress(conn)?);
+        Ok(())
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -78,4 +181,16 @@ mod tests {
         LESSONS_TOTAL.set(50);
         assert_eq!(LESSONS_TOTAL.get(), 50);
     }
+
+    #[test]
+    fn test_refresh_db_gauges() {
+        init_metrics();
+        let db = Database::open_in_memory().unwrap();
+        db.with_conn(|conn| crate::storage::migrate(conn)).unwrap();
+        CHECKPOINTS_TOTAL.set(7);
+
+        refresh_db_gauges(&db).unwrap();
+        assert_eq!(CHECKPOINTS_TOTAL.get(), 0);
+        assert_eq!(AGENTS_IN_PROGRESS.get(), 0);
+    }
 }
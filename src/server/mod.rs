@@ -4,25 +4,40 @@
 //! - MCP server using rmcp with StreamableHttpService
 //! - REST API using axum
 //! - Health and metrics endpoints
+//! - OpenAPI document and optional Swagger UI
 //! - API key authentication middleware
 //! - Graceful shutdown coordination
 //! - Structured logging and tracing observability
 
 mod app;
 mod auth;
+mod indexing;
 mod mcp;
 mod mcp_transport;
 mod metrics;
 pub mod observability;
+mod openapi;
 mod rest;
 mod sse;
 
 pub use app::{App, ServerConfig};
 pub use auth::ApiKeyConfig;
-pub use mcp::{create_mcp_router, get_tools, McpState, ToolRequest, ToolResponse};
+pub use indexing::DEFAULT_INDEX_CONCURRENCY;
+pub use mcp::{
+    create_mcp_router, get_tools, invoke_tool_direct, McpState, ToolErrorCode, ToolRequest,
+    ToolResponse, DEFAULT_LESSON_DUPLICATE_THRESHOLD, DEFAULT_MAX_CHECKPOINT_STATE_BYTES,
+    DEFAULT_MAX_CHECKPOINT_STATE_DEPTH, DEFAULT_MAX_SEARCH_LIMIT, DEFAULT_MIN_FREE_DISK_BYTES,
+    DEFAULT_RECENCY_HALF_LIFE_DAYS, DEFAULT_SLOW_QUERY_MS,
+};
 pub use mcp_transport::{start_mcp_server, McpTransportConfig, NellieMcpHandler};
-pub use metrics::{init_metrics, CHUNKS_TOTAL, EMBEDDING_QUEUE_DEPTH, FILES_TOTAL, LESSONS_TOTAL};
-pub use observability::init_tracing;
+pub use metrics::{
+    init_metrics, refresh_db_gauges, AGENTS_IN_PROGRESS, CHECKPOINTS_TOTAL, CHUNKS_TOTAL,
+    DEFAULT_METRICS_REFRESH_SECS, EMBEDDINGS_TRUNCATED_TOTAL, EMBEDDING_QUEUE_DEPTH, FILES_TOTAL,
+    INDEX_QUEUE_BLOCKED_SENDS_TOTAL, INDEX_QUEUE_DEPTH, INDEX_QUEUE_DROPPED_TOTAL, LESSONS_TOTAL,
+    QUERY_CACHE_HITS, QUERY_CACHE_MISSES,
+};
+pub use observability::{init_tracing, shutdown_tracing};
+pub use openapi::{create_openapi_router, openapi_document};
 pub use rest::{create_rest_router, HealthResponse};
 pub use sse::create_sse_router;
 
@@ -5,7 +5,10 @@
 //! - Request tracing middleware integration
 //! - Configurable log levels
 //! - Span propagation for distributed tracing
+//! - Optional OTLP trace export to an OpenTelemetry collector
 
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::WithExportConfig;
 use tracing_subscriber::{
     filter::EnvFilter, fmt, layer::SubscriberExt, util::SubscriberInitExt, Registry,
 };
@@ -17,6 +20,8 @@ pub struct TracingConfig {
     pub level: String,
     /// Enable JSON output format
     pub json: bool,
+    /// OTLP collector endpoint (e.g. `http://localhost:4317`); export is disabled if unset
+    pub otlp_endpoint: Option<String>,
 }
 
 impl Default for TracingConfig {
@@ -24,6 +29,7 @@ impl Default for TracingConfig {
         Self {
             level: "info".to_string(),
             json: false,
+            otlp_endpoint: None,
         }
     }
 }
@@ -34,18 +40,30 @@ impl Default for TracingConfig {
 /// - Configured log level from environment or config
 /// - Structured logging output (plain text or JSON)
 /// - Request tracing spans
+/// - OTLP span export when `otlp_endpoint` is set
 /// - Proper error handling
 ///
+/// Must be called from within a Tokio runtime when `otlp_endpoint` is set,
+/// since the batch exporter runs on it.
+///
 /// # Arguments
 ///
-/// * `config` - Tracing configuration options
+/// * `level` - Log level filter
+/// * `json` - Enable JSON output format
+/// * `otlp_endpoint` - Optional OTLP collector endpoint
 ///
 /// # Panics
 ///
 /// Panics if tracing subscriber has already been initialized in this process.
-pub fn init_tracing(level: &str, json: bool) {
+pub fn init_tracing(level: &str, json: bool, otlp_endpoint: Option<&str>) {
     let env_filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(level));
 
+    // Build the exporter before the subscriber exists, so report failures afterwards
+    let otlp = otlp_endpoint.map(|endpoint| (endpoint, init_otlp_tracer(endpoint)));
+    let tracer = otlp
+        .as_ref()
+        .and_then(|(_, result)| result.as_ref().ok().cloned());
+
     if json {
         let json_layer = fmt::layer()
             .json()
@@ -54,8 +72,13 @@ pub fn init_tracing(level: &str, json: bool) {
             .with_thread_names(true)
             .with_file(true)
             .with_line_number(true);
+        let otel_layer = tracer.map(|t| tracing_opentelemetry::layer().with_tracer(t));
 
-        Registry::default().with(env_filter).with(json_layer).init();
+        Registry::default()
+            .with(env_filter)
+            .with(json_layer)
+            .with(otel_layer)
+            .init();
     } else {
         let fmt_layer = fmt::layer()
             .with_target(true)
@@ -63,18 +86,62 @@ pub fn init_tracing(level: &str, json: bool) {
             .with_thread_names(true)
             .with_file(true)
             .with_line_number(true);
+        let otel_layer = tracer.map(|t| tracing_opentelemetry::layer().with_tracer(t));
 
-        Registry::default().with(env_filter).with(fmt_layer).init();
+        Registry::default()
+            .with(env_filter)
+            .with(fmt_layer)
+            .with(otel_layer)
+            .init();
+    }
+
+    match otlp {
+        Some((endpoint, Ok(_))) => tracing::info!(endpoint, "OTLP trace export enabled"),
+        Some((endpoint, Err(e))) => {
+            tracing::warn!(endpoint, error = %e, "Failed to initialize OTLP exporter, traces will not be exported");
+        }
+        None => {}
     }
 
     tracing::debug!("Tracing initialized: level={}, json={}", level, json);
 }
 
+/// Build an OTLP (gRPC) tracer and install its provider globally.
+fn init_otlp_tracer(
+    endpoint: &str,
+) -> std::result::Result<opentelemetry_sdk::trace::Tracer, opentelemetry::trace::TraceError> {
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(endpoint)
+        .build()?;
+
+    let provider = opentelemetry_sdk::trace::TracerProvider::builder()
+        .with_batch_exporter(exporter, opentelemetry_sdk::runtime::Tokio)
+        .with_resource(opentelemetry_sdk::Resource::new(vec![
+            opentelemetry::KeyValue::new("service.name", "nellie"),
+            opentelemetry::KeyValue::new("service.version", env!("CARGO_PKG_VERSION")),
+        ]))
+        .build();
+
+    let tracer = provider.tracer("nellie");
+    opentelemetry::global::set_tracer_provider(provider);
+    Ok(tracer)
+}
+
+/// Flush and shut down the OTLP exporter, if one was installed.
+///
+/// Call before process exit so buffered spans are not lost. A no-op when
+/// OTLP export is disabled.
+pub fn shutdown_tracing() {
+    opentelemetry::global::shutdown_tracer_provider();
+}
+
 /// Get current tracing configuration from environment variables.
 ///
 /// Respects these environment variables:
 /// - `NELLIE_LOG_LEVEL` - Log level (default: "info")
 /// - `NELLIE_LOG_JSON` - Enable JSON output (default: false)
+/// - `NELLIE_OTLP_ENDPOINT` - OTLP collector endpoint (default: unset, export disabled)
 ///
 /// # Returns
 ///
@@ -85,8 +152,15 @@ pub fn config_from_env() -> TracingConfig {
     let json = std::env::var("NELLIE_LOG_JSON")
         .map(|v| matches!(v.to_lowercase().as_str(), "true" | "1" | "yes"))
         .unwrap_or(false);
+    let otlp_endpoint = std::env::var("NELLIE_OTLP_ENDPOINT")
+        .ok()
+        .filter(|v| !v.is_empty());
 
-    TracingConfig { level, json }
+    TracingConfig {
+        level,
+        json,
+        otlp_endpoint,
+    }
 }
 
 /// Span context for distributed tracing.
@@ -119,6 +193,8 @@ pub mod spans {
 
     /// Create a span for a tool invocation.
     ///
+    /// The `result` and `latency_ms` fields are recorded once the tool returns.
+    ///
     /// # Arguments
     ///
     /// * `tool_name` - Name of the tool being invoked
@@ -131,6 +207,8 @@ pub mod spans {
         info_span!(
             "tool_invocation",
             tool = %tool_name,
+            result = tracing::field::Empty,
+            latency_ms = tracing::field::Empty,
         )
     }
 
@@ -154,6 +232,72 @@ pub mod spans {
     }
 }
 
+/// Where a tool invocation's time went.
+///
+/// The database and embedding service record into the invocation being
+/// measured; outside [`timings::measure`] recording does nothing.
+pub mod timings {
+    use std::cell::Cell;
+    use std::future::Future;
+    use std::time::Duration;
+
+    /// Time spent generating embeddings and using the database.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub struct Breakdown {
+        pub embedding: Duration,
+        pub db: Duration,
+    }
+
+    impl Breakdown {
+        /// Which of embedding generation, database access or everything
+        /// else took the largest share of `total`.
+        #[must_use]
+        pub fn dominant(&self, total: Duration) -> &'static str {
+            let other = total.saturating_sub(self.embedding + self.db);
+            if self.embedding >= self.db && self.embedding >= other {
+                "embedding"
+            } else if self.db >= other {
+                "database"
+            } else {
+                "other"
+            }
+        }
+    }
+
+    tokio::task_local! {
+        static CURRENT: Cell<Breakdown>;
+    }
+
+    /// Run `future`, returning its output and the time it spent generating
+    /// embeddings and using the database.
+    pub async fn measure<F: Future>(future: F) -> (F::Output, Breakdown) {
+        CURRENT
+            .scope(Cell::new(Breakdown::default()), async move {
+                let output = future.await;
+                (output, CURRENT.with(Cell::get))
+            })
+            .await
+    }
+
+    /// Add embedding time to the current measurement, if any.
+    pub fn record_embedding(elapsed: Duration) {
+        record(|b| b.embedding += elapsed);
+    }
+
+    /// Add database time to the current measurement, if any.
+    pub fn record_db(elapsed: Duration) {
+        record(|b| b.db += elapsed);
+    }
+
+    fn record(add: impl FnOnce(&mut Breakdown)) {
+        let _ = CURRENT.try_with(|current| {
+            let mut breakdown = current.get();
+            add(&mut breakdown);
+            current.set(breakdown);
+        });
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -163,6 +307,7 @@ mod tests {
         let config = TracingConfig::default();
         assert_eq!(config.level, "info");
         assert!(!config.json);
+        assert!(config.otlp_endpoint.is_none());
     }
 
     #[test]
@@ -170,9 +315,14 @@ mod tests {
         let config = TracingConfig {
             level: "debug".to_string(),
             json: true,
+            otlp_endpoint: Some("http://localhost:4317".to_string()),
         };
         assert_eq!(config.level, "debug");
         assert!(config.json);
+        assert_eq!(
+            config.otlp_endpoint.as_deref(),
+            Some("http://localhost:4317")
+        );
     }
 
     #[test]
@@ -196,4 +346,26 @@ mod tests {
         let _guard = span.enter();
         assert!(true);
     }
+
+    #[tokio::test]
+    async fn test_timings_measure() {
+        use std::time::Duration;
+
+        let ((), breakdown) = timings::measure(async {
+            timings::record_db(Duration::from_millis(30));
+            tokio::task::yield_now().await;
+            timings::record_db(Duration::from_millis(20));
+            timings::record_embedding(Duration::from_millis(10));
+        })
+        .await;
+        assert_eq!(breakdown.db, Duration::from_millis(50));
+        assert_eq!(breakdown.embedding, Duration::from_millis(10));
+        assert_eq!(breakdown.dominant(Duration::from_millis(70)), "database");
+        assert_eq!(breakdown.dominant(Duration::from_millis(500)), "other");
+
+        // Recording outside a measurement is a no-op
+        timings::record_db(Duration::from_secs(1));
+        let ((), breakdown) = timings::measure(async {}).await;
+        assert_eq!(breakdown, timings::Breakdown::default());
+    }
 }
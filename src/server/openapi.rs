@@ -0,0 +1,266 @@
+//! OpenAPI description of the REST API.
+//!
+//! The document is derived with `utoipa` from the `#[utoipa::path]`
+//! annotations on the REST and HTTP tool handlers and the `ToSchema`
+//! derives on their response types; add new routes to [`ApiDoc`]. The
+//! `/api/v1/search/code` request schema is taken from the `search_code`
+//! tool, which accepts the same arguments.
+
+use axum::{routing::get, Json, Router};
+use utoipa::openapi::schema::Schema;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::openapi::{OpenApi as OpenApiDocument, RefOr};
+use utoipa::{Modify, OpenApi, PartialSchema, ToSchema};
+use utoipa_swagger_ui::SwaggerUi;
+
+use super::mcp::{self, get_tools};
+use super::rest;
+use crate::storage::DuplicateChunk;
+
+/// Path of the Swagger UI; its assets are served beneath it.
+const DOCS_PATH: &str = "/docs";
+
+/// Whether `path` is part of the API description, served without an API
+/// key so the docs page can load the spec and its assets.
+pub(super) fn is_public_doc_path(path: &str) -> bool {
+    path == "/openapi.json"
+        || path
+            .strip_prefix(DOCS_PATH)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Create the router serving `/openapi.json`, plus the Swagger UI at
+/// `/docs` when `docs_ui` is set.
+///
+/// The Swagger UI assets are compiled into the binary rather than loaded
+/// from a CDN.
+pub fn create_openapi_router(docs_ui: bool) -> Router {
+    if docs_ui {
+        SwaggerUi::new(DOCS_PATH)
+            .url("/openapi.json", ApiDoc::build())
+            .into()
+    } else {
+        Router::new().route("/openapi.json", get(|| async { Json(ApiDoc::build()) }))
+    }
+}
+
+/// Build the OpenAPI document for the REST and HTTP tool endpoints.
+#[must_use]
+pub fn openapi_document() -> serde_json::Value {
+    serde_json::to_value(ApiDoc::build()).unwrap_or_default()
+}
+
+/// The REST and HTTP tool API.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Nellie",
+        description = "Semantic code memory: code search, lessons and agent checkpoints."
+    ),
+    paths(
+        rest::health_check,
+        rest::readiness_check,
+        rest::metrics,
+        rest::status,
+        rest::stats,
+        rest::search_code,
+        mcp::list_tools,
+        mcp::invoke_tool,
+    ),
+    components(schemas(CodeSearchRequest, CodeResult)),
+    modifiers(&Security, &ReadyAlias),
+    security(("bearerAuth" = []), ("apiKeyHeader" = []))
+)]
+struct ApiDoc;
+
+impl ApiDoc {
+    /// The derived document with the package version filled in.
+    fn build() -> OpenApiDocument {
+        let mut doc = Self::openapi();
+        doc.info.version = env!("CARGO_PKG_VERSION").to_string();
+        doc
+    }
+}
+
+/// Registers the two ways of passing the API key.
+struct Security;
+
+impl Modify for Security {
+    fn modify(&self, openapi: &mut OpenApiDocument) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearerAuth",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+        components.add_security_scheme(
+            "apiKeyHeader",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-API-Key"))),
+        );
+    }
+}
+
+/// Documents `/health/ready`, which serves the `/ready` handler.
+struct ReadyAlias;
+
+impl Modify for ReadyAlias {
+    fn modify(&self, openapi: &mut OpenApiDocument) {
+        let Some(mut alias) = openapi.paths.paths.get("/ready").cloned() else {
+            return;
+        };
+        alias.summary = Some("Same as /ready".to_string());
+        openapi.paths.paths.insert("/health/ready".to_string(), alias);
+    }
+}
+
+/// Arguments of `POST /api/v1/search/code`: the `search_code` tool's input
+/// schema.
+pub(super) struct CodeSearchRequest;
+
+impl PartialSchema for CodeSearchRequest {
+    fn schema() -> RefOr<Schema> {
+        get_tools()
+            .into_iter()
+            .find(|tool| tool.name == "search_code")
+            .and_then(|tool| serde_json::from_value(tool.input_schema).ok())
+            .unwrap_or_else(|| RefOr::T(Schema::Object(utoipa::openapi::Object::new())))
+    }
+}
+
+impl ToSchema for CodeSearchRequest {}
+
+/// A `search_code` result, as built by `CodeSearch::hit_json`.
+///
+/// Results are assembled as JSON, so this type only describes them.
+#[derive(ToSchema)]
+#[allow(dead_code)]
+pub(super) struct CodeResult {
+    id: i64,
+    file_path: String,
+    repo: Option<String>,
+    chunk_index: i32,
+    start_line: i32,
+    end_line: i32,
+    /// Omitted when `include_content` is false
+    content: Option<String>,
+    /// Present when `max_content_chars` is set
+    truncated: Option<bool>,
+    /// Set when the chunk's text is neither stored nor readable from disk
+    content_unavailable: Option<bool>,
+    language: Option<String>,
+    git_commit: Option<String>,
+    git_branch: Option<String>,
+    /// Similarity 0.0-1.0; null for symbol matches
+    score: Option<f32>,
+    distance: Option<f32>,
+    /// Other chunks with identical content; only when the server
+    /// deduplicates chunks
+    duplicates: Option<Vec<DuplicateChunk>>,
+    /// Retriever, rank and scores; only with `debug: true`
+    debug: Option<serde_json::Value>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use tower::ServiceExt;
+
+    /// Every route the REST and HTTP tool routers serve.
+    const DOCUMENTED_ROUTES: &[&str] = &[
+        "/health",
+        "/ready",
+        "/health/ready",
+        "/metrics",
+        "/api/v1/status",
+        "/api/v1/stats",
+        "/api/v1/search/code",
+        "/mcp/tools",
+        "/mcp/invoke",
+    ];
+
+    #[test]
+    fn test_document_covers_routes() {
+        let doc = openapi_document();
+        assert!(doc["openapi"].as_str().unwrap().starts_with("3."));
+        assert_eq!(doc["info"]["version"], env!("CARGO_PKG_VERSION"));
+
+        let paths = doc["paths"].as_object().unwrap();
+        let mut documented: Vec<&str> = paths.keys().map(String::as_str).collect();
+        let mut routes = DOCUMENTED_ROUTES.to_vec();
+        documented.sort_unstable();
+        routes.sort_unstable();
+        assert_eq!(documented, routes);
+
+        // Every reference resolves
+        let text = doc.to_string();
+        let schemas = doc["components"]["schemas"].as_object().unwrap();
+        for reference in text.split("#/components/schemas/").skip(1) {
+            let name = reference.split('"').next().unwrap();
+            assert!(schemas.contains_key(name), "missing schema {name}");
+        }
+
+        let search = &schemas["CodeSearchRequest"]["properties"];
+        assert!(search["query"].is_object());
+        assert!(search["include_content"].is_object());
+
+        let schemes = doc["components"]["securitySchemes"].as_object().unwrap();
+        assert!(schemes.contains_key("bearerAuth"));
+        assert!(schemes.contains_key("apiKeyHeader"));
+        assert_eq!(doc["paths"]["/health"]["get"]["security"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_public_doc_paths() {
+        for path in ["/openapi.json", "/docs", "/docs/", "/docs/swagger-ui.css"] {
+            assert!(is_public_doc_path(path), "{path}");
+        }
+        for path in ["/docsx", "/api/v1/status", "/openapi.json/x"] {
+            assert!(!is_public_doc_path(path), "{path}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_serves_document_and_gated_docs_ui() {
+        let response = create_openapi_router(false)
+            .oneshot(
+                Request::builder()
+                    .uri("/openapi.json")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let docs = || Request::builder().uri("/docs/").body(Body::empty()).unwrap();
+        let response = create_openapi_router(false).oneshot(docs()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = create_openapi_router(true).oneshot(docs()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Assets come from the binary, not a CDN
+        let response = create_openapi_router(true)
+            .oneshot(
+                Request::builder()
+                    .uri("/docs/swagger-ui.css")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = create_openapi_router(true)
+            .oneshot(
+                Request::builder()
+                    .uri("/openapi.json")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
@@ -2,31 +2,124 @@
 
 use std::sync::Arc;
 
-use axum::{extract::State, http::StatusCode, response::IntoResponse, routing::get, Json, Router};
+use axum::{
+    body::Body,
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
 use prometheus::{Encoder, TextEncoder};
 use serde::Serialize;
 
-use super::mcp::McpState;
+use super::mcp::{run_search_code, storage_report, CodeSearchMode, McpState, StorageReport};
+use super::openapi::{CodeResult, CodeSearchRequest};
+use crate::embeddings::{EmbeddingService, InitState};
+use crate::watcher::{IndexerStatsSnapshot, ScanStatsSnapshot, WatcherStatsSnapshot};
+
+/// Content type for newline-delimited JSON responses.
+const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
 
 /// Health check response.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, utoipa::ToSchema)]
 pub struct HealthResponse {
+    /// `healthy` or `unhealthy`
     pub status: String,
     pub version: String,
+    /// `ok` or `error`
+    pub database: String,
+}
+
+/// Readiness check response.
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct ReadyResponse {
+    pub ready: bool,
+    /// `ok` or `error`
     pub database: String,
+    /// `ready`, `warming`, `failed` or `disabled`
+    pub embeddings: String,
+    /// Load progress of the embedding model, when one is configured.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub embedding_init: Option<InitState>,
+    pub storage: StorageReport,
+}
+
+/// Status response: version and record counts.
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub(super) struct StatusResponse {
+    version: &'static str,
+    stats: StatusCounts,
+}
+
+/// Record counts reported by `/api/v1/status`.
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub(super) struct StatusCounts {
+    indexed_chunks: i64,
+    lessons: i64,
+    tracked_files: i64,
+}
+
+/// Live counters reported by `/api/v1/stats`.
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub(super) struct StatsResponse {
+    watcher: WatcherStatsSnapshot,
+    scanner: ScanStatsSnapshot,
+    indexer: IndexerStatsSnapshot,
+    index_queue_depth: usize,
+    embeddings: EmbeddingStats,
+}
+
+/// Embedding service state reported by `/api/v1/stats`.
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub(super) struct EmbeddingStats {
+    initialized: bool,
+    queue_depth: usize,
+}
+
+/// Code search response.
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub(super) struct CodeSearchResponse {
+    #[schema(value_type = Vec<CodeResult>)]
+    results: Vec<serde_json::Value>,
+    query: Option<String>,
+    symbol: Option<String>,
+    limit: usize,
+    count: usize,
+    mode: CodeSearchMode,
+}
+
+/// Error response.
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub(super) struct ErrorResponse {
+    error: String,
 }
 
 /// Create REST API router.
 pub fn create_rest_router(state: Arc<McpState>) -> Router {
     Router::new()
         .route("/health", get(health_check))
+        .route("/ready", get(readiness_check))
+        .route("/health/ready", get(readiness_check))
         .route("/metrics", get(metrics))
         .route("/api/v1/status", get(status))
+        .route("/api/v1/stats", get(stats))
+        .route("/api/v1/search/code", post(search_code))
         .with_state(state)
 }
 
 /// Health check endpoint.
-async fn health_check(State(state): State<Arc<McpState>>) -> impl IntoResponse {
+#[utoipa::path(
+    get,
+    path = "/health",
+    summary = "Liveness check",
+    security(),
+    responses(
+        (status = 200, description = "Healthy", body = HealthResponse),
+        (status = 503, description = "Database unavailable", body = HealthResponse),
+    )
+)]
+pub(super) async fn health_check(State(state): State<Arc<McpState>>) -> impl IntoResponse {
     let db_status = match state.db.health_check() {
         Ok(()) => "ok",
         Err(e) => {
@@ -57,8 +150,79 @@ async fn health_check(State(state): State<Arc<McpState>>) -> impl IntoResponse {
     (status_code, Json(response))
 }
 
+/// Readiness endpoint.
+///
+/// Unlike `/health`, this reports 503 until the embedding model has been
+/// loaded and warmed up, so load balancers don't route searches to a server
+/// that would stall on its first inference. A model that failed to load
+/// keeps the check failing, and `embedding_init` says how far loading got
+/// so a slow load can be told apart from a failed one. The body also reports
+/// database size and free disk space; low disk space is flagged but doesn't
+/// fail the check. Served at both `/ready` and `/health/ready`.
+#[utoipa::path(
+    get,
+    path = "/ready",
+    summary = "Readiness check; 503 until the embedding model is warmed up",
+    responses(
+        (status = 200, description = "Ready", body = ReadyResponse),
+        (status = 503, description = "Not ready", body = ReadyResponse),
+    )
+)]
+pub(super) async fn readiness_check(State(state): State<Arc<McpState>>) -> impl IntoResponse {
+    let database = if state.db.health_check().is_ok() {
+        "ok"
+    } else {
+        "error"
+    };
+
+    let embedding_init = state.embeddings.as_ref().map(EmbeddingService::init_state);
+    let embeddings = match &embedding_init {
+        Some(InitState::Ready) => "ready",
+        Some(InitState::Failed(_)) => "failed",
+        Some(_) => "warming",
+        None => "disabled",
+    };
+
+    let ready = database == "ok" && matches!(embeddings, "ready" | "disabled");
+    let status_code = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    tracing::debug!(status = ?status_code, database, embeddings, "Readiness check");
+
+    (
+        status_code,
+        Json(ReadyResponse {
+            ready,
+            database: database.to_string(),
+            embeddings: embeddings.to_string(),
+            embedding_init,
+            storage: storage_report(&state),
+        }),
+    )
+}
+
 /// Prometheus metrics endpoint.
-async fn metrics(State(_state): State<Arc<McpState>>) -> impl IntoResponse {
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    summary = "Prometheus metrics",
+    responses((
+        status = 200,
+        description = "Prometheus text exposition format",
+        body = String,
+        content_type = "text/plain"
+    ))
+)]
+pub(super) async fn metrics(State(state): State<Arc<McpState>>) -> impl IntoResponse {
+    // The queue depth gauges are sampled rather than tracked on every send
+    if let Some(embeddings) = &state.embeddings {
+        embeddings.queue_depth().await;
+    }
+    state.index_queue();
+
     let encoder = TextEncoder::new();
     let metric_families = prometheus::gather();
 
@@ -90,7 +254,13 @@ async fn metrics(State(_state): State<Arc<McpState>>) -> impl IntoResponse {
 }
 
 /// Status endpoint with statistics.
-async fn status(State(state): State<Arc<McpState>>) -> impl IntoResponse {
+#[utoipa::path(
+    get,
+    path = "/api/v1/status",
+    summary = "Version and record counts",
+    responses((status = 200, description = "Status", body = StatusResponse))
+)]
+pub(super) async fn status(State(state): State<Arc<McpState>>) -> impl IntoResponse {
     let chunk_count = state
         .db
         .with_conn(crate::storage::count_chunks)
@@ -122,21 +292,120 @@ async fn status(State(state): State<Arc<McpState>>) -> impl IntoResponse {
         "Status retrieved"
     );
 
-    Json(serde_json::json!({
-        "version": env!("CARGO_PKG_VERSION"),
-        "stats": {
-            "indexed_chunks": chunk_count,
-            "lessons": lesson_count,
-            "tracked_files": file_count
+    Json(StatusResponse {
+        version: env!("CARGO_PKG_VERSION"),
+        stats: StatusCounts {
+            indexed_chunks: chunk_count,
+            lessons: lesson_count,
+            tracked_files: file_count,
+        },
+    })
+}
+
+/// Live watcher, scanner and indexer counters.
+///
+/// A lightweight alternative to `/metrics` for dashboards and scripts. The
+/// counters are in-memory and reset on restart; `get_stats` reports the
+/// persisted lifetime totals.
+#[utoipa::path(
+    get,
+    path = "/api/v1/stats",
+    summary = "Live watcher, scanner and indexer counters and queue depths",
+    responses((status = 200, description = "Counters since startup", body = StatsResponse))
+)]
+pub(super) async fn stats(State(state): State<Arc<McpState>>) -> impl IntoResponse {
+    let embedding_queue_depth = match &state.embeddings {
+        Some(embeddings) => embeddings.queue_depth().await,
+        None => 0,
+    };
+
+    Json(StatsResponse {
+        watcher: state.watcher_stats.snapshot(),
+        scanner: state.scan_stats.snapshot(),
+        indexer: state.indexer_stats(),
+        index_queue_depth: state.index_queue_depth(),
+        embeddings: EmbeddingStats {
+            initialized: state
+                .embeddings
+                .as_ref()
+                .is_some_and(EmbeddingService::is_initialized),
+            queue_depth: embedding_queue_depth,
+        },
+    })
+}
+
+/// Code search endpoint.
+///
+/// Takes the same arguments as the `search_code` MCP tool. Responds with the
+/// usual JSON object by default; with `Accept: application/x-ndjson` the
+/// results are streamed one object per line instead, so large result sets
+/// are serialized incrementally rather than buffered.
+#[utoipa::path(
+    post,
+    path = "/api/v1/search/code",
+    summary = "Code search, with the same arguments as the search_code tool",
+    request_body = CodeSearchRequest,
+    responses(
+        (
+            status = 200,
+            description = "Matching chunks; with Accept: application/x-ndjson, one CodeResult per line",
+            content(
+                (CodeSearchResponse = "application/json"),
+                (CodeResult = "application/x-ndjson"),
+            )
+        ),
+        (status = 400, description = "Invalid arguments or search failure", body = ErrorResponse),
+    )
+)]
+pub(super) async fn search_code(
+    State(state): State<Arc<McpState>>,
+    headers: HeaderMap,
+    Json(args): Json<serde_json::Value>,
+) -> Response {
+    let mut search = match run_search_code(&state, &args).await {
+        Ok(search) => search,
+        Err(e) => {
+            return (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: e })).into_response();
         }
-    }))
+    };
+
+    let wants_ndjson = headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|accept| accept.contains(NDJSON_CONTENT_TYPE));
+
+    if !wants_ndjson {
+        let results: Vec<serde_json::Value> =
+            search.hits.iter().map(|hit| search.hit_json(hit)).collect();
+        return Json(CodeSearchResponse {
+            count: results.len(),
+            results,
+            query: search.query,
+            symbol: search.symbol,
+            limit: search.limit,
+            mode: search.mode,
+        })
+        .into_response();
+    }
+
+    let hits = std::mem::take(&mut search.hits);
+    let lines = futures::stream::iter(hits.into_iter().map(move |hit| {
+        let mut line = search.hit_json(&hit).to_string();
+        line.push('\n');
+        Ok::<_, std::convert::Infallible>(line)
+    }));
+
+    (
+        [(header::CONTENT_TYPE, NDJSON_CONTENT_TYPE)],
+        Body::from_stream(lines),
+    )
+        .into_response()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::storage::{migrate, Database};
-    use axum::body::Body;
     use axum::http::Request;
     use tower::ServiceExt;
 
@@ -164,6 +433,83 @@ mod tests {
         assert_eq!(response.status(), StatusCode::OK);
     }
 
+    #[tokio::test]
+    async fn test_ready_without_embeddings() {
+        let state = create_test_state();
+        let app = create_rest_router(state);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/ready")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_ready_while_warming() {
+        let db = Database::open_in_memory().unwrap();
+        db.with_conn(|conn| migrate(conn)).unwrap();
+        let embeddings = crate::embeddings::EmbeddingService::new(
+            crate::embeddings::EmbeddingConfig::from_data_dir("/tmp", 1),
+        );
+        let app = create_rest_router(Arc::new(McpState::with_embeddings(db, embeddings)));
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/ready")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["embeddings"], "warming");
+        assert_eq!(json["embedding_init"]["state"], "not_started");
+    }
+
+    #[tokio::test]
+    async fn test_ready_after_embedding_load_failure() {
+        let db = Database::open_in_memory().unwrap();
+        db.with_conn(|conn| migrate(conn)).unwrap();
+        let embeddings = crate::embeddings::EmbeddingService::new(
+            crate::embeddings::EmbeddingConfig::from_data_dir("/nonexistent", 1),
+        );
+        assert!(embeddings.init().await.is_err());
+        let app = create_rest_router(Arc::new(McpState::with_embeddings(db, embeddings)));
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/ready")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["ready"], false);
+        assert_eq!(json["embeddings"], "failed");
+        assert_eq!(json["embedding_init"]["state"], "failed");
+        assert!(json["embedding_init"]["reason"].as_str().is_some());
+    }
+
     #[tokio::test]
     async fn test_metrics() {
         let state = create_test_state();
@@ -199,4 +545,161 @@ mod tests {
 
         assert_eq!(response.status(), StatusCode::OK);
     }
+
+    #[tokio::test]
+    async fn test_stats_reports_seeded_counters() {
+        use std::sync::atomic::Ordering;
+
+        let state = create_test_state();
+        let watcher = &state.watcher_stats;
+        watcher.files_detected.store(7, Ordering::Relaxed);
+        watcher.files_indexed.store(5, Ordering::Relaxed);
+        watcher.errors.store(1, Ordering::Relaxed);
+        state.scan_stats.files_found.store(40, Ordering::Relaxed);
+        state.scan_stats.files_skipped.store(12, Ordering::Relaxed);
+
+        let (index_tx, _index_rx) =
+            crate::watcher::index_queue(10, crate::watcher::OverflowPolicy::Block);
+        state.set_index_queue(index_tx.monitor());
+        for name in ["a.rs", "b.rs", "c.rs"] {
+            index_tx
+                .send(crate::watcher::IndexRequest {
+                    path: name.into(),
+                    language: None,
+                    repo: None,
+                })
+                .await
+                .unwrap();
+        }
+
+        let response = create_rest_router(state)
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v1/stats")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["watcher"]["files_detected"], 7);
+        assert_eq!(json["watcher"]["files_indexed"], 5);
+        assert_eq!(json["watcher"]["errors"], 1);
+        assert_eq!(json["scanner"]["files_found"], 40);
+        assert_eq!(json["scanner"]["files_skipped"], 12);
+        assert_eq!(json["indexer"]["files_indexed"], 0);
+        assert_eq!(json["index_queue_depth"], 3);
+        assert_eq!(json["embeddings"]["initialized"], false);
+        assert_eq!(json["embeddings"]["queue_depth"], 0);
+    }
+
+    fn create_search_state() -> Arc<McpState> {
+        let db = Database::open_in_memory().unwrap();
+        db.with_conn(|conn| -> crate::Result<()> {
+            migrate(conn)?;
+            for i in 0..5 {
+                let chunk = crate::storage::ChunkRecord::new(
+                    format!("/src/file{i}.rs"),
+                    0,
+                    1,
+                    3,
+                    "fn parse_config() {}",
+                    "h",
+                )
+                .with_language("rust");
+                crate::storage::insert_chunk(conn, &chunk)?;
+            }
+            Ok(())
+        })
+        .unwrap();
+        Arc::new(McpState::new(db))
+    }
+
+    #[tokio::test]
+    async fn test_search_code_json() {
+        let app = create_rest_router(create_search_state());
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/v1/search/code")
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(r#"{"symbol": "parse_config", "limit": 3}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["count"], 3);
+    }
+
+    #[tokio::test]
+    async fn test_search_code_ndjson() {
+        let app = create_rest_router(create_search_state());
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/v1/search/code")
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .header(header::ACCEPT, NDJSON_CONTENT_TYPE)
+                    .body(Body::from(r#"{"symbol": "parse_config", "limit": 5}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            NDJSON_CONTENT_TYPE
+        );
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let text = String::from_utf8(body.to_vec()).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 5);
+        for line in lines {
+            let result: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert!(result["file_path"]
+                .as_str()
+                .unwrap()
+                .starts_with("/src/file"));
+            assert_eq!(result["content"], "fn parse_config() {}");
+            assert!(result["score"].is_null());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_search_code_bad_request() {
+        let app = create_rest_router(create_test_state());
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/v1/search/code")
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from("{}"))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
 }
@@ -18,10 +18,10 @@ use std::{
     sync::Arc,
     time::Duration,
 };
-use tokio::sync::{mpsc, RwLock};
+use tokio::sync::{broadcast, mpsc, RwLock};
 use tokio_stream::{wrappers::ReceiverStream, StreamExt};
 
-use super::mcp::{get_tools, McpState, ToolRequest};
+use super::mcp::{McpState, ToolRequest};
 
 type SessionId = String;
 type Sessions = Arc<RwLock<HashMap<SessionId, mpsc::Sender<SseMessage>>>>;
@@ -103,6 +103,32 @@ async fn sse_handler(
     
     let (tx, rx) = mpsc::channel::<SseMessage>(64);
     
+    // Forward completed indexing runs to this session
+    let mut index_events = state.mcp_state.subscribe_index_events();
+    let events_tx = tx.clone();
+    tokio::spawn(async move {
+        loop {
+            match index_events.recv().await {
+                Ok(payload) => {
+                    let notification = serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "method": "notifications/index_complete",
+                        "params": payload,
+                    });
+                    if events_tx
+                        .send(SseMessage::Notification(notification))
+                        .await
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+    
     // Store session
     state.sessions.write().await.insert(session_id.clone(), tx);
     
@@ -178,7 +204,7 @@ async fn handle_mcp_request(mcp_state: &McpState, request: JsonRpcRequest) -> Js
             result: Some(serde_json::json!({})),
             error: None,
         },
-        "tools/list" => handle_list_tools(),
+        "tools/list" => handle_list_tools(mcp_state),
         "tools/call" => handle_call_tool(mcp_state, &request.params).await,
         "ping" => Ok(serde_json::json!({})),
         _ => Err(JsonRpcError {
@@ -216,8 +242,8 @@ fn handle_initialize() -> Result<serde_json::Value, JsonRpcError> {
     }))
 }
 
-fn handle_list_tools() -> Result<serde_json::Value, JsonRpcError> {
-    let tools = get_tools();
+fn handle_list_tools(mcp_state: &McpState) -> Result<serde_json::Value, JsonRpcError> {
+    let tools = mcp_state.tools();
     Ok(serde_json::json!({ "tools": tools }))
 }
 
@@ -248,6 +274,10 @@ async fn handle_call_tool(
                 "type": "text",
                 "text": format!("Error: {}", err)
             }],
+            "structuredContent": {
+                "error": err,
+                "error_code": response.error_code
+            },
             "isError": true
         })),
         None => Ok(serde_json::json!({
@@ -8,6 +8,9 @@ use rusqlite::{params, Connection};
 use crate::error::StorageError;
 use crate::Result;
 
+/// Default age in seconds after which an agent's status is considered stale.
+pub const DEFAULT_AGENT_STALE_SECS: i64 = 60 * 60;
+
 /// Get current Unix timestamp as i64.
 #[inline]
 #[allow(clippy::cast_possible_wrap)]
@@ -63,6 +66,16 @@ pub struct AgentStatusInfo {
     pub checkpoint_count: i64,
 }
 
+impl AgentStatusInfo {
+    /// Check if the status hasn't been updated in more than `max_age_seconds`.
+    ///
+    /// Uses the same cutoff as [`cleanup_stale_statuses`].
+    #[must_use]
+    pub fn is_stale(&self, max_age_seconds: i64) -> bool {
+        self.last_updated < now_unix() - max_age_seconds
+    }
+}
+
 /// Get the current status of an agent.
 ///
 /// Returns the agent's status including whether they have work in progress.
@@ -518,4 +531,29 @@ mod tests {
         );
         assert_eq!(AgentStatus::parse("invalid"), None);
     }
+
+    #[test]
+    fn test_is_stale() {
+        let db = setup_db();
+
+        db.with_conn(|conn| {
+            mark_in_progress(conn, "fresh", Some("Task"))?;
+            mark_in_progress(conn, "dead", Some("Task"))?;
+            conn.execute(
+                "UPDATE agent_status SET last_updated = last_updated - 7200 WHERE agent = ?",
+                ["dead"],
+            )
+            .map_err(|e| StorageError::Database(e.to_string()))?;
+
+            assert!(!get_agent_status(conn, "fresh")?.is_stale(3600));
+            assert!(get_agent_status(conn, "dead")?.is_stale(3600));
+            assert!(!get_agent_status(conn, "dead")?.is_stale(10_000));
+
+            // cleanup_stale_statuses removes exactly the stale agents
+            assert_eq!(cleanup_stale_statuses(conn, 3600)?, 1);
+            assert_eq!(get_all_agent_statuses(conn)?.len(), 1);
+            Ok(())
+        })
+        .unwrap();
+    }
 }
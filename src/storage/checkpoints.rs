@@ -1,8 +1,9 @@
 //! Checkpoint storage operations.
 
-use rusqlite::{params, Connection};
+use rusqlite::{params, Connection, OptionalExtension};
 
-use super::models::CheckpointRecord;
+use super::idempotency::{find_by_key, release_expired_key, KeyedTable};
+use super::models::{CheckpointRecord, SessionSummary};
 use crate::error::StorageError;
 use crate::Result;
 
@@ -12,26 +13,66 @@ use crate::Result;
 ///
 /// Returns an error if the database operation fails.
 pub fn insert_checkpoint(conn: &Connection, checkpoint: &CheckpointRecord) -> Result<()> {
+    insert_checkpoint_row(conn, checkpoint, None)?;
+    Ok(())
+}
+
+/// Insert a checkpoint unless `idempotency_key` was already used.
+///
+/// Returns the original checkpoint's id if the key was used within
+/// [`IDEMPOTENCY_WINDOW_SECS`](super::IDEMPOTENCY_WINDOW_SECS); otherwise
+/// inserts `checkpoint` under the key and returns `None`.
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub fn insert_checkpoint_idempotent(
+    conn: &Connection,
+    checkpoint: &CheckpointRecord,
+    idempotency_key: &str,
+) -> Result<Option<String>> {
+    release_expired_key(conn, KeyedTable::Checkpoints, idempotency_key)?;
+    if insert_checkpoint_row(conn, checkpoint, Some(idempotency_key))? == 0 {
+        return find_by_key(conn, KeyedTable::Checkpoints, idempotency_key);
+    }
+    Ok(None)
+}
+
+/// Insert a checkpoint row, skipping it if `idempotency_key` is already taken.
+///
+/// Returns the number of rows inserted.
+fn insert_checkpoint_row(
+    conn: &Connection,
+    checkpoint: &CheckpointRecord,
+    idempotency_key: Option<&str>,
+) -> Result<usize> {
     let state_json = serde_json::to_string(&checkpoint.state)
         .map_err(|e| StorageError::Database(format!("failed to serialize state: {e}")))?;
 
-    conn.execute(
-        "INSERT INTO checkpoints (id, agent, repo, session_id, working_on, state, created_at)
-         VALUES (?, ?, ?, ?, ?, ?, ?)",
-        params![
-            checkpoint.id,
-            checkpoint.agent,
-            checkpoint.repo,
-            checkpoint.session_id,
-            checkpoint.working_on,
-            state_json,
-            checkpoint.created_at,
-        ],
-    )
-    .map_err(|e| StorageError::Database(format!("failed to insert checkpoint: {e}")))?;
+    let inserted = conn
+        .execute(
+            "INSERT INTO checkpoints (id, agent, repo, session_id, working_on, state, created_at, \
+             parent_id, idempotency_key)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+             ON CONFLICT(idempotency_key) DO NOTHING",
+            params![
+                checkpoint.id,
+                checkpoint.agent,
+                checkpoint.repo,
+                checkpoint.session_id,
+                checkpoint.working_on,
+                state_json,
+                checkpoint.created_at,
+                checkpoint.parent_id,
+                idempotency_key,
+            ],
+        )
+        .map_err(|e| StorageError::Database(format!("failed to insert checkpoint: {e}")))?;
 
-    tracing::trace!(id = %checkpoint.id, agent = %checkpoint.agent, "Inserted checkpoint");
-    Ok(())
+    if inserted > 0 {
+        tracing::trace!(id = %checkpoint.id, agent = %checkpoint.agent, "Inserted checkpoint");
+    }
+    Ok(inserted)
 }
 
 /// Get a checkpoint by ID.
@@ -41,7 +82,7 @@ pub fn insert_checkpoint(conn: &Connection, checkpoint: &CheckpointRecord) -> Re
 /// Returns an error if the checkpoint is not found or database operation fails.
 pub fn get_checkpoint(conn: &Connection, id: &str) -> Result<CheckpointRecord> {
     conn.query_row(
-        "SELECT id, agent, repo, session_id, working_on, state, created_at
+        "SELECT id, agent, repo, session_id, working_on, state, created_at, parent_id
          FROM checkpoints WHERE id = ?",
         [id],
         |row| {
@@ -56,6 +97,7 @@ pub fn get_checkpoint(conn: &Connection, id: &str) -> Result<CheckpointRecord> {
                 working_on: row.get(4)?,
                 state,
                 created_at: row.get(6)?,
+                parent_id: row.get(7)?,
             })
         },
     )
@@ -102,20 +144,37 @@ pub fn get_recent_checkpoints(
     conn: &Connection,
     agent: &str,
     limit: usize,
+) -> Result<Vec<CheckpointRecord>> {
+    get_recent_session_checkpoints(conn, agent, None, limit)
+}
+
+/// Get recent checkpoints for an agent, optionally only from one session.
+///
+/// Like [`get_recent_checkpoints`], with the session filter applied in the
+/// query so `limit` counts only matching checkpoints.
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub fn get_recent_session_checkpoints(
+    conn: &Connection,
+    agent: &str,
+    session_id: Option<&str>,
+    limit: usize,
 ) -> Result<Vec<CheckpointRecord>> {
     let limit_i64 = i64::try_from(limit).unwrap_or(0);
     let mut stmt = conn
         .prepare(
-            "SELECT id, agent, repo, session_id, working_on, state, created_at
+            "SELECT id, agent, repo, session_id, working_on, state, created_at, parent_id
              FROM checkpoints
-             WHERE agent = ?
-             ORDER BY created_at DESC
-             LIMIT ?",
+             WHERE agent = ?1 AND (?2 IS NULL OR session_id = ?2)
+             ORDER BY created_at DESC, id DESC
+             LIMIT ?3",
         )
         .map_err(|e| StorageError::Database(e.to_string()))?;
 
     let checkpoints = stmt
-        .query_map(params![agent, limit_i64], |row| {
+        .query_map(params![agent, session_id, limit_i64], |row| {
             let state_json: String = row.get(5)?;
             let state: serde_json::Value = serde_json::from_str(&state_json).unwrap_or_default();
 
@@ -127,6 +186,73 @@ pub fn get_recent_checkpoints(
                 working_on: row.get(4)?,
                 state,
                 created_at: row.get(6)?,
+                parent_id: row.get(7)?,
+            })
+        })
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    let mut result = Vec::new();
+    for cp in checkpoints {
+        result.push(cp.map_err(|e| StorageError::Database(e.to_string()))?);
+    }
+    Ok(result)
+}
+
+/// Get the checkpoints for an agent that come after `cursor` in
+/// [`get_recent_checkpoints`] order.
+///
+/// `cursor` is the id of the last checkpoint already seen. Returns up to
+/// `limit` older checkpoints, newest first, so paging from the first page
+/// walks an agent's whole history without gaps or repeats.
+///
+/// # Errors
+///
+/// Returns an error if `cursor` isn't one of the agent's checkpoints or the
+/// database operation fails.
+pub fn get_checkpoints_before(
+    conn: &Connection,
+    agent: &str,
+    cursor: &str,
+    limit: usize,
+) -> Result<Vec<CheckpointRecord>> {
+    let cursor_at: i64 = conn
+        .query_row(
+            "SELECT created_at FROM checkpoints WHERE id = ? AND agent = ?",
+            params![cursor, agent],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| StorageError::Database(e.to_string()))?
+        .ok_or_else(|| StorageError::NotFound {
+            entity: "checkpoint",
+            id: cursor.to_string(),
+        })?;
+
+    let limit_i64 = i64::try_from(limit).unwrap_or(0);
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, agent, repo, session_id, working_on, state, created_at, parent_id
+             FROM checkpoints
+             WHERE agent = ?1 AND (created_at < ?2 OR (created_at = ?2 AND id < ?3))
+             ORDER BY created_at DESC, id DESC
+             LIMIT ?4",
+        )
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    let checkpoints = stmt
+        .query_map(params![agent, cursor_at, cursor, limit_i64], |row| {
+            let state_json: String = row.get(5)?;
+            let state: serde_json::Value = serde_json::from_str(&state_json).unwrap_or_default();
+
+            Ok(CheckpointRecord {
+                id: row.get(0)?,
+                agent: row.get(1)?,
+                repo: row.get(2)?,
+                session_id: row.get(3)?,
+                working_on: row.get(4)?,
+                state,
+                created_at: row.get(6)?,
+                parent_id: row.get(7)?,
             })
         })
         .map_err(|e| StorageError::Database(e.to_string()))?;
@@ -155,10 +281,10 @@ pub fn get_checkpoints_since(
     let limit_i64 = i64::try_from(limit).unwrap_or(0);
     let mut stmt = conn
         .prepare(
-            "SELECT id, agent, repo, session_id, working_on, state, created_at
+            "SELECT id, agent, repo, session_id, working_on, state, created_at, parent_id
              FROM checkpoints
              WHERE agent = ? AND created_at >= ?
-             ORDER BY created_at DESC
+             ORDER BY created_at DESC, id DESC
              LIMIT ?",
         )
         .map_err(|e| StorageError::Database(e.to_string()))?;
@@ -176,6 +302,7 @@ pub fn get_checkpoints_since(
                 working_on: row.get(4)?,
                 state,
                 created_at: row.get(6)?,
+                parent_id: row.get(7)?,
             })
         })
         .map_err(|e| StorageError::Database(e.to_string()))?;
@@ -199,6 +326,131 @@ pub fn get_latest_checkpoint(conn: &Connection, agent: &str) -> Result<Option<Ch
     Ok(checkpoints.into_iter().next())
 }
 
+/// Get the most recent checkpoint saved under a session.
+///
+/// Returns `None` if the session has no checkpoints.
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub fn get_latest_session_checkpoint(
+    conn: &Connection,
+    session_id: &str,
+) -> Result<Option<CheckpointRecord>> {
+    let id: Option<String> = conn
+        .query_row(
+            "SELECT id FROM checkpoints WHERE session_id = ?
+             ORDER BY created_at DESC, id DESC LIMIT 1",
+            [session_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    id.map(|id| get_checkpoint(conn, &id)).transpose()
+}
+
+/// List an agent's sessions, most recently active first.
+///
+/// Checkpoints saved without a session are grouped into one summary whose
+/// `session_id` is `None`. Ties on the latest checkpoint's timestamp fall
+/// back to `id` order when picking its `working_on`.
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub fn list_sessions(conn: &Connection, agent: &str, limit: usize) -> Result<Vec<SessionSummary>> {
+    let limit_i64 = i64::try_from(limit).unwrap_or(i64::MAX);
+    let mut stmt = conn
+        .prepare(
+            "SELECT c.session_id, COUNT(*), MIN(c.created_at), MAX(c.created_at),
+                    (SELECT l.working_on FROM checkpoints l
+                     WHERE l.agent = c.agent AND l.session_id IS c.session_id
+                     ORDER BY l.created_at DESC, l.id DESC LIMIT 1)
+             FROM checkpoints c
+             WHERE c.agent = ?1
+             GROUP BY c.session_id
+             ORDER BY MAX(c.created_at) DESC, c.session_id
+             LIMIT ?2",
+        )
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    let sessions = stmt
+        .query_map(params![agent, limit_i64], |row| {
+            Ok(SessionSummary {
+                session_id: row.get(0)?,
+                checkpoint_count: row.get(1)?,
+                first_checkpoint_at: row.get(2)?,
+                last_checkpoint_at: row.get(3)?,
+                working_on: row.get(4)?,
+            })
+        })
+        .map_err(|e| StorageError::Database(e.to_string()))?
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    Ok(sessions)
+}
+
+/// Walk parent links back from a checkpoint.
+///
+/// Returns the chain ending at `head_id`, oldest first, holding at most
+/// `max_depth` checkpoints. The walk stops at a checkpoint without a parent
+/// or whose parent has been deleted.
+///
+/// # Errors
+///
+/// Returns an error if `head_id` doesn't exist or the database operation fails.
+pub fn get_checkpoint_chain(
+    conn: &Connection,
+    head_id: &str,
+    max_depth: usize,
+) -> Result<Vec<CheckpointRecord>> {
+    // Surface a missing head as NotFound rather than an empty chain
+    get_checkpoint(conn, head_id)?;
+
+    let depth_i64 = i64::try_from(max_depth).unwrap_or(i64::MAX);
+    let mut stmt = conn
+        .prepare(
+            "WITH RECURSIVE chain(id, depth) AS (
+                 SELECT id, 1 FROM checkpoints WHERE id = ?1
+                 UNION ALL
+                 SELECT c.parent_id, chain.depth + 1
+                 FROM chain JOIN checkpoints c ON c.id = chain.id
+                 WHERE c.parent_id IS NOT NULL AND chain.depth < ?2
+             )
+             SELECT c.id, c.agent, c.repo, c.session_id, c.working_on, c.state, c.created_at,
+                    c.parent_id
+             FROM chain JOIN checkpoints c ON c.id = chain.id
+             ORDER BY chain.depth DESC",
+        )
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    let checkpoints = stmt
+        .query_map(params![head_id, depth_i64], |row| {
+            let state_json: String = row.get(5)?;
+            let state: serde_json::Value = serde_json::from_str(&state_json).unwrap_or_default();
+
+            Ok(CheckpointRecord {
+                id: row.get(0)?,
+                agent: row.get(1)?,
+                repo: row.get(2)?,
+                session_id: row.get(3)?,
+                working_on: row.get(4)?,
+                state,
+                created_at: row.get(6)?,
+                parent_id: row.get(7)?,
+            })
+        })
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    let mut result = Vec::new();
+    for cp in checkpoints {
+        result.push(cp.map_err(|e| StorageError::Database(e.to_string()))?);
+    }
+    Ok(result)
+}
+
 /// Count checkpoints for an agent.
 ///
 /// # Errors
@@ -213,6 +465,37 @@ pub fn count_checkpoints(conn: &Connection, agent: &str) -> Result<i64> {
     .map_err(|e| StorageError::Database(e.to_string()).into())
 }
 
+/// Count checkpoints across all agents.
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub fn count_all_checkpoints(conn: &Connection) -> Result<i64> {
+    conn.query_row("SELECT COUNT(*) FROM checkpoints", [], |row| row.get(0))
+        .map_err(|e| StorageError::Database(e.to_string()).into())
+}
+
+/// Count checkpoints grouped by agent, largest first.
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub fn count_checkpoints_by_agent(conn: &Connection) -> Result<Vec<(String, i64)>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT agent, COUNT(*) AS n FROM checkpoints GROUP BY agent ORDER BY n DESC, agent",
+        )
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    let counts = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| StorageError::Database(e.to_string()))?
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    Ok(counts)
+}
+
 /// Delete old checkpoints for an agent, keeping only the most recent N.
 ///
 /// Returns the number of checkpoints deleted.
@@ -223,7 +506,7 @@ pub fn count_checkpoints(conn: &Connection, agent: &str) -> Result<i64> {
 pub fn cleanup_old_checkpoints(conn: &Connection, agent: &str, keep: usize) -> Result<usize> {
     let sql = "DELETE FROM checkpoints \
          WHERE agent = ? AND id NOT IN ( \
-             SELECT id FROM checkpoints WHERE agent = ? ORDER BY created_at DESC LIMIT ? \
+             SELECT id FROM checkpoints WHERE agent = ? ORDER BY created_at DESC, id DESC LIMIT ? \
          )";
 
     let keep_i64 = i64::try_from(keep).unwrap_or(0);
@@ -273,6 +556,48 @@ mod tests {
         .unwrap();
     }
 
+    #[test]
+    fn test_list_sessions() {
+        let db = setup_db();
+
+        db.with_conn(|conn| {
+            let checkpoints = [
+                (Some("auth"), "Start auth refactor", 100),
+                (Some("auth"), "Finish token refresh", 300),
+                (Some("docs"), "Update README", 200),
+                (None, "Loose note", 150),
+                (None, "Another note", 250),
+            ];
+            for (session, working_on, created_at) in checkpoints {
+                let mut cp = CheckpointRecord::new("agent1", working_on, serde_json::json!({}));
+                cp.session_id = session.map(str::to_string);
+                cp.created_at = created_at;
+                insert_checkpoint(conn, &cp)?;
+            }
+            insert_checkpoint(
+                conn,
+                &CheckpointRecord::new("agent2", "Other", serde_json::json!({})).with_session("x"),
+            )?;
+
+            let sessions = list_sessions(conn, "agent1", 10)?;
+            let ids: Vec<Option<&str>> = sessions.iter().map(|s| s.session_id.as_deref()).collect();
+            assert_eq!(ids, vec![Some("auth"), None, Some("docs")]);
+
+            assert_eq!(sessions[0].checkpoint_count, 2);
+            assert_eq!(sessions[0].first_checkpoint_at, 100);
+            assert_eq!(sessions[0].last_checkpoint_at, 300);
+            assert_eq!(sessions[0].working_on, "Finish token refresh");
+            assert_eq!(sessions[1].checkpoint_count, 2);
+            assert_eq!(sessions[1].working_on, "Another note");
+
+            assert_eq!(list_sessions(conn, "agent1", 1)?.len(), 1);
+            assert!(list_sessions(conn, "nobody", 10)?.is_empty());
+
+            Ok(())
+        })
+        .unwrap();
+    }
+
     #[test]
     fn test_get_recent() {
         let db = setup_db();
@@ -287,6 +612,61 @@ mod tests {
             let recent = get_recent_checkpoints(conn, "agent1", 3)?;
             assert_eq!(recent.len(), 3);
 
+            // Older checkpoints in a session still fill the limit
+            let mut cp = CheckpointRecord::new("agent1", "Session task", serde_json::json!({}))
+                .with_session("s1");
+            cp.created_at = 1;
+            insert_checkpoint(conn, &cp)?;
+            let recent = get_recent_session_checkpoints(conn, "agent1", Some("s1"), 3)?;
+            assert_eq!(recent.len(), 1);
+            assert_eq!(recent[0].working_on, "Session task");
+            assert_eq!(
+                get_recent_session_checkpoints(conn, "agent1", None, 10)?.len(),
+                6
+            );
+
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_get_checkpoints_before() {
+        let db = setup_db();
+
+        db.with_conn(|conn| {
+            // Two share a timestamp, so paging must fall back to id order
+            for (i, created_at) in [100, 200, 200, 300, 400].into_iter().enumerate() {
+                let mut cp =
+                    CheckpointRecord::new("agent1", format!("Task {i}"), serde_json::json!({}));
+                cp.created_at = created_at;
+                insert_checkpoint(conn, &cp)?;
+            }
+            insert_checkpoint(
+                conn,
+                &CheckpointRecord::new("agent2", "Other", serde_json::json!({})),
+            )?;
+
+            let all = get_recent_checkpoints(conn, "agent1", 10)?;
+            assert_eq!(all.len(), 5);
+
+            let mut paged = get_recent_checkpoints(conn, "agent1", 2)?;
+            loop {
+                let cursor = paged.last().unwrap().id.clone();
+                let page = get_checkpoints_before(conn, "agent1", &cursor, 2)?;
+                if page.is_empty() {
+                    break;
+                }
+                paged.extend(page);
+            }
+            let ids = |cps: &[CheckpointRecord]| -> Vec<String> {
+                cps.iter().map(|cp| cp.id.clone()).collect()
+            };
+            assert_eq!(ids(&paged), ids(&all));
+
+            let err = get_checkpoints_before(conn, "agent2", &all[0].id, 2).unwrap_err();
+            assert!(err.to_string().contains("not found"));
+
             Ok(())
         })
         .unwrap();
@@ -395,6 +775,16 @@ mod tests {
             assert_eq!(count_checkpoints(conn, "agent2")?, 1);
             assert_eq!(count_checkpoints(conn, "agent3")?, 0);
 
+            insert_checkpoint(
+                conn,
+                &CheckpointRecord::new("agent2", "Task 3", serde_json::json!({})),
+            )?;
+            assert_eq!(
+                count_checkpoints_by_agent(conn)?,
+                vec![("agent2".to_string(), 2), ("agent1".to_string(), 1)]
+            );
+            assert_eq!(count_all_checkpoints(conn)?, 3);
+
             let agent1_checkpoints = get_recent_checkpoints(conn, "agent1", 10)?;
             assert_eq!(agent1_checkpoints.len(), 1);
             assert_eq!(agent1_checkpoints[0].agent, "agent1");
@@ -403,4 +793,71 @@ mod tests {
         })
         .unwrap();
     }
+
+    #[test]
+    fn test_checkpoint_chain() {
+        let db = setup_db();
+
+        db.with_conn(|conn| {
+            let root = CheckpointRecord::new("agent", "Step 1", serde_json::json!({}))
+                .with_session("session-1");
+            let middle = CheckpointRecord::new("agent", "Step 2", serde_json::json!({}))
+                .with_session("session-1")
+                .with_parent(&root.id);
+            let mut head = CheckpointRecord::new("agent", "Step 3", serde_json::json!({}))
+                .with_session("session-1")
+                .with_parent(&middle.id);
+            head.created_at = middle.created_at + 1;
+            let unrelated = CheckpointRecord::new("agent", "Other", serde_json::json!({}));
+
+            for cp in [&root, &middle, &head, &unrelated] {
+                insert_checkpoint(conn, cp)?;
+            }
+
+            let chain = get_checkpoint_chain(conn, &head.id, 10)?;
+            let steps: Vec<&str> = chain.iter().map(|cp| cp.working_on.as_str()).collect();
+            assert_eq!(steps, vec!["Step 1", "Step 2", "Step 3"]);
+            assert_eq!(chain[2].parent_id.as_deref(), Some(middle.id.as_str()));
+
+            // Depth limit keeps the newest links
+            let chain = get_checkpoint_chain(conn, &head.id, 2)?;
+            assert_eq!(chain.len(), 2);
+            assert_eq!(chain[0].id, middle.id);
+
+            // A deleted parent ends the chain
+            delete_checkpoint(conn, &root.id)?;
+            assert_eq!(get_checkpoint_chain(conn, &head.id, 10)?.len(), 2);
+
+            assert_eq!(get_checkpoint_chain(conn, &unrelated.id, 10)?.len(), 1);
+            assert!(get_checkpoint_chain(conn, "missing", 10).is_err());
+
+            let latest = get_latest_session_checkpoint(conn, "session-1")?.unwrap();
+            assert_eq!(latest.id, head.id);
+            assert!(get_latest_session_checkpoint(conn, "session-2")?.is_none());
+
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_insert_checkpoint_idempotent() {
+        let db = setup_db();
+
+        db.with_conn(|conn| {
+            let first = CheckpointRecord::new("agent", "Task", serde_json::json!({}));
+            assert!(insert_checkpoint_idempotent(conn, &first, "key-1")?.is_none());
+
+            let retry = CheckpointRecord::new("agent", "Task", serde_json::json!({}));
+            assert_eq!(
+                insert_checkpoint_idempotent(conn, &retry, "key-1")?,
+                Some(first.id.clone())
+            );
+            assert!(insert_checkpoint_idempotent(conn, &retry, "key-2")?.is_none());
+            assert_eq!(count_checkpoints(conn, "agent")?, 2);
+
+            Ok(())
+        })
+        .unwrap();
+    }
 }
@@ -2,7 +2,9 @@
 
 use rusqlite::Connection;
 
+use super::meta::get_distance_metric;
 use super::models::{CheckpointRecord, SearchResult};
+use super::vector::vector_k;
 use crate::error::StorageError;
 use crate::Result;
 
@@ -10,14 +12,17 @@ const CHECKPOINT_VEC_TABLE: &str = "checkpoint_embeddings";
 
 /// Initialize checkpoint vector table.
 ///
+/// Uses the distance metric recorded for the database.
+///
 /// # Errors
 ///
 /// Returns an error if the table cannot be created.
 pub fn init_checkpoint_vectors(conn: &Connection) -> Result<()> {
+    let option = get_distance_metric(conn)?.column_option();
     let sql = format!(
         "CREATE VIRTUAL TABLE IF NOT EXISTS {CHECKPOINT_VEC_TABLE} USING vec0(
             id TEXT PRIMARY KEY,
-            embedding FLOAT[384]
+            embedding FLOAT[384]{option}
         )"
     );
 
@@ -57,6 +62,9 @@ pub fn store_checkpoint_embedding(
 
 /// Search checkpoints by embedding similarity.
 ///
+/// Results are nearest first; checkpoints at the same distance are ordered
+/// newest first, then by ID.
+///
 /// # Errors
 ///
 /// Returns an error if the search query fails.
@@ -79,20 +87,21 @@ pub fn search_checkpoints_by_embedding(
         .map_err(|e| StorageError::Vector(format!("failed to prepare search: {e}")))?;
 
     let candidates: Vec<(String, f32)> = stmt
-        .query_map(
-            rusqlite::params![blob, i64::try_from(limit).unwrap_or(10)],
-            |row| Ok((row.get(0)?, row.get(1)?)),
-        )
+        .query_map(rusqlite::params![blob, vector_k(limit)], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
         .map_err(|e| StorageError::Vector(e.to_string()))?
         .filter_map(std::result::Result::ok)
         .collect();
 
+    let metric = get_distance_metric(conn)?;
     let mut results = Vec::new();
     for (id, distance) in candidates {
         if let Ok(checkpoint) = super::checkpoints::get_checkpoint(conn, &id) {
-            results.push(SearchResult::new(checkpoint, distance));
+            results.push(SearchResult::with_metric(checkpoint, distance, metric));
         }
     }
+    SearchResult::sort_by_distance(&mut results, |c| (c.created_at, c.id.as_str()));
 
     Ok(results)
 }
@@ -111,7 +120,7 @@ pub fn search_checkpoints_by_text(
 
     let mut stmt = conn
         .prepare(
-            "SELECT id, agent, repo, session_id, working_on, state, created_at
+            "SELECT id, agent, repo, session_id, working_on, state, created_at, parent_id
              FROM checkpoints
              WHERE working_on LIKE ?
              ORDER BY created_at DESC
@@ -135,6 +144,7 @@ pub fn search_checkpoints_by_text(
                     working_on: row.get(4)?,
                     state,
                     created_at: row.get(6)?,
+                    parent_id: row.get(7)?,
                 })
             },
         )
@@ -159,7 +169,7 @@ pub fn search_checkpoints_by_agent(
 ) -> Result<Vec<CheckpointRecord>> {
     let mut stmt = conn
         .prepare(
-            "SELECT id, agent, repo, session_id, working_on, state, created_at
+            "SELECT id, agent, repo, session_id, working_on, state, created_at, parent_id
              FROM checkpoints
              WHERE agent = ?
              ORDER BY created_at DESC
@@ -183,6 +193,7 @@ pub fn search_checkpoints_by_agent(
                     working_on: row.get(4)?,
                     state,
                     created_at: row.get(6)?,
+                    parent_id: row.get(7)?,
                 })
             },
         )
@@ -207,7 +218,7 @@ pub fn search_checkpoints_by_repo(
 ) -> Result<Vec<CheckpointRecord>> {
     let mut stmt = conn
         .prepare(
-            "SELECT id, agent, repo, session_id, working_on, state, created_at
+            "SELECT id, agent, repo, session_id, working_on, state, created_at, parent_id
              FROM checkpoints
              WHERE repo = ?
              ORDER BY created_at DESC
@@ -231,6 +242,7 @@ pub fn search_checkpoints_by_repo(
                     working_on: row.get(4)?,
                     state,
                     created_at: row.get(6)?,
+                    parent_id: row.get(7)?,
                 })
             },
         )
@@ -255,7 +267,7 @@ pub fn search_checkpoints_by_session(
 ) -> Result<Vec<CheckpointRecord>> {
     let mut stmt = conn
         .prepare(
-            "SELECT id, agent, repo, session_id, working_on, state, created_at
+            "SELECT id, agent, repo, session_id, working_on, state, created_at, parent_id
              FROM checkpoints
              WHERE session_id = ?
              ORDER BY created_at DESC
@@ -279,6 +291,7 @@ pub fn search_checkpoints_by_session(
                     working_on: row.get(4)?,
                     state,
                     created_at: row.get(6)?,
+                    parent_id: row.get(7)?,
                 })
             },
         )
@@ -304,7 +317,7 @@ pub fn search_checkpoints_by_agent_and_repo(
 ) -> Result<Vec<CheckpointRecord>> {
     let mut stmt = conn
         .prepare(
-            "SELECT id, agent, repo, session_id, working_on, state, created_at
+            "SELECT id, agent, repo, session_id, working_on, state, created_at, parent_id
              FROM checkpoints
              WHERE agent = ? AND repo = ?
              ORDER BY created_at DESC
@@ -328,6 +341,7 @@ pub fn search_checkpoints_by_agent_and_repo(
                     working_on: row.get(4)?,
                     state,
                     created_at: row.get(6)?,
+                    parent_id: row.get(7)?,
                 })
             },
         )
@@ -351,6 +365,35 @@ mod tests {
         db
     }
 
+    #[test]
+    fn test_search_by_embedding_breaks_ties() {
+        crate::storage::init_sqlite_vec();
+        let db = setup_db();
+
+        db.with_conn(|conn| {
+            init_checkpoint_vectors(conn)?;
+            let mut embedding = vec![0.0_f32; 384];
+            embedding[0] = 1.0;
+            for (id, created_at) in [("cp_b", 100), ("cp_c", 200), ("cp_a", 100)] {
+                let mut checkpoint =
+                    CheckpointRecord::new("agent", "Same task", serde_json::json!({}));
+                checkpoint.id = id.to_string();
+                checkpoint.created_at = created_at;
+                insert_checkpoint(conn, &checkpoint)?;
+                store_checkpoint_embedding(conn, id, &embedding)?;
+            }
+
+            for _ in 0..3 {
+                let results = search_checkpoints_by_embedding(conn, &embedding, 10)?;
+                let order: Vec<&str> = results.iter().map(|r| r.record.id.as_str()).collect();
+                assert_eq!(order, vec!["cp_c", "cp_a", "cp_b"]);
+            }
+
+            Ok(())
+        })
+        .unwrap();
+    }
+
     #[test]
     fn test_search_by_text() {
         let db = setup_db();
@@ -0,0 +1,339 @@
+//! Content-hash deduplication of code chunks.
+//!
+//! With deduplication on, every chunk the indexer stores is recorded in the
+//! `chunk_content` table under the hash of its text. The first chunk with a
+//! given hash is the group's canonical chunk and holds its embedding; later
+//! chunks with the same text reference it instead of being embedded again.
+//! They still get a `chunks` row, so grep, keyword search and per-file
+//! listings see every copy, and vector search matches each copy's path,
+//! repository and language against its filters.
+
+use std::collections::{HashMap, HashSet};
+
+use rusqlite::{params, Connection, OptionalExtension};
+
+use super::chunks::insert_chunk;
+use super::models::{ChunkDuplicates, ChunkRecord, DuplicateChunk};
+use super::vector::{delete_vector, get_vectors, insert_vector};
+use crate::error::StorageError;
+use crate::Result;
+
+/// Vector table name for chunk embeddings.
+const CHUNK_VEC_TABLE: &str = "chunk_embeddings";
+
+/// Which of `hashes` are already held by deduplicated chunks outside
+/// `file_path`.
+///
+/// Chunks of `file_path` itself are left out because re-indexing the file
+/// replaces them, so their embeddings can't be reused.
+///
+/// # Errors
+///
+/// Returns an error if the query fails.
+pub fn shared_content_hashes(
+    conn: &Connection,
+    hashes: &[String],
+    file_path: &str,
+) -> Result<HashSet<String>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT 1 FROM chunk_content g JOIN chunks c ON c.id = g.chunk_id
+             WHERE g.content_hash = ? AND c.file_path != ? LIMIT 1",
+        )
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    let mut shared = HashSet::new();
+    for hash in hashes {
+        if shared.contains(hash) {
+            continue;
+        }
+        let found = stmt
+            .exists(params![hash, file_path])
+            .map_err(|e| StorageError::Database(e.to_string()))?;
+        if found {
+            shared.insert(hash.clone());
+        }
+    }
+    Ok(shared)
+}
+
+/// Insert a chunk, referencing an existing chunk with the same content.
+///
+/// When a deduplicated chunk with `content_hash` already exists, the new
+/// chunk is stored without an embedding and points at that chunk's group.
+/// Otherwise it becomes the canonical chunk for the hash, embedding and all.
+///
+/// Returns the assigned ID.
+///
+/// # Errors
+///
+/// Returns an error if the insertion fails.
+pub fn insert_chunk_deduped(
+    conn: &Connection,
+    chunk: &ChunkRecord,
+    content_hash: &str,
+) -> Result<i64> {
+    let canonical: Option<i64> = conn
+        .query_row(
+            "SELECT canonical_id FROM chunk_content WHERE content_hash = ? LIMIT 1",
+            [content_hash],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    let (id, canonical_id) = if let Some(canonical_id) = canonical {
+        let reference = ChunkRecord {
+            embedding: None,
+            ..chunk.clone()
+        };
+        (insert_chunk(conn, &reference)?, canonical_id)
+    } else {
+        let id = insert_chunk(conn, chunk)?;
+        (id, id)
+    };
+
+    conn.execute(
+        "INSERT INTO chunk_content (chunk_id, content_hash, canonical_id) VALUES (?, ?, ?)",
+        params![id, content_hash, canonical_id],
+    )
+    .map_err(|e| StorageError::Database(format!("failed to record chunk content: {e}")))?;
+
+    Ok(id)
+}
+
+/// Drop the content records of chunks about to be deleted.
+///
+/// A deleted canonical chunk hands its embedding and its group to the
+/// lowest-numbered remaining copy, so search keeps finding the content.
+/// Call before the chunks' vectors are deleted.
+///
+/// # Errors
+///
+/// Returns an error if a database update fails.
+pub(super) fn release_chunk_content(conn: &Connection, ids: &[i64]) -> Result<()> {
+    for &id in ids {
+        let canonical: Option<i64> = conn
+            .query_row(
+                "SELECT canonical_id FROM chunk_content WHERE chunk_id = ?",
+                [id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| StorageError::Database(e.to_string()))?;
+        let Some(canonical) = canonical else {
+            continue;
+        };
+
+        conn.execute("DELETE FROM chunk_content WHERE chunk_id = ?", [id])
+            .map_err(|e| StorageError::Database(e.to_string()))?;
+        if canonical != id {
+            continue;
+        }
+
+        let successor: Option<i64> = conn
+            .query_row(
+                "SELECT MIN(chunk_id) FROM chunk_content WHERE canonical_id = ?",
+                [id],
+                |row| row.get(0),
+            )
+            .map_err(|e| StorageError::Database(e.to_string()))?;
+        let Some(successor) = successor else {
+            continue;
+        };
+
+        conn.execute(
+            "UPDATE chunk_content SET canonical_id = ? WHERE canonical_id = ?",
+            [successor, id],
+        )
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+        // Without sqlite-vec there are no embeddings to hand over
+        if let Ok(mut vectors) = get_vectors(conn, CHUNK_VEC_TABLE, &[id]) {
+            if let Some(embedding) = vectors.remove(&id) {
+                let _ = delete_vector(conn, CHUNK_VEC_TABLE, successor);
+                insert_vector(conn, CHUNK_VEC_TABLE, successor, &embedding)?;
+            }
+        }
+        tracing::trace!(from = id, to = successor, "Promoted duplicate chunk");
+    }
+    Ok(())
+}
+
+/// Other chunks sharing each chunk's content, for deduplicated chunks.
+///
+/// Chunks without copies are left out of the map.
+///
+/// # Errors
+///
+/// Returns an error if the query fails.
+pub fn get_chunk_duplicates(conn: &Connection, ids: &[i64]) -> Result<ChunkDuplicates> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT c.id, c.file_path, c.start_line, c.end_line, c.repo
+             FROM chunk_content g
+             JOIN chunk_content m ON m.canonical_id = g.canonical_id
+             JOIN chunks c ON c.id = m.chunk_id
+             WHERE g.chunk_id = ?1 AND m.chunk_id != ?1
+             ORDER BY c.file_path, c.start_line",
+        )
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    let mut duplicates = HashMap::new();
+    for &id in ids {
+        let copies = stmt
+            .query_map([id], |row| {
+                Ok(DuplicateChunk {
+                    id: row.get(0)?,
+                    file_path: row.get(1)?,
+                    start_line: row.get(2)?,
+                    end_line: row.get(3)?,
+                    repo: row.get(4)?,
+                })
+            })
+            .map_err(|e| StorageError::Database(e.to_string()))?
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| StorageError::Database(e.to_string()))?;
+        if !copies.is_empty() {
+            duplicates.insert(id, copies);
+        }
+    }
+    Ok(duplicates)
+}
+
+/// The copies referencing each of `canonical_ids`, in id order.
+///
+/// Copies have no embedding of their own, so vector search only returns
+/// canonical chunks; this finds the chunks each one stands for. Chunks
+/// without copies are left out of the map.
+///
+/// # Errors
+///
+/// Returns an error if the query fails.
+pub(super) fn get_group_copies(
+    conn: &Connection,
+    canonical_ids: &[i64],
+) -> Result<HashMap<i64, Vec<i64>>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT chunk_id FROM chunk_content
+             WHERE canonical_id = ?1 AND chunk_id != ?1
+             ORDER BY chunk_id",
+        )
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    let mut groups = HashMap::new();
+    for &id in canonical_ids {
+        let copies = stmt
+            .query_map([id], |row| row.get(0))
+            .map_err(|e| StorageError::Database(e.to_string()))?
+            .collect::<std::result::Result<Vec<i64>, _>>()
+            .map_err(|e| StorageError::Database(e.to_string()))?;
+        if !copies.is_empty() {
+            groups.insert(id, copies);
+        }
+    }
+    Ok(groups)
+}
+
+/// Count deduplicated chunks stored as references to another chunk.
+///
+/// # Errors
+///
+/// Returns an error if the query fails.
+pub fn count_duplicate_chunks(conn: &Connection) -> Result<i64> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM chunk_content WHERE canonical_id != chunk_id",
+        [],
+        |row| row.get(0),
+    )
+    .map_err(|e| StorageError::Database(e.to_string()).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{delete_chunks_by_file, get_chunk, migrate, Database};
+
+    fn setup_db() -> Database {
+        let db = Database::open_in_memory().unwrap();
+        db.with_conn(|conn| migrate(conn)).unwrap();
+        db
+    }
+
+    fn chunk(path: &str, index: i32, content: &str) -> ChunkRecord {
+        ChunkRecord::new(path, index, 1, 3, content, "filehash")
+    }
+
+    #[test]
+    fn test_duplicates_reference_first_copy() {
+        let db = setup_db();
+
+        db.with_conn(|conn| {
+            let license = "// Licensed under the Apache License";
+            let a = insert_chunk_deduped(conn, &chunk("/a.rs", 0, license), "h1")?;
+            let b = insert_chunk_deduped(conn, &chunk("/b.rs", 0, license), "h1")?;
+            let c = insert_chunk_deduped(conn, &chunk("/c.rs", 0, license), "h1")?;
+            insert_chunk_deduped(conn, &chunk("/c.rs", 1, "fn unique() {}"), "h2")?;
+
+            assert_eq!(count_duplicate_chunks(conn)?, 2);
+            // Every copy keeps its own row
+            assert_eq!(get_chunk(conn, b)?.content, license);
+
+            let duplicates = get_chunk_duplicates(conn, &[a, c])?;
+            let paths = |id: i64| -> Vec<String> {
+                duplicates[&id]
+                    .iter()
+                    .map(|d| d.file_path.clone())
+                    .collect()
+            };
+            assert_eq!(paths(a), vec!["/b.rs", "/c.rs"]);
+            assert_eq!(paths(c), vec!["/a.rs", "/b.rs"]);
+
+            let hashes = vec!["h1".to_string(), "h2".to_string(), "h3".to_string()];
+            let shared = shared_content_hashes(conn, &hashes, "/c.rs")?;
+            assert_eq!(shared, HashSet::from(["h1".to_string()]));
+
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_deleting_canonical_promotes_copy() {
+        let db = setup_db();
+
+        db.with_conn(|conn| {
+            let a = insert_chunk_deduped(conn, &chunk("/a.rs", 0, "same"), "h1")?;
+            let b = insert_chunk_deduped(conn, &chunk("/b.rs", 0, "same"), "h1")?;
+            let c = insert_chunk_deduped(conn, &chunk("/c.rs", 0, "same"), "h1")?;
+
+            delete_chunks_by_file(conn, "/a.rs")?;
+            let canonical: i64 = conn
+                .query_row(
+                    "SELECT canonical_id FROM chunk_content WHERE chunk_id = ?",
+                    [c],
+                    |row| row.get(0),
+                )
+                .unwrap();
+            assert_eq!(canonical, b);
+            assert_eq!(count_duplicate_chunks(conn)?, 1);
+            assert!(get_chunk_duplicates(conn, &[a])?.is_empty());
+
+            // A new copy joins the promoted group
+            let d = insert_chunk_deduped(conn, &chunk("/d.rs", 0, "same"), "h1")?;
+            assert_eq!(get_chunk_duplicates(conn, &[d])?[&d].len(), 2);
+
+            delete_chunks_by_file(conn, "/b.rs")?;
+            delete_chunks_by_file(conn, "/c.rs")?;
+            delete_chunks_by_file(conn, "/d.rs")?;
+            let remaining: i64 = conn
+                .query_row("SELECT COUNT(*) FROM chunk_content", [], |row| row.get(0))
+                .unwrap();
+            assert_eq!(remaining, 0);
+
+            Ok(())
+        })
+        .unwrap();
+    }
+}
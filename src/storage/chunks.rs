@@ -4,8 +4,12 @@
 
 use rusqlite::{params, Connection};
 
-use super::models::ChunkRecord;
-use super::vector::{delete_vector, insert_vector, EMBEDDING_DIM};
+use super::chunk_content::release_chunk_content;
+use super::meta::get_distance_metric;
+use super::models::{
+    ChunkLineStats, ChunkRecord, FileChunkStats, IndexedRevision, RepoBackfill, RepoStats,
+};
+use super::vector::{create_vec_table, delete_vector, insert_vector, EMBEDDING_DIM};
 use crate::error::StorageError;
 use crate::Result;
 
@@ -14,20 +18,15 @@ const CHUNK_VEC_TABLE: &str = "chunk_embeddings";
 
 /// Initialize chunk vector table.
 ///
+/// Uses the distance metric recorded for the database.
+///
 /// # Errors
 ///
 /// Returns an error if the table cannot be created.
 pub fn init_chunk_vectors(conn: &Connection) -> Result<()> {
     // Create vec0 table for chunk embeddings
-    let sql = format!(
-        "CREATE VIRTUAL TABLE IF NOT EXISTS {CHUNK_VEC_TABLE} USING vec0(
-            id INTEGER PRIMARY KEY,
-            embedding FLOAT[{EMBEDDING_DIM}]
-        )"
-    );
-
-    conn.execute(&sql, [])
-        .map_err(|e| StorageError::Vector(format!("failed to create chunk vec table: {e}")))?;
+    let metric = get_distance_metric(conn)?;
+    create_vec_table(conn, CHUNK_VEC_TABLE, EMBEDDING_DIM, metric)?;
 
     tracing::debug!("Chunk vector table initialized");
     Ok(())
@@ -42,8 +41,9 @@ pub fn init_chunk_vectors(conn: &Connection) -> Result<()> {
 /// Returns an error if the insertion fails.
 pub fn insert_chunk(conn: &Connection, chunk: &ChunkRecord) -> Result<i64> {
     let sql = "
-        INSERT INTO chunks (file_path, chunk_index, start_line, end_line, content, language, file_hash, indexed_at)
-        VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+        INSERT INTO chunks (file_path, chunk_index, start_line, end_line, content, language, file_hash, indexed_at, repo,
+                            git_commit, git_branch)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
     ";
 
     conn.execute(
@@ -57,6 +57,9 @@ pub fn insert_chunk(conn: &Connection, chunk: &ChunkRecord) -> Result<i64> {
             chunk.language,
             chunk.file_hash,
             chunk.indexed_at,
+            chunk.repo,
+            chunk.git_commit,
+            chunk.git_branch,
         ],
     )
     .map_err(|e| StorageError::Database(format!("failed to insert chunk: {e}")))?;
@@ -98,7 +101,8 @@ pub fn insert_chunks_batch(conn: &Connection, chunks: &[ChunkRecord]) -> Result<
 /// Returns an error if the chunk is not found or query fails.
 pub fn get_chunk(conn: &Connection, id: i64) -> Result<ChunkRecord> {
     let sql = "
-        SELECT id, file_path, chunk_index, start_line, end_line, content, language, file_hash, indexed_at
+        SELECT id, file_path, chunk_index, start_line, end_line, content, language, file_hash, indexed_at, repo,
+               git_commit, git_branch
         FROM chunks
         WHERE id = ?
     ";
@@ -114,6 +118,9 @@ pub fn get_chunk(conn: &Connection, id: i64) -> Result<ChunkRecord> {
             language: row.get(6)?,
             file_hash: row.get(7)?,
             indexed_at: row.get(8)?,
+            repo: row.get(9)?,
+            git_commit: row.get(10)?,
+            git_branch: row.get(11)?,
             embedding: None,
         })
     })
@@ -134,7 +141,8 @@ pub fn get_chunk(conn: &Connection, id: i64) -> Result<ChunkRecord> {
 /// Returns an error if the query fails.
 pub fn get_chunks_by_file(conn: &Connection, file_path: &str) -> Result<Vec<ChunkRecord>> {
     let sql = "
-        SELECT id, file_path, chunk_index, start_line, end_line, content, language, file_hash, indexed_at
+        SELECT id, file_path, chunk_index, start_line, end_line, content, language, file_hash, indexed_at, repo,
+               git_commit, git_branch
         FROM chunks
         WHERE file_path = ?
         ORDER BY chunk_index
@@ -156,6 +164,9 @@ pub fn get_chunks_by_file(conn: &Connection, file_path: &str) -> Result<Vec<Chun
                 language: row.get(6)?,
                 file_hash: row.get(7)?,
                 indexed_at: row.get(8)?,
+                repo: row.get(9)?,
+                git_commit: row.get(10)?,
+                git_branch: row.get(11)?,
                 embedding: None,
             })
         })
@@ -176,6 +187,8 @@ pub fn get_chunks_by_file(conn: &Connection, file_path: &str) -> Result<Vec<Chun
 ///
 /// Returns an error if the deletion fails.
 pub fn delete_chunk(conn: &Connection, id: i64) -> Result<()> {
+    release_chunk_content(conn, &[id])?;
+
     // Delete from vector table first
     let _ = delete_vector(conn, CHUNK_VEC_TABLE, id);
 
@@ -208,6 +221,9 @@ pub fn delete_chunks_by_file(conn: &Connection, file_path: &str) -> Result<usize
         mapped_rows.flatten().collect()
     };
 
+    // Hand shared embeddings to surviving copies before dropping vectors
+    release_chunk_content(conn, &ids)?;
+
     // Delete from vector table
     for id in &ids {
         let _ = delete_vector(conn, CHUNK_VEC_TABLE, *id);
@@ -222,6 +238,131 @@ pub fn delete_chunks_by_file(conn: &Connection, file_path: &str) -> Result<usize
     Ok(count)
 }
 
+/// Move a file's chunks and file state to a new path.
+///
+/// Embeddings are keyed by chunk ID, so they carry over unchanged. Any
+/// existing chunks or state at `new_path` are replaced. Call inside a
+/// transaction to keep chunks and file state consistent.
+///
+/// Returns the number of chunks moved.
+///
+/// # Errors
+///
+/// Returns an error if the update fails.
+pub fn rename_file_path(conn: &Connection, old_path: &str, new_path: &str) -> Result<usize> {
+    delete_chunks_by_file(conn, new_path)?;
+
+    let count = conn
+        .execute(
+            "UPDATE chunks SET file_path = ? WHERE file_path = ?",
+            [new_path, old_path],
+        )
+        .map_err(|e| StorageError::Database(format!("failed to rename chunks: {e}")))?;
+
+    conn.execute("DELETE FROM file_state WHERE path = ?", [new_path])
+        .map_err(|e| StorageError::Database(format!("failed to rename file state: {e}")))?;
+    conn.execute(
+        "UPDATE file_state SET path = ? WHERE path = ?",
+        [new_path, old_path],
+    )
+    .map_err(|e| StorageError::Database(format!("failed to rename file state: {e}")))?;
+
+    tracing::debug!(
+        from = old_path,
+        to = new_path,
+        count,
+        "Renamed chunks for file"
+    );
+    Ok(count)
+}
+
+/// Set the repository for all chunks of a file.
+///
+/// Returns the number of chunks updated.
+///
+/// # Errors
+///
+/// Returns an error if the update fails.
+pub fn set_chunks_repo(conn: &Connection, file_path: &str, repo: &str) -> Result<usize> {
+    conn.execute(
+        "UPDATE chunks SET repo = ? WHERE file_path = ? AND repo IS NOT ?",
+        [repo, file_path, repo],
+    )
+    .map_err(|e| StorageError::Database(format!("failed to set chunk repo: {e}")).into())
+}
+
+/// Fill in the repository of chunks indexed without one.
+///
+/// Calls `infer_repo` once for each file with unattributed chunks and tags
+/// the file's chunks with the repository it returns, leaving their content
+/// and embeddings untouched. Files it returns `None` for are counted as
+/// unattributed.
+///
+/// # Errors
+///
+/// Returns an error if a query or update fails.
+pub fn backfill_chunk_repos(
+    conn: &Connection,
+    mut infer_repo: impl FnMut(&str) -> Option<String>,
+) -> Result<RepoBackfill> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT file_path, COUNT(*) FROM chunks WHERE repo IS NULL
+             GROUP BY file_path ORDER BY file_path",
+        )
+        .map_err(|e| StorageError::Database(format!("failed to prepare query: {e}")))?;
+    let files = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+        })
+        .map_err(|e| StorageError::Database(format!("failed to list unattributed files: {e}")))?
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| StorageError::Database(format!("failed to read row: {e}")))?;
+
+    let mut report = RepoBackfill::default();
+    for (file_path, chunk_count) in files {
+        if let Some(repo) = infer_repo(&file_path) {
+            #[allow(clippy::cast_possible_wrap)]
+            let updated = set_chunks_repo(conn, &file_path, &repo)? as i64;
+            report.files_updated += 1;
+            report.chunks_updated += updated;
+        } else {
+            report.files_unattributed += 1;
+            report.chunks_unattributed += chunk_count;
+        }
+    }
+
+    tracing::info!(
+        files = report.files_updated,
+        chunks = report.chunks_updated,
+        unattributed = report.chunks_unattributed,
+        "Backfilled chunk repositories"
+    );
+    Ok(report)
+}
+
+/// Set the git revision for all chunks of a file.
+///
+/// Used when a file is unchanged but the checkout has moved, since its chunks
+/// still match the new revision. Returns the number of chunks updated.
+///
+/// # Errors
+///
+/// Returns an error if the update fails.
+pub fn set_chunks_git_revision(
+    conn: &Connection,
+    file_path: &str,
+    commit: &str,
+    branch: Option<&str>,
+) -> Result<usize> {
+    conn.execute(
+        "UPDATE chunks SET git_commit = ?1, git_branch = ?2
+         WHERE file_path = ?3 AND (git_commit IS NOT ?1 OR git_branch IS NOT ?2)",
+        params![commit, branch, file_path],
+    )
+    .map_err(|e| StorageError::Database(format!("failed to set chunk revision: {e}")).into())
+}
+
 /// Update a chunk's embedding.
 ///
 /// # Errors
@@ -262,6 +403,174 @@ pub fn count_chunks_for_file(conn: &Connection, file_path: &str) -> Result<i64>
     .map_err(|e| StorageError::Database(format!("failed to count chunks: {e}")).into())
 }
 
+/// Count chunks grouped by language, largest first.
+///
+/// Chunks without a detected language are reported as `"unknown"`.
+///
+/// # Errors
+///
+/// Returns an error if the query fails.
+pub fn count_chunks_by_language(conn: &Connection) -> Result<Vec<(String, i64)>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT COALESCE(NULLIF(language, ''), 'unknown') AS lang, COUNT(*) AS n
+             FROM chunks GROUP BY lang ORDER BY n DESC, lang",
+        )
+        .map_err(|e| StorageError::Database(format!("failed to prepare query: {e}")))?;
+
+    let counts = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| StorageError::Database(format!("failed to count chunks: {e}")))?
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| StorageError::Database(format!("failed to read row: {e}")))?;
+
+    Ok(counts)
+}
+
+/// Summarize lines of code covered by the index.
+///
+/// Reports total and average lines per chunk, the longest chunk, and the
+/// `top_files` files with the most chunks. A single grouped query computes
+/// the totals over every file as window aggregates, so they're unaffected by
+/// the `LIMIT`.
+///
+/// # Errors
+///
+/// Returns an error if the query fails.
+#[allow(clippy::cast_precision_loss)]
+pub fn chunk_line_stats(conn: &Connection, top_files: usize) -> Result<ChunkLineStats> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT file_path, COUNT(*) AS n, SUM(end_line - start_line + 1),
+                    SUM(COUNT(*)) OVER (),
+                    SUM(SUM(end_line - start_line + 1)) OVER (),
+                    MAX(MAX(end_line - start_line + 1)) OVER ()
+             FROM chunks GROUP BY file_path ORDER BY n DESC, file_path LIMIT ?",
+        )
+        .map_err(|e| StorageError::Database(format!("failed to prepare query: {e}")))?;
+
+    // A limit of 0 would drop the row carrying the totals
+    let limit = i64::try_from(top_files.max(1)).unwrap_or(i64::MAX);
+    let rows = stmt
+        .query_map([limit], |row| {
+            Ok((
+                FileChunkStats {
+                    file_path: row.get(0)?,
+                    chunk_count: row.get(1)?,
+                    line_count: row.get(2)?,
+                },
+                row.get::<_, i64>(3)?,
+                row.get(4)?,
+                row.get(5)?,
+            ))
+        })
+        .map_err(|e| StorageError::Database(format!("failed to query line stats: {e}")))?
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| StorageError::Database(format!("failed to read row: {e}")))?;
+
+    let mut stats = ChunkLineStats::default();
+    let mut total_chunks = 0;
+    for (file, chunks, lines, max_lines) in rows {
+        total_chunks = chunks;
+        stats.total_lines = lines;
+        stats.max_chunk_lines = max_lines;
+        stats.largest_files.push(file);
+    }
+    stats.largest_files.truncate(top_files);
+
+    if total_chunks > 0 {
+        stats.average_chunk_lines = stats.total_lines as f64 / total_chunks as f64;
+    }
+    Ok(stats)
+}
+
+/// Get the most recent `indexed_at` timestamp across all chunks.
+///
+/// Returns `None` if nothing has been indexed yet.
+///
+/// # Errors
+///
+/// Returns an error if the query fails.
+pub fn latest_indexed_at(conn: &Connection) -> Result<Option<i64>> {
+    conn.query_row("SELECT MAX(indexed_at) FROM chunks", [], |row| row.get(0))
+        .map_err(|e| StorageError::Database(format!("failed to query indexed_at: {e}")).into())
+}
+
+/// Summarize indexed code per repository, largest first.
+///
+/// Chunks indexed without a repository are grouped under `repo: None`.
+///
+/// # Errors
+///
+/// Returns an error if the query fails.
+pub fn list_repos(conn: &Connection) -> Result<Vec<RepoStats>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT repo, COUNT(*) AS n, COUNT(DISTINCT file_path),
+                    GROUP_CONCAT(DISTINCT NULLIF(language, '')), MAX(indexed_at)
+             FROM chunks GROUP BY repo ORDER BY n DESC, repo",
+        )
+        .map_err(|e| StorageError::Database(format!("failed to prepare query: {e}")))?;
+
+    let repos = stmt
+        .query_map([], |row| {
+            let languages: Option<String> = row.get(3)?;
+            let mut languages: Vec<String> = languages
+                .unwrap_or_default()
+                .split(',')
+                .filter(|l| !l.is_empty())
+                .map(String::from)
+                .collect();
+            languages.sort();
+
+            Ok(RepoStats {
+                repo: row.get(0)?,
+                chunk_count: row.get(1)?,
+                file_count: row.get(2)?,
+                languages,
+                last_indexed_at: row.get(4)?,
+            })
+        })
+        .map_err(|e| StorageError::Database(format!("failed to list repos: {e}")))?
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| StorageError::Database(format!("failed to read row: {e}")))?;
+
+    Ok(repos)
+}
+
+/// List the git revisions indexed chunks were recorded at, largest first.
+///
+/// Chunks indexed outside a git repository are omitted.
+///
+/// # Errors
+///
+/// Returns an error if the query fails.
+pub fn list_indexed_revisions(conn: &Connection) -> Result<Vec<IndexedRevision>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT repo, git_commit, git_branch, MIN(file_path), COUNT(*) AS n
+             FROM chunks WHERE git_commit IS NOT NULL
+             GROUP BY repo, git_commit, git_branch ORDER BY n DESC, repo",
+        )
+        .map_err(|e| StorageError::Database(format!("failed to prepare query: {e}")))?;
+
+    let revisions = stmt
+        .query_map([], |row| {
+            Ok(IndexedRevision {
+                repo: row.get(0)?,
+                git_commit: row.get(1)?,
+                git_branch: row.get(2)?,
+                sample_path: row.get(3)?,
+                chunk_count: row.get(4)?,
+            })
+        })
+        .map_err(|e| StorageError::Database(format!("failed to list revisions: {e}")))?
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| StorageError::Database(format!("failed to read row: {e}")))?;
+
+    Ok(revisions)
+}
+
 /// Delete all chunks under a path prefix (directory).
 ///
 /// Returns the number of chunks deleted.
@@ -291,6 +600,9 @@ pub fn delete_chunks_by_path_prefix(conn: &Connection, path_prefix: &str) -> Res
         mapped_rows.flatten().collect()
     };
 
+    // Hand shared embeddings to surviving copies before dropping vectors
+    release_chunk_content(conn, &ids)?;
+
     // Delete from vector table
     for id in &ids {
         let _ = delete_vector(conn, CHUNK_VEC_TABLE, *id);
@@ -498,6 +810,164 @@ mod tests {
         .unwrap();
     }
 
+    #[test]
+    fn test_rename_file_path() {
+        let db = setup_test_db();
+
+        db.with_conn(|conn| {
+            insert_chunk(conn, &ChunkRecord::new("/old.rs", 0, 1, 5, "c1", "h1"))?;
+            insert_chunk(conn, &ChunkRecord::new("/old.rs", 1, 6, 10, "c2", "h1"))?;
+            crate::storage::upsert_file_state(
+                conn,
+                &crate::storage::FileState::new("/old.rs", 100, 10, "h1"),
+            )?;
+
+            let moved = rename_file_path(conn, "/old.rs", "/new.rs")?;
+            assert_eq!(moved, 2);
+
+            assert!(get_chunks_by_file(conn, "/old.rs")?.is_empty());
+            assert_eq!(get_chunks_by_file(conn, "/new.rs")?.len(), 2);
+            assert!(crate::storage::get_file_state(conn, "/old.rs")?.is_none());
+            assert_eq!(
+                crate::storage::get_file_state(conn, "/new.rs")?
+                    .unwrap()
+                    .hash,
+                "h1"
+            );
+
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_chunk_repo() {
+        let db = setup_test_db();
+
+        db.with_conn(|conn| {
+            let id = insert_chunk(
+                conn,
+                &ChunkRecord::new("/a/lib.rs", 0, 1, 5, "c1", "h1").with_repo("alpha"),
+            )?;
+            assert_eq!(get_chunk(conn, id)?.repo, Some("alpha".to_string()));
+
+            insert_chunk(conn, &ChunkRecord::new("/b/lib.rs", 0, 1, 5, "c2", "h2"))?;
+            assert_eq!(set_chunks_repo(conn, "/b/lib.rs", "beta")?, 1);
+            assert_eq!(set_chunks_repo(conn, "/b/lib.rs", "beta")?, 0);
+            assert_eq!(
+                get_chunks_by_file(conn, "/b/lib.rs")?[0].repo,
+                Some("beta".to_string())
+            );
+
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_chunk_git_revision() {
+        let db = setup_test_db();
+
+        db.with_conn(|conn| {
+            let id = insert_chunk(
+                conn,
+                &ChunkRecord::new("/a/lib.rs", 0, 1, 5, "c1", "h1")
+                    .with_repo("alpha")
+                    .with_git_revision("aaa", Some("main".to_string())),
+            )?;
+            let chunk = get_chunk(conn, id)?;
+            assert_eq!(chunk.git_commit.as_deref(), Some("aaa"));
+            assert_eq!(chunk.git_branch.as_deref(), Some("main"));
+
+            assert_eq!(set_chunks_git_revision(conn, "/a/lib.rs", "bbb", None)?, 1);
+            assert_eq!(set_chunks_git_revision(conn, "/a/lib.rs", "bbb", None)?, 0);
+            let chunk = get_chunk(conn, id)?;
+            assert_eq!(chunk.git_commit.as_deref(), Some("bbb"));
+            assert!(chunk.git_branch.is_none());
+
+            insert_chunk(conn, &ChunkRecord::new("/b/lib.rs", 0, 1, 5, "c2", "h2"))?;
+            let revisions = list_indexed_revisions(conn)?;
+            assert_eq!(revisions.len(), 1);
+            assert_eq!(revisions[0].repo.as_deref(), Some("alpha"));
+            assert_eq!(revisions[0].git_commit, "bbb");
+            assert_eq!(revisions[0].sample_path, "/a/lib.rs");
+            assert_eq!(revisions[0].chunk_count, 1);
+
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_list_repos() {
+        let db = setup_test_db();
+
+        db.with_conn(|conn| {
+            for (path, idx, lang, repo) in [
+                ("/p/a.rs", 0, "rust", Some("payments")),
+                ("/p/a.rs", 1, "rust", Some("payments")),
+                ("/p/b.py", 0, "python", Some("payments")),
+                ("/b/c.rs", 0, "rust", Some("billing")),
+                ("/x/d.rs", 0, "rust", None),
+            ] {
+                let mut chunk = ChunkRecord::new(path, idx, 1, 5, "code", "h").with_language(lang);
+                chunk.repo = repo.map(String::from);
+                insert_chunk(conn, &chunk)?;
+            }
+
+            let repos = list_repos(conn)?;
+            assert_eq!(repos.len(), 3);
+
+            assert_eq!(repos[0].repo.as_deref(), Some("payments"));
+            assert_eq!(repos[0].chunk_count, 3);
+            assert_eq!(repos[0].file_count, 2);
+            assert_eq!(repos[0].languages, vec!["python", "rust"]);
+
+            assert!(repos.iter().any(|r| r.repo.is_none() && r.chunk_count == 1));
+
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_backfill_chunk_repos() {
+        let db = setup_test_db();
+
+        db.with_conn(|conn| {
+            for (path, idx, repo) in [
+                ("/p/a.rs", 0, None),
+                ("/p/a.rs", 1, None),
+                ("/p/b.rs", 0, Some("payments")),
+                ("/tmp/scratch.rs", 0, None),
+            ] {
+                let mut chunk = ChunkRecord::new(path, idx, 1, 5, "code", "h");
+                chunk.repo = repo.map(String::from);
+                insert_chunk(conn, &chunk)?;
+            }
+
+            let mut asked = Vec::new();
+            let report = backfill_chunk_repos(conn, |path| {
+                asked.push(path.to_string());
+                path.starts_with("/p/").then(|| "payments".to_string())
+            })?;
+
+            // Only files missing a repo are looked up, once each
+            assert_eq!(asked, vec!["/p/a.rs", "/tmp/scratch.rs"]);
+            assert_eq!(report.files_updated, 1);
+            assert_eq!(report.chunks_updated, 2);
+            assert_eq!(report.files_unattributed, 1);
+            assert_eq!(report.chunks_unattributed, 1);
+
+            let repos = list_repos(conn)?;
+            assert_eq!(repos[0].repo.as_deref(), Some("payments"));
+            assert_eq!(repos[0].chunk_count, 3);
+
+            Ok(())
+        })
+        .unwrap();
+    }
+
     #[test]
     fn test_count_chunks() {
         let db = setup_test_db();
@@ -527,4 +997,80 @@ mod tests {
 
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_count_chunks_by_language() {
+        let db = setup_test_db();
+
+        db.with_conn(|conn| {
+            assert!(count_chunks_by_language(conn)?.is_empty());
+            assert_eq!(latest_indexed_at(conn)?, None);
+
+            insert_chunk(
+                conn,
+                &ChunkRecord::new("/a.rs", 0, 1, 5, "a", "h").with_language("rust"),
+            )?;
+            insert_chunk(
+                conn,
+                &ChunkRecord::new("/a.rs", 1, 6, 9, "b", "h").with_language("rust"),
+            )?;
+            insert_chunk(
+                conn,
+                &ChunkRecord::new("/b.ts", 0, 1, 5, "c", "h").with_language("typescript"),
+            )?;
+            insert_chunk(
+                conn,
+                &ChunkRecord::new("/c.txt", 0, 1, 5, "d", "h").with_language(""),
+            )?;
+
+            let counts = count_chunks_by_language(conn)?;
+            assert_eq!(counts[0], ("rust".to_string(), 2));
+            assert!(counts.contains(&("typescript".to_string(), 1)));
+            assert!(counts.contains(&("unknown".to_string(), 1)));
+
+            assert!(latest_indexed_at(conn)?.is_some());
+
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_chunk_line_stats() {
+        let db = setup_test_db();
+
+        db.with_conn(|conn| {
+            let stats = chunk_line_stats(conn, 10)?;
+            assert_eq!(stats.total_lines, 0);
+            assert!(stats.average_chunk_lines.abs() < f64::EPSILON);
+            assert!(stats.largest_files.is_empty());
+
+            // big.rs: 3 chunks covering 10 + 10 + 40 lines; small.rs: 1 line
+            insert_chunk(conn, &ChunkRecord::new("/big.rs", 0, 1, 10, "a", "h"))?;
+            insert_chunk(conn, &ChunkRecord::new("/big.rs", 1, 11, 20, "b", "h"))?;
+            insert_chunk(conn, &ChunkRecord::new("/big.rs", 2, 21, 60, "c", "h"))?;
+            insert_chunk(conn, &ChunkRecord::new("/small.rs", 0, 1, 1, "d", "h"))?;
+
+            let stats = chunk_line_stats(conn, 10)?;
+            assert_eq!(stats.total_lines, 61);
+            assert!((stats.average_chunk_lines - 15.25).abs() < f64::EPSILON);
+            assert_eq!(stats.max_chunk_lines, 40);
+            assert_eq!(stats.largest_files.len(), 2);
+            assert_eq!(stats.largest_files[0].file_path, "/big.rs");
+            assert_eq!(stats.largest_files[0].chunk_count, 3);
+            assert_eq!(stats.largest_files[0].line_count, 60);
+
+            // Totals still cover every file when the list is cut short
+            let stats = chunk_line_stats(conn, 1)?;
+            assert_eq!(stats.largest_files.len(), 1);
+            assert_eq!(stats.total_lines, 61);
+
+            let stats = chunk_line_stats(conn, 0)?;
+            assert!(stats.largest_files.is_empty());
+            assert_eq!(stats.max_chunk_lines, 40);
+
+            Ok(())
+        })
+        .unwrap();
+    }
 }
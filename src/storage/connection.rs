@@ -6,7 +6,7 @@
 //! - Automatic sqlite-vec extension loading
 
 use parking_lot::Mutex;
-use rusqlite::{Connection, OpenFlags};
+use rusqlite::{Connection, OpenFlags, Transaction, TransactionBehavior};
 use std::path::Path;
 use std::sync::Arc;
 
@@ -122,13 +122,19 @@ impl Database {
     where
         F: FnOnce(&Connection) -> Result<T>,
     {
+        let started = std::time::Instant::now();
         let conn = self.conn.lock();
-        f(&conn)
+        let result = f(&conn);
+        drop(conn);
+        crate::server::observability::timings::record_db(started.elapsed());
+        result
     }
 
     /// Execute a function that may modify the database.
     ///
-    /// Wraps the operation in an immediate transaction.
+    /// Wraps the operation in an immediate transaction. The transaction is
+    /// rolled back if the function fails or panics, so none of its writes
+    /// are committed and the connection stays usable.
     ///
     /// # Errors
     ///
@@ -137,27 +143,19 @@ impl Database {
     where
         F: FnOnce(&Connection) -> Result<T>,
     {
-        let result = {
-            let conn = self.conn.lock();
+        let started = std::time::Instant::now();
+        let conn = self.conn.lock();
 
-            conn.execute_batch("BEGIN IMMEDIATE")
-                .map_err(|e| StorageError::Database(format!("failed to begin transaction: {e}")))?;
-
-            let tx_result = match f(&conn) {
-                Ok(result) => {
-                    conn.execute_batch("COMMIT")
-                        .map_err(|e| StorageError::Database(format!("failed to commit: {e}")))?;
-                    Ok(result)
-                }
-                Err(e) => {
-                    let _ = conn.execute_batch("ROLLBACK");
-                    Err(e)
-                }
-            };
-            drop(conn);
-            tx_result
-        };
-        result
+        // Rolls back when dropped without committing, including on unwind
+        let tx = Transaction::new_unchecked(&conn, TransactionBehavior::Immediate)
+            .map_err(|e| StorageError::Database(format!("failed to begin transaction: {e}")))?;
+
+        let result = f(&tx)?;
+        tx.commit()
+            .map_err(|e| StorageError::Database(format!("failed to commit: {e}")))?;
+        drop(conn);
+        crate::server::observability::timings::record_db(started.elapsed());
+        Ok(result)
     }
 
     /// Get the database path.
@@ -166,6 +164,20 @@ impl Database {
         &self.path
     }
 
+    /// Bytes the database occupies on disk, including its WAL file.
+    ///
+    /// Returns `None` for in-memory databases or if the file can't be
+    /// stat'ed.
+    #[must_use]
+    pub fn size_on_disk(&self) -> Option<u64> {
+        if self.path == ":memory:" {
+            return None;
+        }
+        let main = std::fs::metadata(&self.path).ok()?.len();
+        let wal = std::fs::metadata(format!("{}-wal", self.path)).map_or(0, |m| m.len());
+        Some(main + wal)
+    }
+
     /// Check if the database is healthy.
     ///
     /// # Errors
@@ -297,6 +309,47 @@ mod tests {
         assert_eq!(count, 0);
     }
 
+    #[test]
+    fn test_with_transaction_rollback_on_panic() {
+        let db = Database::open_in_memory().unwrap();
+        db.with_conn(|conn| {
+            conn.execute("CREATE TABLE test (id INTEGER PRIMARY KEY)", [])
+                .map_err(|e| StorageError::Database(e.to_string()))?;
+            Ok(())
+        })
+        .unwrap();
+
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            db.with_transaction::<_, ()>(|conn| {
+                conn.execute("INSERT INTO test (id) VALUES (1)", [])
+                    .map_err(|e| StorageError::Database(e.to_string()))?;
+                panic!("simulated crash");
+            })
+        }));
+        assert!(panicked.is_err());
+
+        // Rolled back, and later transactions still work
+        db.with_transaction(|conn| {
+            conn.execute("INSERT INTO test (id) VALUES (2)", [])
+                .map_err(|e| StorageError::Database(e.to_string()))?;
+            Ok(())
+        })
+        .unwrap();
+        let ids: Vec<i64> = db
+            .with_conn(|conn| {
+                let mut stmt = conn
+                    .prepare("SELECT id FROM test")
+                    .map_err(|e| StorageError::Database(e.to_string()))?;
+                let rows = stmt
+                    .query_map([], |row| row.get(0))
+                    .map_err(|e| StorageError::Database(e.to_string()))?;
+                Ok(rows.flatten().collect())
+            })
+            .unwrap();
+
+        assert_eq!(ids, vec![2]);
+    }
+
     #[test]
     fn test_wal_mode_enabled() {
         let db = Database::open_in_memory().unwrap();
@@ -337,4 +390,33 @@ mod tests {
 
         assert_eq!(value, 123);
     }
+
+    #[test]
+    fn test_size_on_disk() {
+        assert!(Database::open_in_memory().unwrap().size_on_disk().is_none());
+
+        let tmp = TempDir::new().unwrap();
+        let db = Database::open(tmp.path().join("test.db")).unwrap();
+        db.with_conn(|conn| {
+            conn.execute_batch("CREATE TABLE t (x TEXT); INSERT INTO t VALUES ('data');")
+                .map_err(|e| StorageError::Database(e.to_string()).into())
+        })
+        .unwrap();
+        assert!(db.size_on_disk().unwrap() > 0);
+    }
+
+    #[tokio::test]
+    async fn test_db_time_is_measured() {
+        let db = Database::open_in_memory().unwrap();
+        let (result, breakdown) = crate::server::observability::timings::measure(async {
+            db.with_conn(|_| {
+                std::thread::sleep(std::time::Duration::from_millis(5));
+                Ok(())
+            })
+        })
+        .await;
+        result.unwrap();
+        assert!(breakdown.db >= std::time::Duration::from_millis(5));
+        assert!(breakdown.embedding.is_zero());
+    }
 }
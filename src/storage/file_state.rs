@@ -4,6 +4,7 @@ use rusqlite::Connection;
 use std::path::Path;
 
 use super::models::FileState;
+use super::Database;
 use crate::error::StorageError;
 use crate::Result;
 
@@ -211,6 +212,38 @@ mod tests {
         })
         .unwrap();
     }
+
+    #[test]
+    fn test_needs_reindex_by_hash() {
+        let db = setup_db();
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("lib.rs");
+        std::fs::write(&file, "fn main() {}").unwrap();
+        let path = file.to_string_lossy().to_string();
+        let hash = compute_content_hash(b"fn main() {}");
+
+        // New file needs reindex
+        assert!(needs_reindex_by_hash(&db, &path, 100, 12, false).unwrap());
+
+        db.with_conn(|conn| upsert_file_state(conn, &FileState::new(&path, 100, 12, &hash)))
+            .unwrap();
+
+        // Metadata unchanged - fast path, no reindex
+        assert!(!needs_reindex_by_hash(&db, &path, 100, 12, false).unwrap());
+
+        // Size changed - always reindex
+        assert!(needs_reindex_by_hash(&db, &path, 100, 13, false).unwrap());
+
+        // Touched (mtime changed, same content) - no reindex, mtime refreshed
+        assert!(!needs_reindex_by_hash(&db, &path, 200, 12, false).unwrap());
+        let state = db.with_conn(|conn| get_file_state(conn, &path)).unwrap();
+        assert_eq!(state.unwrap().mtime, 200);
+
+        // Same-size edit with preserved mtime is only caught when verifying
+        std::fs::write(&file, "fn test() {}").unwrap();
+        assert!(!needs_reindex_by_hash(&db, &path, 200, 12, false).unwrap());
+        assert!(needs_reindex_by_hash(&db, &path, 200, 12, true).unwrap());
+    }
 }
 
 /// Check if a file needs reindexing based on mtime AND size.
@@ -237,6 +270,71 @@ pub fn needs_reindex_by_metadata(
     }
 }
 
+/// Compute the content hash stored in `FileState.hash`.
+///
+/// Uses blake3, matching the hash the indexer records for each file.
+#[must_use]
+pub fn compute_content_hash(content: &[u8]) -> String {
+    blake3::hash(content).to_hex().to_string()
+}
+
+/// Check if a file needs reindexing, falling back to a content hash comparison.
+///
+/// The metadata fast path is used when mtime and size both match, unless
+/// `verify_hash` is set. When the size differs the file has certainly changed.
+/// Otherwise (mtime changed with identical size, or verification forced) the
+/// file is read and its hash compared against the stored one, so touch-only
+/// changes don't trigger a re-embed and same-size edits with preserved mtimes
+/// are still caught. When the hash matches, the stored mtime is refreshed so
+/// subsequent checks take the fast path again.
+///
+/// Takes the database rather than a connection so the file is read and
+/// hashed without holding the connection lock.
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub fn needs_reindex_by_hash(
+    db: &Database,
+    path: &str,
+    current_mtime: i64,
+    current_size: i64,
+    verify_hash: bool,
+) -> Result<bool> {
+    let Some(state) = db.with_conn(|conn| get_file_state(conn, path))? else {
+        return Ok(true); // New file
+    };
+
+    if state.size != current_size {
+        return Ok(true);
+    }
+
+    if state.mtime == current_mtime && !verify_hash {
+        return Ok(false);
+    }
+
+    let Ok(content) = std::fs::read(path) else {
+        return Ok(true);
+    };
+
+    if state.hash.is_empty() || compute_content_hash(&content) != state.hash {
+        return Ok(true);
+    }
+
+    if state.mtime != current_mtime {
+        db.with_conn(|conn| {
+            conn.execute(
+                "UPDATE file_state SET mtime = ? WHERE path = ?",
+                rusqlite::params![current_mtime, path],
+            )
+            .map_err(|e| StorageError::Database(e.to_string()))?;
+            Ok(())
+        })?;
+    }
+
+    Ok(false)
+}
+
 /// List tracked file paths under a given path prefix.
 ///
 /// # Errors
@@ -0,0 +1,70 @@
+//! Idempotency keys for retry-safe writes.
+//!
+//! Clients resend `add_lesson` and `add_checkpoint` after timeouts. A write
+//! carrying a key that was already used within [`IDEMPOTENCY_WINDOW_SECS`]
+//! resolves to the original record instead of inserting a duplicate. Keys are
+//! stored in a uniquely indexed `idempotency_key` column.
+
+use rusqlite::{Connection, OptionalExtension};
+
+use crate::error::StorageError;
+use crate::Result;
+
+/// How long an idempotency key keeps resolving to its original record.
+pub const IDEMPOTENCY_WINDOW_SECS: i64 = 24 * 60 * 60;
+
+/// Tables whose rows can carry an idempotency key.
+#[derive(Debug, Clone, Copy)]
+pub(super) enum KeyedTable {
+    Lessons,
+    Checkpoints,
+}
+
+impl KeyedTable {
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Lessons => "lessons",
+            Self::Checkpoints => "checkpoints",
+        }
+    }
+}
+
+/// Oldest `created_at` still inside the window.
+fn window_start() -> i64 {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    i64::try_from(now).unwrap_or_default() - IDEMPOTENCY_WINDOW_SECS
+}
+
+/// Id of the record written with `key` inside the window, if any.
+pub(super) fn find_by_key(
+    conn: &Connection,
+    table: KeyedTable,
+    key: &str,
+) -> Result<Option<String>> {
+    conn.query_row(
+        &format!(
+            "SELECT id FROM {} WHERE idempotency_key = ? AND created_at >= ?",
+            table.name()
+        ),
+        rusqlite::params![key, window_start()],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|e| StorageError::Database(format!("failed to look up idempotency key: {e}")).into())
+}
+
+/// Free `key` if it was last used before the window, so it can be reused.
+pub(super) fn release_expired_key(conn: &Connection, table: KeyedTable, key: &str) -> Result<()> {
+    conn.execute(
+        &format!(
+            "UPDATE {} SET idempotency_key = NULL WHERE idempotency_key = ? AND created_at < ?",
+            table.name()
+        ),
+        rusqlite::params![key, window_start()],
+    )
+    .map_err(|e| StorageError::Database(format!("failed to release idempotency key: {e}")))?;
+    Ok(())
+}
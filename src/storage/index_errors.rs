@@ -0,0 +1,155 @@
+//! Log of files that failed to index.
+//!
+//! The indexer records each failure here, keyed by path, and clears the
+//! entry once the file indexes successfully or is removed. The table is the
+//! worklist behind the `list_index_errors` and `retry_index_errors` tools.
+
+use rusqlite::{params, Connection};
+
+use super::models::IndexErrorRecord;
+use crate::error::StorageError;
+use crate::Result;
+
+/// Get current Unix timestamp as i64.
+#[inline]
+#[allow(clippy::cast_possible_wrap)]
+fn now_unix() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+/// Record a failed attempt to index `file_path`.
+///
+/// A file that already failed keeps its first failure time; the error is
+/// replaced with the latest one and the attempt count goes up.
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub fn record_index_error(conn: &Connection, file_path: &str, error: &str) -> Result<()> {
+    let now = now_unix();
+    conn.execute(
+        "INSERT INTO index_errors (file_path, error, attempts, first_failed_at, last_failed_at)
+         VALUES (?1, ?2, 1, ?3, ?3)
+         ON CONFLICT(file_path) DO UPDATE SET
+            error = excluded.error,
+            attempts = attempts + 1,
+            last_failed_at = excluded.last_failed_at",
+        params![file_path, error, now],
+    )
+    .map_err(|e| StorageError::Database(format!("failed to record index error: {e}")))?;
+
+    Ok(())
+}
+
+/// Forget any recorded failure for `file_path`.
+///
+/// Returns `true` if there was one.
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub fn clear_index_error(conn: &Connection, file_path: &str) -> Result<bool> {
+    let deleted = conn
+        .execute("DELETE FROM index_errors WHERE file_path = ?", [file_path])
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+    Ok(deleted > 0)
+}
+
+/// List recorded failures, most recent first.
+///
+/// With `path_prefix`, only files whose path starts with it are listed.
+///
+/// # Errors
+///
+/// Returns an error if the database query fails.
+pub fn list_index_errors(
+    conn: &Connection,
+    path_prefix: Option<&str>,
+    limit: usize,
+) -> Result<Vec<IndexErrorRecord>> {
+    #[allow(clippy::cast_possible_wrap)]
+    let limit = limit as i64;
+    let mut stmt = conn
+        .prepare(
+            "SELECT file_path, error, attempts, first_failed_at, last_failed_at
+             FROM index_errors
+             WHERE ?1 IS NULL OR substr(file_path, 1, length(?1)) = ?1
+             ORDER BY last_failed_at DESC, file_path
+             LIMIT ?2",
+        )
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    let errors = stmt
+        .query_map(params![path_prefix, limit], |row| {
+            Ok(IndexErrorRecord {
+                file_path: row.get(0)?,
+                error: row.get(1)?,
+                attempts: row.get(2)?,
+                first_failed_at: row.get(3)?,
+                last_failed_at: row.get(4)?,
+            })
+        })
+        .map_err(|e| StorageError::Database(e.to_string()))?
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    Ok(errors)
+}
+
+/// Count recorded failures, optionally under `path_prefix`.
+///
+/// # Errors
+///
+/// Returns an error if the database query fails.
+pub fn count_index_errors(conn: &Connection, path_prefix: Option<&str>) -> Result<i64> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM index_errors
+         WHERE ?1 IS NULL OR substr(file_path, 1, length(?1)) = ?1",
+        [path_prefix],
+        |row| row.get(0),
+    )
+    .map_err(|e| StorageError::Database(e.to_string()).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{migrate, Database};
+
+    #[test]
+    fn test_index_errors_lifecycle() {
+        let db = Database::open_in_memory().unwrap();
+        db.with_conn(|conn| migrate(conn)).unwrap();
+
+        db.with_conn(|conn| {
+            record_index_error(conn, "/repo/src/a.rs", "stream did not contain valid UTF-8")?;
+            record_index_error(conn, "/repo/src/a.rs", "permission denied")?;
+            record_index_error(conn, "/other/b.rs", "embedding failed")?;
+
+            let errors = list_index_errors(conn, None, 10)?;
+            assert_eq!(errors.len(), 2);
+            let a = errors
+                .iter()
+                .find(|e| e.file_path == "/repo/src/a.rs")
+                .unwrap();
+            assert_eq!(a.error, "permission denied");
+            assert_eq!(a.attempts, 2);
+            assert!(a.first_failed_at <= a.last_failed_at);
+
+            let scoped = list_index_errors(conn, Some("/repo/"), 10)?;
+            assert_eq!(scoped.len(), 1);
+            assert_eq!(count_index_errors(conn, Some("/repo/"))?, 1);
+            assert_eq!(list_index_errors(conn, None, 1)?.len(), 1);
+
+            assert!(clear_index_error(conn, "/repo/src/a.rs")?);
+            assert!(!clear_index_error(conn, "/repo/src/a.rs")?);
+            assert_eq!(count_index_errors(conn, None)?, 1);
+
+            Ok(())
+        })
+        .unwrap();
+    }
+}
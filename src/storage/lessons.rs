@@ -2,6 +2,8 @@
 
 use rusqlite::{params, Connection};
 
+use super::idempotency::{find_by_key, release_expired_key, KeyedTable};
+use super::lessons_search::LESSON_VEC_TABLE;
 use super::models::LessonRecord;
 use crate::error::StorageError;
 use crate::Result;
@@ -12,28 +14,79 @@ use crate::Result;
 ///
 /// Returns an error if JSON serialization or database insertion fails.
 pub fn insert_lesson(conn: &Connection, lesson: &LessonRecord) -> Result<()> {
+    insert_lesson_row(conn, lesson, None)?;
+    Ok(())
+}
+
+/// Insert a lesson unless `idempotency_key` was already used.
+///
+/// Returns the original lesson's id if the key was used within
+/// [`IDEMPOTENCY_WINDOW_SECS`](super::IDEMPOTENCY_WINDOW_SECS); otherwise
+/// inserts `lesson` under the key and returns `None`.
+///
+/// # Errors
+///
+/// Returns an error if JSON serialization or database insertion fails.
+pub fn insert_lesson_idempotent(
+    conn: &Connection,
+    lesson: &LessonRecord,
+    idempotency_key: &str,
+) -> Result<Option<String>> {
+    release_expired_key(conn, KeyedTable::Lessons, idempotency_key)?;
+    if insert_lesson_row(conn, lesson, Some(idempotency_key))? == 0 {
+        return find_by_key(conn, KeyedTable::Lessons, idempotency_key);
+    }
+    Ok(None)
+}
+
+/// Id of the lesson recorded with `idempotency_key` within the window.
+///
+/// # Errors
+///
+/// Returns an error if the database query fails.
+pub fn find_lesson_by_idempotency_key(
+    conn: &Connection,
+    idempotency_key: &str,
+) -> Result<Option<String>> {
+    find_by_key(conn, KeyedTable::Lessons, idempotency_key)
+}
+
+/// Insert a lesson row, skipping it if `idempotency_key` is already taken.
+///
+/// Returns the number of rows inserted.
+fn insert_lesson_row(
+    conn: &Connection,
+    lesson: &LessonRecord,
+    idempotency_key: Option<&str>,
+) -> Result<usize> {
     let tags_json = serde_json::to_string(&lesson.tags)
         .map_err(|e| StorageError::Database(format!("failed to serialize tags: {e}")))?;
 
-    conn.execute(
-        "INSERT INTO lessons (id, title, content, tags, severity, agent, repo, created_at, updated_at)
-         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
-        params![
-            lesson.id,
-            lesson.title,
-            lesson.content,
-            tags_json,
-            lesson.severity,
-            lesson.agent,
-            lesson.repo,
-            lesson.created_at,
-            lesson.updated_at,
-        ],
-    )
-    .map_err(|e| StorageError::Database(format!("failed to insert lesson: {e}")))?;
+    let inserted = conn
+        .execute(
+            "INSERT INTO lessons (id, title, content, tags, severity, agent, repo, created_at, updated_at,
+                                  idempotency_key)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+             ON CONFLICT(idempotency_key) DO NOTHING",
+            params![
+                lesson.id,
+                lesson.title,
+                lesson.content,
+                tags_json,
+                lesson.severity,
+                lesson.agent,
+                lesson.repo,
+                lesson.created_at,
+                lesson.updated_at,
+                idempotency_key,
+            ],
+        )
+        .map_err(|e| StorageError::Database(format!("failed to insert lesson: {e}")))?;
 
-    tracing::trace!(id = %lesson.id, "Inserted lesson");
-    Ok(())
+    if inserted > 0 {
+        tracing::trace!(id = %lesson.id, "Inserted lesson");
+    }
+    Ok(inserted)
 }
 
 /// Get a lesson by ID.
@@ -43,7 +96,8 @@ pub fn insert_lesson(conn: &Connection, lesson: &LessonRecord) -> Result<()> {
 /// Returns an error if the lesson is not found or database query fails.
 pub fn get_lesson(conn: &Connection, id: &str) -> Result<LessonRecord> {
     conn.query_row(
-        "SELECT id, title, content, tags, severity, agent, repo, created_at, updated_at
+        "SELECT id, title, content, tags, severity, agent, repo, created_at, updated_at,
+                hit_count, useful_count, last_accessed, archived
          FROM lessons WHERE id = ?",
         [id],
         |row| {
@@ -60,6 +114,10 @@ pub fn get_lesson(conn: &Connection, id: &str) -> Result<LessonRecord> {
                 repo: row.get(6)?,
                 created_at: row.get(7)?,
                 updated_at: row.get(8)?,
+                hit_count: row.get(9)?,
+                useful_count: row.get(10)?,
+                last_accessed: row.get(11)?,
+                archived: row.get(12)?,
                 embedding: None,
             })
         },
@@ -115,7 +173,7 @@ pub fn update_lesson(conn: &Connection, lesson: &LessonRecord) -> Result<()> {
     Ok(())
 }
 
-/// Delete a lesson by ID.
+/// Permanently delete a lesson and its embedding by ID.
 ///
 /// # Errors
 ///
@@ -133,6 +191,51 @@ pub fn delete_lesson(conn: &Connection, id: &str) -> Result<()> {
         .into());
     }
 
+    // The vector table only exists when vector search is available
+    let _ = conn.execute(
+        &format!("DELETE FROM {LESSON_VEC_TABLE} WHERE id = ?"),
+        [id],
+    );
+
+    Ok(())
+}
+
+/// Archive a lesson, hiding it from listings and search until restored.
+///
+/// The row and its embedding are kept, so [`restore_lesson`] is instant.
+///
+/// # Errors
+///
+/// Returns an error if the lesson is not found or database update fails.
+pub fn archive_lesson(conn: &Connection, id: &str) -> Result<()> {
+    set_lesson_archived(conn, id, true)
+}
+
+/// Restore an archived lesson. Restoring a live lesson is a no-op.
+///
+/// # Errors
+///
+/// Returns an error if the lesson is not found or database update fails.
+pub fn restore_lesson(conn: &Connection, id: &str) -> Result<()> {
+    set_lesson_archived(conn, id, false)
+}
+
+fn set_lesson_archived(conn: &Connection, id: &str, archived: bool) -> Result<()> {
+    let rows = conn
+        .execute(
+            "UPDATE lessons SET archived = ? WHERE id = ?",
+            params![archived, id],
+        )
+        .map_err(|e| StorageError::Database(format!("failed to archive lesson: {e}")))?;
+
+    if rows == 0 {
+        return Err(StorageError::NotFound {
+            entity: "lesson",
+            id: id.to_string(),
+        }
+        .into());
+    }
+
     Ok(())
 }
 
@@ -144,8 +247,9 @@ pub fn delete_lesson(conn: &Connection, id: &str) -> Result<()> {
 pub fn list_lessons(conn: &Connection) -> Result<Vec<LessonRecord>> {
     let mut stmt = conn
         .prepare(
-            "SELECT id, title, content, tags, severity, agent, repo, created_at, updated_at
-             FROM lessons ORDER BY created_at DESC",
+            "SELECT id, title, content, tags, severity, agent, repo, created_at, updated_at,
+                    hit_count, useful_count, last_accessed, archived
+             FROM lessons WHERE archived = 0 ORDER BY created_at DESC, id DESC",
         )
         .map_err(|e| StorageError::Database(e.to_string()))?;
 
@@ -164,6 +268,10 @@ pub fn list_lessons(conn: &Connection) -> Result<Vec<LessonRecord>> {
                 repo: row.get(6)?,
                 created_at: row.get(7)?,
                 updated_at: row.get(8)?,
+                hit_count: row.get(9)?,
+                useful_count: row.get(10)?,
+                last_accessed: row.get(11)?,
+                archived: row.get(12)?,
                 embedding: None,
             })
         })
@@ -184,8 +292,10 @@ pub fn list_lessons(conn: &Connection) -> Result<Vec<LessonRecord>> {
 pub fn list_lessons_by_severity(conn: &Connection, severity: &str) -> Result<Vec<LessonRecord>> {
     let mut stmt = conn
         .prepare(
-            "SELECT id, title, content, tags, severity, agent, repo, created_at, updated_at
-             FROM lessons WHERE severity = ? ORDER BY created_at DESC",
+            "SELECT id, title, content, tags, severity, agent, repo, created_at, updated_at,
+                    hit_count, useful_count, last_accessed, archived
+             FROM lessons WHERE severity = ? AND archived = 0
+             ORDER BY created_at DESC, id DESC",
         )
         .map_err(|e| StorageError::Database(e.to_string()))?;
 
@@ -204,6 +314,10 @@ pub fn list_lessons_by_severity(conn: &Connection, severity: &str) -> Result<Vec
                 repo: row.get(6)?,
                 created_at: row.get(7)?,
                 updated_at: row.get(8)?,
+                hit_count: row.get(9)?,
+                useful_count: row.get(10)?,
+                last_accessed: row.get(11)?,
+                archived: row.get(12)?,
                 embedding: None,
             })
         })
@@ -224,8 +338,10 @@ pub fn list_lessons_by_severity(conn: &Connection, severity: &str) -> Result<Vec
 pub fn list_lessons_by_agent(conn: &Connection, agent: &str) -> Result<Vec<LessonRecord>> {
     let mut stmt = conn
         .prepare(
-            "SELECT id, title, content, tags, severity, agent, repo, created_at, updated_at
-             FROM lessons WHERE agent = ? ORDER BY created_at DESC",
+            "SELECT id, title, content, tags, severity, agent, repo, created_at, updated_at,
+                    hit_count, useful_count, last_accessed, archived
+             FROM lessons WHERE agent = ? AND archived = 0
+             ORDER BY created_at DESC, id DESC",
         )
         .map_err(|e| StorageError::Database(e.to_string()))?;
 
@@ -244,6 +360,55 @@ pub fn list_lessons_by_agent(conn: &Connection, agent: &str) -> Result<Vec<Lesso
                 repo: row.get(6)?,
                 created_at: row.get(7)?,
                 updated_at: row.get(8)?,
+                hit_count: row.get(9)?,
+                useful_count: row.get(10)?,
+                last_accessed: row.get(11)?,
+                archived: row.get(12)?,
+                embedding: None,
+            })
+        })
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    let mut result = Vec::new();
+    for lesson in lessons {
+        result.push(lesson.map_err(|e| StorageError::Database(e.to_string()))?);
+    }
+    Ok(result)
+}
+
+/// List archived lessons, newest first.
+///
+/// # Errors
+///
+/// Returns an error if the database query fails.
+pub fn list_archived_lessons(conn: &Connection) -> Result<Vec<LessonRecord>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, title, content, tags, severity, agent, repo, created_at, updated_at,
+                    hit_count, useful_count, last_accessed, archived
+             FROM lessons WHERE archived = 1 ORDER BY created_at DESC, id DESC",
+        )
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    let lessons = stmt
+        .query_map([], |row| {
+            let tags_json: String = row.get(3)?;
+            let tags: Vec<String> = serde_json::from_str(&tags_json).unwrap_or_default();
+
+            Ok(LessonRecord {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                content: row.get(2)?,
+                tags,
+                severity: row.get(4)?,
+                agent: row.get(5)?,
+                repo: row.get(6)?,
+                created_at: row.get(7)?,
+                updated_at: row.get(8)?,
+                hit_count: row.get(9)?,
+                useful_count: row.get(10)?,
+                last_accessed: row.get(11)?,
+                archived: row.get(12)?,
                 embedding: None,
             })
         })
@@ -256,14 +421,214 @@ pub fn list_lessons_by_agent(conn: &Connection, agent: &str) -> Result<Vec<Lesso
     Ok(result)
 }
 
-/// Count total lessons.
+/// Count archived lessons.
+///
+/// # Errors
+///
+/// Returns an error if the database query fails.
+pub fn count_archived_lessons(conn: &Connection) -> Result<i64> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM lessons WHERE archived = 1",
+        [],
+        |row| row.get(0),
+    )
+    .map_err(|e| StorageError::Database(e.to_string()).into())
+}
+
+/// Count lessons, not counting archived ones.
 ///
 /// # Errors
 ///
 /// Returns an error if the database query fails.
 pub fn count_lessons(conn: &Connection) -> Result<i64> {
-    conn.query_row("SELECT COUNT(*) FROM lessons", [], |row| row.get(0))
-        .map_err(|e| StorageError::Database(e.to_string()).into())
+    conn.query_row(
+        "SELECT COUNT(*) FROM lessons WHERE archived = 0",
+        [],
+        |row| row.get(0),
+    )
+    .map_err(|e| StorageError::Database(e.to_string()).into())
+}
+
+/// Record that lessons were returned to an agent.
+///
+/// Bumps `hit_count` and sets `last_accessed` for each id. Unknown ids are
+/// ignored.
+///
+/// # Errors
+///
+/// Returns an error if the database update fails.
+pub fn record_lesson_hits(conn: &Connection, ids: &[&str]) -> Result<()> {
+    let now = i64::try_from(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+    )
+    .unwrap_or_default();
+
+    let mut stmt = conn
+        .prepare("UPDATE lessons SET hit_count = hit_count + 1, last_accessed = ? WHERE id = ?")
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+    for id in ids {
+        stmt.execute(params![now, id])
+            .map_err(|e| StorageError::Database(format!("failed to record lesson hit: {e}")))?;
+    }
+
+    Ok(())
+}
+
+/// Record that a lesson helped an agent, returning its new `useful_count`.
+///
+/// # Errors
+///
+/// Returns an error if the lesson is not found or database update fails.
+pub fn mark_lesson_useful(conn: &Connection, id: &str) -> Result<i64> {
+    conn.query_row(
+        "UPDATE lessons SET useful_count = useful_count + 1 WHERE id = ? RETURNING useful_count",
+        [id],
+        |row| row.get(0),
+    )
+    .map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => StorageError::NotFound {
+            entity: "lesson",
+            id: id.to_string(),
+        }
+        .into(),
+        e => StorageError::Database(format!("failed to mark lesson useful: {e}")).into(),
+    })
+}
+
+/// Count lessons grouped by severity, largest first.
+///
+/// # Errors
+///
+/// Returns an error if the database query fails.
+pub fn count_lessons_by_severity(conn: &Connection) -> Result<Vec<(String, i64)>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT severity, COUNT(*) AS n FROM lessons WHERE archived = 0
+             GROUP BY severity ORDER BY n DESC, severity",
+        )
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    let counts = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| StorageError::Database(e.to_string()))?
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    Ok(counts)
+}
+
+/// Count lessons grouped by repository, largest first.
+///
+/// Lessons not tied to a repository are reported as `"unscoped"`.
+///
+/// # Errors
+///
+/// Returns an error if the query fails.
+pub fn count_lessons_by_repo(conn: &Connection) -> Result<Vec<(String, i64)>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT COALESCE(NULLIF(repo, ''), 'unscoped') AS r, COUNT(*) AS n
+             FROM lessons WHERE archived = 0 GROUP BY r ORDER BY n DESC, r",
+        )
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    let counts = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| StorageError::Database(e.to_string()))?
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    Ok(counts)
+}
+
+/// Severities every server accepts, most severe first.
+pub const DEFAULT_SEVERITIES: [&str; 3] = ["critical", "warning", "info"];
+
+/// Most edits between a stored severity and the one it's corrected to.
+const MAX_SEVERITY_EDITS: usize = 2;
+
+/// Canonical form of `severity` (trimmed, lowercased) if it is one of
+/// [`DEFAULT_SEVERITIES`] or `extra`.
+#[must_use]
+pub fn canonical_severity(severity: &str, extra: &[String]) -> Option<String> {
+    let severity = severity.trim().to_lowercase();
+    let known = DEFAULT_SEVERITIES.contains(&severity.as_str())
+        || extra.iter().any(|e| e.eq_ignore_ascii_case(&severity));
+    known.then_some(severity)
+}
+
+/// The allowed severity closest to a misspelled one, if any is within
+/// two edits (`crticial` -> `critical`).
+#[must_use]
+pub fn closest_severity(severity: &str, extra: &[String]) -> Option<String> {
+    let severity = severity.trim().to_lowercase();
+    DEFAULT_SEVERITIES
+        .iter()
+        .map(|s| (*s).to_string())
+        .chain(extra.iter().map(|e| e.to_lowercase()))
+        .map(|allowed| (edit_distance(&severity, &allowed), allowed))
+        .filter(|(distance, _)| *distance <= MAX_SEVERITY_EDITS)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, allowed)| allowed)
+}
+
+/// Levenshtein distance between two strings, by character.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut row = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = prev[j] + usize::from(ca != *cb);
+            row.push(substitution.min(prev[j + 1] + 1).min(row[j] + 1));
+        }
+        prev = row;
+    }
+    prev[b.len()]
+}
+
+/// Rewrite lesson severities that aren't in the allowed set.
+///
+/// Case and whitespace variants become their canonical form, misspellings
+/// become the closest allowed severity, and anything else becomes `info`.
+/// Returns the number of lessons changed.
+///
+/// # Errors
+///
+/// Returns an error if the database query or update fails.
+pub fn normalize_lesson_severities(conn: &Connection, extra: &[String]) -> Result<usize> {
+    let mut stmt = conn
+        .prepare("SELECT DISTINCT severity FROM lessons")
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+    let stored: Vec<String> = stmt
+        .query_map([], |row| row.get(0))
+        .map_err(|e| StorageError::Database(e.to_string()))?
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    let mut changed = 0;
+    for severity in stored {
+        let corrected = canonical_severity(&severity, extra)
+            .or_else(|| closest_severity(&severity, extra))
+            .unwrap_or_else(|| "info".to_string());
+        if corrected == severity {
+            continue;
+        }
+
+        let rows = conn
+            .execute(
+                "UPDATE lessons SET severity = ? WHERE severity = ?",
+                params![corrected, severity],
+            )
+            .map_err(|e| StorageError::Database(e.to_string()))?;
+        tracing::info!(from = %severity, to = %corrected, lessons = rows, "Corrected lesson severity");
+        changed += rows;
+    }
+
+    Ok(changed)
 }
 
 #[cfg(test)]
@@ -340,6 +705,45 @@ mod tests {
         .unwrap();
     }
 
+    #[test]
+    fn test_archive_and_restore() {
+        let db = setup_db();
+
+        db.with_conn(|conn| {
+            let kept = LessonRecord::new("Kept", "Content", vec!["rust".to_string()]);
+            let archived = LessonRecord::new("Archived", "Content", vec!["rust".to_string()])
+                .with_severity("critical");
+            insert_lesson(conn, &kept)?;
+            insert_lesson(conn, &archived)?;
+
+            archive_lesson(conn, &archived.id)?;
+            assert!(get_lesson(conn, &archived.id)?.archived);
+            assert!(!get_lesson(conn, &kept.id)?.archived);
+
+            // Hidden from listings, counts and text search
+            let ids = |lessons: Vec<LessonRecord>| -> Vec<String> {
+                lessons.into_iter().map(|l| l.id).collect()
+            };
+            assert_eq!(ids(list_lessons(conn)?), vec![kept.id.clone()]);
+            assert!(list_lessons_by_severity(conn, "critical")?.is_empty());
+            assert_eq!(count_lessons(conn)?, 1);
+            assert_eq!(count_archived_lessons(conn)?, 1);
+            assert!(crate::storage::search_lessons_by_text(conn, "Archived", 10)?.is_empty());
+            assert_eq!(ids(list_archived_lessons(conn)?), vec![archived.id.clone()]);
+
+            restore_lesson(conn, &archived.id)?;
+            assert!(!get_lesson(conn, &archived.id)?.archived);
+            assert_eq!(count_lessons(conn)?, 2);
+            assert_eq!(count_archived_lessons(conn)?, 0);
+
+            assert!(archive_lesson(conn, "missing").is_err());
+            assert!(restore_lesson(conn, "missing").is_err());
+
+            Ok(())
+        })
+        .unwrap();
+    }
+
     #[test]
     fn test_list_by_severity() {
         let db = setup_db();
@@ -385,4 +789,223 @@ mod tests {
         })
         .unwrap();
     }
+
+    #[test]
+    fn test_concurrent_inserts_have_unique_ids() {
+        let db = setup_db();
+
+        let handles: Vec<_> = (0..8)
+            .map(|t| {
+                let db = db.clone();
+                std::thread::spawn(move || {
+                    for i in 0..100 {
+                        let lesson = LessonRecord::new(format!("L{t}-{i}"), "C", vec![]);
+                        db.with_conn(|conn| insert_lesson(conn, &lesson)).unwrap();
+                    }
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        db.with_conn(|conn| {
+            assert_eq!(count_lessons(conn)?, 800);
+            let distinct: i64 = conn
+                .query_row("SELECT COUNT(DISTINCT id) FROM lessons", [], |row| {
+                    row.get(0)
+                })
+                .unwrap();
+            assert_eq!(distinct, 800);
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_count_by_severity() {
+        let db = setup_db();
+
+        db.with_conn(|conn| {
+            insert_lesson(
+                conn,
+                &LessonRecord::new("L1", "C1", vec![]).with_severity("critical"),
+            )?;
+            insert_lesson(
+                conn,
+                &LessonRecord::new("L2", "C2", vec![]).with_severity("critical"),
+            )?;
+            insert_lesson(conn, &LessonRecord::new("L3", "C3", vec![]))?;
+
+            let counts = count_lessons_by_severity(conn)?;
+            assert_eq!(
+                counts,
+                vec![("critical".to_string(), 2), ("info".to_string(), 1)]
+            );
+
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_count_by_repo() {
+        let db = setup_db();
+
+        db.with_conn(|conn| {
+            for title in ["L1", "L2"] {
+                insert_lesson(
+                    conn,
+                    &LessonRecord::new(title, "C", vec![]).with_repo("payments"),
+                )?;
+            }
+            insert_lesson(
+                conn,
+                &LessonRecord::new("L3", "C", vec![]).with_repo("billing"),
+            )?;
+            insert_lesson(conn, &LessonRecord::new("L4", "C", vec![]))?;
+
+            let counts = count_lessons_by_repo(conn)?;
+            assert_eq!(
+                counts,
+                vec![
+                    ("payments".to_string(), 2),
+                    ("billing".to_string(), 1),
+                    ("unscoped".to_string(), 1),
+                ]
+            );
+
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_lesson_usage_tracking() {
+        let db = setup_db();
+        let lesson = LessonRecord::new("Title", "Content", vec![]);
+        let id = lesson.id.clone();
+
+        db.with_conn(|conn| insert_lesson(conn, &lesson)).unwrap();
+
+        let fresh = db.with_conn(|conn| get_lesson(conn, &id)).unwrap();
+        assert_eq!(fresh.hit_count, 0);
+        assert_eq!(fresh.useful_count, 0);
+        assert!(fresh.last_accessed.is_none());
+
+        db.with_conn(|conn| record_lesson_hits(conn, &[id.as_str(), "missing"]))
+            .unwrap();
+        db.with_conn(|conn| record_lesson_hits(conn, &[id.as_str()]))
+            .unwrap();
+        assert_eq!(
+            db.with_conn(|conn| mark_lesson_useful(conn, &id)).unwrap(),
+            1
+        );
+
+        let used = db.with_conn(|conn| get_lesson(conn, &id)).unwrap();
+        assert_eq!(used.hit_count, 2);
+        assert_eq!(used.useful_count, 1);
+        assert!(used.last_accessed.is_some());
+
+        let err = db
+            .with_conn(|conn| mark_lesson_useful(conn, "missing"))
+            .unwrap_err();
+        assert!(err.to_string().contains("not found"));
+    }
+
+    #[test]
+    fn test_insert_lesson_idempotent() {
+        let db = setup_db();
+
+        db.with_conn(|conn| {
+            let first = LessonRecord::new("Retry", "Sent once", vec![]);
+            assert!(insert_lesson_idempotent(conn, &first, "key-1")?.is_none());
+
+            // A retry with a fresh id resolves to the original
+            let retry = LessonRecord::new("Retry", "Sent once", vec![]);
+            assert_eq!(
+                insert_lesson_idempotent(conn, &retry, "key-1")?,
+                Some(first.id.clone())
+            );
+            assert_eq!(
+                find_lesson_by_idempotency_key(conn, "key-1")?,
+                Some(first.id.clone())
+            );
+            assert_eq!(count_lessons(conn)?, 1);
+
+            // Once the window has passed the key can be reused
+            conn.execute(
+                "UPDATE lessons SET created_at = 0 WHERE id = ?",
+                [&first.id],
+            )
+            .unwrap();
+            assert!(find_lesson_by_idempotency_key(conn, "key-1")?.is_none());
+            assert!(insert_lesson_idempotent(conn, &retry, "key-1")?.is_none());
+            assert_eq!(count_lessons(conn)?, 2);
+
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_canonical_and_closest_severity() {
+        let extra = vec!["security".to_string()];
+        assert_eq!(
+            canonical_severity(" Critical ", &[]).as_deref(),
+            Some("critical")
+        );
+        assert_eq!(
+            canonical_severity("SECURITY", &extra).as_deref(),
+            Some("security")
+        );
+        assert!(canonical_severity("security", &[]).is_none());
+        assert!(canonical_severity("crticial", &[]).is_none());
+
+        assert_eq!(
+            closest_severity("crticial", &[]).as_deref(),
+            Some("critical")
+        );
+        assert_eq!(closest_severity("warnign", &[]).as_deref(), Some("warning"));
+        assert_eq!(
+            closest_severity("securty", &extra).as_deref(),
+            Some("security")
+        );
+        assert!(closest_severity("blocker", &[]).is_none());
+    }
+
+    #[test]
+    fn test_normalize_lesson_severities() {
+        let db = setup_db();
+
+        db.with_conn(|conn| {
+            for severity in ["crticial", "Warning", "blocker", "info", "security"] {
+                insert_lesson(
+                    conn,
+                    &LessonRecord::new(severity, "C", vec![]).with_severity(severity),
+                )?;
+            }
+
+            let changed = normalize_lesson_severities(conn, &["security".to_string()])?;
+            assert_eq!(changed, 3);
+
+            let counts = count_lessons_by_severity(conn)?;
+            assert_eq!(
+                counts,
+                vec![
+                    ("info".to_string(), 2),
+                    ("critical".to_string(), 1),
+                    ("security".to_string(), 1),
+                    ("warning".to_string(), 1),
+                ]
+            );
+
+            assert_eq!(normalize_lesson_severities(conn, &[])?, 1);
+            assert_eq!(normalize_lesson_severities(conn, &[])?, 0);
+
+            Ok(())
+        })
+        .unwrap();
+    }
 }
@@ -2,22 +2,27 @@
 
 use rusqlite::Connection;
 
+use super::meta::get_distance_metric;
 use super::models::{LessonRecord, SearchResult};
+use super::vector::vector_k;
 use crate::error::StorageError;
 use crate::Result;
 
-const LESSON_VEC_TABLE: &str = "lesson_embeddings";
+pub(super) const LESSON_VEC_TABLE: &str = "lesson_embeddings";
 
 /// Initialize lesson vector table.
 ///
+/// Uses the distance metric recorded for the database.
+///
 /// # Errors
 ///
 /// Returns an error if the table cannot be created.
 pub fn init_lesson_vectors(conn: &Connection) -> Result<()> {
+    let option = get_distance_metric(conn)?.column_option();
     let sql = format!(
         "CREATE VIRTUAL TABLE IF NOT EXISTS {LESSON_VEC_TABLE} USING vec0(
             id TEXT PRIMARY KEY,
-            embedding FLOAT[384]
+            embedding FLOAT[384]{option}
         )"
     );
 
@@ -53,6 +58,9 @@ pub fn store_lesson_embedding(conn: &Connection, lesson_id: &str, embedding: &[f
 
 /// Search lessons by embedding similarity.
 ///
+/// Results are nearest first; lessons at the same distance are ordered
+/// newest first, then by ID.
+///
 /// # Errors
 ///
 /// Returns an error if the search query fails.
@@ -75,20 +83,21 @@ pub fn search_lessons_by_embedding(
         .map_err(|e| StorageError::Vector(format!("failed to prepare search: {e}")))?;
 
     let candidates: Vec<(String, f32)> = stmt
-        .query_map(
-            rusqlite::params![blob, i64::try_from(limit).unwrap_or(10)],
-            |row| Ok((row.get(0)?, row.get(1)?)),
-        )
+        .query_map(rusqlite::params![blob, vector_k(limit)], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
         .map_err(|e| StorageError::Vector(e.to_string()))?
         .filter_map(std::result::Result::ok)
         .collect();
 
+    let metric = get_distance_metric(conn)?;
     let mut results = Vec::new();
     for (id, distance) in candidates {
         if let Ok(lesson) = super::lessons::get_lesson(conn, &id) {
-            results.push(SearchResult::new(lesson, distance));
+            results.push(SearchResult::with_metric(lesson, distance, metric));
         }
     }
+    SearchResult::sort_by_distance(&mut results, |l| (l.created_at, l.id.as_str()));
 
     Ok(results)
 }
@@ -107,9 +116,10 @@ pub fn search_lessons_by_text(
 
     let mut stmt = conn
         .prepare(
-            "SELECT id, title, content, tags, severity, agent, repo, created_at, updated_at
+            "SELECT id, title, content, tags, severity, agent, repo, created_at, updated_at,
+                    hit_count, useful_count, last_accessed, archived
              FROM lessons
-             WHERE title LIKE ? OR content LIKE ?
+             WHERE (title LIKE ? OR content LIKE ?) AND archived = 0
              ORDER BY created_at DESC
              LIMIT ?",
         )
@@ -132,6 +142,10 @@ pub fn search_lessons_by_text(
                     repo: row.get(6)?,
                     created_at: row.get(7)?,
                     updated_at: row.get(8)?,
+                    hit_count: row.get(9)?,
+                    useful_count: row.get(10)?,
+                    last_accessed: row.get(11)?,
+                    archived: row.get(12)?,
                     embedding: None,
                 })
             },
@@ -156,9 +170,10 @@ pub fn search_lessons_by_tag(conn: &Connection, tag: &str) -> Result<Vec<LessonR
 
     let mut stmt = conn
         .prepare(
-            "SELECT id, title, content, tags, severity, agent, repo, created_at, updated_at
+            "SELECT id, title, content, tags, severity, agent, repo, created_at, updated_at,
+                    hit_count, useful_count, last_accessed, archived
              FROM lessons
-             WHERE tags LIKE ?
+             WHERE tags LIKE ? AND archived = 0
              ORDER BY created_at DESC",
         )
         .map_err(|e| StorageError::Database(e.to_string()))?;
@@ -178,6 +193,10 @@ pub fn search_lessons_by_tag(conn: &Connection, tag: &str) -> Result<Vec<LessonR
                 repo: row.get(6)?,
                 created_at: row.get(7)?,
                 updated_at: row.get(8)?,
+                hit_count: row.get(9)?,
+                useful_count: row.get(10)?,
+                last_accessed: row.get(11)?,
+                archived: row.get(12)?,
                 embedding: None,
             })
         })
@@ -208,9 +227,10 @@ pub fn search_lessons_by_tags_all(conn: &Connection, tags: &[&str]) -> Result<Ve
     let where_condition = where_clauses.join(" AND ");
 
     let sql = format!(
-        "SELECT id, title, content, tags, severity, agent, repo, created_at, updated_at
+        "SELECT id, title, content, tags, severity, agent, repo, created_at, updated_at,
+                hit_count, useful_count, last_accessed, archived
          FROM lessons
-         WHERE {where_condition}
+         WHERE ({where_condition}) AND archived = 0
          ORDER BY created_at DESC"
     );
 
@@ -233,6 +253,10 @@ pub fn search_lessons_by_tags_all(conn: &Connection, tags: &[&str]) -> Result<Ve
                 repo: row.get(6)?,
                 created_at: row.get(7)?,
                 updated_at: row.get(8)?,
+                hit_count: row.get(9)?,
+                useful_count: row.get(10)?,
+                last_accessed: row.get(11)?,
+                archived: row.get(12)?,
                 embedding: None,
             })
         })
@@ -263,9 +287,10 @@ pub fn search_lessons_by_tags_any(conn: &Connection, tags: &[&str]) -> Result<Ve
     let where_condition = where_clauses.join(" OR ");
 
     let sql = format!(
-        "SELECT id, title, content, tags, severity, agent, repo, created_at, updated_at
+        "SELECT id, title, content, tags, severity, agent, repo, created_at, updated_at,
+                hit_count, useful_count, last_accessed, archived
          FROM lessons
-         WHERE {where_condition}
+         WHERE ({where_condition}) AND archived = 0
          ORDER BY created_at DESC"
     );
 
@@ -288,6 +313,10 @@ pub fn search_lessons_by_tags_any(conn: &Connection, tags: &[&str]) -> Result<Ve
                 repo: row.get(6)?,
                 created_at: row.get(7)?,
                 updated_at: row.get(8)?,
+                hit_count: row.get(9)?,
+                useful_count: row.get(10)?,
+                last_accessed: row.get(11)?,
+                archived: row.get(12)?,
                 embedding: None,
             })
         })
@@ -307,7 +336,7 @@ pub fn search_lessons_by_tags_any(conn: &Connection, tags: &[&str]) -> Result<Ve
 /// Returns an error if the query fails.
 pub fn get_all_tags(conn: &Connection) -> Result<Vec<(String, i64)>> {
     let mut stmt = conn
-        .prepare("SELECT tags FROM lessons")
+        .prepare("SELECT tags FROM lessons WHERE archived = 0")
         .map_err(|e| StorageError::Database(e.to_string()))?;
 
     let mut tag_counts: std::collections::HashMap<String, i64> = std::collections::HashMap::new();
@@ -333,6 +362,46 @@ pub fn get_all_tags(conn: &Connection) -> Result<Vec<(String, i64)>> {
     Ok(result)
 }
 
+/// Existing tags starting with `prefix`, most used first.
+///
+/// Matching ignores case and treats spaces, `_` and `-` alike, so
+/// `error_` finds both `error-handling` and `Error_Handling`.
+///
+/// # Errors
+///
+/// Returns an error if the query fails.
+pub fn suggest_tags(conn: &Connection, prefix: &str, limit: usize) -> Result<Vec<(String, i64)>> {
+    let prefix = normalize_tag(prefix);
+    let mut tags = get_all_tags(conn)?;
+    tags.retain(|(tag, _)| normalize_tag(tag).starts_with(&prefix));
+    tags.truncate(limit);
+    Ok(tags)
+}
+
+/// Canonical form of a tag: trimmed, lowercased, with runs of spaces, `_`
+/// and `-` collapsed to a single `-`.
+#[must_use]
+pub fn normalize_tag(tag: &str) -> String {
+    tag.trim()
+        .to_lowercase()
+        .split(|c: char| c.is_whitespace() || c == '_' || c == '-')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Normalize tags, dropping empty ones and duplicates that normalize alike.
+#[must_use]
+pub fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::with_capacity(tags.len());
+    for tag in tags.iter().map(|tag| normalize_tag(tag)) {
+        if !tag.is_empty() && !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    normalized
+}
+
 /// Filter lessons by tag and severity.
 ///
 /// # Errors
@@ -347,9 +416,10 @@ pub fn filter_lessons_by_tag_and_severity(
 
     let mut stmt = conn
         .prepare(
-            "SELECT id, title, content, tags, severity, agent, repo, created_at, updated_at
+            "SELECT id, title, content, tags, severity, agent, repo, created_at, updated_at,
+                    hit_count, useful_count, last_accessed, archived
              FROM lessons
-             WHERE tags LIKE ? AND severity = ?
+             WHERE tags LIKE ? AND severity = ? AND archived = 0
              ORDER BY created_at DESC",
         )
         .map_err(|e| StorageError::Database(e.to_string()))?;
@@ -369,6 +439,10 @@ pub fn filter_lessons_by_tag_and_severity(
                 repo: row.get(6)?,
                 created_at: row.get(7)?,
                 updated_at: row.get(8)?,
+                hit_count: row.get(9)?,
+                useful_count: row.get(10)?,
+                last_accessed: row.get(11)?,
+                archived: row.get(12)?,
                 embedding: None,
             })
         })
@@ -392,6 +466,40 @@ mod tests {
         db
     }
 
+    #[test]
+    fn test_search_by_embedding_breaks_ties() {
+        crate::storage::init_sqlite_vec();
+        let db = setup_db();
+
+        db.with_conn(|conn| {
+            use crate::storage::LessonRecord;
+
+            init_lesson_vectors(conn)?;
+            let mut embedding = vec![0.0_f32; 384];
+            embedding[0] = 1.0;
+            for (id, created_at) in [("lesson_b", 100), ("lesson_c", 200), ("lesson_a", 100)] {
+                let mut lesson = LessonRecord::new(id, "content", vec![]);
+                lesson.id = id.to_string();
+                lesson.created_at = created_at;
+                insert_lesson(conn, &lesson)?;
+                store_lesson_embedding(conn, id, &embedding)?;
+            }
+
+            for _ in 0..3 {
+                let results = search_lessons_by_embedding(conn, &embedding, 10)?;
+                let order: Vec<&str> = results.iter().map(|r| r.record.id.as_str()).collect();
+                assert_eq!(order, vec!["lesson_c", "lesson_a", "lesson_b"]);
+            }
+
+            // A limit past sqlite-vec's maximum k is clamped, not rejected
+            let results = search_lessons_by_embedding(conn, &embedding, usize::MAX)?;
+            assert_eq!(results.len(), 3);
+
+            Ok(())
+        })
+        .unwrap();
+    }
+
     #[test]
     fn test_search_by_text() {
         let db = setup_db();
@@ -595,4 +703,58 @@ mod tests {
         })
         .unwrap();
     }
+
+    #[test]
+    fn test_normalize_tag() {
+        assert_eq!(normalize_tag("error_handling"), "error-handling");
+        assert_eq!(normalize_tag("  Error Handling "), "error-handling");
+        assert_eq!(normalize_tag("error--handling"), "error-handling");
+        assert_eq!(normalize_tag("__"), "");
+
+        let tags = vec![
+            "Rust".to_string(),
+            "error_handling".to_string(),
+            "error-handling".to_string(),
+            " ".to_string(),
+        ];
+        assert_eq!(normalize_tags(&tags), vec!["rust", "error-handling"]);
+    }
+
+    #[test]
+    fn test_suggest_tags() {
+        let db = setup_db();
+
+        db.with_conn(|conn| {
+            use crate::storage::LessonRecord;
+
+            let tags = |t: &[&str]| t.iter().map(ToString::to_string).collect::<Vec<_>>();
+            insert_lesson(
+                conn,
+                &LessonRecord::new("L1", "C1", tags(&["error-handling"])),
+            )?;
+            insert_lesson(
+                conn,
+                &LessonRecord::new("L2", "C2", tags(&["error-handling"])),
+            )?;
+            insert_lesson(
+                conn,
+                &LessonRecord::new("L3", "C3", tags(&["error_codes", "rust"])),
+            )?;
+
+            let suggestions = suggest_tags(conn, "ERROR_", 10)?;
+            assert_eq!(
+                suggestions,
+                vec![
+                    ("error-handling".to_string(), 2),
+                    ("error_codes".to_string(), 1)
+                ]
+            );
+
+            assert_eq!(suggest_tags(conn, "err", 1)?.len(), 1);
+            assert_eq!(suggest_tags(conn, "", 10)?.len(), 3);
+            assert!(suggest_tags(conn, "python", 10)?.is_empty());
+            Ok(())
+        })
+        .unwrap();
+    }
 }
@@ -0,0 +1,262 @@
+//! Key/value metadata about the database itself.
+//!
+//! Records facts that must survive restarts regardless of the current
+//! configuration, such as which embedding model produced the stored vectors.
+
+use rusqlite::{params, Connection};
+
+use super::models::EmbeddingModelInfo;
+use super::vector::DistanceMetric;
+use crate::error::StorageError;
+use crate::Result;
+
+/// Metadata key for the embedding model name.
+const EMBEDDING_MODEL_KEY: &str = "embedding_model";
+
+/// Metadata key for the embedding dimension.
+const EMBEDDING_DIM_KEY: &str = "embedding_dim";
+
+/// Metadata key for the vector distance metric.
+const DISTANCE_METRIC_KEY: &str = "distance_metric";
+
+/// Get current Unix timestamp as i64.
+#[inline]
+#[allow(clippy::cast_possible_wrap)]
+fn now_unix() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+/// Get a metadata value.
+///
+/// # Errors
+///
+/// Returns an error if the database query fails.
+pub fn get_meta(conn: &Connection, key: &str) -> Result<Option<String>> {
+    let result = conn.query_row("SELECT value FROM meta WHERE key = ?", [key], |row| {
+        row.get(0)
+    });
+
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(StorageError::Database(format!("failed to get meta '{key}': {e}")).into()),
+    }
+}
+
+/// Set a metadata value, replacing any existing one.
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub fn set_meta(conn: &Connection, key: &str, value: &str) -> Result<()> {
+    conn.execute(
+        "INSERT INTO meta (key, value, updated_at) VALUES (?, ?, ?)
+         ON CONFLICT(key) DO UPDATE SET
+            value = excluded.value,
+            updated_at = excluded.updated_at",
+        params![key, value, now_unix()],
+    )
+    .map_err(|e| StorageError::Database(format!("failed to set meta '{key}': {e}")))?;
+
+    Ok(())
+}
+
+/// Remove a metadata value if present.
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub fn delete_meta(conn: &Connection, key: &str) -> Result<()> {
+    conn.execute("DELETE FROM meta WHERE key = ?", [key])
+        .map_err(|e| StorageError::Database(format!("failed to delete meta '{key}': {e}")))?;
+
+    Ok(())
+}
+
+/// Get the embedding model recorded for this database, if any.
+///
+/// # Errors
+///
+/// Returns an error if the database query fails.
+pub fn get_embedding_model(conn: &Connection) -> Result<Option<EmbeddingModelInfo>> {
+    let Some(name) = get_meta(conn, EMBEDDING_MODEL_KEY)? else {
+        return Ok(None);
+    };
+    let dimension = get_meta(conn, EMBEDDING_DIM_KEY)?
+        .and_then(|d| d.parse().ok())
+        .unwrap_or_default();
+
+    Ok(Some(EmbeddingModelInfo { name, dimension }))
+}
+
+/// Record the embedding model on first use.
+///
+/// The first model recorded wins, so the value keeps describing the stored
+/// vectors even if the server later restarts with a different model. Returns
+/// the recorded model, which differs from `model` on such a mismatch.
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub fn record_embedding_model(
+    conn: &Connection,
+    model: &EmbeddingModelInfo,
+) -> Result<EmbeddingModelInfo> {
+    if let Some(recorded) = get_embedding_model(conn)? {
+        return Ok(recorded);
+    }
+
+    set_meta(conn, EMBEDDING_MODEL_KEY, &model.name)?;
+    set_meta(conn, EMBEDDING_DIM_KEY, &model.dimension.to_string())?;
+    tracing::info!(model = %model.name, dim = model.dimension, "Recorded embedding model");
+
+    Ok(model.clone())
+}
+
+/// Get the distance metric recorded for this database.
+///
+/// Defaults to L2, which every database used before the metric was recorded.
+///
+/// # Errors
+///
+/// Returns an error if the query fails or the recorded value is invalid.
+pub fn get_distance_metric(conn: &Connection) -> Result<DistanceMetric> {
+    match get_meta(conn, DISTANCE_METRIC_KEY)? {
+        Some(value) => Ok(value.parse().map_err(StorageError::Vector)?),
+        None => Ok(DistanceMetric::default()),
+    }
+}
+
+/// Record the distance metric, or check it matches the recorded one.
+///
+/// The vec0 tables bake the metric in when created, so a database can't
+/// switch metrics without rebuilding its vectors. Databases whose vector
+/// tables predate this record are treated as L2.
+///
+/// # Errors
+///
+/// Returns an error if `metric` differs from the database's metric or the
+/// database operation fails.
+pub fn ensure_distance_metric(conn: &Connection, metric: DistanceMetric) -> Result<()> {
+    if get_meta(conn, DISTANCE_METRIC_KEY)?.is_none() {
+        let legacy_tables: bool = conn
+            .query_row(
+                "SELECT COUNT(*) > 0 FROM sqlite_master WHERE name = 'chunk_embeddings'",
+                [],
+                |row| row.get(0),
+            )
+            .map_err(|e| StorageError::Database(e.to_string()))?;
+        let recorded = if legacy_tables {
+            DistanceMetric::L2
+        } else {
+            metric
+        };
+        set_meta(conn, DISTANCE_METRIC_KEY, recorded.as_str())?;
+    }
+
+    let recorded = get_distance_metric(conn)?;
+    if recorded != metric {
+        return Err(StorageError::Vector(format!(
+            "database vectors use the '{}' distance metric but '{}' is configured; \
+             keep the original metric or rebuild the database",
+            recorded.as_str(),
+            metric.as_str()
+        ))
+        .into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{migrate, Database};
+
+    fn setup_db() -> Database {
+        let db = Database::open_in_memory().unwrap();
+        db.with_conn(|conn| migrate(conn)).unwrap();
+        db
+    }
+
+    #[test]
+    fn test_meta_roundtrip() {
+        let db = setup_db();
+
+        db.with_conn(|conn| {
+            assert_eq!(get_meta(conn, "missing")?, None);
+
+            set_meta(conn, "key", "one")?;
+            assert_eq!(get_meta(conn, "key")?.as_deref(), Some("one"));
+
+            set_meta(conn, "key", "two")?;
+            assert_eq!(get_meta(conn, "key")?.as_deref(), Some("two"));
+
+            delete_meta(conn, "key")?;
+            assert_eq!(get_meta(conn, "key")?, None);
+            delete_meta(conn, "key")?;
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_record_embedding_model_first_wins() {
+        let db = setup_db();
+        let minilm = EmbeddingModelInfo {
+            name: "all-MiniLM-L6-v2".to_string(),
+            dimension: 384,
+        };
+        let other = EmbeddingModelInfo {
+            name: "bge-small-en".to_string(),
+            dimension: 384,
+        };
+
+        db.with_conn(|conn| {
+            assert_eq!(get_embedding_model(conn)?, None);
+
+            assert_eq!(record_embedding_model(conn, &minilm)?, minilm);
+            assert_eq!(record_embedding_model(conn, &other)?, minilm);
+            assert_eq!(get_embedding_model(conn)?, Some(minilm.clone()));
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_ensure_distance_metric() {
+        let db = setup_db();
+
+        db.with_conn(|conn| {
+            assert_eq!(get_distance_metric(conn)?, DistanceMetric::L2);
+
+            ensure_distance_metric(conn, DistanceMetric::Cosine)?;
+            assert_eq!(get_distance_metric(conn)?, DistanceMetric::Cosine);
+            ensure_distance_metric(conn, DistanceMetric::Cosine)?;
+
+            let err = ensure_distance_metric(conn, DistanceMetric::L2).unwrap_err();
+            assert!(err.to_string().contains("'cosine' distance metric"));
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_ensure_distance_metric_legacy_tables_are_l2() {
+        let db = setup_db();
+
+        db.with_conn(|conn| {
+            // Stand-in for a vec0 table created before the metric was recorded
+            conn.execute("CREATE TABLE chunk_embeddings (id INTEGER PRIMARY KEY)", [])
+                .map_err(|e| StorageError::Database(e.to_string()))?;
+
+            assert!(ensure_distance_metric(conn, DistanceMetric::Cosine).is_err());
+            ensure_distance_metric(conn, DistanceMetric::L2)?;
+            Ok(())
+        })
+        .unwrap();
+    }
+}
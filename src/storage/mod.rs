@@ -10,57 +10,95 @@
 mod agent_status;
 mod checkpoints;
 mod checkpoints_search;
+mod chunk_content;
 mod chunks;
 mod connection;
 mod file_state;
+mod idempotency;
+mod index_errors;
 mod lessons;
 mod lessons_search;
+mod meta;
 mod models;
 mod schema;
 mod search;
+mod stats;
 mod vector;
 
 pub use agent_status::{
     cleanup_stale_statuses, count_agents_in_progress, get_agent_status, get_agents_in_progress,
     get_all_agent_statuses, has_in_progress_work, mark_idle, mark_in_progress, AgentStatus,
-    AgentStatusInfo,
+    AgentStatusInfo, DEFAULT_AGENT_STALE_SECS,
 };
 pub use checkpoints::{
-    cleanup_old_checkpoints, count_checkpoints, delete_checkpoint, get_checkpoint,
-    get_checkpoints_since, get_latest_checkpoint, get_recent_checkpoints, insert_checkpoint,
+    cleanup_old_checkpoints, count_all_checkpoints, count_checkpoints, count_checkpoints_by_agent,
+    delete_checkpoint, get_checkpoint, get_checkpoint_chain, get_checkpoints_before,
+    get_checkpoints_since, get_latest_checkpoint, get_latest_session_checkpoint,
+    get_recent_checkpoints, get_recent_session_checkpoints, insert_checkpoint,
+    insert_checkpoint_idempotent, list_sessions,
 };
 pub use checkpoints_search::{
     init_checkpoint_vectors, search_checkpoints_by_agent, search_checkpoints_by_agent_and_repo,
     search_checkpoints_by_embedding, search_checkpoints_by_repo, search_checkpoints_by_session,
     search_checkpoints_by_text, store_checkpoint_embedding,
 };
+pub use chunk_content::{
+    count_duplicate_chunks, get_chunk_duplicates, insert_chunk_deduped, shared_content_hashes,
+};
 pub use chunks::{
-    count_chunks, count_chunks_by_path_prefix, count_chunks_for_file, delete_chunk,
-    delete_chunks_by_file, delete_chunks_by_path_prefix, get_chunk, get_chunks_by_file,
-    init_chunk_vectors, insert_chunk, insert_chunks_batch, list_files_by_path_prefix,
-    update_chunk_embedding,
+    backfill_chunk_repos, chunk_line_stats, count_chunks, count_chunks_by_language,
+    count_chunks_by_path_prefix, count_chunks_for_file, delete_chunk, delete_chunks_by_file,
+    delete_chunks_by_path_prefix, get_chunk, get_chunks_by_file, init_chunk_vectors, insert_chunk,
+    insert_chunks_batch, latest_indexed_at, list_files_by_path_prefix, list_indexed_revisions,
+    list_repos, rename_file_path, set_chunks_git_revision, set_chunks_repo, update_chunk_embedding,
 };
 pub use connection::Database;
 pub use file_state::{
-    count_tracked_files, delete_file_state, delete_file_state_by_prefix, find_stale_entries,
-    get_file_state, list_file_paths, list_file_paths_by_prefix, needs_reindex,
-    needs_reindex_by_metadata, upsert_file_state,
+    compute_content_hash, count_tracked_files, delete_file_state, delete_file_state_by_prefix,
+    find_stale_entries, get_file_state, list_file_paths, list_file_paths_by_prefix, needs_reindex,
+    needs_reindex_by_hash, needs_reindex_by_metadata, upsert_file_state,
+};
+pub use idempotency::IDEMPOTENCY_WINDOW_SECS;
+pub use index_errors::{
+    clear_index_error, count_index_errors, list_index_errors, record_index_error,
 };
 pub use lessons::{
-    count_lessons, delete_lesson, get_lesson, insert_lesson, list_lessons, list_lessons_by_agent,
-    list_lessons_by_severity, update_lesson,
+    archive_lesson, canonical_severity, closest_severity, count_archived_lessons, count_lessons,
+    count_lessons_by_repo, count_lessons_by_severity, delete_lesson,
+    find_lesson_by_idempotency_key, get_lesson, insert_lesson, insert_lesson_idempotent,
+    list_archived_lessons, list_lessons, list_lessons_by_agent, list_lessons_by_severity,
+    mark_lesson_useful, normalize_lesson_severities, record_lesson_hits, restore_lesson,
+    update_lesson, DEFAULT_SEVERITIES,
 };
 pub use lessons_search::{
-    filter_lessons_by_tag_and_severity, get_all_tags, init_lesson_vectors,
-    search_lessons_by_embedding, search_lessons_by_tag, search_lessons_by_tags_all,
-    search_lessons_by_tags_any, search_lessons_by_text, store_lesson_embedding,
+    filter_lessons_by_tag_and_severity, get_all_tags, init_lesson_vectors, normalize_tag,
+    normalize_tags, search_lessons_by_embedding, search_lessons_by_tag, search_lessons_by_tags_all,
+    search_lessons_by_tags_any, search_lessons_by_text, store_lesson_embedding, suggest_tags,
+};
+pub use meta::{
+    delete_meta, ensure_distance_metric, get_distance_metric, get_embedding_model, get_meta,
+    record_embedding_model, set_meta,
+};
+pub use models::{
+    CheckpointRecord, ChunkDuplicates, ChunkLineStats, ChunkRecord, DuplicateChunk,
+    EmbeddingModelInfo, FileChunkStats, FileState, IndexErrorRecord, IndexedRevision, LessonRecord,
+    RepoBackfill, RepoStats, SearchResult, SessionSummary,
+};
+pub use schema::{migrate, rollback, verify_schema, SCHEMA_VERSION};
+pub use search::{
+    compile_path_glob, content_has_symbol, find_chunks_like_file, find_similar_chunks,
+    get_chunk_embedding, grep_chunks, rerank_by_recency, rerank_mmr, search_chunks,
+    search_chunks_by_symbol, search_chunks_by_text, GrepHit, GrepLine, GrepPattern, SearchOptions,
+};
+pub use stats::{
+    add_stats, get_stats, record_scan, set_stat, STAT_CHUNKS_CREATED, STAT_FILES_DELETED,
+    STAT_FILES_INDEXED, STAT_FILES_SCANNED, STAT_INDEX_ERRORS, STAT_LAST_INDEXED_AT,
+    STAT_LAST_SCAN_AT, STAT_SCANS,
 };
-pub use models::{CheckpointRecord, ChunkRecord, FileState, LessonRecord, SearchResult};
-pub use schema::{migrate, verify_schema, SCHEMA_VERSION};
-pub use search::{search_chunks, search_chunks_by_text, SearchOptions};
 pub use vector::{
-    create_vec_table, delete_vector, init_sqlite_vec, insert_vector, load_extension,
-    search_similar, EMBEDDING_DIM,
+    count_vector_rows, create_vec_table, delete_vector, get_vectors, init_sqlite_vec,
+    insert_vector, load_extension, rebuild_vector_tables, search_similar, DistanceMetric,
+    VectorTableRebuild, EMBEDDING_DIM, MAX_VECTOR_K, VECTOR_TABLES,
 };
 
 /// Initialize storage with migrations.
@@ -72,25 +110,148 @@ pub use vector::{
 ///
 /// Returns an error if database initialization fails or sqlite-vec extension cannot be loaded.
 pub fn init_storage(db: &Database) -> crate::Result<()> {
+    init_storage_with(db, true, None).map(|_| ())
+}
+
+/// Initialize storage, optionally tolerating a missing sqlite-vec extension.
+///
+/// With `require_vector_search` false, a failure to load sqlite-vec is
+/// logged and the vec0 tables are skipped so text search, lessons, and
+/// checkpoints keep working. Returns whether vector search is available.
+///
+/// A `metric` is recorded on first use and must match on later calls;
+/// `None` keeps whatever the database already uses (L2 for new databases).
+///
+/// # Errors
+///
+/// Returns an error if database initialization fails, if sqlite-vec
+/// cannot be loaded and `require_vector_search` is true, or if `metric`
+/// differs from the one the database's vectors were created with.
+pub fn init_storage_with(
+    db: &Database,
+    require_vector_search: bool,
+    metric: Option<DistanceMetric>,
+) -> crate::Result<bool> {
     // Initialize sqlite-vec globally - must happen before any DB connections
     init_sqlite_vec();
 
     db.with_conn(|conn| {
         // Verify sqlite-vec extension is available
-        load_extension(conn)?;
+        let vector_search = vector_search_available(load_extension(conn), require_vector_search)?;
+        init_schema(conn, vector_search, metric)?;
+        Ok(vector_search)
+    })
+}
 
-        // Run migrations
-        migrate(conn)?;
+/// Decide whether to continue without vector search after loading sqlite-vec.
+fn vector_search_available(loaded: crate::Result<()>, required: bool) -> crate::Result<bool> {
+    match loaded {
+        Ok(()) => Ok(true),
+        Err(e) if required => Err(e),
+        Err(e) => {
+            tracing::warn!(
+                "VECTOR SEARCH DISABLED: {e}. Semantic search tools will return errors; \
+                 text search, lessons, and checkpoints still work."
+            );
+            Ok(false)
+        }
+    }
+}
+
+/// Run migrations and create vector tables when vector search is available.
+fn init_schema(
+    conn: &rusqlite::Connection,
+    vector_search: bool,
+    metric: Option<DistanceMetric>,
+) -> crate::Result<()> {
+    // Run migrations
+    migrate(conn)?;
 
-        // Initialize vector tables for semantic search
+    // Initialize vector tables for semantic search
+    if vector_search {
+        if let Some(metric) = metric {
+            ensure_distance_metric(conn, metric)?;
+        }
         init_chunk_vectors(conn)?;
         init_lesson_vectors(conn)?;
         init_checkpoint_vectors(conn)?;
+    }
 
-        // Verify schema
-        verify_schema(conn)?;
+    // Verify schema
+    verify_schema(conn)?;
 
+    if vector_search {
         tracing::info!("Storage initialized with vector tables, schema version {SCHEMA_VERSION}");
-        Ok(())
-    })
+    } else {
+        tracing::info!(
+            "Storage initialized without vector tables, schema version {SCHEMA_VERSION}"
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::StorageError;
+
+    fn missing_extension() -> crate::Result<()> {
+        Err(StorageError::Vector("sqlite-vec extension not available".to_string()).into())
+    }
+
+    #[test]
+    fn test_vector_search_available() {
+        assert!(vector_search_available(Ok(()), true).unwrap());
+        assert!(vector_search_available(Ok(()), false).unwrap());
+        assert!(vector_search_available(missing_extension(), true).is_err());
+        assert!(!vector_search_available(missing_extension(), false).unwrap());
+    }
+
+    #[test]
+    fn test_init_schema_without_vector_search() {
+        let db = Database::open_in_memory().unwrap();
+        db.with_conn(|conn| init_schema(conn, false, None)).unwrap();
+
+        db.with_conn(|conn| {
+            let vec_tables: i64 = conn
+                .query_row(
+                    "SELECT COUNT(*) FROM sqlite_master WHERE name LIKE '%_embeddings'",
+                    [],
+                    |row| row.get(0),
+                )
+                .map_err(|e| StorageError::Database(e.to_string()))?;
+            assert_eq!(vec_tables, 0);
+
+            // Non-vector storage still works
+            let lesson = LessonRecord::new("Title", "Content", vec!["tag".to_string()]);
+            insert_lesson(conn, &lesson)?;
+            assert_eq!(search_lessons_by_text(conn, "Content", 10)?.len(), 1);
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_init_storage_with_vector_search() {
+        let db = Database::open_in_memory().unwrap();
+        assert!(init_storage_with(&db, false, None).unwrap());
+    }
+
+    #[test]
+    fn test_init_storage_distance_metric_mismatch() {
+        let db = Database::open_in_memory().unwrap();
+        let cosine = Some(DistanceMetric::Cosine);
+        assert!(init_storage_with(&db, true, cosine).unwrap());
+        assert!(init_storage_with(&db, true, cosine).unwrap());
+
+        // Unspecified keeps the recorded metric
+        init_storage(&db).unwrap();
+        assert_eq!(
+            db.with_conn(get_distance_metric).unwrap(),
+            DistanceMetric::Cosine
+        );
+
+        let err = init_storage_with(&db, true, Some(DistanceMetric::L2)).unwrap_err();
+        assert!(err.to_string().contains("distance metric"));
+    }
 }
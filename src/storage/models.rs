@@ -10,6 +10,8 @@
 use serde::{Deserialize, Serialize};
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use super::vector::DistanceMetric;
+
 /// Get current Unix timestamp.
 fn now_unix() -> i64 {
     SystemTime::now()
@@ -19,18 +21,11 @@ fn now_unix() -> i64 {
 }
 
 /// Generate a unique ID with a given prefix.
+///
+/// IDs are UUID v7, so they are time-ordered and sort lexicographically
+/// by creation time.
 fn generate_id(prefix: &str) -> String {
-    use std::collections::hash_map::RandomState;
-    use std::hash::{BuildHasher, Hasher};
-
-    let mut hasher = RandomState::new().build_hasher();
-    hasher.write_u128(
-        std::time::SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .map(|d| d.as_nanos())
-            .unwrap_or(0),
-    );
-    format!("{}_{:x}", prefix, hasher.finish())
+    format!("{}_{}", prefix, uuid::Uuid::now_v7().simple())
 }
 
 /// A code chunk with its embedding and metadata.
@@ -57,9 +52,18 @@ pub struct ChunkRecord {
     /// Programming language (e.g., "rust", "python").
     pub language: Option<String>,
 
+    /// Repository this chunk belongs to (name of the nearest git root).
+    pub repo: Option<String>,
+
     /// Hash of the source file for change detection.
     pub file_hash: String,
 
+    /// Commit SHA the repository was checked out at when indexed.
+    pub git_commit: Option<String>,
+
+    /// Branch checked out when indexed (`None` for a detached `HEAD`).
+    pub git_branch: Option<String>,
+
     /// Unix timestamp when this chunk was indexed.
     pub indexed_at: i64,
 
@@ -87,7 +91,10 @@ impl ChunkRecord {
             end_line,
             content: content.into(),
             language: None,
+            repo: None,
             file_hash: file_hash.into(),
+            git_commit: None,
+            git_branch: None,
             indexed_at: now_unix(),
             embedding: None,
         }
@@ -100,6 +107,21 @@ impl ChunkRecord {
         self
     }
 
+    /// Set the repository.
+    #[must_use]
+    pub fn with_repo(mut self, repo: impl Into<String>) -> Self {
+        self.repo = Some(repo.into());
+        self
+    }
+
+    /// Set the git revision the chunk was indexed from.
+    #[must_use]
+    pub fn with_git_revision(mut self, commit: impl Into<String>, branch: Option<String>) -> Self {
+        self.git_commit = Some(commit.into());
+        self.git_branch = branch;
+        self
+    }
+
     /// Set the embedding vector.
     #[must_use]
     pub fn with_embedding(mut self, embedding: Vec<f32>) -> Self {
@@ -144,6 +166,22 @@ pub struct LessonRecord {
     /// Unix timestamp when last updated.
     pub updated_at: i64,
 
+    /// Times this lesson has been returned by search.
+    #[serde(default)]
+    pub hit_count: i64,
+
+    /// Times an agent marked this lesson as useful.
+    #[serde(default)]
+    pub useful_count: i64,
+
+    /// Unix timestamp when last returned by search (None = never).
+    #[serde(default)]
+    pub last_accessed: Option<i64>,
+
+    /// Soft-deleted: hidden from listings and search until restored.
+    #[serde(default)]
+    pub archived: bool,
+
     /// Embedding vector for semantic search.
     #[serde(skip)]
     pub embedding: Option<Vec<f32>>,
@@ -164,6 +202,10 @@ impl LessonRecord {
             repo: None,
             created_at: now,
             updated_at: now,
+            hit_count: 0,
+            useful_count: 0,
+            last_accessed: None,
+            archived: false,
             embedding: None,
         }
     }
@@ -220,6 +262,10 @@ pub struct CheckpointRecord {
 
     /// Unix timestamp when created.
     pub created_at: i64,
+
+    /// Checkpoint this one continues from (optional).
+    #[serde(default)]
+    pub parent_id: Option<String>,
 }
 
 impl CheckpointRecord {
@@ -238,6 +284,7 @@ impl CheckpointRecord {
             repo: None,
             session_id: None,
             created_at: now_unix(),
+            parent_id: None,
         }
     }
 
@@ -254,6 +301,32 @@ impl CheckpointRecord {
         self.session_id = Some(session_id.into());
         self
     }
+
+    /// Set the checkpoint this one continues from.
+    #[must_use]
+    pub fn with_parent(mut self, parent_id: impl Into<String>) -> Self {
+        self.parent_id = Some(parent_id.into());
+        self
+    }
+}
+
+/// Summary of one agent session's checkpoints.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionSummary {
+    /// Session identifier (`None` groups checkpoints saved without one).
+    pub session_id: Option<String>,
+
+    /// Number of checkpoints in the session.
+    pub checkpoint_count: i64,
+
+    /// Unix timestamp of the session's first checkpoint.
+    pub first_checkpoint_at: i64,
+
+    /// Unix timestamp of the session's latest checkpoint.
+    pub last_checkpoint_at: i64,
+
+    /// `working_on` of the session's latest checkpoint.
+    pub working_on: String,
 }
 
 /// File state for incremental indexing.
@@ -289,6 +362,140 @@ impl FileState {
     }
 }
 
+/// Embedding model that produced the stored vectors.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmbeddingModelInfo {
+    /// Model name (e.g. `all-MiniLM-L6-v2`).
+    pub name: String,
+
+    /// Vector dimension.
+    pub dimension: usize,
+}
+
+/// Summary of the code indexed for one repository.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepoStats {
+    /// Repository name (`None` for chunks indexed without one).
+    pub repo: Option<String>,
+
+    /// Number of chunks.
+    pub chunk_count: i64,
+
+    /// Number of distinct files.
+    pub file_count: i64,
+
+    /// Languages present, sorted.
+    pub languages: Vec<String>,
+
+    /// Unix timestamp of the most recently indexed chunk.
+    pub last_indexed_at: i64,
+}
+
+/// Outcome of filling in the repository of chunks indexed without one.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RepoBackfill {
+    /// Files whose chunks were attributed to a repository.
+    pub files_updated: i64,
+
+    /// Chunks attributed to a repository.
+    pub chunks_updated: i64,
+
+    /// Files no repository could be inferred for.
+    pub files_unattributed: i64,
+
+    /// Chunks of those files, still without a repository.
+    pub chunks_unattributed: i64,
+}
+
+/// A file that failed to index, kept until it indexes successfully.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexErrorRecord {
+    /// Path of the file.
+    pub file_path: String,
+
+    /// Error from the most recent attempt.
+    pub error: String,
+
+    /// Failed attempts since the file last indexed successfully.
+    pub attempts: i64,
+
+    /// Unix timestamp of the first failure.
+    pub first_failed_at: i64,
+
+    /// Unix timestamp of the most recent failure.
+    pub last_failed_at: i64,
+}
+
+/// A git revision that indexed chunks were recorded at.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexedRevision {
+    /// Repository name (`None` for chunks indexed without one).
+    pub repo: Option<String>,
+
+    /// Commit SHA stored on the chunks.
+    pub git_commit: String,
+
+    /// Branch stored on the chunks.
+    pub git_branch: Option<String>,
+
+    /// One indexed file from this revision, to locate the working tree.
+    pub sample_path: String,
+
+    /// Number of chunks recorded at this revision.
+    pub chunk_count: i64,
+}
+
+/// How much code the chunk index covers.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChunkLineStats {
+    /// Source lines covered by all chunks.
+    pub total_lines: i64,
+
+    /// Mean lines per chunk (0.0 when nothing is indexed).
+    pub average_chunk_lines: f64,
+
+    /// Lines in the longest chunk.
+    pub max_chunk_lines: i64,
+
+    /// Files with the most chunks, largest first.
+    pub largest_files: Vec<FileChunkStats>,
+}
+
+/// Chunk and line counts for one indexed file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileChunkStats {
+    /// Indexed file path.
+    pub file_path: String,
+
+    /// Number of chunks.
+    pub chunk_count: i64,
+
+    /// Source lines covered by the file's chunks.
+    pub line_count: i64,
+}
+
+/// Another chunk with the same content as a search hit.
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct DuplicateChunk {
+    /// Chunk ID.
+    pub id: i64,
+
+    /// Indexed file path.
+    pub file_path: String,
+
+    /// Starting line number (1-based).
+    pub start_line: i32,
+
+    /// Ending line number (1-based, inclusive).
+    pub end_line: i32,
+
+    /// Repository the copy belongs to.
+    pub repo: Option<String>,
+}
+
+/// Other chunks sharing each chunk's content, keyed by chunk ID.
+pub type ChunkDuplicates = std::collections::HashMap<i64, Vec<DuplicateChunk>>;
+
 /// Search result with similarity score.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SearchResult<T> {
@@ -309,13 +516,33 @@ impl<T> SearchResult<T> {
     /// This maps the distance range [0, 2] to score range [1, 0].
     #[must_use]
     pub fn new(record: T, distance: f32) -> Self {
-        let score = (1.0 - (distance / 2.0)).clamp(0.0, 1.0);
+        Self::with_metric(record, distance, DistanceMetric::L2)
+    }
+
+    /// Create a search result, scoring the distance under `metric`.
+    #[must_use]
+    pub fn with_metric(record: T, distance: f32, metric: DistanceMetric) -> Self {
         Self {
             record,
             distance,
-            score,
+            score: metric.score(distance),
         }
     }
+
+    /// Sort results nearest first. Ties in distance, common between
+    /// near-identical normalized embeddings, go to the newest record by
+    /// `created_at` and then the lowest `id`, so the order is the same on
+    /// every call. `key` returns a record's `(created_at, id)`.
+    pub(super) fn sort_by_distance(results: &mut [Self], key: impl Fn(&T) -> (i64, &str)) {
+        results.sort_by(|a, b| {
+            let (a_created, a_id) = key(&a.record);
+            let (b_created, b_id) = key(&b.record);
+            a.distance
+                .total_cmp(&b.distance)
+                .then_with(|| b_created.cmp(&a_created))
+                .then_with(|| a_id.cmp(b_id))
+        });
+    }
 }
 
 #[cfg(test)]
@@ -458,4 +685,37 @@ mod tests {
 
         assert_ne!(cp1.id, cp2.id);
     }
+
+    #[test]
+    fn test_ids_are_time_ordered() {
+        let ids: Vec<String> = (0..100)
+            .map(|_| LessonRecord::new("L", "C", vec![]).id)
+            .collect();
+
+        let mut sorted = ids.clone();
+        sorted.sort();
+        assert_eq!(ids, sorted);
+    }
+
+    #[test]
+    fn test_unique_ids_across_threads() {
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                std::thread::spawn(|| {
+                    (0..1000)
+                        .map(|_| CheckpointRecord::new("a", "w", serde_json::json!({})).id)
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+
+        let mut seen = std::collections::HashSet::new();
+        for handle in handles {
+            for id in handle.join().unwrap() {
+                assert!(id.starts_with("checkpoint_"));
+                assert!(seen.insert(id), "duplicate id generated");
+            }
+        }
+        assert_eq!(seen.len(), 8000);
+    }
 }
@@ -1,6 +1,10 @@
 //! Database schema definitions and migrations.
 //!
-//! Provides versioned schema migrations for safe database upgrades.
+//! Migrations are numbered and listed in [`MIGRATIONS`]. Each one has `up`
+//! SQL and, where the change can be reverted, `down` SQL. Applied versions
+//! are recorded in the `schema_migrations` table, and each migration runs in
+//! its own transaction so a failure leaves the database at the previous
+//! version.
 
 use rusqlite::Connection;
 
@@ -8,81 +12,29 @@ use crate::error::StorageError;
 use crate::Result;
 
 /// Current schema version.
-pub const SCHEMA_VERSION: i32 = 1;
+pub const SCHEMA_VERSION: i32 = 12;
 
-/// Run all pending migrations.
-///
-/// # Errors
-///
-/// Returns an error if migrations fail.
-pub fn migrate(conn: &Connection) -> Result<()> {
-    // Create migrations table if not exists
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS schema_migrations (
-            version INTEGER PRIMARY KEY,
-            applied_at INTEGER NOT NULL
-        )",
-        [],
-    )
-    .map_err(|e| StorageError::Migration(format!("failed to create migrations table: {e}")))?;
-
-    let current_version = get_current_version(conn)?;
-    tracing::info!(
-        current = current_version,
-        target = SCHEMA_VERSION,
-        "Checking database migrations"
-    );
-
-    if current_version < 1 {
-        migrate_v1(conn)?;
-    }
-
-    // Add future migrations here:
-    // if current_version < 2 {
-    //     migrate_v2(conn)?;
-    // }
-
-    Ok(())
+/// A numbered schema change.
+#[derive(Debug, Clone, Copy)]
+struct Migration {
+    /// Version the database is at once this migration is applied.
+    version: i32,
+    /// Short description for logs.
+    description: &'static str,
+    /// SQL applied when upgrading.
+    up: &'static str,
+    /// SQL that reverts `up`, if the migration can be rolled back.
+    down: Option<&'static str>,
 }
 
-/// Get the current schema version.
-fn get_current_version(conn: &Connection) -> Result<i32> {
-    let result = conn.query_row(
-        "SELECT COALESCE(MAX(version), 0) FROM schema_migrations",
-        [],
-        |row| row.get(0),
-    );
-
-    match result {
-        Ok(version) => Ok(version),
-        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(0),
-        Err(e) => Err(StorageError::Migration(format!("failed to get version: {e}")).into()),
-    }
-}
-
-/// Record a migration as applied.
-fn record_migration(conn: &Connection, version: i32) -> Result<()> {
-    let now = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs();
-    let now_i64 = i64::try_from(now).unwrap_or_default();
-
-    conn.execute(
-        "INSERT INTO schema_migrations (version, applied_at) VALUES (?, ?)",
-        rusqlite::params![version, now_i64],
-    )
-    .map_err(|e| StorageError::Migration(format!("failed to record migration: {e}")))?;
-
-    Ok(())
-}
-
-/// Migration v1: Initial schema with all tables.
-fn migrate_v1(conn: &Connection) -> Result<()> {
-    tracing::info!("Applying migration v1: Initial schema");
-
-    conn.execute_batch(
-        r"
+/// All migrations, in version order.
+///
+/// Append new migrations here and bump [`SCHEMA_VERSION`] to match.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "Initial schema",
+        up: r"
         -- Code chunks table
         CREATE TABLE IF NOT EXISTS chunks (
             id INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -163,11 +115,388 @@ fn migrate_v1(conn: &Connection) -> Result<()> {
             created_at INTEGER NOT NULL
         );
         ",
+        down: Some(
+            r"
+            DROP TABLE IF EXISTS watch_dirs;
+            DROP TABLE IF EXISTS agent_status;
+            DROP TABLE IF EXISTS file_state;
+            DROP TABLE IF EXISTS checkpoints;
+            DROP TABLE IF EXISTS lessons;
+            DROP TABLE IF EXISTS chunks;
+            ",
+        ),
+    },
+    Migration {
+        version: 2,
+        description: "Chunk repository column",
+        up: r"
+        ALTER TABLE chunks ADD COLUMN repo TEXT;
+        CREATE INDEX IF NOT EXISTS idx_chunks_repo ON chunks(repo);
+        ",
+        down: Some(
+            r"
+            DROP INDEX IF EXISTS idx_chunks_repo;
+            ALTER TABLE chunks DROP COLUMN repo;
+            ",
+        ),
+    },
+    // Key/value metadata (embedding model, etc.)
+    Migration {
+        version: 3,
+        description: "Metadata table",
+        up: r"
+        CREATE TABLE IF NOT EXISTS meta (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL,
+            updated_at INTEGER NOT NULL
+        );
+        ",
+        down: Some("DROP TABLE IF EXISTS meta;"),
+    },
+    Migration {
+        version: 4,
+        description: "Lesson usage tracking",
+        up: r"
+        ALTER TABLE lessons ADD COLUMN hit_count INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE lessons ADD COLUMN useful_count INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE lessons ADD COLUMN last_accessed INTEGER;
+        ",
+        down: Some(
+            r"
+            ALTER TABLE lessons DROP COLUMN last_accessed;
+            ALTER TABLE lessons DROP COLUMN useful_count;
+            ALTER TABLE lessons DROP COLUMN hit_count;
+            ",
+        ),
+    },
+    Migration {
+        version: 5,
+        description: "Checkpoint parent links",
+        up: r"
+        ALTER TABLE checkpoints ADD COLUMN parent_id TEXT;
+        CREATE INDEX IF NOT EXISTS idx_checkpoints_parent ON checkpoints(parent_id);
+        CREATE INDEX IF NOT EXISTS idx_checkpoints_session ON checkpoints(session_id);
+        ",
+        down: Some(
+            r"
+            DROP INDEX IF EXISTS idx_checkpoints_session;
+            DROP INDEX IF EXISTS idx_checkpoints_parent;
+            ALTER TABLE checkpoints DROP COLUMN parent_id;
+            ",
+        ),
+    },
+    Migration {
+        version: 6,
+        description: "Chunk git revision columns",
+        up: r"
+        ALTER TABLE chunks ADD COLUMN git_commit TEXT;
+        ALTER TABLE chunks ADD COLUMN git_branch TEXT;
+        ",
+        down: Some(
+            r"
+            ALTER TABLE chunks DROP COLUMN git_branch;
+            ALTER TABLE chunks DROP COLUMN git_commit;
+            ",
+        ),
+    },
+    Migration {
+        version: 7,
+        description: "Idempotency keys",
+        up: r"
+        ALTER TABLE lessons ADD COLUMN idempotency_key TEXT;
+        ALTER TABLE checkpoints ADD COLUMN idempotency_key TEXT;
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_lessons_idempotency_key
+            ON lessons(idempotency_key);
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_checkpoints_idempotency_key
+            ON checkpoints(idempotency_key);
+        ",
+        down: Some(
+            r"
+            DROP INDEX IF EXISTS idx_checkpoints_idempotency_key;
+            DROP INDEX IF EXISTS idx_lessons_idempotency_key;
+            ALTER TABLE checkpoints DROP COLUMN idempotency_key;
+            ALTER TABLE lessons DROP COLUMN idempotency_key;
+            ",
+        ),
+    },
+    // An external-content FTS5 table kept in sync by triggers, so keyword
+    // search works without embeddings. Terms are stemmed (`retried` matches
+    // `retry`) and `_` is a token character so identifiers like
+    // `parse_config` stay whole.
+    Migration {
+        version: 8,
+        description: "Chunk full-text index",
+        up: r"
+        CREATE VIRTUAL TABLE IF NOT EXISTS chunks_fts USING fts5(
+            content,
+            content = 'chunks',
+            content_rowid = 'id',
+            tokenize = 'porter unicode61 tokenchars ''_'''
+        );
+
+        CREATE TRIGGER IF NOT EXISTS chunks_fts_insert AFTER INSERT ON chunks BEGIN
+            INSERT INTO chunks_fts (rowid, content) VALUES (new.id, new.content);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS chunks_fts_delete AFTER DELETE ON chunks BEGIN
+            INSERT INTO chunks_fts (chunks_fts, rowid, content)
+                VALUES ('delete', old.id, old.content);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS chunks_fts_update AFTER UPDATE OF content ON chunks BEGIN
+            INSERT INTO chunks_fts (chunks_fts, rowid, content)
+                VALUES ('delete', old.id, old.content);
+            INSERT INTO chunks_fts (rowid, content) VALUES (new.id, new.content);
+        END;
+
+        -- Index chunks written before this migration
+        INSERT INTO chunks_fts (chunks_fts) VALUES ('rebuild');
+        ",
+        down: Some(
+            r"
+            DROP TRIGGER IF EXISTS chunks_fts_update;
+            DROP TRIGGER IF EXISTS chunks_fts_delete;
+            DROP TRIGGER IF EXISTS chunks_fts_insert;
+            DROP TABLE IF EXISTS chunks_fts;
+            ",
+        ),
+    },
+    Migration {
+        version: 9,
+        description: "Statistics table",
+        up: r"
+        CREATE TABLE IF NOT EXISTS stats (
+            name TEXT PRIMARY KEY,
+            value INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL
+        );
+        ",
+        down: Some("DROP TABLE IF EXISTS stats;"),
+    },
+    Migration {
+        version: 10,
+        description: "Lesson archive flag",
+        up: r"
+        ALTER TABLE lessons ADD COLUMN archived INTEGER NOT NULL DEFAULT 0;
+        CREATE INDEX IF NOT EXISTS idx_lessons_archived ON lessons(archived);
+        ",
+        down: Some(
+            r"
+            DROP INDEX IF EXISTS idx_lessons_archived;
+            ALTER TABLE lessons DROP COLUMN archived;
+            ",
+        ),
+    },
+    // Deduplicated chunks: each row maps a chunk to its content hash and to
+    // the canonical chunk holding the group's embedding
+    Migration {
+        version: 11,
+        description: "Chunk content deduplication",
+        up: r"
+        CREATE TABLE IF NOT EXISTS chunk_content (
+            chunk_id INTEGER PRIMARY KEY,
+            content_hash TEXT NOT NULL,
+            canonical_id INTEGER NOT NULL
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_chunk_content_hash ON chunk_content(content_hash);
+        CREATE INDEX IF NOT EXISTS idx_chunk_content_canonical ON chunk_content(canonical_id);
+        ",
+        down: Some(
+            r"
+            DROP INDEX IF EXISTS idx_chunk_content_canonical;
+            DROP INDEX IF EXISTS idx_chunk_content_hash;
+            DROP TABLE IF EXISTS chunk_content;
+            ",
+        ),
+    },
+    Migration {
+        version: 12,
+        description: "Index error log",
+        up: r"
+        CREATE TABLE IF NOT EXISTS index_errors (
+            file_path TEXT PRIMARY KEY,
+            error TEXT NOT NULL,
+            attempts INTEGER NOT NULL DEFAULT 1,
+            first_failed_at INTEGER NOT NULL,
+            last_failed_at INTEGER NOT NULL
+        );
+        ",
+        down: Some("DROP TABLE IF EXISTS index_errors;"),
+    },
+];
+
+/// Run all pending migrations.
+///
+/// # Errors
+///
+/// Returns an error if a migration fails, or if the database was written by
+/// a newer build whose schema this one doesn't know.
+pub fn migrate(conn: &Connection) -> Result<()> {
+    migrate_to(conn, MIGRATIONS, SCHEMA_VERSION)
+}
+
+/// Revert applied migrations, newest first, until the database is at
+/// `target` version.
+///
+/// Nothing is reverted unless every migration above `target` has down SQL.
+///
+/// # Errors
+///
+/// Returns an error if a migration can't be rolled back, if reverting one
+/// fails (earlier reverts stay applied), or if the database is newer than
+/// this build.
+pub fn rollback(conn: &Connection, target: i32) -> Result<()> {
+    rollback_to(conn, MIGRATIONS, target)
+}
+
+/// Apply `migrations` up to and including `target` version.
+fn migrate_to(conn: &Connection, migrations: &[Migration], target: i32) -> Result<()> {
+    ensure_migrations_table(conn)?;
+
+    let current_version = get_current_version(conn)?;
+    check_not_newer(current_version, migrations)?;
+    tracing::info!(
+        current = current_version,
+        target,
+        "Checking database migrations"
+    );
+
+    for migration in migrations
+        .iter()
+        .filter(|m| m.version > current_version && m.version <= target)
+    {
+        apply_migration(conn, migration)?;
+    }
+
+    Ok(())
+}
+
+/// Revert `migrations` above `target` version.
+fn rollback_to(conn: &Connection, migrations: &[Migration], target: i32) -> Result<()> {
+    ensure_migrations_table(conn)?;
+
+    let current_version = get_current_version(conn)?;
+    check_not_newer(current_version, migrations)?;
+
+    let to_revert: Vec<&Migration> = migrations
+        .iter()
+        .rev()
+        .filter(|m| m.version > target && m.version <= current_version)
+        .collect();
+
+    if let Some(m) = to_revert.iter().find(|m| m.down.is_none()) {
+        return Err(StorageError::Migration(format!(
+            "migration v{} ({}) cannot be rolled back",
+            m.version, m.description
+        ))
+        .into());
+    }
+
+    for migration in to_revert {
+        revert_migration(conn, migration)?;
+    }
+
+    Ok(())
+}
+
+/// Create the table recording applied migrations.
+fn ensure_migrations_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS schema_migrations (
+            version INTEGER PRIMARY KEY,
+            applied_at INTEGER NOT NULL
+        )",
+        [],
     )
-    .map_err(|e| StorageError::Migration(format!("v1 migration failed: {e}")))?;
+    .map_err(|e| StorageError::Migration(format!("failed to create migrations table: {e}")))?;
 
-    record_migration(conn, 1)?;
-    tracing::info!("Migration v1 complete");
+    Ok(())
+}
+
+/// Refuse to touch a database migrated by a newer build.
+fn check_not_newer(current_version: i32, migrations: &[Migration]) -> Result<()> {
+    let latest = migrations.last().map_or(0, |m| m.version);
+    if current_version > latest {
+        return Err(StorageError::Migration(format!(
+            "database schema version {current_version} is newer than this build supports \
+             ({latest}); upgrade nellie or restore a backup"
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+/// Apply one migration and record it, in a single transaction.
+fn apply_migration(conn: &Connection, migration: &Migration) -> Result<()> {
+    let version = migration.version;
+    tracing::info!("Applying migration v{version}: {}", migration.description);
+
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(|e| StorageError::Migration(format!("v{version} migration failed: {e}")))?;
+    tx.execute_batch(migration.up)
+        .map_err(|e| StorageError::Migration(format!("v{version} migration failed: {e}")))?;
+    record_migration(&tx, version)?;
+    tx.commit()
+        .map_err(|e| StorageError::Migration(format!("v{version} migration failed: {e}")))?;
+
+    tracing::info!("Migration v{version} complete");
+    Ok(())
+}
+
+/// Revert one migration and remove its record, in a single transaction.
+fn revert_migration(conn: &Connection, migration: &Migration) -> Result<()> {
+    let version = migration.version;
+    let Some(down) = migration.down else {
+        return Err(
+            StorageError::Migration(format!("migration v{version} cannot be rolled back")).into(),
+        );
+    };
+    tracing::info!("Reverting migration v{version}: {}", migration.description);
+
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(|e| StorageError::Migration(format!("v{version} rollback failed: {e}")))?;
+    tx.execute_batch(down)
+        .map_err(|e| StorageError::Migration(format!("v{version} rollback failed: {e}")))?;
+    tx.execute("DELETE FROM schema_migrations WHERE version = ?", [version])
+        .map_err(|e| StorageError::Migration(format!("v{version} rollback failed: {e}")))?;
+    tx.commit()
+        .map_err(|e| StorageError::Migration(format!("v{version} rollback failed: {e}")))?;
+
+    tracing::info!("Migration v{version} reverted");
+    Ok(())
+}
+
+/// Get the current schema version.
+fn get_current_version(conn: &Connection) -> Result<i32> {
+    let result = conn.query_row(
+        "SELECT COALESCE(MAX(version), 0) FROM schema_migrations",
+        [],
+        |row| row.get(0),
+    );
+
+    match result {
+        Ok(version) => Ok(version),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(0),
+        Err(e) => Err(StorageError::Migration(format!("failed to get version: {e}")).into()),
+    }
+}
+
+/// Record a migration as applied.
+fn record_migration(conn: &Connection, version: i32) -> Result<()> {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let now_i64 = i64::try_from(now).unwrap_or_default();
+
+    conn.execute(
+        "INSERT INTO schema_migrations (version, applied_at) VALUES (?, ?)",
+        rusqlite::params![version, now_i64],
+    )
+    .map_err(|e| StorageError::Migration(format!("failed to record migration: {e}")))?;
 
     Ok(())
 }
@@ -185,6 +514,9 @@ pub fn verify_schema(conn: &Connection) -> Result<()> {
         "file_state",
         "agent_status",
         "watch_dirs",
+        "meta",
+        "chunks_fts",
+        "stats",
     ];
 
     for table in tables {
@@ -439,4 +771,425 @@ mod tests {
         })
         .unwrap();
     }
+
+    #[test]
+    fn test_migrate_v1_database_to_v2() {
+        let db = Database::open_in_memory().unwrap();
+        db.with_conn(|conn| {
+            migrate_to(conn, MIGRATIONS, 1)?;
+            conn.execute(
+                "INSERT INTO chunks (file_path, chunk_index, start_line, end_line, content, \
+                 file_hash, indexed_at)
+                 VALUES ('/old.rs', 0, 1, 2, 'code', 'h', 0)",
+                [],
+            )
+            .unwrap();
+
+            migrate(conn)?;
+            assert_eq!(get_current_version(conn)?, SCHEMA_VERSION);
+
+            let repo: Option<String> = conn
+                .query_row(
+                    "SELECT repo FROM chunks WHERE file_path = '/old.rs'",
+                    [],
+                    |row| row.get(0),
+                )
+                .unwrap();
+            assert!(repo.is_none());
+
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_migrate_v2_database_to_v3() {
+        let db = Database::open_in_memory().unwrap();
+        db.with_conn(|conn| {
+            migrate_to(conn, MIGRATIONS, 2)?;
+
+            migrate(conn)?;
+            assert_eq!(get_current_version(conn)?, SCHEMA_VERSION);
+            verify_schema(conn)?;
+
+            conn.execute(
+                "INSERT INTO meta (key, value, updated_at) VALUES ('k', 'v', 0)",
+                [],
+            )
+            .unwrap();
+
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_migrate_v3_database_to_v4() {
+        let db = Database::open_in_memory().unwrap();
+        db.with_conn(|conn| {
+            migrate_to(conn, MIGRATIONS, 3)?;
+            conn.execute(
+                "INSERT INTO lessons (id, title, content, tags, severity, created_at, updated_at)
+                 VALUES ('old', 'Old', 'Kept', '[]', 'info', 0, 0)",
+                [],
+            )
+            .unwrap();
+
+            migrate(conn)?;
+            assert_eq!(get_current_version(conn)?, SCHEMA_VERSION);
+
+            let (hits, useful, accessed): (i64, i64, Option<i64>) = conn
+                .query_row(
+                    "SELECT hit_count, useful_count, last_accessed FROM lessons WHERE id = 'old'",
+                    [],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+                )
+                .unwrap();
+            assert_eq!((hits, useful, accessed), (0, 0, None));
+
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_migrate_v4_database_to_v5() {
+        let db = Database::open_in_memory().unwrap();
+        db.with_conn(|conn| {
+            migrate_to(conn, MIGRATIONS, 4)?;
+            conn.execute(
+                "INSERT INTO checkpoints (id, agent, working_on, state, created_at)
+                 VALUES ('old', 'agent', 'Task', '{}', 0)",
+                [],
+            )
+            .unwrap();
+
+            migrate(conn)?;
+            assert_eq!(get_current_version(conn)?, SCHEMA_VERSION);
+
+            let parent: Option<String> = conn
+                .query_row(
+                    "SELECT parent_id FROM checkpoints WHERE id = 'old'",
+                    [],
+                    |row| row.get(0),
+                )
+                .unwrap();
+            assert!(parent.is_none());
+
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_migrate_v5_database_to_v6() {
+        let db = Database::open_in_memory().unwrap();
+        db.with_conn(|conn| {
+            migrate_to(conn, MIGRATIONS, 5)?;
+            conn.execute(
+                "INSERT INTO chunks (file_path, chunk_index, start_line, end_line, content, file_hash, indexed_at)
+                 VALUES ('/a.rs', 0, 1, 1, 'fn a() {}', 'h', 0)",
+                [],
+            )
+            .unwrap();
+
+            migrate(conn)?;
+            assert_eq!(get_current_version(conn)?, SCHEMA_VERSION);
+
+            let (commit, branch): (Option<String>, Option<String>) = conn
+                .query_row(
+                    "SELECT git_commit, git_branch FROM chunks WHERE file_path = '/a.rs'",
+                    [],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .unwrap();
+            assert!(commit.is_none());
+            assert!(branch.is_none());
+
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_migrate_v7_database_to_v8() {
+        let db = Database::open_in_memory().unwrap();
+        db.with_conn(|conn| {
+            migrate_to(conn, MIGRATIONS, 7)?;
+            conn.execute(
+                "INSERT INTO chunks (file_path, chunk_index, start_line, end_line, content, file_hash, indexed_at)
+                 VALUES ('/a.rs', 0, 1, 1, 'fn retry_with_backoff() {}', 'h', 0)",
+                [],
+            )
+            .unwrap();
+
+            migrate(conn)?;
+            assert_eq!(get_current_version(conn)?, SCHEMA_VERSION);
+
+            // Existing chunks are indexed by the rebuild
+            let count = |conn: &Connection, term: &str| -> i64 {
+                conn.query_row(
+                    "SELECT COUNT(*) FROM chunks_fts WHERE chunks_fts MATCH ?",
+                    [term],
+                    |row| row.get(0),
+                )
+                .unwrap()
+            };
+            assert_eq!(count(conn, "retry_with_backoff"), 1);
+
+            // Triggers keep the index in sync
+            conn.execute(
+                "UPDATE chunks SET content = 'fn parse_config() {}' WHERE file_path = '/a.rs'",
+                [],
+            )
+            .unwrap();
+            assert_eq!(count(conn, "retry_with_backoff"), 0);
+            assert_eq!(count(conn, "parse_config"), 1);
+
+            conn.execute("DELETE FROM chunks WHERE file_path = '/a.rs'", [])
+                .unwrap();
+            assert_eq!(count(conn, "parse_config"), 0);
+
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_migrate_v9_database_to_v10() {
+        let db = Database::open_in_memory().unwrap();
+        db.with_conn(|conn| {
+            migrate_to(conn, MIGRATIONS, 9)?;
+            conn.execute(
+                "INSERT INTO lessons (id, title, content, tags, created_at, updated_at)
+                 VALUES ('old', 'Old lesson', 'Content', '[]', 0, 0)",
+                [],
+            )
+            .unwrap();
+
+            migrate(conn)?;
+            assert_eq!(get_current_version(conn)?, SCHEMA_VERSION);
+
+            // Existing lessons stay live
+            let archived: bool = conn
+                .query_row("SELECT archived FROM lessons WHERE id = 'old'", [], |row| {
+                    row.get(0)
+                })
+                .unwrap();
+            assert!(!archived);
+
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_migrate_v10_database_to_v11() {
+        let db = Database::open_in_memory().unwrap();
+        db.with_conn(|conn| {
+            migrate_to(conn, MIGRATIONS, 10)?;
+            assert!(!table_exists(conn, "chunk_content"));
+
+            migrate(conn)?;
+            assert_eq!(get_current_version(conn)?, SCHEMA_VERSION);
+            assert!(table_exists(conn, "chunk_content"));
+
+            rollback(conn, 10)?;
+            assert!(!table_exists(conn, "chunk_content"));
+
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_migrate_v11_database_to_v12() {
+        let db = Database::open_in_memory().unwrap();
+        db.with_conn(|conn| {
+            migrate_to(conn, MIGRATIONS, 11)?;
+            assert!(!table_exists(conn, "index_errors"));
+
+            migrate(conn)?;
+            assert_eq!(get_current_version(conn)?, SCHEMA_VERSION);
+            assert!(table_exists(conn, "index_errors"));
+
+            rollback(conn, 11)?;
+            assert!(!table_exists(conn, "index_errors"));
+
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    fn table_exists(conn: &Connection, table: &str) -> bool {
+        conn.query_row(
+            "SELECT 1 FROM sqlite_master WHERE type='table' AND name=?",
+            [table],
+            |_| Ok(true),
+        )
+        .unwrap_or(false)
+    }
+
+    #[test]
+    fn test_migrations_are_numbered_in_order() {
+        let versions: Vec<i32> = MIGRATIONS.iter().map(|m| m.version).collect();
+        let expected: Vec<i32> = (1..=SCHEMA_VERSION).collect();
+        assert_eq!(versions, expected);
+    }
+
+    #[test]
+    fn test_migrate_each_version_from_v0() {
+        let db = Database::open_in_memory().unwrap();
+        db.with_conn(|conn| {
+            for target in 1..=SCHEMA_VERSION {
+                migrate_to(conn, MIGRATIONS, target)?;
+                assert_eq!(get_current_version(conn)?, target);
+
+                // Re-running at the same target is a no-op
+                migrate_to(conn, MIGRATIONS, target)?;
+                assert_eq!(get_current_version(conn)?, target);
+            }
+
+            let recorded: i64 = conn
+                .query_row("SELECT COUNT(*) FROM schema_migrations", [], |row| {
+                    row.get(0)
+                })
+                .unwrap();
+            assert_eq!(recorded, i64::from(SCHEMA_VERSION));
+            verify_schema(conn)?;
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_migrate_refuses_newer_schema() {
+        let db = Database::open_in_memory().unwrap();
+        db.with_conn(|conn| {
+            migrate(conn)?;
+            record_migration(conn, SCHEMA_VERSION + 1)?;
+
+            let err = migrate(conn).unwrap_err();
+            assert!(err.to_string().contains("newer than this build"));
+            let err = rollback(conn, 0).unwrap_err();
+            assert!(err.to_string().contains("newer than this build"));
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_rollback_to_v0_and_back() {
+        let db = Database::open_in_memory().unwrap();
+        db.with_conn(|conn| {
+            migrate(conn)?;
+            rollback(conn, 0)?;
+
+            assert_eq!(get_current_version(conn)?, 0);
+            for table in ["chunks", "lessons", "meta", "chunks_fts", "stats"] {
+                assert!(!table_exists(conn, table), "{table} still exists");
+            }
+
+            migrate(conn)?;
+            assert_eq!(get_current_version(conn)?, SCHEMA_VERSION);
+            verify_schema(conn)?;
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_rollback_keeps_older_data() {
+        let db = Database::open_in_memory().unwrap();
+        db.with_conn(|conn| {
+            migrate(conn)?;
+            conn.execute(
+                "INSERT INTO chunks (file_path, chunk_index, start_line, end_line, content, \
+                 file_hash, indexed_at, git_commit)
+                 VALUES ('/a.rs', 0, 1, 1, 'fn a() {}', 'h', 0, 'abc')",
+                [],
+            )
+            .unwrap();
+
+            rollback(conn, 5)?;
+            assert_eq!(get_current_version(conn)?, 5);
+            assert!(conn
+                .query_row("SELECT git_commit FROM chunks", [], |row| row
+                    .get::<_, Option<String>>(0))
+                .is_err());
+            let content: String = conn
+                .query_row("SELECT content FROM chunks", [], |row| row.get(0))
+                .unwrap();
+            assert_eq!(content, "fn a() {}");
+
+            migrate(conn)?;
+            let commit: Option<String> = conn
+                .query_row("SELECT git_commit FROM chunks", [], |row| row.get(0))
+                .unwrap();
+            assert!(commit.is_none());
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_rollback_without_down_fails_before_reverting() {
+        let migrations = [
+            Migration {
+                version: 1,
+                description: "a",
+                up: "CREATE TABLE a (x INTEGER);",
+                down: None,
+            },
+            Migration {
+                version: 2,
+                description: "b",
+                up: "CREATE TABLE b (x INTEGER);",
+                down: Some("DROP TABLE b;"),
+            },
+        ];
+        let db = Database::open_in_memory().unwrap();
+        db.with_conn(|conn| {
+            migrate_to(conn, &migrations, 2)?;
+
+            let err = rollback_to(conn, &migrations, 0).unwrap_err();
+            assert!(err.to_string().contains("v1 (a) cannot be rolled back"));
+            assert_eq!(get_current_version(conn)?, 2);
+            assert!(table_exists(conn, "b"));
+
+            rollback_to(conn, &migrations, 1)?;
+            assert_eq!(get_current_version(conn)?, 1);
+            assert!(!table_exists(conn, "b"));
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_failed_migration_is_rolled_back() {
+        let migrations = [
+            Migration {
+                version: 1,
+                description: "a",
+                up: "CREATE TABLE a (x INTEGER);",
+                down: None,
+            },
+            Migration {
+                version: 2,
+                description: "broken",
+                up: "CREATE TABLE b (x INTEGER); NOT VALID SQL;",
+                down: None,
+            },
+        ];
+        let db = Database::open_in_memory().unwrap();
+        db.with_conn(|conn| {
+            let err = migrate_to(conn, &migrations, 2).unwrap_err();
+            assert!(err.to_string().contains("v2 migration failed"));
+            assert_eq!(get_current_version(conn)?, 1);
+            assert!(table_exists(conn, "a"));
+            assert!(!table_exists(conn, "b"));
+            Ok(())
+        })
+        .unwrap();
+    }
 }
@@ -3,9 +3,13 @@
 //! Provides vector similarity search across chunks, lessons, and checkpoints.
 
 use rusqlite::Connection;
+use serde::Serialize;
 
+use super::chunk_content::get_group_copies;
+use super::chunks::get_chunks_by_file;
+use super::meta::get_distance_metric;
 use super::models::{ChunkRecord, SearchResult};
-use super::vector::search_similar;
+use super::vector::{get_vectors, search_similar};
 use crate::error::StorageError;
 use crate::Result;
 
@@ -24,8 +28,29 @@ pub struct SearchOptions {
     /// Filter by programming language.
     pub language: Option<String>,
 
+    /// Leave out chunks tagged with any of these languages.
+    pub exclude_languages: Vec<String>,
+
     /// Filter by file path pattern.
     pub path_pattern: Option<String>,
+
+    /// Restrict results to paths under this prefix.
+    pub path_prefix: Option<String>,
+
+    /// Restrict results to paths matching this glob.
+    pub path_glob: Option<String>,
+
+    /// Restrict results to chunks from this repository.
+    pub repo: Option<String>,
+
+    /// Only chunks indexed at or after this Unix timestamp.
+    pub indexed_after: Option<i64>,
+
+    /// Only chunks indexed before this Unix timestamp.
+    pub indexed_before: Option<i64>,
+
+    /// Populate `ChunkRecord.embedding` on results (needed for reranking).
+    pub include_embeddings: bool,
 }
 
 impl Default for SearchOptions {
@@ -34,7 +59,14 @@ impl Default for SearchOptions {
             limit: 10,
             min_score: 0.0,
             language: None,
+            exclude_languages: Vec::new(),
             path_pattern: None,
+            path_prefix: None,
+            path_glob: None,
+            repo: None,
+            indexed_after: None,
+            indexed_before: None,
+            include_embeddings: false,
         }
     }
 }
@@ -63,12 +95,149 @@ impl SearchOptions {
         self
     }
 
+    /// Leave out chunks tagged with any of `languages`.
+    #[must_use]
+    pub fn with_exclude_languages(mut self, languages: &[&str]) -> Self {
+        self.exclude_languages = languages.iter().map(|l| (*l).to_string()).collect();
+        self
+    }
+
     /// Filter by file path pattern (SQL LIKE).
     #[must_use]
     pub fn with_path_pattern(mut self, pattern: impl Into<String>) -> Self {
         self.path_pattern = Some(pattern.into());
         self
     }
+
+    /// Filter by path prefix.
+    ///
+    /// An absolute prefix (`/repo/services/`) matches from the start of the
+    /// path; a relative one (`services/payments/`) matches at any directory
+    /// boundary.
+    #[must_use]
+    pub fn with_path_prefix(mut self, prefix: &str) -> Self {
+        self.path_prefix = Some(prefix.to_string());
+        self
+    }
+
+    /// Filter by path glob (e.g. `services/**/*.rs`).
+    ///
+    /// Relative globs match at any directory boundary, like `with_path_prefix`.
+    #[must_use]
+    pub fn with_path_glob(mut self, glob: &str) -> Self {
+        self.path_glob = Some(glob.to_string());
+        self
+    }
+
+    /// Filter by repository name.
+    #[must_use]
+    pub fn with_repo(mut self, repo: impl Into<String>) -> Self {
+        self.repo = Some(repo.into());
+        self
+    }
+
+    /// Only return chunks indexed at or after `timestamp` (Unix seconds).
+    #[must_use]
+    pub const fn with_indexed_after(mut self, timestamp: i64) -> Self {
+        self.indexed_after = Some(timestamp);
+        self
+    }
+
+    /// Only return chunks indexed before `timestamp` (Unix seconds).
+    #[must_use]
+    pub const fn with_indexed_before(mut self, timestamp: i64) -> Self {
+        self.indexed_before = Some(timestamp);
+        self
+    }
+
+    /// Return stored embeddings alongside results.
+    #[must_use]
+    pub const fn with_embeddings(mut self) -> Self {
+        self.include_embeddings = true;
+        self
+    }
+}
+
+/// Escape `%`, `_` and `\` for use in a `LIKE ... ESCAPE '\'` pattern.
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Compile a path glob, anchoring relative globs at any directory boundary.
+///
+/// # Errors
+///
+/// Returns an error if the glob is invalid.
+pub fn compile_path_glob(glob: &str) -> Result<globset::GlobMatcher> {
+    let anchored = if glob.starts_with('/') || glob.starts_with("**") {
+        glob.to_string()
+    } else {
+        format!("**/{glob}")
+    };
+
+    globset::Glob::new(&anchored)
+        .map(|g| g.compile_matcher())
+        .map_err(|e| StorageError::Database(format!("invalid path glob '{glob}': {e}")).into())
+}
+
+/// Append the SQL-expressible filters in `options` to a query over `chunks c`.
+///
+/// Path globs can't be expressed in SQL and are left to the caller.
+fn push_chunk_filters(
+    options: &SearchOptions,
+    sql: &mut String,
+    params: &mut Vec<rusqlite::types::Value>,
+) {
+    if let Some(ref lang) = options.language {
+        sql.push_str(" AND c.language = ?");
+        params.push(rusqlite::types::Value::Text(lang.clone()));
+    }
+
+    if !options.exclude_languages.is_empty() {
+        let placeholders = vec!["?"; options.exclude_languages.len()].join(", ");
+        sql.push_str(&format!(
+            " AND (c.language IS NULL OR c.language NOT IN ({placeholders}))"
+        ));
+        params.extend(
+            options
+                .exclude_languages
+                .iter()
+                .map(|lang| rusqlite::types::Value::Text(lang.clone())),
+        );
+    }
+
+    if let Some(ref pattern) = options.path_pattern {
+        sql.push_str(" AND c.file_path LIKE ?");
+        params.push(rusqlite::types::Value::Text(pattern.clone()));
+    }
+
+    if let Some(ref prefix) = options.path_prefix {
+        let like = if prefix.starts_with('/') {
+            format!("{}%", escape_like(prefix))
+        } else {
+            format!("%/{}%", escape_like(prefix))
+        };
+        sql.push_str(" AND c.file_path LIKE ? ESCAPE '\\'");
+        params.push(rusqlite::types::Value::Text(like));
+    }
+
+    if let Some(ref repo) = options.repo {
+        sql.push_str(" AND c.repo = ?");
+        params.push(rusqlite::types::Value::Text(repo.clone()));
+    }
+
+    if let Some(after) = options.indexed_after {
+        sql.push_str(" AND c.indexed_at >= ?");
+        params.push(rusqlite::types::Value::Integer(after));
+    }
+
+    if let Some(before) = options.indexed_before {
+        sql.push_str(" AND c.indexed_at < ?");
+        params.push(rusqlite::types::Value::Integer(before));
+    }
 }
 
 /// Search for similar code chunks.
@@ -91,35 +260,49 @@ pub fn search_chunks(
     query_embedding: &[f32],
     options: &SearchOptions,
 ) -> Result<Vec<SearchResult<ChunkRecord>>> {
+    let path_matcher = options
+        .path_glob
+        .as_deref()
+        .map(compile_path_glob)
+        .transpose()?;
+
     // Get candidate IDs from vector search
-    // Request more than limit to account for filtering
-    let candidate_limit = options.limit * 3;
+    // Request more than limit to account for filtering; path, repo and
+    // indexed-at scoping discard far more candidates than language filtering does
+    let overfetch = if options.path_prefix.is_some()
+        || path_matcher.is_some()
+        || options.repo.is_some()
+        || options.indexed_after.is_some()
+        || options.indexed_before.is_some()
+    {
+        10
+    } else {
+        3
+    };
+    let candidate_limit = options.limit.saturating_mul(overfetch);
     let candidates = search_similar(conn, CHUNK_VEC_TABLE, query_embedding, candidate_limit)?;
+    let metric = get_distance_metric(conn)?;
 
     if candidates.is_empty() {
         return Ok(Vec::new());
     }
 
-    // Build filtered query
-    let mut conditions = vec!["c.id IN (".to_string()];
-    let placeholders: Vec<String> = candidates.iter().map(|_| "?".to_string()).collect();
-    conditions.push(placeholders.join(","));
-    conditions.push(")".to_string());
-
-    if let Some(ref lang) = options.language {
-        conditions.push(format!(" AND c.language = '{lang}'"));
-    }
-
-    if let Some(ref pattern) = options.path_pattern {
-        conditions.push(format!(" AND c.file_path LIKE '{pattern}'"));
-    }
+    let (candidates, group_of) = with_duplicate_copies(conn, candidates)?;
 
-    let sql = format!(
-        "SELECT c.id, c.file_path, c.chunk_index, c.start_line, c.end_line, c.content, c.language, c.file_hash, c.indexed_at
+    // Build filtered query; all values are bound as parameters
+    let placeholders: Vec<&str> = candidates.iter().map(|_| "?").collect();
+    let mut sql = format!(
+        "SELECT c.id, c.file_path, c.chunk_index, c.start_line, c.end_line, c.content, c.language, c.file_hash, c.indexed_at, c.repo,
+                c.git_commit, c.git_branch
          FROM chunks c
-         WHERE {}",
-        conditions.join("")
+         WHERE c.id IN ({})",
+        placeholders.join(",")
     );
+    let mut params: Vec<rusqlite::types::Value> = candidates
+        .iter()
+        .map(|(id, _)| rusqlite::types::Value::Integer(*id))
+        .collect();
+    push_chunk_filters(options, &mut sql, &mut params);
 
     let mut stmt = conn
         .prepare(&sql)
@@ -128,13 +311,8 @@ pub fn search_chunks(
     // Create a map of id -> distance for quick lookup
     let distance_map: std::collections::HashMap<i64, f32> = candidates.iter().copied().collect();
 
-    // Execute query with candidate IDs as parameters
-    let params: Vec<i64> = candidates.iter().map(|(id, _)| *id).collect();
-    let param_refs: Vec<&dyn rusqlite::ToSql> =
-        params.iter().map(|id| id as &dyn rusqlite::ToSql).collect();
-
     let rows = stmt
-        .query_map(param_refs.as_slice(), |row| {
+        .query_map(rusqlite::params_from_iter(params), |row| {
             Ok(ChunkRecord {
                 id: Some(row.get(0)?),
                 file_path: row.get(1)?,
@@ -145,6 +323,9 @@ pub fn search_chunks(
                 language: row.get(6)?,
                 file_hash: row.get(7)?,
                 indexed_at: row.get(8)?,
+                repo: row.get(9)?,
+                git_commit: row.get(10)?,
+                git_branch: row.get(11)?,
                 embedding: None,
             })
         })
@@ -154,9 +335,14 @@ pub fn search_chunks(
     for row in rows {
         let chunk =
             row.map_err(|e| StorageError::Database(format!("failed to read result: {e}")))?;
+        if let Some(ref matcher) = path_matcher {
+            if !matcher.is_match(&chunk.file_path) {
+                continue;
+            }
+        }
         let chunk_id = chunk.id.unwrap_or(0);
         let distance = distance_map.get(&chunk_id).copied().unwrap_or(f32::MAX);
-        let result = SearchResult::new(chunk, distance);
+        let result = SearchResult::with_metric(chunk, distance, metric);
 
         // Apply score filter
         if result.score >= options.min_score {
@@ -164,14 +350,31 @@ pub fn search_chunks(
         }
     }
 
-    // Sort by distance (ascending) and limit
+    // Sort by distance (ascending), keep one chunk per content group and
+    // limit. A canonical chunk has the lowest id in its group, so it wins ties.
+    let group = |result: &SearchResult<ChunkRecord>| {
+        let id = result.record.id.unwrap_or(0);
+        group_of.get(&id).copied().unwrap_or(id)
+    };
     results.sort_by(|a, b| {
         a.distance
             .partial_cmp(&b.distance)
             .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.record.id.cmp(&b.record.id))
     });
+    let mut seen_groups = std::collections::HashSet::new();
+    results.retain(|result| seen_groups.insert(group(result)));
     results.truncate(options.limit);
 
+    if options.include_embeddings {
+        // Copies use their canonical chunk's embedding
+        let ids: Vec<i64> = results.iter().map(group).collect();
+        let mut vectors = get_vectors(conn, CHUNK_VEC_TABLE, &ids)?;
+        for result in &mut results {
+            result.record.embedding = vectors.remove(&group(result));
+        }
+    }
+
     tracing::debug!(
         count = results.len(),
         limit = options.limit,
@@ -181,22 +384,600 @@ pub fn search_chunks(
     Ok(results)
 }
 
-/// Search for similar code by text (requires embedding generation).
+/// Canonical chunk ID of each vector search candidate, keyed by chunk ID.
+type CanonicalIds = std::collections::HashMap<i64, i64>;
+
+/// Add the deduplicated copies of vector search candidates.
+///
+/// Only canonical chunks of deduplicated content have embeddings. Their
+/// copies share the distance and are filtered on their own path, repo and
+/// language, so a copy in scope stands in for a canonical chunk that isn't.
+/// Also returns each candidate's canonical chunk.
+fn with_duplicate_copies(
+    conn: &Connection,
+    mut candidates: Vec<(i64, f32)>,
+) -> Result<(Vec<(i64, f32)>, CanonicalIds)> {
+    let canonical_ids: Vec<i64> = candidates.iter().map(|(id, _)| *id).collect();
+    let groups = get_group_copies(conn, &canonical_ids)?;
+    let mut group_of: CanonicalIds = canonical_ids.iter().map(|&id| (id, id)).collect();
+    for (canonical, distance) in candidates.clone() {
+        for &copy in groups.get(&canonical).into_iter().flatten() {
+            group_of.insert(copy, canonical);
+            candidates.push((copy, distance));
+        }
+    }
+    Ok((candidates, group_of))
+}
+
+/// Get the stored embedding for a chunk.
+///
+/// Returns `None` if the chunk has no embedding (e.g. indexed without an
+/// embedding service).
+///
+/// # Errors
+///
+/// Returns an error if the vector lookup fails.
+pub fn get_chunk_embedding(conn: &Connection, chunk_id: i64) -> Result<Option<Vec<f32>>> {
+    Ok(get_vectors(conn, CHUNK_VEC_TABLE, &[chunk_id])?.remove(&chunk_id))
+}
+
+/// Find chunks semantically similar to an existing chunk.
+///
+/// Uses the chunk's stored embedding as the query vector, so no inference is
+/// needed. The chunk itself is excluded from the results.
+///
+/// # Errors
+///
+/// Returns an error if the chunk has no stored embedding or the search fails.
+pub fn find_similar_chunks(
+    conn: &Connection,
+    chunk_id: i64,
+    limit: usize,
+) -> Result<Vec<SearchResult<ChunkRecord>>> {
+    let embedding = get_chunk_embedding(conn, chunk_id)?.ok_or_else(|| StorageError::NotFound {
+        entity: "chunk embedding",
+        id: chunk_id.to_string(),
+    })?;
+
+    let mut results = search_chunks(conn, &embedding, &SearchOptions::new(limit + 1))?;
+    results.retain(|r| r.record.id != Some(chunk_id));
+    results.truncate(limit);
+    Ok(results)
+}
+
+/// Find chunks similar to a file as a whole.
 ///
-/// This is a convenience wrapper that will be used when embeddings are available.
-/// For now, it's a placeholder that returns an error.
+/// Averages the stored embeddings of `file_path`'s chunks that overlap the
+/// inclusive `lines` range (every chunk if `None`) into one normalized
+/// query vector, so no inference is needed. The file's own chunks are
+/// excluded from the results. Returns how many embeddings were pooled along
+/// with the results.
 ///
 /// # Errors
 ///
-/// Returns an error because embeddings are not yet integrated.
+/// Returns `NotFound` if none of those chunks has a stored embedding, or an
+/// error if the search fails.
+pub fn find_chunks_like_file(
+    conn: &Connection,
+    file_path: &str,
+    lines: Option<(i32, i32)>,
+    options: &SearchOptions,
+) -> Result<(usize, Vec<SearchResult<ChunkRecord>>)> {
+    let chunks = get_chunks_by_file(conn, file_path)?;
+    let ids: Vec<i64> = chunks
+        .iter()
+        .filter(|c| match lines {
+            Some((start, end)) => c.start_line <= end && c.end_line >= start,
+            None => true,
+        })
+        .filter_map(|c| c.id)
+        .collect();
+    let vectors = get_vectors(conn, CHUNK_VEC_TABLE, &ids)?;
+
+    let Some(dim) = vectors.values().next().map(Vec::len) else {
+        return Err(StorageError::NotFound {
+            entity: "file embeddings",
+            id: file_path.to_string(),
+        }
+        .into());
+    };
+    let mut query = vec![0.0; dim];
+    for vector in vectors.values() {
+        for (sum, value) in query.iter_mut().zip(vector) {
+            *sum += value;
+        }
+    }
+    crate::embeddings::l2_normalize(&mut query);
+
+    // The file's own chunks tend to rank first, so fetch past all of them
+    let mut fetch = options.clone();
+    fetch.limit = options.limit + chunks.len();
+    let mut results = search_chunks(conn, &query, &fetch)?;
+    results.retain(|r| r.record.file_path != file_path);
+    results.truncate(options.limit);
+    Ok((vectors.len(), results))
+}
+
+/// Rerank results with Maximal Marginal Relevance.
+///
+/// Greedily picks up to `limit` results, each maximising
+/// `lambda * sim(query, d) - (1 - lambda) * max sim(d, selected)`, so
+/// near-duplicates of already-selected chunks are pushed down. `lambda = 1.0`
+/// is pure relevance ordering; lower values favour diversity.
+///
+/// Results need `record.embedding` populated (see
+/// [`SearchOptions::with_embeddings`]); those without one fall back to
+/// their search score and are never penalised as duplicates.
+#[must_use]
+pub fn rerank_mmr(
+    query_embedding: &[f32],
+    candidates: Vec<SearchResult<ChunkRecord>>,
+    lambda: f32,
+    limit: usize,
+) -> Vec<SearchResult<ChunkRecord>> {
+    let lambda = lambda.clamp(0.0, 1.0);
+    let relevance: Vec<f32> = candidates
+        .iter()
+        .map(|r| {
+            r.record
+                .embedding
+                .as_deref()
+                .map_or(r.score, |e| cosine_similarity(query_embedding, e))
+        })
+        .collect();
+
+    let mut remaining: Vec<usize> = (0..candidates.len()).collect();
+    let mut selected: Vec<usize> = Vec::with_capacity(limit.min(candidates.len()));
+
+    while selected.len() < limit && !remaining.is_empty() {
+        let mut best_pos = 0;
+        let mut best_score = f32::NEG_INFINITY;
+
+        for (pos, &idx) in remaining.iter().enumerate() {
+            let redundancy = candidates[idx]
+                .record
+                .embedding
+                .as_deref()
+                .map_or(0.0, |e| {
+                    selected
+                        .iter()
+                        .filter_map(|&s| candidates[s].record.embedding.as_deref())
+                        .map(|other| cosine_similarity(e, other))
+                        .fold(0.0_f32, f32::max)
+                });
+            let score = lambda * relevance[idx] - (1.0 - lambda) * redundancy;
+            if score > best_score {
+                best_score = score;
+                best_pos = pos;
+            }
+        }
+
+        selected.push(remaining.remove(best_pos));
+    }
+
+    // Pull the selected results out in selection order
+    let mut slots: Vec<Option<SearchResult<ChunkRecord>>> =
+        candidates.into_iter().map(Some).collect();
+    selected
+        .into_iter()
+        .filter_map(|idx| slots[idx].take())
+        .collect()
+}
+
+/// Blend result scores with the age of each record, then re-sort.
+///
+/// Each score becomes `(1 - weight) * score + weight * decay`, where
+/// `decay = 0.5^(age / half_life_secs)` is 1.0 for a brand-new record and
+/// halves every `half_life_secs`. `weight = 0.0` leaves scores and order
+/// untouched.
+#[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+pub fn rerank_by_recency<T>(
+    results: &mut [SearchResult<T>],
+    created_at: impl Fn(&T) -> i64,
+    weight: f32,
+    half_life_secs: i64,
+    now: i64,
+) {
+    let weight = weight.clamp(0.0, 1.0);
+    if weight <= 0.0 || half_life_secs <= 0 {
+        return;
+    }
+
+    for result in results.iter_mut() {
+        let age = (now - created_at(&result.record)).max(0);
+        let decay = 0.5_f64.powf(age as f64 / half_life_secs as f64) as f32;
+        result.score = (1.0 - weight) * result.score + weight * decay;
+    }
+    results.sort_by(|a, b| b.score.total_cmp(&a.score));
+}
+
+/// Cosine similarity between two vectors (0.0 if either is zero).
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+/// Find code chunks that reference an exact identifier.
+///
+/// Matches `symbol` as a whole identifier (so `parse` does not match
+/// `parse_args`), ordered by file path and chunk position.
+///
+/// # Errors
+///
+/// Returns an error if the query fails.
+pub fn search_chunks_by_symbol(
+    conn: &Connection,
+    symbol: &str,
+    limit: usize,
+) -> Result<Vec<ChunkRecord>> {
+    let symbol = symbol.trim();
+    if symbol.is_empty() || limit == 0 {
+        return Ok(Vec::new());
+    }
+
+    let pattern = format!("%{}%", escape_like(symbol));
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, file_path, chunk_index, start_line, end_line, content, language, file_hash, indexed_at, repo,
+                    git_commit, git_branch
+             FROM chunks
+             WHERE content != '' AND content LIKE ? ESCAPE '\\'
+             ORDER BY file_path, chunk_index",
+        )
+        .map_err(|e| StorageError::Database(format!("failed to prepare symbol search: {e}")))?;
+
+    let rows = stmt
+        .query_map([&pattern], |row| {
+            Ok(ChunkRecord {
+                id: Some(row.get(0)?),
+                file_path: row.get(1)?,
+                chunk_index: row.get(2)?,
+                start_line: row.get(3)?,
+                end_line: row.get(4)?,
+                content: row.get(5)?,
+                language: row.get(6)?,
+                file_hash: row.get(7)?,
+                indexed_at: row.get(8)?,
+                repo: row.get(9)?,
+                git_commit: row.get(10)?,
+                git_branch: row.get(11)?,
+                embedding: None,
+            })
+        })
+        .map_err(|e| StorageError::Database(format!("failed to execute symbol search: {e}")))?;
+
+    // LIKE is a substring match (and case-insensitive for ASCII), so confirm
+    // an exact identifier match before accepting the row
+    let mut results = Vec::new();
+    for row in rows {
+        let chunk =
+            row.map_err(|e| StorageError::Database(format!("failed to read result: {e}")))?;
+        if content_has_symbol(&chunk.content, symbol) {
+            results.push(chunk);
+            if results.len() >= limit {
+                break;
+            }
+        }
+    }
+
+    tracing::debug!(symbol, count = results.len(), "Symbol search completed");
+
+    Ok(results)
+}
+
+/// Check whether `content` contains `symbol` as a whole identifier.
+#[must_use]
+pub fn content_has_symbol(content: &str, symbol: &str) -> bool {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+
+    content.match_indices(symbol).any(|(start, _)| {
+        let before = content[..start].chars().next_back();
+        let after = content[start + symbol.len()..].chars().next();
+        !before.is_some_and(is_ident) && !after.is_some_and(is_ident)
+    })
+}
+
+/// Most query terms used for a keyword search.
+const MAX_FTS_TERMS: usize = 32;
+
+/// Build an FTS5 query matching any identifier-like term in `query`.
+///
+/// Each term is quoted so FTS5 operators and punctuation in the query are
+/// taken literally. Returns `None` if the query has no terms.
+fn fts_query(query: &str) -> Option<String> {
+    let mut terms: Vec<String> = Vec::new();
+    let words = query
+        .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|word| !word.is_empty());
+    for word in words {
+        let term = format!("\"{}\"", word.to_lowercase());
+        if !terms.contains(&term) {
+            terms.push(term);
+        }
+        if terms.len() >= MAX_FTS_TERMS {
+            break;
+        }
+    }
+
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" OR "))
+    }
+}
+
+/// Search code chunks by keyword, ranked by BM25.
+///
+/// The fallback for code search when no embedding service is available.
+/// Chunks matching any term of `query` are returned best first. BM25 has no
+/// fixed range, so `score` is relative to the best match in this result set
+/// (1.0 for the top hit) and `distance` is `1.0 - score`; `min_score` applies
+/// to that relative score.
+///
+/// # Errors
+///
+/// Returns an error if the query fails or the path glob is invalid.
 pub fn search_chunks_by_text(
-    _conn: &Connection,
-    _query: &str,
-    _options: &SearchOptions,
+    conn: &Connection,
+    query: &str,
+    options: &SearchOptions,
 ) -> Result<Vec<SearchResult<ChunkRecord>>> {
-    Err(crate::Error::internal(
-        "Text search requires embedding integration (Phase 1.3)",
-    ))
+    let Some(fts_query) = fts_query(query) else {
+        return Ok(Vec::new());
+    };
+    if options.limit == 0 {
+        return Ok(Vec::new());
+    }
+
+    let path_matcher = options
+        .path_glob
+        .as_deref()
+        .map(compile_path_glob)
+        .transpose()?;
+
+    let mut sql = String::from(
+        "SELECT c.id, c.file_path, c.chunk_index, c.start_line, c.end_line, c.content, c.language, c.file_hash, c.indexed_at, c.repo,
+                c.git_commit, c.git_branch, bm25(chunks_fts)
+         FROM chunks_fts
+         JOIN chunks c ON c.id = chunks_fts.rowid
+         WHERE chunks_fts MATCH ?",
+    );
+    let mut params = vec![rusqlite::types::Value::Text(fts_query)];
+    push_chunk_filters(options, &mut sql, &mut params);
+
+    // Globs are matched after the query, so fetch extra rows to filter from
+    let overfetch = if path_matcher.is_some() { 10 } else { 1 };
+    sql.push_str(" ORDER BY bm25(chunks_fts) LIMIT ?");
+    params.push(rusqlite::types::Value::Integer(
+        i64::try_from(options.limit.saturating_mul(overfetch)).unwrap_or(i64::MAX),
+    ));
+
+    let mut stmt = conn
+        .prepare(&sql)
+        .map_err(|e| StorageError::Database(format!("failed to prepare text search: {e}")))?;
+
+    let rows = stmt
+        .query_map(rusqlite::params_from_iter(params), |row| {
+            let chunk = ChunkRecord {
+                id: Some(row.get(0)?),
+                file_path: row.get(1)?,
+                chunk_index: row.get(2)?,
+                start_line: row.get(3)?,
+                end_line: row.get(4)?,
+                content: row.get(5)?,
+                language: row.get(6)?,
+                file_hash: row.get(7)?,
+                indexed_at: row.get(8)?,
+                repo: row.get(9)?,
+                git_commit: row.get(10)?,
+                git_branch: row.get(11)?,
+                embedding: None,
+            };
+            Ok((chunk, row.get::<_, f64>(12)?))
+        })
+        .map_err(|e| StorageError::Database(format!("failed to execute text search: {e}")))?;
+
+    // bm25() is negative, lower is better
+    let mut matches = Vec::new();
+    for row in rows {
+        let (chunk, rank) =
+            row.map_err(|e| StorageError::Database(format!("failed to read result: {e}")))?;
+        if let Some(ref matcher) = path_matcher {
+            if !matcher.is_match(&chunk.file_path) {
+                continue;
+            }
+        }
+        matches.push((chunk, rank));
+        if matches.len() >= options.limit {
+            break;
+        }
+    }
+
+    let best = matches.first().map_or(0.0, |(_, rank)| *rank);
+    #[allow(clippy::cast_possible_truncation)]
+    let results: Vec<SearchResult<ChunkRecord>> = matches
+        .into_iter()
+        .map(|(chunk, rank)| {
+            let score = if best < 0.0 {
+                (rank / best).clamp(0.0, 1.0) as f32
+            } else {
+                1.0
+            };
+            SearchResult {
+                record: chunk,
+                distance: 1.0 - score,
+                score,
+            }
+        })
+        .filter(|r| r.score >= options.min_score)
+        .collect();
+
+    tracing::debug!(count = results.len(), "Text search completed");
+
+    Ok(results)
+}
+
+/// Pattern for [`grep_chunks`].
+#[derive(Debug, Clone)]
+pub enum GrepPattern {
+    /// Case-sensitive substring.
+    Literal(String),
+    /// Regular expression, matched one line at a time.
+    Regex(regex::Regex),
+}
+
+impl GrepPattern {
+    /// Compile a regular expression pattern.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the expression is invalid.
+    pub fn regex(pattern: &str) -> Result<Self> {
+        regex::Regex::new(pattern)
+            .map(Self::Regex)
+            .map_err(|e| StorageError::Database(format!("invalid regex '{pattern}': {e}")).into())
+    }
+
+    /// Byte ranges of every match in `line`.
+    fn find_in(&self, line: &str) -> Vec<(usize, usize)> {
+        match self {
+            Self::Literal(literal) => line
+                .match_indices(literal.as_str())
+                .map(|(start, m)| (start, start + m.len()))
+                .collect(),
+            Self::Regex(re) => re.find_iter(line).map(|m| (m.start(), m.end())).collect(),
+        }
+    }
+}
+
+/// A line of a chunk matching a [`GrepPattern`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GrepLine {
+    /// Line number in the file.
+    pub line: i64,
+    /// Text of the line.
+    pub text: String,
+    /// Byte offsets `(start, end)` of each match within `text`.
+    pub ranges: Vec<(usize, usize)>,
+}
+
+/// A chunk with at least one line matching a [`GrepPattern`].
+#[derive(Debug, Clone)]
+pub struct GrepHit {
+    /// The matching chunk.
+    pub chunk: ChunkRecord,
+    /// Matching lines, in order.
+    pub lines: Vec<GrepLine>,
+}
+
+/// Find code chunks containing `pattern`, without embeddings or ranking.
+///
+/// Literal patterns are case-sensitive substrings; regexes are matched line
+/// by line, so they can't span lines. Results are ordered by file path and
+/// chunk position, up to `options.limit`. `min_score` is ignored.
+///
+/// # Errors
+///
+/// Returns an error if the query fails or the path glob is invalid.
+pub fn grep_chunks(
+    conn: &Connection,
+    pattern: &GrepPattern,
+    options: &SearchOptions,
+) -> Result<Vec<GrepHit>> {
+    if options.limit == 0 {
+        return Ok(Vec::new());
+    }
+
+    let path_matcher = options
+        .path_glob
+        .as_deref()
+        .map(compile_path_glob)
+        .transpose()?;
+
+    let mut sql = String::from(
+        "SELECT c.id, c.file_path, c.chunk_index, c.start_line, c.end_line, c.content, c.language, c.file_hash, c.indexed_at, c.repo,
+                c.git_commit, c.git_branch
+         FROM chunks c
+         WHERE c.content != ''",
+    );
+    let mut params = Vec::new();
+    // Chunks indexed without their text (`store_chunk_content` off) are
+    // excluded, or a regex matching the empty string would report them.
+    // instr() is case-sensitive, unlike LIKE; regexes are checked row by row
+    if let GrepPattern::Literal(ref literal) = pattern {
+        sql.push_str(" AND instr(c.content, ?) > 0");
+        params.push(rusqlite::types::Value::Text(literal.clone()));
+    }
+    push_chunk_filters(options, &mut sql, &mut params);
+    sql.push_str(" ORDER BY c.file_path, c.chunk_index");
+
+    let mut stmt = conn
+        .prepare(&sql)
+        .map_err(|e| StorageError::Database(format!("failed to prepare grep: {e}")))?;
+
+    let rows = stmt
+        .query_map(rusqlite::params_from_iter(params), |row| {
+            Ok(ChunkRecord {
+                id: Some(row.get(0)?),
+                file_path: row.get(1)?,
+                chunk_index: row.get(2)?,
+                start_line: row.get(3)?,
+                end_line: row.get(4)?,
+                content: row.get(5)?,
+                language: row.get(6)?,
+                file_hash: row.get(7)?,
+                indexed_at: row.get(8)?,
+                repo: row.get(9)?,
+                git_commit: row.get(10)?,
+                git_branch: row.get(11)?,
+                embedding: None,
+            })
+        })
+        .map_err(|e| StorageError::Database(format!("failed to execute grep: {e}")))?;
+
+    let mut hits = Vec::new();
+    for row in rows {
+        let chunk =
+            row.map_err(|e| StorageError::Database(format!("failed to read result: {e}")))?;
+        if let Some(ref matcher) = path_matcher {
+            if !matcher.is_match(&chunk.file_path) {
+                continue;
+            }
+        }
+
+        let lines: Vec<GrepLine> = chunk
+            .content
+            .lines()
+            .zip(i64::from(chunk.start_line)..)
+            .filter_map(|(text, line)| {
+                let ranges = pattern.find_in(text);
+                (!ranges.is_empty()).then(|| GrepLine {
+                    line,
+                    text: text.to_string(),
+                    ranges,
+                })
+            })
+            .collect();
+        if lines.is_empty() {
+            continue;
+        }
+
+        hits.push(GrepHit { chunk, lines });
+        if hits.len() >= options.limit {
+            break;
+        }
+    }
+
+    tracing::debug!(count = hits.len(), "Grep completed");
+
+    Ok(hits)
 }
 
 #[cfg(test)]
@@ -234,5 +1015,488 @@ mod tests {
         assert_eq!(opts.min_score, 0.0);
     }
 
+    #[test]
+    fn test_content_has_symbol() {
+        assert!(content_has_symbol("fn parse(input: &str)", "parse"));
+        assert!(content_has_symbol("let x = parse;", "parse"));
+        assert!(content_has_symbol("parse", "parse"));
+        assert!(!content_has_symbol("fn parse_args()", "parse"));
+        assert!(!content_has_symbol("fn reparse()", "parse"));
+        assert!(content_has_symbol("reparse(); parse()", "parse"));
+        assert!(!content_has_symbol("fn Parse()", "parse"));
+    }
+
+    #[test]
+    fn test_find_similar_chunks() {
+        crate::storage::init_sqlite_vec();
+        let db = crate::storage::Database::open_in_memory().unwrap();
+        db.with_conn(|conn| {
+            crate::storage::load_extension(conn)?;
+            crate::storage::migrate(conn)?;
+            crate::storage::init_chunk_vectors(conn)?;
+
+            let base = crate::embeddings::placeholder_embedding("retry with backoff");
+            let mut near = base.clone();
+            near[0] += 0.05;
+            crate::embeddings::l2_normalize(&mut near);
+            let far = crate::embeddings::placeholder_embedding("unrelated");
+
+            let source = crate::storage::insert_chunk(
+                conn,
+                &ChunkRecord::new("/a.rs", 0, 1, 5, "retry()", "h").with_embedding(base),
+            )?;
+            let similar = crate::storage::insert_chunk(
+                conn,
+                &ChunkRecord::new("/b.rs", 0, 1, 5, "retry_again()", "h").with_embedding(near),
+            )?;
+            crate::storage::insert_chunk(
+                conn,
+                &ChunkRecord::new("/c.rs", 0, 1, 5, "other()", "h").with_embedding(far),
+            )?;
+            let bare =
+                crate::storage::insert_chunk(conn, &ChunkRecord::new("/d.rs", 0, 1, 5, "x", "h"))?;
+
+            assert!(get_chunk_embedding(conn, source)?.is_some());
+            assert!(get_chunk_embedding(conn, bare)?.is_none());
+
+            let results = find_similar_chunks(conn, source, 2)?;
+            assert_eq!(results.len(), 2);
+            assert_eq!(results[0].record.id, Some(similar));
+            assert!(results.iter().all(|r| r.record.id != Some(source)));
+
+            assert!(find_similar_chunks(conn, bare, 2).is_err());
+
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_find_chunks_like_file() {
+        crate::storage::init_sqlite_vec();
+        let db = crate::storage::Database::open_in_memory().unwrap();
+        db.with_conn(|conn| {
+            crate::storage::load_extension(conn)?;
+            crate::storage::migrate(conn)?;
+            crate::storage::init_chunk_vectors(conn)?;
+
+            let auth = crate::embeddings::placeholder_embedding("session auth");
+            let mut near = auth.clone();
+            near[0] += 0.05;
+            crate::embeddings::l2_normalize(&mut near);
+            let far = crate::embeddings::placeholder_embedding("unrelated");
+
+            for (path, index, start, embedding) in [
+                ("/src/auth/session.rs", 0, 1, auth.clone()),
+                ("/src/auth/session.rs", 1, 40, auth),
+                ("/src/auth/token.rs", 0, 1, near),
+                ("/src/billing.rs", 0, 1, far.clone()),
+            ] {
+                let chunk = ChunkRecord::new(path, index, start, start + 30, "code", "h")
+                    .with_embedding(embedding);
+                crate::storage::insert_chunk(conn, &chunk)?;
+            }
+
+            let opts = SearchOptions::new(1);
+            let (pooled, results) =
+                find_chunks_like_file(conn, "/src/auth/session.rs", None, &opts)?;
+            assert_eq!(pooled, 2);
+            assert_eq!(results.len(), 1);
+            assert_eq!(results[0].record.file_path, "/src/auth/token.rs");
+
+            // A line range pools only the chunks it overlaps
+            let (pooled, results) = find_chunks_like_file(
+                conn,
+                "/src/auth/session.rs",
+                Some((45, 50)),
+                &SearchOptions::new(10),
+            )?;
+            assert_eq!(pooled, 1);
+            assert!(results
+                .iter()
+                .all(|r| r.record.file_path != "/src/auth/session.rs"));
+
+            assert!(find_chunks_like_file(conn, "/src/missing.rs", None, &opts).is_err());
+            assert!(
+                find_chunks_like_file(conn, "/src/auth/session.rs", Some((500, 600)), &opts)
+                    .is_err()
+            );
+
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_search_chunks_repo_filter() {
+        crate::storage::init_sqlite_vec();
+        let db = crate::storage::Database::open_in_memory().unwrap();
+        db.with_conn(|conn| {
+            crate::storage::load_extension(conn)?;
+            crate::storage::migrate(conn)?;
+            crate::storage::init_chunk_vectors(conn)?;
+
+            let embedding = crate::embeddings::placeholder_embedding("charge card");
+            for (path, repo) in [
+                ("/work/payments/src/charge.rs", "payments"),
+                ("/clones/payments/src/charge.rs", "payments"),
+                ("/work/billing/src/charge.rs", "billing"),
+            ] {
+                crate::storage::insert_chunk(
+                    conn,
+                    &ChunkRecord::new(path, 0, 1, 5, "charge()", "h")
+                        .with_repo(repo)
+                        .with_embedding(embedding.clone()),
+                )?;
+            }
+
+            let opts = SearchOptions::new(10).with_repo("payments");
+            let results = search_chunks(conn, &embedding, &opts)?;
+            assert_eq!(results.len(), 2);
+            assert!(results
+                .iter()
+                .all(|r| r.record.repo.as_deref() == Some("payments")));
+
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_search_chunks_filters_deduplicated_copies() {
+        crate::storage::init_sqlite_vec();
+        let db = crate::storage::Database::open_in_memory().unwrap();
+        db.with_conn(|conn| {
+            crate::storage::load_extension(conn)?;
+            crate::storage::migrate(conn)?;
+            crate::storage::init_chunk_vectors(conn)?;
+
+            // The billing copy is canonical and holds the only embedding
+            let embedding = crate::embeddings::placeholder_embedding("license header");
+            let mut ids = Vec::new();
+            for (path, repo) in [
+                ("/work/billing/src/lib.rs", "billing"),
+                ("/work/payments/src/lib.rs", "payments"),
+                ("/work/payments/src/main.rs", "payments"),
+            ] {
+                ids.push(crate::storage::insert_chunk_deduped(
+                    conn,
+                    &ChunkRecord::new(path, 0, 1, 3, "// License", "h")
+                        .with_repo(repo)
+                        .with_embedding(embedding.clone()),
+                    "license",
+                )?);
+            }
+
+            // A copy in the requested repo stands in for the canonical chunk
+            let opts = SearchOptions::new(10).with_repo("payments");
+            let results = search_chunks(conn, &embedding, &opts)?;
+            assert_eq!(results.len(), 1);
+            assert_eq!(results[0].record.id, Some(ids[1]));
+
+            // Unfiltered, the group is returned once, as its canonical chunk
+            let opts = SearchOptions::new(10).with_embeddings();
+            let results = search_chunks(conn, &embedding, &opts)?;
+            assert_eq!(results.len(), 1);
+            assert_eq!(results[0].record.id, Some(ids[0]));
+            assert!(results[0].record.embedding.is_some());
+
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_search_chunks_indexed_at_filter() {
+        crate::storage::init_sqlite_vec();
+        let db = crate::storage::Database::open_in_memory().unwrap();
+        db.with_conn(|conn| {
+            crate::storage::load_extension(conn)?;
+            crate::storage::migrate(conn)?;
+            crate::storage::init_chunk_vectors(conn)?;
+
+            let embedding = crate::embeddings::placeholder_embedding("parse config");
+            for (path, indexed_at) in [("/old.rs", 1_000), ("/mid.rs", 2_000), ("/new.rs", 3_000)] {
+                let mut chunk = ChunkRecord::new(path, 0, 1, 5, "parse()", "h")
+                    .with_embedding(embedding.clone());
+                chunk.indexed_at = indexed_at;
+                crate::storage::insert_chunk(conn, &chunk)?;
+            }
+
+            let paths = |opts: &SearchOptions| -> crate::Result<Vec<String>> {
+                let mut paths: Vec<String> = search_chunks(conn, &embedding, opts)?
+                    .into_iter()
+                    .map(|r| r.record.file_path)
+                    .collect();
+                paths.sort();
+                Ok(paths)
+            };
+
+            let recent = SearchOptions::new(10).with_indexed_after(2_000);
+            assert_eq!(paths(&recent)?, vec!["/mid.rs", "/new.rs"]);
+
+            let stale = SearchOptions::new(10).with_indexed_before(2_000);
+            assert_eq!(paths(&stale)?, vec!["/old.rs"]);
+
+            let window = SearchOptions::new(10)
+                .with_indexed_after(1_500)
+                .with_indexed_before(2_500);
+            assert_eq!(paths(&window)?, vec!["/mid.rs"]);
+
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_search_chunks_by_symbol() {
+        let db = crate::storage::Database::open_in_memory().unwrap();
+        db.with_conn(|conn| {
+            crate::storage::migrate(conn)?;
+
+            let chunks = [
+                ("/a.rs", "fn handle_request() {}"),
+                ("/b.rs", "fn handle_request_inner() {}"),
+                ("/c.rs", "handle_request();"),
+                ("/d.rs", "fn unrelated() {}"),
+            ];
+            for (path, content) in chunks {
+                crate::storage::insert_chunk(conn, &ChunkRecord::new(path, 0, 1, 1, content, "h"))?;
+            }
+
+            let results = search_chunks_by_symbol(conn, "handle_request", 10)?;
+            let paths: Vec<&str> = results.iter().map(|c| c.file_path.as_str()).collect();
+            assert_eq!(paths, vec!["/a.rs", "/c.rs"]);
+
+            let limited = search_chunks_by_symbol(conn, "handle_request", 1)?;
+            assert_eq!(limited.len(), 1);
+
+            assert!(search_chunks_by_symbol(conn, "  ", 10)?.is_empty());
+
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_search_options_path_scope() {
+        let opts = SearchOptions::new(10)
+            .with_path_prefix("services/payments/")
+            .with_path_glob("**/*.rs");
+
+        assert_eq!(opts.path_prefix, Some("services/payments/".to_string()));
+        assert_eq!(opts.path_glob, Some("**/*.rs".to_string()));
+    }
+
+    #[test]
+    fn test_escape_like() {
+        assert_eq!(escape_like("src/my_mod%"), "src/my\\_mod\\%");
+        assert_eq!(escape_like("a\\b"), "a\\\\b");
+    }
+
+    #[test]
+    fn test_compile_path_glob() {
+        let relative = compile_path_glob("services/payments/**/*.rs").unwrap();
+        assert!(relative.is_match("/repo/services/payments/src/retry.rs"));
+        assert!(!relative.is_match("/repo/services/billing/src/retry.rs"));
+        assert!(!relative.is_match("/repo/services/payments/src/retry.py"));
+
+        let absolute = compile_path_glob("/repo/**/*.ts").unwrap();
+        assert!(absolute.is_match("/repo/web/app.ts"));
+        assert!(!absolute.is_match("/other/web/app.ts"));
+
+        assert!(compile_path_glob("src/[").is_err());
+    }
+
+    fn result_with_embedding(
+        path: &str,
+        distance: f32,
+        embedding: Vec<f32>,
+    ) -> SearchResult<ChunkRecord> {
+        let chunk = ChunkRecord::new(path, 0, 1, 1, "code", "h").with_embedding(embedding);
+        SearchResult::new(chunk, distance)
+    }
+
+    #[test]
+    fn test_rerank_mmr_prefers_diverse_results() {
+        let query = [1.0, 0.0, 0.0];
+        let candidates = vec![
+            result_with_embedding("/a.rs", 0.1, vec![0.9, 0.1, 0.0]),
+            // Near-duplicate of /a.rs
+            result_with_embedding("/a_copy.rs", 0.1, vec![0.9, 0.1, 0.0]),
+            result_with_embedding("/b.rs", 0.4, vec![0.7, 0.0, 0.7]),
+        ];
+
+        let relevance_only = rerank_mmr(&query, candidates.clone(), 1.0, 2);
+        assert_eq!(relevance_only[0].record.file_path, "/a.rs");
+        assert_eq!(relevance_only[1].record.file_path, "/a_copy.rs");
+
+        let diverse = rerank_mmr(&query, candidates, 0.3, 2);
+        assert_eq!(diverse[0].record.file_path, "/a.rs");
+        assert_eq!(diverse[1].record.file_path, "/b.rs");
+    }
+
+    #[test]
+    fn test_rerank_mmr_limits_and_handles_missing_embeddings() {
+        let query = [1.0, 0.0];
+        let candidates = vec![
+            SearchResult::new(ChunkRecord::new("/x.rs", 0, 1, 1, "code", "h"), 0.2),
+            result_with_embedding("/y.rs", 0.5, vec![0.5, 0.5]),
+            result_with_embedding("/z.rs", 1.5, vec![0.0, 1.0]),
+        ];
+
+        let reranked = rerank_mmr(&query, candidates, 0.7, 2);
+        assert_eq!(reranked.len(), 2);
+        assert_eq!(reranked[0].record.file_path, "/x.rs");
+
+        assert!(rerank_mmr(&query, Vec::new(), 0.7, 5).is_empty());
+    }
+
+    #[test]
+    fn test_rerank_by_recency() {
+        let day = 24 * 60 * 60;
+        let now = 1000 * day;
+        let mut results = vec![
+            // Stale but slightly closer match
+            SearchResult::new(now - 720 * day, 0.2),
+            SearchResult::new(now - day, 0.3),
+        ];
+
+        rerank_by_recency(&mut results, |created| *created, 0.0, 90 * day, now);
+        assert_eq!(results[0].record, now - 720 * day);
+
+        rerank_by_recency(&mut results, |created| *created, 0.5, 90 * day, now);
+        assert_eq!(results[0].record, now - day);
+        // Fresh record keeps nearly its full decay factor
+        assert!(results[0].score > 0.9);
+        assert!(results[1].score < 0.5);
+    }
+
+    #[test]
+    fn test_cosine_similarity() {
+        assert!((cosine_similarity(&[1.0, 0.0], &[1.0, 0.0]) - 1.0).abs() < 1e-6);
+        assert!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+    }
+
     // Integration tests that require sqlite-vec are in integration test files
+
+    #[test]
+    fn test_fts_query() {
+        assert_eq!(
+            fts_query("where are webhooks retried?").as_deref(),
+            Some("\"where\" OR \"are\" OR \"webhooks\" OR \"retried\"")
+        );
+        // FTS5 syntax is quoted away and duplicates dropped
+        assert_eq!(
+            fts_query("parse_config AND \"Parse_Config\"*").as_deref(),
+            Some("\"parse_config\" OR \"and\"")
+        );
+        assert!(fts_query("  -- ()").is_none());
+    }
+
+    #[test]
+    fn test_search_chunks_by_text() {
+        let db = crate::storage::Database::open_in_memory().unwrap();
+        db.with_conn(|conn| {
+            crate::storage::migrate(conn)?;
+            let mut webhook = ChunkRecord::new(
+                "/repo/src/webhooks.rs",
+                0,
+                1,
+                10,
+                "fn retry_webhook() { retry(); retry(); }",
+                "h1",
+            );
+            webhook.language = Some("rust".to_string());
+            crate::storage::insert_chunk(conn, &webhook)?;
+            crate::storage::insert_chunk(
+                conn,
+                &ChunkRecord::new("/repo/src/http.py", 0, 1, 5, "def retry(): pass", "h2"),
+            )?;
+            crate::storage::insert_chunk(
+                conn,
+                &ChunkRecord::new("/repo/src/config.rs", 0, 1, 5, "fn load() {}", "h3"),
+            )?;
+
+            let results = search_chunks_by_text(conn, "retry webhook", &SearchOptions::new(10))?;
+            assert_eq!(results.len(), 2);
+            assert_eq!(results[0].record.file_path, "/repo/src/webhooks.rs");
+            assert!((results[0].score - 1.0).abs() < f32::EPSILON);
+            assert!(results[1].score <= results[0].score);
+
+            let opts = SearchOptions::new(10).with_language("rust");
+            let results = search_chunks_by_text(conn, "retry", &opts)?;
+            assert_eq!(results.len(), 1);
+
+            // Untagged chunks survive a language exclusion
+            let opts = SearchOptions::new(10).with_exclude_languages(&["rust", "doc"]);
+            let results = search_chunks_by_text(conn, "retry", &opts)?;
+            assert_eq!(results.len(), 1);
+            assert_eq!(results[0].record.file_path, "/repo/src/http.py");
+
+            let opts = SearchOptions::new(10).with_path_glob("**/*.py");
+            let results = search_chunks_by_text(conn, "retry", &opts)?;
+            assert_eq!(results.len(), 1);
+            assert_eq!(results[0].record.file_path, "/repo/src/http.py");
+
+            assert!(
+                search_chunks_by_text(conn, "nothing_matches", &SearchOptions::new(10))?.is_empty()
+            );
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_grep_chunks() {
+        let db = crate::storage::Database::open_in_memory().unwrap();
+        db.with_conn(|conn| {
+            crate::storage::migrate(conn)?;
+            let mut config = ChunkRecord::new(
+                "/repo/src/config.rs",
+                0,
+                10,
+                12,
+                "fn load() {\n    let key = \"MAX_RETRIES\"; // MAX_RETRIES\n}",
+                "h1",
+            );
+            config.language = Some("rust".to_string());
+            crate::storage::insert_chunk(conn, &config)?;
+            crate::storage::insert_chunk(
+                conn,
+                &ChunkRecord::new("/repo/app.py", 0, 1, 1, "max_retries = 3", "h2"),
+            )?;
+
+            let literal = GrepPattern::Literal("MAX_RETRIES".to_string());
+            let hits = grep_chunks(conn, &literal, &SearchOptions::new(10))?;
+            assert_eq!(hits.len(), 1, "literal match is case-sensitive");
+            assert_eq!(hits[0].chunk.file_path, "/repo/src/config.rs");
+            assert_eq!(hits[0].lines.len(), 1);
+            let line = &hits[0].lines[0];
+            assert_eq!(line.line, 11);
+            assert_eq!(line.ranges, vec![(15, 26), (32, 43)]);
+            assert_eq!(&line.text[15..26], "MAX_RETRIES");
+
+            let regex = GrepPattern::regex(r"(?i)max_retries\s*=")?;
+            let hits = grep_chunks(conn, &regex, &SearchOptions::new(10))?;
+            assert_eq!(hits.len(), 1);
+            assert_eq!(hits[0].chunk.file_path, "/repo/app.py");
+            assert_eq!(hits[0].lines[0].ranges, vec![(0, 13)]);
+
+            let regex = GrepPattern::regex("(?i)max_retries")?;
+            let opts = SearchOptions::new(10).with_language("rust");
+            assert_eq!(grep_chunks(conn, &regex, &opts)?.len(), 1);
+            let opts = SearchOptions::new(10).with_path_prefix("/repo/src/");
+            assert_eq!(grep_chunks(conn, &regex, &opts)?.len(), 1);
+            assert_eq!(grep_chunks(conn, &regex, &SearchOptions::new(1))?.len(), 1);
+
+            let missing = GrepPattern::Literal("nothing_matches".to_string());
+            assert!(grep_chunks(conn, &missing, &SearchOptions::new(10))?.is_empty());
+
+            assert!(GrepPattern::regex("(unclosed").is_err());
+            Ok(())
+        })
+        .unwrap();
+    }
 }
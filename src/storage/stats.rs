@@ -0,0 +1,174 @@
+//! Lifetime indexing statistics.
+//!
+//! In-memory watcher and indexer counters reset on restart; these named
+//! counters accumulate them in the database so lifetime activity and the
+//! time of the last scan survive.
+
+use std::collections::BTreeMap;
+
+use rusqlite::{params, Connection};
+
+use crate::error::StorageError;
+use crate::Result;
+
+/// Files that produced new chunks.
+pub const STAT_FILES_INDEXED: &str = "files_indexed";
+
+/// Chunks stored.
+pub const STAT_CHUNKS_CREATED: &str = "chunks_created";
+
+/// Files removed from the index.
+pub const STAT_FILES_DELETED: &str = "files_deleted";
+
+/// Files that failed to index or be removed.
+pub const STAT_INDEX_ERRORS: &str = "index_errors";
+
+/// Unix time a file last produced new chunks.
+pub const STAT_LAST_INDEXED_AT: &str = "last_indexed_at";
+
+/// Directory scans (watcher startup scans and indexing tool walks).
+pub const STAT_SCANS: &str = "scans";
+
+/// Files found by directory scans.
+pub const STAT_FILES_SCANNED: &str = "files_scanned";
+
+/// Unix time of the last directory scan.
+pub const STAT_LAST_SCAN_AT: &str = "last_scan_at";
+
+/// Get current Unix timestamp as i64.
+#[inline]
+#[allow(clippy::cast_possible_wrap)]
+fn now_unix() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+/// Add `deltas` to the named counters, creating any that don't exist.
+///
+/// Zero deltas are skipped so idle flushes don't touch the database.
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub fn add_stats(conn: &Connection, deltas: &[(&str, u64)]) -> Result<()> {
+    let now = now_unix();
+    for &(name, delta) in deltas {
+        if delta == 0 {
+            continue;
+        }
+        conn.execute(
+            "INSERT INTO stats (name, value, updated_at) VALUES (?, ?, ?)
+             ON CONFLICT(name) DO UPDATE SET
+                value = value + excluded.value,
+                updated_at = excluded.updated_at",
+            params![name, i64::try_from(delta).unwrap_or(i64::MAX), now],
+        )
+        .map_err(|e| StorageError::Database(format!("failed to update stat '{name}': {e}")))?;
+    }
+
+    Ok(())
+}
+
+/// Set a named value, such as a timestamp, replacing any existing one.
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub fn set_stat(conn: &Connection, name: &str, value: i64) -> Result<()> {
+    conn.execute(
+        "INSERT INTO stats (name, value, updated_at) VALUES (?, ?, ?)
+         ON CONFLICT(name) DO UPDATE SET
+            value = excluded.value,
+            updated_at = excluded.updated_at",
+        params![name, value, now_unix()],
+    )
+    .map_err(|e| StorageError::Database(format!("failed to set stat '{name}': {e}")))?;
+
+    Ok(())
+}
+
+/// Record a directory scan that found `files_found` files.
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub fn record_scan(conn: &Connection, files_found: u64) -> Result<()> {
+    add_stats(conn, &[(STAT_SCANS, 1), (STAT_FILES_SCANNED, files_found)])?;
+    set_stat(conn, STAT_LAST_SCAN_AT, now_unix())
+}
+
+/// Get all recorded statistics by name.
+///
+/// # Errors
+///
+/// Returns an error if the database query fails.
+pub fn get_stats(conn: &Connection) -> Result<BTreeMap<String, i64>> {
+    let mut stmt = conn
+        .prepare("SELECT name, value FROM stats")
+        .map_err(|e| StorageError::Database(format!("failed to prepare stats query: {e}")))?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+        })
+        .map_err(|e| StorageError::Database(format!("failed to query stats: {e}")))?;
+
+    rows.collect::<std::result::Result<_, _>>()
+        .map_err(|e| StorageError::Database(format!("failed to read stats: {e}")).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{migrate, Database};
+
+    #[test]
+    fn test_add_stats_accumulates() {
+        let db = Database::open_in_memory().unwrap();
+        db.with_conn(|conn| {
+            migrate(conn)?;
+            assert!(get_stats(conn)?.is_empty());
+
+            add_stats(conn, &[(STAT_FILES_INDEXED, 3), (STAT_CHUNKS_CREATED, 12)])?;
+            add_stats(
+                conn,
+                &[
+                    (STAT_FILES_INDEXED, 2),
+                    (STAT_CHUNKS_CREATED, 0),
+                    (STAT_INDEX_ERRORS, 0),
+                ],
+            )?;
+
+            let stats = get_stats(conn)?;
+            assert_eq!(stats[STAT_FILES_INDEXED], 5);
+            assert_eq!(stats[STAT_CHUNKS_CREATED], 12);
+            // Zero deltas don't create counters
+            assert!(!stats.contains_key(STAT_INDEX_ERRORS));
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_set_stat_and_record_scan() {
+        let db = Database::open_in_memory().unwrap();
+        db.with_conn(|conn| {
+            migrate(conn)?;
+            set_stat(conn, STAT_LAST_INDEXED_AT, 100)?;
+            set_stat(conn, STAT_LAST_INDEXED_AT, 200)?;
+
+            record_scan(conn, 40)?;
+            record_scan(conn, 2)?;
+
+            let stats = get_stats(conn)?;
+            assert_eq!(stats[STAT_LAST_INDEXED_AT], 200);
+            assert_eq!(stats[STAT_SCANS], 2);
+            assert_eq!(stats[STAT_FILES_SCANNED], 42);
+            assert!(stats[STAT_LAST_SCAN_AT] > 0);
+            Ok(())
+        })
+        .unwrap();
+    }
+}
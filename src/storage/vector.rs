@@ -2,18 +2,98 @@
 //!
 //! Provides helpers for creating and querying vec0 virtual tables
 //! for efficient similarity search.
+//!
+//! Distances are L2 by default. Stored and query embeddings are expected to
+//! be unit-normalized (the embedding worker guarantees this), which bounds
+//! distances to [0, 2] and lets `SearchResult::new` map them to a [0, 1]
+//! score. Models whose vectors aren't normalized should use
+//! [`DistanceMetric::Cosine`] instead, which ignores vector magnitude.
 
-use rusqlite::Connection;
+use rusqlite::{Connection, OptionalExtension};
 use sqlite_vec::sqlite3_vec_init;
 use std::sync::Once;
 
 use crate::error::StorageError;
 use crate::Result;
 
+/// Largest k sqlite-vec accepts in one nearest-neighbour query.
+pub const MAX_VECTOR_K: usize = 4096;
+
+/// The k to bind for a nearest-neighbour query wanting `limit` rows.
+///
+/// Every vector search goes through this, so a derived candidate count can't
+/// exceed [`MAX_VECTOR_K`] and make sqlite-vec reject the query.
+pub fn vector_k(limit: usize) -> i64 {
+    i64::try_from(limit.min(MAX_VECTOR_K)).unwrap_or(i64::MAX)
+}
+
 /// Vector dimension for embeddings.
 /// all-MiniLM-L6-v2 produces 384-dimensional vectors.
 pub const EMBEDDING_DIM: usize = 384;
 
+/// Distance metric used by the vec0 tables.
+///
+/// Fixed when the vector tables are created and recorded in the `meta`
+/// table, so it can't change for an existing database.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DistanceMetric {
+    /// Euclidean distance. Use with unit-normalized embeddings, such as
+    /// all-MiniLM-L6-v2 as produced by the embedding worker.
+    #[default]
+    L2,
+    /// Cosine distance (`1 - cosine similarity`). Use with models whose
+    /// embeddings aren't normalized.
+    Cosine,
+}
+
+impl DistanceMetric {
+    /// Convert metric to string representation.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::L2 => "l2",
+            Self::Cosine => "cosine",
+        }
+    }
+
+    /// Map a distance under this metric to a score in [0, 1].
+    ///
+    /// L2 distances between unit vectors lie in [0, 2] and map linearly to
+    /// [1, 0]. Cosine distances map to cosine similarity, with opposing
+    /// vectors (similarity below zero) clamped to 0.
+    #[must_use]
+    pub fn score(self, distance: f32) -> f32 {
+        let score = match self {
+            Self::L2 => 1.0 - (distance / 2.0),
+            Self::Cosine => 1.0 - distance,
+        };
+        score.clamp(0.0, 1.0)
+    }
+
+    /// Column option for a vec0 `FLOAT[n]` column.
+    #[must_use]
+    pub const fn column_option(self) -> &'static str {
+        match self {
+            Self::L2 => "",
+            Self::Cosine => " distance_metric=cosine",
+        }
+    }
+}
+
+impl std::str::FromStr for DistanceMetric {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "l2" => Ok(Self::L2),
+            "cosine" => Ok(Self::Cosine),
+            _ => Err(format!(
+                "unknown distance metric '{s}' (expected 'l2' or 'cosine')"
+            )),
+        }
+    }
+}
+
 // Static guard to ensure sqlite-vec is initialized exactly once
 static INIT: Once = Once::new();
 
@@ -83,22 +163,34 @@ pub fn load_extension(conn: &Connection) -> Result<()> {
 /// * `conn` - Database connection
 /// * `table_name` - Name for the virtual table
 /// * `dimension` - Vector dimension (e.g., 384 for all-MiniLM-L6-v2)
+/// * `metric` - Distance metric used by KNN queries on the table
 ///
 /// # Errors
 ///
 /// Returns an error if the table cannot be created.
-pub fn create_vec_table(conn: &Connection, table_name: &str, dimension: usize) -> Result<()> {
+pub fn create_vec_table(
+    conn: &Connection,
+    table_name: &str,
+    dimension: usize,
+    metric: DistanceMetric,
+) -> Result<()> {
+    let option = metric.column_option();
     let sql = format!(
         "CREATE VIRTUAL TABLE IF NOT EXISTS {table_name} USING vec0(
             id INTEGER PRIMARY KEY,
-            embedding FLOAT[{dimension}]
+            embedding FLOAT[{dimension}]{option}
         )"
     );
 
     conn.execute(&sql, [])
         .map_err(|e| StorageError::Vector(format!("failed to create vec table: {e}")))?;
 
-    tracing::debug!(table = table_name, dim = dimension, "Created vec0 table");
+    tracing::debug!(
+        table = table_name,
+        dim = dimension,
+        metric = metric.as_str(),
+        "Created vec0 table"
+    );
     Ok(())
 }
 
@@ -129,7 +221,7 @@ pub fn insert_vector(
     Ok(())
 }
 
-/// Search for similar vectors using cosine distance.
+/// Search for similar vectors using the table's distance metric.
 ///
 /// # Arguments
 ///
@@ -165,9 +257,8 @@ pub fn search_similar(
         .prepare(&sql)
         .map_err(|e| StorageError::Vector(format!("failed to prepare search: {e}")))?;
 
-    let limit_i64 = i64::try_from(limit).unwrap_or(i64::MAX);
     let results = stmt
-        .query_map(rusqlite::params![blob, limit_i64], |row| {
+        .query_map(rusqlite::params![blob, vector_k(limit)], |row| {
             Ok((row.get::<_, i64>(0)?, row.get::<_, f32>(1)?))
         })
         .map_err(|e| StorageError::Vector(format!("failed to execute search: {e}")))?;
@@ -182,6 +273,48 @@ pub fn search_similar(
     Ok(matches)
 }
 
+/// Fetch stored vectors by ID from a vec0 table.
+///
+/// IDs without a stored vector are omitted from the result.
+///
+/// # Errors
+///
+/// Returns an error if the query fails.
+pub fn get_vectors(
+    conn: &Connection,
+    table_name: &str,
+    ids: &[i64],
+) -> Result<std::collections::HashMap<i64, Vec<f32>>> {
+    let mut vectors = std::collections::HashMap::with_capacity(ids.len());
+    if ids.is_empty() {
+        return Ok(vectors);
+    }
+
+    let placeholders: Vec<&str> = ids.iter().map(|_| "?").collect();
+    let sql = format!(
+        "SELECT id, embedding FROM {table_name} WHERE id IN ({})",
+        placeholders.join(",")
+    );
+
+    let mut stmt = conn
+        .prepare(&sql)
+        .map_err(|e| StorageError::Vector(format!("failed to prepare vector fetch: {e}")))?;
+
+    let rows = stmt
+        .query_map(rusqlite::params_from_iter(ids), |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, Vec<u8>>(1)?))
+        })
+        .map_err(|e| StorageError::Vector(format!("failed to fetch vectors: {e}")))?;
+
+    for row in rows {
+        let (id, blob) =
+            row.map_err(|e| StorageError::Vector(format!("failed to read vector: {e}")))?;
+        vectors.insert(id, blob_to_vector(&blob));
+    }
+
+    Ok(vectors)
+}
+
 /// Delete a vector from a vec0 table.
 ///
 /// # Errors
@@ -194,13 +327,114 @@ pub fn delete_vector(conn: &Connection, table_name: &str, id: i64) -> Result<()>
     Ok(())
 }
 
+/// Vector tables created by `init_storage`, in report order.
+pub const VECTOR_TABLES: [&str; 3] = [
+    "chunk_embeddings",
+    "lesson_embeddings",
+    "checkpoint_embeddings",
+];
+
+/// Count the rows in each vector table that exists.
+///
+/// Tables are missing when storage was initialized without sqlite-vec;
+/// those are left out rather than reported as empty.
+///
+/// # Errors
+///
+/// Returns an error if a table exists but cannot be counted.
+pub fn count_vector_rows(conn: &Connection) -> Result<Vec<(String, i64)>> {
+    let mut counts = Vec::with_capacity(VECTOR_TABLES.len());
+    for table in VECTOR_TABLES {
+        let exists: bool = conn
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE name = ?)",
+                [table],
+                |row| row.get(0),
+            )
+            .map_err(|e| StorageError::Vector(format!("failed to look up {table}: {e}")))?;
+        if !exists {
+            continue;
+        }
+
+        counts.push((table.to_string(), count_rows(conn, table)?));
+    }
+    Ok(counts)
+}
+
+/// Row counts for one table rebuilt by [`rebuild_vector_tables`].
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct VectorTableRebuild {
+    /// Vector table name
+    pub table: String,
+    /// Rows before the rebuild
+    pub rows_before: i64,
+    /// Rows after the rebuild
+    pub rows_after: i64,
+}
+
+/// Rebuild each vector table that exists, compacting its storage.
+///
+/// vec0 keeps the slots of deleted vectors in its chunk storage, so after
+/// many re-indexes KNN scans read a lot of dead space. Each table is copied
+/// aside, dropped, recreated from its original definition (so the ID type
+/// and distance metric are kept), and refilled. Call this inside a
+/// transaction so a failure leaves the tables as they were.
+///
+/// # Errors
+///
+/// Returns an error if a table cannot be copied, recreated, or refilled.
+pub fn rebuild_vector_tables(conn: &Connection) -> Result<Vec<VectorTableRebuild>> {
+    let mut rebuilt = Vec::with_capacity(VECTOR_TABLES.len());
+    for table in VECTOR_TABLES {
+        let definition: Option<String> = conn
+            .query_row(
+                "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = ?",
+                [table],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| StorageError::Vector(format!("failed to look up {table}: {e}")))?;
+        let Some(definition) = definition else {
+            continue;
+        };
+
+        let rows_before = count_rows(conn, table)?;
+        conn.execute_batch(&format!(
+            "DROP TABLE IF EXISTS temp.vec_rebuild;
+             CREATE TEMP TABLE vec_rebuild AS SELECT id, embedding FROM {table};
+             DROP TABLE {table};
+             {definition};
+             INSERT INTO {table} (id, embedding)
+                 SELECT id, embedding FROM temp.vec_rebuild ORDER BY id;
+             DROP TABLE temp.vec_rebuild;"
+        ))
+        .map_err(|e| StorageError::Vector(format!("failed to rebuild {table}: {e}")))?;
+        let rows_after = count_rows(conn, table)?;
+
+        tracing::info!(table, rows_before, rows_after, "Rebuilt vector table");
+        rebuilt.push(VectorTableRebuild {
+            table: table.to_string(),
+            rows_before,
+            rows_after,
+        });
+    }
+    Ok(rebuilt)
+}
+
+/// Count the rows in a vector table.
+fn count_rows(conn: &Connection, table: &str) -> Result<i64> {
+    conn.query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| {
+        row.get(0)
+    })
+    .map_err(|e| StorageError::Vector(format!("failed to count {table}: {e}")).into())
+}
+
 /// Convert a vector to a blob for storage.
 fn vector_to_blob(v: &[f32]) -> Vec<u8> {
     v.iter().flat_map(|f| f.to_le_bytes()).collect()
 }
 
 /// Convert a blob back to a vector.
-#[allow(dead_code)]
 fn blob_to_vector(blob: &[u8]) -> Vec<f32> {
     blob.chunks_exact(4)
         .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
@@ -272,7 +506,7 @@ mod tests {
     fn test_create_vec_table() {
         let db = create_test_db();
         db.with_conn(|conn| {
-            create_vec_table(conn, "test_vectors", 4)?;
+            create_vec_table(conn, "test_vectors", 4, DistanceMetric::L2)?;
             Ok(())
         })
         .unwrap();
@@ -283,7 +517,7 @@ mod tests {
         let db = create_test_db();
 
         db.with_conn(|conn| {
-            create_vec_table(conn, "test_vectors", 4)?;
+            create_vec_table(conn, "test_vectors", 4, DistanceMetric::L2)?;
 
             // Insert test vectors
             insert_vector(conn, "test_vectors", 1, &[1.0, 0.0, 0.0, 0.0])?;
@@ -304,12 +538,70 @@ mod tests {
         .unwrap();
     }
 
+    #[test]
+    fn test_search_limit_above_max_k() {
+        let db = create_test_db();
+
+        db.with_conn(|conn| {
+            create_vec_table(conn, "test_vectors", 4, DistanceMetric::L2)?;
+            insert_vector(conn, "test_vectors", 1, &[1.0, 0.0, 0.0, 0.0])?;
+
+            // sqlite-vec rejects k above its maximum, so the limit is clamped
+            let results = search_similar(conn, "test_vectors", &[1.0, 0.0, 0.0, 0.0], usize::MAX)?;
+            assert_eq!(results.len(), 1);
+
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_normalized_identical_vectors_score_one() {
+        let db = create_test_db();
+
+        let mut embedding = vec![3.0, -1.0, 2.0, 5.0];
+        crate::embeddings::l2_normalize(&mut embedding);
+
+        db.with_conn(|conn| {
+            create_vec_table(conn, "test_vectors", 4, DistanceMetric::L2)?;
+            insert_vector(conn, "test_vectors", 1, &embedding)?;
+
+            let results = search_similar(conn, "test_vectors", &embedding, 1)?;
+            let result = crate::storage::SearchResult::new((), results[0].1);
+            assert!(result.distance.abs() < 1e-5);
+            assert!((result.score - 1.0).abs() < 1e-5);
+
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_get_vectors() {
+        let db = create_test_db();
+
+        db.with_conn(|conn| {
+            create_vec_table(conn, "test_vectors", 4, DistanceMetric::L2)?;
+            insert_vector(conn, "test_vectors", 1, &[1.0, 0.0, 0.0, 0.0])?;
+            insert_vector(conn, "test_vectors", 2, &[0.0, 1.0, 0.0, 0.0])?;
+
+            let vectors = get_vectors(conn, "test_vectors", &[1, 2, 3])?;
+            assert_eq!(vectors.len(), 2);
+            assert_eq!(vectors[&2], vec![0.0, 1.0, 0.0, 0.0]);
+
+            assert!(get_vectors(conn, "test_vectors", &[])?.is_empty());
+
+            Ok(())
+        })
+        .unwrap();
+    }
+
     #[test]
     fn test_delete_vector() {
         let db = create_test_db();
 
         db.with_conn(|conn| {
-            create_vec_table(conn, "test_vectors", 4)?;
+            create_vec_table(conn, "test_vectors", 4, DistanceMetric::L2)?;
             insert_vector(conn, "test_vectors", 1, &[1.0, 0.0, 0.0, 0.0])?;
             insert_vector(conn, "test_vectors", 2, &[0.0, 1.0, 0.0, 0.0])?;
 
@@ -323,4 +615,125 @@ mod tests {
         })
         .unwrap();
     }
+
+    #[test]
+    fn test_distance_metric_parse_and_score() {
+        assert_eq!("l2".parse::<DistanceMetric>(), Ok(DistanceMetric::L2));
+        assert_eq!(
+            "Cosine".parse::<DistanceMetric>(),
+            Ok(DistanceMetric::Cosine)
+        );
+        assert!("dot".parse::<DistanceMetric>().is_err());
+
+        assert!((DistanceMetric::L2.score(0.0) - 1.0).abs() < f32::EPSILON);
+        assert!((DistanceMetric::L2.score(1.0) - 0.5).abs() < f32::EPSILON);
+        assert!((DistanceMetric::Cosine.score(0.25) - 0.75).abs() < f32::EPSILON);
+        assert!(DistanceMetric::Cosine.score(1.5).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn test_cosine_vec_table_ignores_magnitude() {
+        let db = create_test_db();
+
+        db.with_conn(|conn| {
+            create_vec_table(conn, "test_vectors", 4, DistanceMetric::Cosine)?;
+            // Same direction as the query but much longer
+            insert_vector(conn, "test_vectors", 1, &[10.0, 0.0, 0.0, 0.0])?;
+            // Closer in L2 terms but pointing elsewhere
+            insert_vector(conn, "test_vectors", 2, &[0.5, 0.5, 0.0, 0.0])?;
+
+            let results = search_similar(conn, "test_vectors", &[1.0, 0.0, 0.0, 0.0], 2)?;
+            assert_eq!(results[0].0, 1);
+            assert!(results[0].1.abs() < 1e-5);
+            assert!((DistanceMetric::Cosine.score(results[0].1) - 1.0).abs() < 1e-5);
+
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_count_vector_rows() {
+        let db = create_test_db();
+        db.with_conn(|conn| {
+            assert!(count_vector_rows(conn)?.is_empty());
+
+            create_vec_table(conn, "chunk_embeddings", 4, DistanceMetric::L2)?;
+            create_vec_table(conn, "lesson_embeddings", 4, DistanceMetric::L2)?;
+            insert_vector(conn, "chunk_embeddings", 1, &[0.1, 0.2, 0.3, 0.4])?;
+            insert_vector(conn, "chunk_embeddings", 2, &[0.4, 0.3, 0.2, 0.1])?;
+
+            let counts = count_vector_rows(conn)?;
+            assert_eq!(
+                counts,
+                vec![
+                    ("chunk_embeddings".to_string(), 2),
+                    ("lesson_embeddings".to_string(), 0),
+                ]
+            );
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_rebuild_vector_tables() {
+        let db = create_test_db();
+        db.with_conn(|conn| {
+            create_vec_table(conn, "chunk_embeddings", 4, DistanceMetric::Cosine)?;
+            conn.execute_batch(
+                "CREATE VIRTUAL TABLE lesson_embeddings USING vec0(
+                    id TEXT PRIMARY KEY,
+                    embedding FLOAT[4]
+                )",
+            )
+            .unwrap();
+            for id in 1..=3 {
+                insert_vector(conn, "chunk_embeddings", id, &[10.0, 0.0, 0.0, 0.0])?;
+            }
+            insert_vector(conn, "chunk_embeddings", 4, &[0.0, 1.0, 0.0, 0.0])?;
+            delete_vector(conn, "chunk_embeddings", 2)?;
+            conn.execute(
+                "INSERT INTO lesson_embeddings (id, embedding) VALUES (?, ?)",
+                rusqlite::params!["lesson_a", vector_to_blob(&[0.0, 0.0, 1.0, 0.0])],
+            )
+            .unwrap();
+            Ok(())
+        })
+        .unwrap();
+
+        let rebuilt = db.with_transaction(rebuild_vector_tables).unwrap();
+        assert_eq!(
+            rebuilt,
+            vec![
+                VectorTableRebuild {
+                    table: "chunk_embeddings".to_string(),
+                    rows_before: 3,
+                    rows_after: 3,
+                },
+                VectorTableRebuild {
+                    table: "lesson_embeddings".to_string(),
+                    rows_before: 1,
+                    rows_after: 1,
+                },
+            ]
+        );
+
+        db.with_conn(|conn| {
+            // Vectors, IDs, and the cosine metric survive the rebuild
+            let results = search_similar(conn, "chunk_embeddings", &[1.0, 0.0, 0.0, 0.0], 10)?;
+            let mut ids: Vec<i64> = results.iter().map(|(id, _)| *id).collect();
+            assert!(results[1].1.abs() < 1e-5);
+            assert_eq!(ids.pop(), Some(4));
+            ids.sort_unstable();
+            assert_eq!(ids, vec![1, 3]);
+
+            let lesson: String = conn
+                .query_row("SELECT id FROM lesson_embeddings", [], |row| row.get(0))
+                .unwrap();
+            assert_eq!(lesson, "lesson_a");
+            Ok(())
+        })
+        .unwrap();
+    }
 }
@@ -2,6 +2,8 @@
 
 use std::path::Path;
 
+use super::filter::{CONFIG_LANGUAGE, DOC_LANGUAGE};
+
 /// Chunk of code from a file.
 #[derive(Debug, Clone)]
 pub struct CodeChunk {
@@ -59,7 +61,7 @@ impl Chunker {
 
     /// Chunk file content into pieces.
     #[must_use]
-    pub fn chunk_content(&self, content: &str, _language: Option<&str>) -> Vec<CodeChunk> {
+    pub fn chunk_content(&self, content: &str, language: Option<&str>) -> Vec<CodeChunk> {
         let lines: Vec<&str> = content.lines().collect();
 
         if lines.is_empty() {
@@ -76,6 +78,10 @@ impl Chunker {
             }];
         }
 
+        if matches!(language, Some(DOC_LANGUAGE | CONFIG_LANGUAGE)) {
+            return self.chunk_text(&lines);
+        }
+
         let mut chunks = Vec::new();
         let mut start = 0;
         let mut index = 0;
@@ -109,6 +115,54 @@ impl Chunker {
         chunks
     }
 
+    /// Chunk documentation or config text at paragraph and section breaks.
+    ///
+    /// Unlike code, chunks don't overlap: a paragraph reads on its own, and
+    /// repeating it in two chunks only splits its matches between them.
+    fn chunk_text(&self, lines: &[&str]) -> Vec<CodeChunk> {
+        let mut chunks = Vec::new();
+        let mut start = 0;
+
+        while start < lines.len() {
+            let end = self.find_text_chunk_end(lines, start);
+            chunks.push(CodeChunk {
+                start_line: start + 1,
+                end_line: end,
+                content: lines[start..end].join("\n"),
+                index: chunks.len(),
+            });
+            start = end;
+        }
+
+        chunks
+    }
+
+    /// Find the paragraph break nearest past the target size, or the last one
+    /// before it if the paragraph runs over the maximum size.
+    fn find_text_chunk_end(&self, lines: &[&str], start: usize) -> usize {
+        let min_end = (start + self.config.min_lines).clamp(start + 1, lines.len());
+        let ideal_end = (start + self.config.target_lines).min(lines.len());
+        let max_end = (start + self.config.max_lines).min(lines.len());
+
+        (ideal_end..=max_end)
+            .find(|&i| Self::is_text_break(lines, i))
+            .or_else(|| {
+                (min_end..ideal_end)
+                    .rev()
+                    .find(|&i| Self::is_text_break(lines, i))
+            })
+            .unwrap_or(max_end)
+    }
+
+    /// Check if a text chunk can end before `pos`: after a blank line or
+    /// before a Markdown heading.
+    fn is_text_break(lines: &[&str], pos: usize) -> bool {
+        if pos >= lines.len() {
+            return true;
+        }
+        lines[pos - 1].trim().is_empty() || lines[pos].starts_with('#')
+    }
+
     /// Find a good end point for a chunk.
     fn find_chunk_end(&self, lines: &[&str], start: usize) -> usize {
         let ideal_end = (start + self.config.target_lines).min(lines.len());
@@ -172,14 +226,18 @@ impl Chunker {
         good_starts.iter().any(|s| line.starts_with(s))
     }
 
-    /// Chunk a file from path.
+    /// Chunk a file from path, as `language` (see
+    /// [`FileTypes::detect_language`](super::FileTypes::detect_language)).
     ///
     /// # Errors
     ///
     /// Returns an error if the file cannot be read.
-    pub fn chunk_file(&self, path: &Path) -> std::io::Result<Vec<CodeChunk>> {
+    pub fn chunk_file(
+        &self,
+        path: &Path,
+        language: Option<&str>,
+    ) -> std::io::Result<Vec<CodeChunk>> {
         let content = std::fs::read_to_string(path)?;
-        let language = super::filter::FileFilter::detect_language(path);
         Ok(self.chunk_content(&content, language))
     }
 }
@@ -231,6 +289,37 @@ mod tests {
         assert!(chunks.is_empty());
     }
 
+    #[test]
+    fn test_chunk_doc_at_paragraphs() {
+        let chunker = Chunker::new(ChunkerConfig {
+            target_lines: 10,
+            min_lines: 3,
+            max_lines: 15,
+            overlap_lines: 2,
+        });
+
+        // Three sections of a heading and seven lines of prose
+        let content: String = (1..=3)
+            .map(|s| {
+                let body: Vec<String> = (1..=7).map(|i| format!("section {s} line {i}")).collect();
+                format!("# Section {s}\n{}\n", body.join("\n"))
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let chunks = chunker.chunk_content(&content, Some(DOC_LANGUAGE));
+
+        assert_eq!(chunks.len(), 3);
+        for (i, chunk) in chunks.iter().enumerate() {
+            assert!(chunk.content.starts_with(&format!("# Section {}", i + 1)));
+            assert_eq!(chunk.index, i);
+        }
+        // No overlap and nothing dropped
+        assert_eq!(chunks[1].start_line, chunks[0].end_line + 1);
+        assert_eq!(chunks[2].start_line, chunks[1].end_line + 1);
+        assert_eq!(chunks[2].end_line, content.lines().count());
+    }
+
     #[test]
     fn test_good_break_points() {
         let lines = vec![
@@ -39,6 +39,11 @@ pub struct EventBatch {
     pub modified: Vec<PathBuf>,
     /// Deleted files (need removal from index).
     pub deleted: Vec<PathBuf>,
+    /// Renamed files as `(from, to)` pairs.
+    ///
+    /// Consumers should try to move the existing index entries and fall back
+    /// to deleting `from` and indexing `to` (see `split_renames`).
+    pub renamed: Vec<(PathBuf, PathBuf)>,
 }
 
 impl EventBatch {
@@ -52,6 +57,8 @@ impl EventBatch {
     pub fn add(&mut self, event: FileEvent) {
         match event {
             FileEvent::Modified(path) => {
+                // Delete followed by create (atomic save) is just a modify
+                self.deleted.retain(|p| p != &path);
                 if !self.modified.contains(&path) {
                     self.modified.push(path);
                 }
@@ -64,14 +71,41 @@ impl EventBatch {
                 }
             }
             FileEvent::Renamed { from, to } => {
-                // Treat as delete + create
-                self.modified.retain(|p| p != &from);
-                if !self.deleted.contains(&from) {
-                    self.deleted.push(from);
-                }
-                if !self.modified.contains(&to) {
-                    self.modified.push(to);
-                }
+                self.modified.retain(|p| p != &from && p != &to);
+                self.deleted.retain(|p| p != &from && p != &to);
+                self.renamed.retain(|(f, t)| f != &from && t != &to);
+                self.renamed.push((from, to));
+            }
+        }
+    }
+
+    /// Merge a later batch into this one.
+    ///
+    /// Deletes are applied before modifies, so a path deleted and then
+    /// recreated collapses into a single reindex, while a path modified in
+    /// this batch and deleted in the later one ends up deleted.
+    pub fn merge(&mut self, later: Self) {
+        for path in later.deleted {
+            self.add(FileEvent::Deleted(path));
+        }
+        for path in later.modified {
+            self.add(FileEvent::Modified(path));
+        }
+        for (from, to) in later.renamed {
+            self.add(FileEvent::Renamed { from, to });
+        }
+    }
+
+    /// Convert renames into a delete of the old path and a modify of the new one.
+    ///
+    /// This is the fallback when a rename can't be applied in place.
+    pub fn split_renames(&mut self) {
+        for (from, to) in std::mem::take(&mut self.renamed) {
+            if !self.deleted.contains(&from) {
+                self.deleted.push(from);
+            }
+            if !self.modified.contains(&to) {
+                self.modified.push(to);
             }
         }
     }
@@ -79,19 +113,20 @@ impl EventBatch {
     /// Check if batch is empty.
     #[must_use]
     pub fn is_empty(&self) -> bool {
-        self.modified.is_empty() && self.deleted.is_empty()
+        self.modified.is_empty() && self.deleted.is_empty() && self.renamed.is_empty()
     }
 
     /// Get total number of events.
     #[must_use]
     pub fn len(&self) -> usize {
-        self.modified.len() + self.deleted.len()
+        self.modified.len() + self.deleted.len() + self.renamed.len()
     }
 
     /// Clear all events.
     pub fn clear(&mut self) {
         self.modified.clear();
         self.deleted.clear();
+        self.renamed.clear();
     }
 }
 
@@ -135,6 +170,37 @@ mod tests {
         assert_eq!(batch.deleted.len(), 1);
     }
 
+    #[test]
+    fn test_event_batch_delete_then_modify() {
+        let mut batch = EventBatch::new();
+        batch.add(FileEvent::Deleted(PathBuf::from("/a.rs")));
+        batch.add(FileEvent::Modified(PathBuf::from("/a.rs")));
+
+        assert_eq!(batch.modified, vec![PathBuf::from("/a.rs")]);
+        assert!(batch.deleted.is_empty());
+    }
+
+    #[test]
+    fn test_event_batch_merge() {
+        let mut batch = EventBatch::new();
+        batch.add(FileEvent::Modified(PathBuf::from("/a.rs")));
+        batch.add(FileEvent::Deleted(PathBuf::from("/b.rs")));
+        batch.add(FileEvent::Modified(PathBuf::from("/c.rs")));
+
+        let mut later = EventBatch::new();
+        later.add(FileEvent::Modified(PathBuf::from("/a.rs"))); // repeated save
+        later.add(FileEvent::Modified(PathBuf::from("/b.rs"))); // recreated
+        later.add(FileEvent::Deleted(PathBuf::from("/c.rs"))); // removed
+
+        batch.merge(later);
+
+        assert_eq!(
+            batch.modified,
+            vec![PathBuf::from("/a.rs"), PathBuf::from("/b.rs")]
+        );
+        assert_eq!(batch.deleted, vec![PathBuf::from("/c.rs")]);
+    }
+
     #[test]
     fn test_event_batch_renamed() {
         let mut batch = EventBatch::new();
@@ -143,6 +209,17 @@ mod tests {
             to: PathBuf::from("/new.rs"),
         });
 
+        assert_eq!(
+            batch.renamed,
+            vec![(PathBuf::from("/old.rs"), PathBuf::from("/new.rs"))]
+        );
+        assert!(batch.modified.is_empty());
+        assert!(batch.deleted.is_empty());
+        assert_eq!(batch.len(), 1);
+
+        // Fallback: delete + create
+        batch.split_renames();
+        assert!(batch.renamed.is_empty());
         assert_eq!(batch.modified, vec![PathBuf::from("/new.rs")]);
         assert_eq!(batch.deleted, vec![PathBuf::from("/old.rs")]);
     }
@@ -4,6 +4,8 @@ use std::path::Path;
 
 use ignore::gitignore::{Gitignore, GitignoreBuilder};
 
+use super::scanner::{is_ignored_path, NELLIE_IGNORE_FILE, SKIP_DIRS};
+use crate::config::Config;
 use crate::Result;
 
 /// Supported code file extensions and their languages.
@@ -44,36 +46,218 @@ const CODE_EXTENSIONS: &[(&str, &str)] = &[
     ("svelte", "svelte"),
 ];
 
+/// Documentation extensions indexed with `index_docs`.
+pub const DOC_EXTENSIONS: &[&str] = &["md", "markdown", "mdx", "rst", "txt", "adoc"];
+
+/// Configuration extensions indexed with `index_docs`.
+pub const CONFIG_EXTENSIONS: &[&str] = &[
+    "yaml",
+    "yml",
+    "json",
+    "toml",
+    "ini",
+    "cfg",
+    "conf",
+    "properties",
+];
+
+/// Language documentation files are tagged with under `index_docs`.
+pub const DOC_LANGUAGE: &str = "doc";
+
+/// Language configuration files are tagged with under `index_docs`.
+pub const CONFIG_LANGUAGE: &str = "config";
+
+/// Which file extensions are indexed and the language each is tagged with.
+///
+/// The built-in [`CODE_EXTENSIONS`] table plus the extensions, documentation
+/// indexing and allowlist from [`Config`]. Build one at startup and hand it
+/// to every walker, filter and indexing path.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileTypes {
+    /// Extensions on top of [`CODE_EXTENSIONS`], with their language if one
+    /// was given.
+    extra: Vec<(String, Option<String>)>,
+    /// Index documentation and config files as `doc` / `config`.
+    docs: bool,
+    /// Extensions to restrict indexing to; empty allows every extension.
+    allowlist: Vec<String>,
+}
+
+impl FileTypes {
+    /// The file types configured in `config`.
+    #[must_use]
+    pub fn from_config(config: &Config) -> Self {
+        Self::default()
+            .with_extensions(&config.extra_languages, &config.extra_code_extensions)
+            .with_docs(config.index_docs)
+            .with_allowlist(&config.index_extensions)
+    }
+
+    /// Add extensions to index beyond the built-in table.
+    ///
+    /// `languages` maps extensions to the language they are tagged with,
+    /// overriding the built-in language for an extension it already knows;
+    /// `code_extensions` are indexed without a language unless `languages`
+    /// also names them. Extensions may have a leading dot and are matched
+    /// case-insensitively. Replaces any extensions set before.
+    #[must_use]
+    pub fn with_extensions<'a>(
+        mut self,
+        languages: impl IntoIterator<Item = (&'a String, &'a String)>,
+        code_extensions: &[String],
+    ) -> Self {
+        self.extra = languages
+            .into_iter()
+            .map(|(ext, lang)| (normalize_extension(ext), Some(lang.clone())))
+            .collect();
+        for ext in code_extensions {
+            let ext = normalize_extension(ext);
+            if !self.extra.iter().any(|(e, _)| *e == ext) {
+                self.extra.push((ext, None));
+            }
+        }
+        self
+    }
+
+    /// Set whether documentation and config files are indexed.
+    ///
+    /// With it, [`DOC_EXTENSIONS`] and [`CONFIG_EXTENSIONS`] are indexed and
+    /// tagged [`DOC_LANGUAGE`] or [`CONFIG_LANGUAGE`]. Markdown, YAML, JSON
+    /// and TOML are code extensions, so they are indexed either way; this
+    /// only adds the others (`.rst`, `.txt`, `.ini`, ...) and retags them.
+    #[must_use]
+    pub const fn with_docs(mut self, docs: bool) -> Self {
+        self.docs = docs;
+        self
+    }
+
+    /// Restrict indexing to `allowlist`, normalized like
+    /// [`Self::with_extensions`]; empty allows every extension.
+    #[must_use]
+    pub fn with_allowlist(mut self, allowlist: &[String]) -> Self {
+        self.allowlist = allowlist.iter().map(|e| normalize_extension(e)).collect();
+        self
+    }
+
+    /// Check if a path has an extension that is indexed: a code file, or a
+    /// documentation or config file under `index_docs`, that passes the
+    /// extension allowlist.
+    #[must_use]
+    pub fn is_code_file(&self, path: &Path) -> bool {
+        path.extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|ext| {
+                let ext = ext.to_lowercase();
+                self.allows(&ext)
+                    && (self.text_language(&ext).is_some()
+                        || CODE_EXTENSIONS.iter().any(|(e, _)| *e == ext)
+                        || self.extra.iter().any(|(e, _)| *e == ext))
+            })
+    }
+
+    /// Get the language for a file based on extension.
+    #[must_use]
+    pub fn detect_language(&self, path: &Path) -> Option<&str> {
+        path.extension().and_then(|e| e.to_str()).and_then(|ext| {
+            let ext = ext.to_lowercase();
+            self.extra
+                .iter()
+                .find(|(e, _)| *e == ext)
+                .and_then(|(_, lang)| lang.as_deref())
+                .or_else(|| self.text_language(&ext))
+                .or_else(|| {
+                    CODE_EXTENSIONS
+                        .iter()
+                        .find(|(e, _)| *e == ext)
+                        .map(|(_, lang)| *lang)
+                })
+        })
+    }
+
+    /// Whether the allowlist lets `ext` (normalized) be indexed.
+    fn allows(&self, ext: &str) -> bool {
+        self.allowlist.is_empty() || self.allowlist.iter().any(|e| e == ext)
+    }
+
+    /// `doc` or `config` for a documentation or config extension, when
+    /// documentation is indexed.
+    fn text_language(&self, ext: &str) -> Option<&'static str> {
+        if !self.docs {
+            None
+        } else if DOC_EXTENSIONS.contains(&ext) {
+            Some(DOC_LANGUAGE)
+        } else if CONFIG_EXTENSIONS.contains(&ext) {
+            Some(CONFIG_LANGUAGE)
+        } else {
+            None
+        }
+    }
+}
+
+/// Dependency lockfiles, matched by exact file name.
+///
+/// These are generated, churn on every dependency bump and drown out real
+/// code in search results.
+pub const LOCKFILE_NAMES: &[&str] = &[
+    "package-lock.json",
+    "npm-shrinkwrap.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "bun.lockb",
+    "Cargo.lock",
+    "go.sum",
+    "poetry.lock",
+    "Pipfile.lock",
+    "uv.lock",
+    "composer.lock",
+    "Gemfile.lock",
+    "mix.lock",
+    "pubspec.lock",
+    "Podfile.lock",
+    "packages.lock.json",
+    "flake.lock",
+];
+
+/// Suffixes of minified assets.
+const MINIFIED_SUFFIXES: &[&str] = &[".min.js", ".min.css"];
+
 /// File filter for indexing.
 #[derive(Debug)]
 pub struct FileFilter {
     gitignore: Option<Gitignore>,
-    #[allow(dead_code)]
     base_path: std::path::PathBuf,
+    skip_dirs: Vec<String>,
+    skip_noise: bool,
+    file_types: FileTypes,
 }
 
 impl FileFilter {
     /// Create a new file filter.
     ///
-    /// If a `.gitignore` exists in `base_path`, it will be used for filtering.
+    /// The `.gitignore`, `.ignore` and `.nellieignore` files in `base_path`
+    /// are used for filtering, as `build_walker` does.
     pub fn new(base_path: impl AsRef<Path>) -> Self {
         let base_path = base_path.as_ref().to_path_buf();
-        let gitignore_path = base_path.join(".gitignore");
-
-        let gitignore = if gitignore_path.exists() {
-            let mut builder = GitignoreBuilder::new(&base_path);
-            if builder.add(&gitignore_path).is_none() {
-                builder.build().ok()
-            } else {
-                None
+
+        let mut builder = GitignoreBuilder::new(&base_path);
+        let mut found = false;
+        for name in [".gitignore", ".ignore", NELLIE_IGNORE_FILE] {
+            let ignore_path = base_path.join(name);
+            if ignore_path.exists() {
+                found = true;
+                if let Some(e) = builder.add(&ignore_path) {
+                    tracing::warn!(path = %ignore_path.display(), error = %e, "Invalid ignore file");
+                }
             }
-        } else {
-            None
-        };
+        }
+        let gitignore = if found { builder.build().ok() } else { None };
 
         Self {
             gitignore,
             base_path,
+            skip_dirs: default_skip_dirs(),
+            skip_noise: true,
+            file_types: FileTypes::default(),
         }
     }
 
@@ -99,9 +283,40 @@ impl FileFilter {
         Ok(Self {
             gitignore: Some(gitignore),
             base_path,
+            skip_dirs: default_skip_dirs(),
+            skip_noise: true,
+            file_types: FileTypes::default(),
         })
     }
 
+    /// Set the directory names to skip, as in `WatcherConfig::skip_dirs`.
+    #[must_use]
+    pub fn with_skip_dirs(mut self, skip_dirs: Vec<String>) -> Self {
+        self.skip_dirs = skip_dirs;
+        self
+    }
+
+    /// Set whether lockfiles and minified assets are skipped (on by default).
+    #[must_use]
+    pub const fn with_skip_noise(mut self, skip: bool) -> Self {
+        self.skip_noise = skip;
+        self
+    }
+
+    /// Set which extensions are indexed (the built-in table by default).
+    #[must_use]
+    pub fn with_file_types(mut self, file_types: FileTypes) -> Self {
+        self.file_types = file_types;
+        self
+    }
+
+    /// Get the language for a file based on extension, as
+    /// [`FileTypes::detect_language`].
+    #[must_use]
+    pub fn detect_language(&self, path: &Path) -> Option<&str> {
+        self.file_types.detect_language(path)
+    }
+
     /// Check if a file should be indexed.
     #[must_use]
     pub fn should_index(&self, path: &Path) -> bool {
@@ -111,159 +326,220 @@ impl FileFilter {
         }
 
         // Must be a code file
-        if !Self::is_code_file(path) {
+        if !self.file_types.is_code_file(path) {
             return false;
         }
 
-        // Must not be ignored
+        // Must not be ignored, by the file itself or an ignored directory
+        let relative = path.strip_prefix(&self.base_path).unwrap_or(path);
         if let Some(ref gi) = self.gitignore {
-            if gi.matched(path, false).is_ignore() {
+            if gi.matched_path_or_any_parents(relative, false).is_ignore() {
                 return false;
             }
         }
 
         // Default ignores
-        if Self::is_default_ignored(path) {
+        if self.is_default_ignored(relative) {
             return false;
         }
 
-        true
-    }
+        if self.skip_noise && Self::is_noise_file(path) {
+            return false;
+        }
 
-    /// Check if a path is a code file based on extension.
-    #[must_use]
-    pub fn is_code_file(path: &Path) -> bool {
-        path.extension()
-            .and_then(|e| e.to_str())
-            .is_some_and(|ext| {
-                CODE_EXTENSIONS
-                    .iter()
-                    .any(|(e, _)| *e == ext.to_lowercase())
-            })
+        true
     }
 
-    /// Get the language for a file based on extension.
+    /// Check if a path is a dependency lockfile or minified asset.
     #[must_use]
-    pub fn detect_language(path: &Path) -> Option<&'static str> {
-        path.extension().and_then(|e| e.to_str()).and_then(|ext| {
-            CODE_EXTENSIONS
-                .iter()
-                .find(|(e, _)| *e == ext.to_lowercase())
-                .map(|(_, lang)| *lang)
-        })
-    }
-
-    /// Check if a path matches default ignore patterns.
-    fn is_default_ignored(path: &Path) -> bool {
-        let path_str = path.to_string_lossy();
-
-        // Dotdir heuristic: any path component starting with '.' is likely junk
-        // (e.g., .mypy_cache, .pytest_cache, .tox, .cache, .next, .nuxt, .terraform)
-        // Exception: .github (workflows, actions, etc.)
-        for component in path_str.split('/') {
-            if component.starts_with('.')
-                && component.len() > 1
-                && component != ".github"
-                && component != ".gitignore"
-            {
-                return true;
-            }
+    pub fn is_noise_file(path: &Path) -> bool {
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            return false;
+        };
+        if LOCKFILE_NAMES.contains(&name) {
+            return true;
         }
+        let lower = name.to_lowercase();
+        MINIFIED_SUFFIXES
+            .iter()
+            .any(|suffix| lower.ends_with(suffix))
+    }
 
-        // Non-dot directories to ignore
-        let ignored_dirs = [
-            "/node_modules/",
-            "/target/",
-            "/build/",
-            "/dist/",
-            "/__pycache__/",
-            "/venv/",
-            "/vendor/",
-            "/obj/",
-            "/bin/",
-            "/coverage/",
-            "/egg-info/",
-        ];
-
-        for dir in ignored_dirs {
-            if path_str.contains(dir) {
-                return true;
-            }
+    /// Check if a path, relative to the base path, is hidden or under a
+    /// skipped directory, or is a known junk file.
+    fn is_default_ignored(&self, relative: &Path) -> bool {
+        if is_ignored_path(relative, &self.skip_dirs) {
+            return true;
         }
 
         // Common files to ignore
-        if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-            let ignored_files = [".DS_Store", "Thumbs.db", ".env", ".env.local"];
-            if ignored_files.contains(&name) {
-                return true;
-            }
-
-            // Ignore lock files
-            let lower = name.to_lowercase();
-            if lower.ends_with(".lock")
-                || lower.ends_with("-lock.json")
-                || lower == "package-lock.json"
-            {
-                return true;
-            }
+        relative
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|name| name == "Thumbs.db")
+    }
+}
 
-            // Ignore minified files
-            if lower.ends_with(".min.js") || lower.ends_with(".min.css") {
-                return true;
-            }
-        }
+/// The walker's default skip list.
+fn default_skip_dirs() -> Vec<String> {
+    SKIP_DIRS.iter().map(|&d| d.to_string()).collect()
+}
 
-        false
-    }
+/// Lowercase an extension and drop any leading dot.
+fn normalize_extension(ext: &str) -> String {
+    ext.trim_start_matches('.').to_lowercase()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::HashMap;
     use std::fs;
     use tempfile::TempDir;
 
     #[test]
     fn test_is_code_file() {
-        assert!(FileFilter::is_code_file(Path::new("main.rs")));
-        assert!(FileFilter::is_code_file(Path::new("app.py")));
-        assert!(FileFilter::is_code_file(Path::new("index.tsx")));
-        assert!(!FileFilter::is_code_file(Path::new("image.png")));
-        assert!(!FileFilter::is_code_file(Path::new("document.pdf")));
+        let types = FileTypes::default();
+        assert!(types.is_code_file(Path::new("main.rs")));
+        assert!(types.is_code_file(Path::new("app.py")));
+        assert!(types.is_code_file(Path::new("index.tsx")));
+        assert!(!types.is_code_file(Path::new("image.png")));
+        assert!(!types.is_code_file(Path::new("document.pdf")));
     }
 
     #[test]
     fn test_detect_language() {
+        let types = FileTypes::default();
+        assert_eq!(types.detect_language(Path::new("main.rs")), Some("rust"));
+        assert_eq!(types.detect_language(Path::new("app.py")), Some("python"));
+        assert_eq!(
+            types.detect_language(Path::new("index.tsx")),
+            Some("typescript")
+        );
+        assert_eq!(types.detect_language(Path::new("unknown.xyz")), None);
+    }
+
+    #[test]
+    fn test_with_extensions() {
+        let languages = HashMap::from([
+            (".TF".to_string(), "terraform".to_string()),
+            ("bzl".to_string(), "starlark".to_string()),
+        ]);
+        let types = FileTypes::default()
+            .with_extensions(&languages, &["rbi".to_string(), "bzl".to_string()]);
+
+        assert!(!FileTypes::default().is_code_file(Path::new("infra/main.tf")));
+        assert!(types.is_code_file(Path::new("infra/main.tf")));
         assert_eq!(
-            FileFilter::detect_language(Path::new("main.rs")),
-            Some("rust")
+            types.detect_language(Path::new("infra/main.tf")),
+            Some("terraform")
         );
         assert_eq!(
-            FileFilter::detect_language(Path::new("app.py")),
-            Some("python")
+            types.detect_language(Path::new("BUILD.bzl")),
+            Some("starlark")
         );
+        assert!(types.is_code_file(Path::new("sorbet/rails.rbi")));
+        assert_eq!(types.detect_language(Path::new("sorbet/rails.rbi")), None);
+        // Built-in extensions are unaffected
+        assert_eq!(types.detect_language(Path::new("main.rs")), Some("rust"));
+    }
+
+    #[test]
+    fn test_docs_and_allowlist() {
+        // Documentation stays out of scope unless enabled, though markdown
+        // and YAML are code extensions in their own right
+        let types = FileTypes::default();
+        assert!(!types.is_code_file(Path::new("notes.rst")));
         assert_eq!(
-            FileFilter::detect_language(Path::new("index.tsx")),
-            Some("typescript")
+            types.detect_language(Path::new("README.md")),
+            Some("markdown")
+        );
+        assert_eq!(types.detect_language(Path::new("ci.yaml")), Some("yaml"));
+
+        let types = FileTypes::default().with_docs(true);
+        assert!(types.is_code_file(Path::new("notes.rst")));
+        assert_eq!(
+            types.detect_language(Path::new("README.md")),
+            Some(DOC_LANGUAGE)
+        );
+        assert_eq!(
+            types.detect_language(Path::new("notes.rst")),
+            Some(DOC_LANGUAGE)
+        );
+        assert_eq!(
+            types.detect_language(Path::new("ci.yaml")),
+            Some(CONFIG_LANGUAGE)
+        );
+        assert_eq!(
+            types.detect_language(Path::new("setup.ini")),
+            Some(CONFIG_LANGUAGE)
+        );
+        assert_eq!(types.detect_language(Path::new("main.rs")), Some("rust"));
+
+        let types = FileTypes::default()
+            .with_docs(true)
+            .with_allowlist(&[".MD".to_string(), "rs".to_string()]);
+        assert!(types.is_code_file(Path::new("README.md")));
+        assert!(types.is_code_file(Path::new("main.rs")));
+        assert!(!types.is_code_file(Path::new("app.py")));
+        assert!(!types.is_code_file(Path::new("ci.yaml")));
+    }
+
+    #[test]
+    fn test_file_types_from_config() {
+        let config = Config {
+            extra_languages: HashMap::from([("tf".to_string(), "terraform".to_string())]),
+            index_docs: true,
+            index_extensions: vec!["tf".to_string(), "rst".to_string()],
+            ..Config::default()
+        };
+        let types = FileTypes::from_config(&config);
+        assert_eq!(
+            types.detect_language(Path::new("main.tf")),
+            Some("terraform")
         );
-        assert_eq!(FileFilter::detect_language(Path::new("unknown.xyz")), None);
+        assert!(types.is_code_file(Path::new("notes.rst")));
+        assert!(!types.is_code_file(Path::new("main.rs")));
+    }
+
+    #[test]
+    fn test_filter_uses_file_types() {
+        let tmp = TempDir::new().unwrap();
+        fs::write(tmp.path().join("main.tf"), "resource {}").unwrap();
+
+        let filter = FileFilter::new(tmp.path());
+        assert!(!filter.should_index(&tmp.path().join("main.tf")));
+
+        let types = FileTypes::default().with_extensions(&HashMap::new(), &["tf".to_string()]);
+        let filter = FileFilter::new(tmp.path()).with_file_types(types);
+        assert!(filter.should_index(&tmp.path().join("main.tf")));
     }
 
     #[test]
     fn test_default_ignored() {
-        assert!(FileFilter::is_default_ignored(Path::new(
-            "/project/node_modules/pkg/index.js"
-        )));
-        assert!(FileFilter::is_default_ignored(Path::new(
-            "/project/.git/config"
-        )));
-        assert!(FileFilter::is_default_ignored(Path::new(
-            "/project/target/debug/main"
-        )));
-        assert!(FileFilter::is_default_ignored(Path::new("/project/.env")));
-        assert!(!FileFilter::is_default_ignored(Path::new(
-            "/project/src/main.rs"
-        )));
+        let filter = FileFilter::new("/project");
+        assert!(filter.is_default_ignored(Path::new("node_modules/pkg/index.js")));
+        assert!(filter.is_default_ignored(Path::new(".git/config")));
+        assert!(filter.is_default_ignored(Path::new("target/debug/main")));
+        assert!(filter.is_default_ignored(Path::new(".env")));
+        assert!(filter.is_default_ignored(Path::new("lib/Thumbs.db")));
+        assert!(!filter.is_default_ignored(Path::new("src/main.rs")));
+        assert!(!filter.is_default_ignored(Path::new(".github/workflows/ci.yml")));
+
+        let filter = FileFilter::new("/project").with_skip_dirs(vec!["generated".to_string()]);
+        assert!(filter.is_default_ignored(Path::new("generated/api.rs")));
+        assert!(!filter.is_default_ignored(Path::new("target/debug/main.rs")));
+    }
+
+    #[test]
+    fn test_filter_judges_paths_relative_to_base() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().join(".hidden-parent").join("repo");
+        fs::create_dir_all(base.join("src")).unwrap();
+        fs::write(base.join("src/main.rs"), "fn main() {}").unwrap();
+
+        let filter = FileFilter::new(&base);
+        assert!(filter.should_index(&base.join("src/main.rs")));
     }
 
     #[test]
@@ -276,11 +552,14 @@ mod tests {
         // Create test files
         fs::write(tmp.path().join("main.rs"), "fn main() {}").unwrap();
         fs::write(tmp.path().join("debug.log"), "log content").unwrap();
+        fs::create_dir(tmp.path().join("test_output")).unwrap();
+        fs::write(tmp.path().join("test_output/gen.rs"), "fn gen() {}").unwrap();
 
         let filter = FileFilter::new(tmp.path());
 
         assert!(filter.should_index(&tmp.path().join("main.rs")));
         assert!(!filter.should_index(&tmp.path().join("debug.log")));
+        assert!(!filter.should_index(&tmp.path().join("test_output/gen.rs")));
     }
 
     #[test]
@@ -294,4 +573,45 @@ mod tests {
         assert!(filter.should_index(&tmp.path().join("main.rs")));
         assert!(!filter.should_index(&tmp.path().join("test.rs")));
     }
+
+    #[test]
+    fn test_is_noise_file() {
+        assert!(FileFilter::is_noise_file(Path::new(
+            "/project/package-lock.json"
+        )));
+        assert!(FileFilter::is_noise_file(Path::new(
+            "/project/pnpm-lock.yaml"
+        )));
+        assert!(FileFilter::is_noise_file(Path::new("/project/Cargo.lock")));
+        assert!(FileFilter::is_noise_file(Path::new(
+            "/project/static/app.min.js"
+        )));
+        assert!(FileFilter::is_noise_file(Path::new(
+            "/project/static/site.MIN.CSS"
+        )));
+        assert!(!FileFilter::is_noise_file(Path::new(
+            "/project/package.json"
+        )));
+        assert!(!FileFilter::is_noise_file(Path::new(
+            "/project/src/lock.rs"
+        )));
+        assert!(!FileFilter::is_noise_file(Path::new(
+            "/project/static/app.js"
+        )));
+    }
+
+    #[test]
+    fn test_skip_noise_override() {
+        let tmp = TempDir::new().unwrap();
+        fs::write(tmp.path().join("package-lock.json"), "{}").unwrap();
+        fs::write(tmp.path().join("app.min.js"), "var a=1;").unwrap();
+
+        let filter = FileFilter::new(tmp.path());
+        assert!(!filter.should_index(&tmp.path().join("package-lock.json")));
+        assert!(!filter.should_index(&tmp.path().join("app.min.js")));
+
+        let filter = FileFilter::new(tmp.path()).with_skip_noise(false);
+        assert!(filter.should_index(&tmp.path().join("package-lock.json")));
+        assert!(filter.should_index(&tmp.path().join("app.min.js")));
+    }
 }
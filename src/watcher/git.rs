@@ -0,0 +1,196 @@
+//! Git revision detection for indexed files.
+//!
+//! Reads `.git/HEAD` and the refs directly instead of shelling out, so
+//! indexing works on hosts without a `git` binary.
+
+use std::path::{Path, PathBuf};
+
+/// The revision a working tree is checked out at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitRevision {
+    /// Full commit SHA of `HEAD`.
+    pub commit: String,
+
+    /// Branch name, or `None` for a detached `HEAD`.
+    pub branch: Option<String>,
+}
+
+/// Detect the revision of the git repository containing `path`.
+///
+/// Returns `None` if `path` isn't inside a repository or `HEAD` can't be
+/// resolved (e.g. a fresh repository with no commits).
+#[must_use]
+pub fn detect_git_revision(path: &Path) -> Option<GitRevision> {
+    let start = if path.is_dir() { path } else { path.parent()? };
+    let root = start.ancestors().find(|dir| dir.join(".git").exists())?;
+    let git_dir = resolve_git_dir(&root.join(".git"))?;
+    read_revision(&git_dir)
+}
+
+/// Resolve `.git` to the actual git directory.
+///
+/// Worktrees and submodules use a `.git` file containing `gitdir: <path>`.
+fn resolve_git_dir(dot_git: &Path) -> Option<PathBuf> {
+    if dot_git.is_dir() {
+        return Some(dot_git.to_path_buf());
+    }
+
+    let contents = std::fs::read_to_string(dot_git).ok()?;
+    let target = contents.trim().strip_prefix("gitdir:")?.trim();
+    let target = Path::new(target);
+    if target.is_absolute() {
+        Some(target.to_path_buf())
+    } else {
+        Some(dot_git.parent()?.join(target))
+    }
+}
+
+/// Read `HEAD` from a git directory and resolve it to a commit.
+fn read_revision(git_dir: &Path) -> Option<GitRevision> {
+    let head = std::fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+
+    let Some(reference) = head.strip_prefix("ref:").map(str::trim) else {
+        return is_sha(head).then(|| GitRevision {
+            commit: head.to_string(),
+            branch: None,
+        });
+    };
+
+    let commit = resolve_ref(git_dir, reference)?;
+    let branch = reference
+        .strip_prefix("refs/heads/")
+        .unwrap_or(reference)
+        .to_string();
+    Some(GitRevision {
+        commit,
+        branch: Some(branch),
+    })
+}
+
+/// Resolve a ref name to a commit SHA via loose refs, then `packed-refs`.
+///
+/// Linked worktrees keep shared refs in the directory named by `commondir`.
+fn resolve_ref(git_dir: &Path, reference: &str) -> Option<String> {
+    let common_dir = std::fs::read_to_string(git_dir.join("commondir"))
+        .ok()
+        .map(|dir| git_dir.join(dir.trim()));
+
+    std::iter::once(git_dir)
+        .chain(common_dir.as_deref())
+        .find_map(|dir| {
+            let loose = std::fs::read_to_string(dir.join(reference))
+                .ok()
+                .map(|sha| sha.trim().to_string())
+                .filter(|sha| is_sha(sha));
+            loose.or_else(|| find_packed_ref(dir, reference))
+        })
+}
+
+/// Look up a ref in `packed-refs`.
+fn find_packed_ref(dir: &Path, reference: &str) -> Option<String> {
+    let packed = std::fs::read_to_string(dir.join("packed-refs")).ok()?;
+    packed.lines().find_map(|line| {
+        let (sha, name) = line.split_once(' ')?;
+        (name.trim() == reference && is_sha(sha)).then(|| sha.to_string())
+    })
+}
+
+/// Whether `s` looks like a full SHA-1 or SHA-256 object name.
+fn is_sha(s: &str) -> bool {
+    matches!(s.len(), 40 | 64) && s.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    const SHA_A: &str = "1111111111111111111111111111111111111111";
+    const SHA_B: &str = "2222222222222222222222222222222222222222";
+
+    fn init_repo(root: &Path) -> PathBuf {
+        let git_dir = root.join(".git");
+        fs::create_dir_all(git_dir.join("refs/heads")).unwrap();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/lib.rs"), "fn lib() {}").unwrap();
+        git_dir
+    }
+
+    #[test]
+    fn test_detect_branch_from_loose_ref() {
+        let tmp = TempDir::new().unwrap();
+        let git_dir = init_repo(tmp.path());
+        fs::write(git_dir.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        fs::write(git_dir.join("refs/heads/main"), format!("{SHA_A}\n")).unwrap();
+
+        let revision = detect_git_revision(&tmp.path().join("src/lib.rs")).unwrap();
+        assert_eq!(revision.commit, SHA_A);
+        assert_eq!(revision.branch.as_deref(), Some("main"));
+    }
+
+    #[test]
+    fn test_detect_branch_from_packed_refs() {
+        let tmp = TempDir::new().unwrap();
+        let git_dir = init_repo(tmp.path());
+        fs::write(git_dir.join("HEAD"), "ref: refs/heads/release/1.0\n").unwrap();
+        fs::write(
+            git_dir.join("packed-refs"),
+            format!(
+                "# pack-refs with: peeled fully-peeled sorted\n\
+                 {SHA_A} refs/heads/main\n\
+                 {SHA_B} refs/heads/release/1.0\n"
+            ),
+        )
+        .unwrap();
+
+        let revision = detect_git_revision(tmp.path()).unwrap();
+        assert_eq!(revision.commit, SHA_B);
+        assert_eq!(revision.branch.as_deref(), Some("release/1.0"));
+    }
+
+    #[test]
+    fn test_detect_detached_head() {
+        let tmp = TempDir::new().unwrap();
+        let git_dir = init_repo(tmp.path());
+        fs::write(git_dir.join("HEAD"), format!("{SHA_B}\n")).unwrap();
+
+        let revision = detect_git_revision(&tmp.path().join("src/lib.rs")).unwrap();
+        assert_eq!(revision.commit, SHA_B);
+        assert!(revision.branch.is_none());
+    }
+
+    #[test]
+    fn test_detect_worktree_git_file() {
+        let tmp = TempDir::new().unwrap();
+        let main_git = init_repo(&tmp.path().join("main"));
+        fs::write(main_git.join("refs/heads/feature"), SHA_A).unwrap();
+        let worktree_git = main_git.join("worktrees/feature");
+        fs::create_dir_all(&worktree_git).unwrap();
+        fs::write(worktree_git.join("HEAD"), "ref: refs/heads/feature").unwrap();
+        fs::write(worktree_git.join("commondir"), "../..").unwrap();
+
+        let worktree = tmp.path().join("feature");
+        fs::create_dir_all(&worktree).unwrap();
+        fs::write(
+            worktree.join(".git"),
+            format!("gitdir: {}\n", worktree_git.display()),
+        )
+        .unwrap();
+
+        let revision = detect_git_revision(&worktree).unwrap();
+        assert_eq!(revision.commit, SHA_A);
+        assert_eq!(revision.branch.as_deref(), Some("feature"));
+    }
+
+    #[test]
+    fn test_no_revision_outside_repo_or_before_first_commit() {
+        let tmp = TempDir::new().unwrap();
+        assert!(detect_git_revision(tmp.path()).is_none());
+
+        let git_dir = init_repo(tmp.path());
+        fs::write(git_dir.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        assert!(detect_git_revision(tmp.path()).is_none());
+    }
+}
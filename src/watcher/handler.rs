@@ -4,10 +4,12 @@ use std::path::PathBuf;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
+use serde::Serialize;
 use tokio::sync::mpsc;
 
 use super::events::EventBatch;
-use super::filter::FileFilter;
+use super::filter::{FileFilter, FileTypes};
+use super::queue::IndexSender;
 use crate::Result;
 
 /// Statistics for file watching.
@@ -41,7 +43,7 @@ impl WatcherStats {
 }
 
 /// Snapshot of watcher stats.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, utoipa::ToSchema)]
 pub struct WatcherStatsSnapshot {
     pub files_detected: u64,
     pub files_filtered: u64,
@@ -57,6 +59,8 @@ pub struct IndexRequest {
     pub path: PathBuf,
     /// Detected language.
     pub language: Option<String>,
+    /// Repository name; detected from the nearest git root when `None`.
+    pub repo: Option<String>,
 }
 
 /// Event handler configuration.
@@ -66,13 +70,19 @@ pub struct HandlerConfig {
     pub base_path: PathBuf,
     /// Custom ignore patterns.
     pub ignore_patterns: Vec<String>,
+    /// Directory names to skip, from `WatcherConfig::skip_dirs`.
+    pub skip_dirs: Vec<String>,
+    /// Skip dependency lockfiles and minified assets.
+    pub skip_noise_files: bool,
+    /// Extensions to index, from `WatcherConfig::file_types`.
+    pub file_types: FileTypes,
 }
 
 /// Event handler that filters and processes file changes.
 pub struct EventHandler {
     filter: FileFilter,
     stats: Arc<WatcherStats>,
-    index_tx: mpsc::Sender<IndexRequest>,
+    index_tx: IndexSender,
     delete_tx: mpsc::Sender<PathBuf>,
 }
 
@@ -85,7 +95,7 @@ impl EventHandler {
     pub fn new(
         config: &HandlerConfig,
         stats: Arc<WatcherStats>,
-        index_tx: mpsc::Sender<IndexRequest>,
+        index_tx: IndexSender,
         delete_tx: mpsc::Sender<PathBuf>,
     ) -> Result<Self> {
         let patterns: Vec<&str> = config.ignore_patterns.iter().map(String::as_str).collect();
@@ -93,7 +103,10 @@ impl EventHandler {
             FileFilter::new(&config.base_path)
         } else {
             FileFilter::with_patterns(&config.base_path, &patterns)?
-        };
+        }
+        .with_skip_dirs(config.skip_dirs.clone())
+        .with_skip_noise(config.skip_noise_files)
+        .with_file_types(config.file_types.clone());
 
         Ok(Self {
             filter,
@@ -113,15 +126,17 @@ impl EventHandler {
         // Process modified files
         for path in batch.modified {
             if self.filter.should_index(&path) {
-                let language = FileFilter::detect_language(&path).map(String::from);
+                let language = self.filter.detect_language(&path).map(String::from);
                 let request = IndexRequest {
                     path: path.clone(),
                     language,
+                    repo: None,
                 };
 
                 if self.index_tx.send(request).await.is_ok() {
                     self.stats.files_indexed.fetch_add(1, Ordering::Relaxed);
                 } else {
+                    tracing::warn!(path = %path.display(), "Index queue closed; dropping change");
                     self.stats.errors.fetch_add(1, Ordering::Relaxed);
                 }
             } else {
@@ -158,6 +173,7 @@ impl EventHandler {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::watcher::{index_queue, OverflowPolicy, WatcherConfig};
     use std::fs;
     use tempfile::TempDir;
 
@@ -167,12 +183,15 @@ mod tests {
         fs::write(tmp.path().join("main.rs"), "fn main() {}").unwrap();
 
         let stats = WatcherStats::new();
-        let (index_tx, mut index_rx) = mpsc::channel(10);
+        let (index_tx, index_rx) = index_queue(10, OverflowPolicy::Block);
         let (delete_tx, _delete_rx) = mpsc::channel(10);
 
         let config = HandlerConfig {
             base_path: tmp.path().to_path_buf(),
             ignore_patterns: vec![],
+            skip_dirs: WatcherConfig::default().skip_dirs,
+            skip_noise_files: true,
+            file_types: FileTypes::default(),
         };
 
         let handler = EventHandler::new(&config, stats.clone(), index_tx, delete_tx).unwrap();
@@ -199,12 +218,15 @@ mod tests {
         let tmp = TempDir::new().unwrap();
 
         let stats = WatcherStats::new();
-        let (index_tx, _index_rx) = mpsc::channel(10);
+        let (index_tx, _index_rx) = index_queue(10, OverflowPolicy::Block);
         let (delete_tx, mut delete_rx) = mpsc::channel(10);
 
         let config = HandlerConfig {
             base_path: tmp.path().to_path_buf(),
             ignore_patterns: vec![],
+            skip_dirs: WatcherConfig::default().skip_dirs,
+            skip_noise_files: true,
+            file_types: FileTypes::default(),
         };
 
         let handler = EventHandler::new(&config, stats.clone(), index_tx, delete_tx).unwrap();
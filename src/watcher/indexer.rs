@@ -1,22 +1,96 @@
 //! Incremental indexing service.
 
-use std::path::Path;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
-use blake3::Hasher;
+use parking_lot::Mutex;
 use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
 
-use super::chunker::Chunker;
+use super::chunker::{Chunker, CodeChunk};
+use super::events::EventBatch;
+use super::git::detect_git_revision;
 use super::handler::IndexRequest;
+use super::queue::IndexReceiver;
 use crate::embeddings::EmbeddingService;
-use crate::storage::{delete_chunks_by_file, insert_chunk, ChunkRecord, Database};
+use crate::storage::{
+    add_stats, clear_index_error, delete_chunks_by_file, get_file_state, insert_chunk_deduped,
+    insert_chunks_batch, record_index_error, rename_file_path, set_chunks_git_revision,
+    set_chunks_repo, set_stat, shared_content_hashes, upsert_file_state, ChunkRecord, Database,
+    FileState, STAT_CHUNKS_CREATED, STAT_FILES_DELETED, STAT_FILES_INDEXED, STAT_INDEX_ERRORS,
+    STAT_LAST_INDEXED_AT,
+};
 use crate::Result;
 
+/// How often `Indexer::run` adds its counters to the database's lifetime stats.
+pub const STATS_FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Statistics for indexing, since the indexer was created.
+#[derive(Debug, Default)]
+pub struct IndexerStats {
+    pub files_indexed: AtomicU64,
+    pub chunks_created: AtomicU64,
+    pub files_deleted: AtomicU64,
+    pub errors: AtomicU64,
+    /// Unix time a file last produced chunks (0 = none yet).
+    pub last_indexed_at: AtomicI64,
+}
+
+impl IndexerStats {
+    /// Get snapshot of current stats.
+    #[must_use]
+    pub fn snapshot(&self) -> IndexerStatsSnapshot {
+        IndexerStatsSnapshot {
+            files_indexed: self.files_indexed.load(Ordering::Relaxed),
+            chunks_created: self.chunks_created.load(Ordering::Relaxed),
+            files_deleted: self.files_deleted.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            last_indexed_at: self.last_indexed_at.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Snapshot of indexer stats.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, utoipa::ToSchema)]
+pub struct IndexerStatsSnapshot {
+    pub files_indexed: u64,
+    pub chunks_created: u64,
+    pub files_deleted: u64,
+    pub errors: u64,
+    pub last_indexed_at: i64,
+}
+
+impl IndexerStatsSnapshot {
+    /// Counters accumulated since `earlier`, keeping the current timestamp.
+    #[must_use]
+    pub const fn since(&self, earlier: &Self) -> Self {
+        Self {
+            files_indexed: self.files_indexed.saturating_sub(earlier.files_indexed),
+            chunks_created: self.chunks_created.saturating_sub(earlier.chunks_created),
+            files_deleted: self.files_deleted.saturating_sub(earlier.files_deleted),
+            errors: self.errors.saturating_sub(earlier.errors),
+            last_indexed_at: self.last_indexed_at,
+        }
+    }
+}
+
 /// Indexer service that processes files and stores chunks.
 pub struct Indexer {
     db: Database,
     embeddings: Option<EmbeddingService>,
     chunker: Chunker,
+    /// Detected repository per directory, to avoid re-walking for every file.
+    repo_cache: Mutex<HashMap<PathBuf, Option<String>>>,
+    stats: IndexerStats,
+    /// Stats already added to the database by `flush_stats`.
+    flushed_stats: Mutex<IndexerStatsSnapshot>,
+    /// Store chunk text; when false only the path and line range are kept.
+    store_content: bool,
+    /// Reuse the embedding of chunks whose text is already indexed.
+    dedupe: bool,
 }
 
 impl Indexer {
@@ -27,15 +101,119 @@ impl Indexer {
             db,
             embeddings,
             chunker: Chunker::default_chunker(),
+            repo_cache: Mutex::new(HashMap::new()),
+            stats: IndexerStats::default(),
+            flushed_stats: Mutex::new(IndexerStatsSnapshot::default()),
+            store_content: true,
+            dedupe: false,
         }
     }
 
+    /// Set whether chunk text is stored in the database.
+    ///
+    /// Without it, chunks keep only their file path and line range and
+    /// search reads the text back from disk. Embeddings are still computed
+    /// from the full text.
+    #[must_use]
+    pub const fn with_store_content(mut self, store: bool) -> Self {
+        self.store_content = store;
+        self
+    }
+
+    /// Set whether chunks with already-indexed text are deduplicated.
+    ///
+    /// A duplicate is stored as a reference to the first chunk with the same
+    /// content hash and isn't embedded again.
+    #[must_use]
+    pub const fn with_dedupe(mut self, dedupe: bool) -> Self {
+        self.dedupe = dedupe;
+        self
+    }
+
+    /// Indexing activity since this indexer was created.
+    #[must_use]
+    pub fn stats(&self) -> IndexerStatsSnapshot {
+        self.stats.snapshot()
+    }
+
+    /// Add activity since the last flush to the database's lifetime stats.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database update fails; the activity is then
+    /// included in the next flush.
+    pub fn flush_stats(&self) -> Result<()> {
+        let mut flushed = self.flushed_stats.lock();
+        let current = self.stats.snapshot();
+        if current == *flushed {
+            return Ok(());
+        }
+
+        let delta = current.since(&flushed);
+        self.db.with_transaction(|conn| {
+            add_stats(
+                conn,
+                &[
+                    (STAT_FILES_INDEXED, delta.files_indexed),
+                    (STAT_CHUNKS_CREATED, delta.chunks_created),
+                    (STAT_FILES_DELETED, delta.files_deleted),
+                    (STAT_INDEX_ERRORS, delta.errors),
+                ],
+            )?;
+            if current.last_indexed_at > flushed.last_indexed_at {
+                set_stat(conn, STAT_LAST_INDEXED_AT, current.last_indexed_at)?;
+            }
+            Ok(())
+        })?;
+
+        *flushed = current;
+        drop(flushed);
+        Ok(())
+    }
+
     /// Index a single file.
     ///
+    /// Returns the number of chunks stored (0 if the file was unchanged).
+    /// Failures are recorded in the index error log, and a success clears
+    /// the file's entry.
+    ///
     /// # Errors
     ///
     /// Returns an error if indexing fails.
     pub async fn index_file(&self, request: &IndexRequest) -> Result<usize> {
+        let result = self.index_file_inner(request).await;
+        self.log_index_result(&request.path, &result);
+        match result {
+            Ok(0) => {}
+            Ok(chunks) => {
+                self.stats.files_indexed.fetch_add(1, Ordering::Relaxed);
+                self.stats
+                    .chunks_created
+                    .fetch_add(chunks as u64, Ordering::Relaxed);
+                self.stats
+                    .last_indexed_at
+                    .store(chrono::Utc::now().timestamp(), Ordering::Relaxed);
+            }
+            Err(_) => {
+                self.stats.errors.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        result
+    }
+
+    /// Record a failure in the index error log, or clear it on success.
+    fn log_index_result(&self, path: &Path, result: &Result<usize>) {
+        let path_str = path.to_string_lossy();
+        let logged = self.db.with_conn(|conn| match result {
+            Ok(_) => clear_index_error(conn, &path_str).map(|_| ()),
+            Err(e) => record_index_error(conn, &path_str, &e.to_string()),
+        });
+        if let Err(e) = logged {
+            tracing::warn!(path = %path.display(), error = %e, "Failed to update index error log");
+        }
+    }
+
+    async fn index_file_inner(&self, request: &IndexRequest) -> Result<usize> {
         let path = &request.path;
 
         if !path.exists() {
@@ -46,62 +224,120 @@ impl Indexer {
         // Read file content
         let content = tokio::fs::read_to_string(path).await?;
         let file_hash = compute_hash(&content);
+        let revision = detect_git_revision(path);
 
         // Check if already indexed with same hash
         if self.is_already_indexed(path, &file_hash)? {
+            // An explicit repo still applies to unchanged files
+            if let Some(ref repo) = request.repo {
+                self.db
+                    .with_conn(|conn| set_chunks_repo(conn, &path.to_string_lossy(), repo))?;
+            }
+            // Unchanged content is still current at the new checkout
+            if let Some(ref revision) = revision {
+                self.db.with_conn(|conn| {
+                    set_chunks_git_revision(
+                        conn,
+                        &path.to_string_lossy(),
+                        &revision.commit,
+                        revision.branch.as_deref(),
+                    )
+                })?;
+            }
             tracing::debug!(path = %path.display(), "File unchanged, skipping");
             return Ok(0);
         }
 
-        // Remove old chunks
-        self.db.with_conn(|conn| {
-            delete_chunks_by_file(conn, &path.to_string_lossy())?;
-            Ok(())
-        })?;
-
         // Chunk the file
         let chunks = self
             .chunker
             .chunk_content(&content, request.language.as_deref());
 
+        let path_str = path.to_string_lossy().to_string();
         if chunks.is_empty() {
+            // Remove old chunks
+            self.db
+                .with_conn(|conn| delete_chunks_by_file(conn, &path_str))?;
             return Ok(0);
         }
 
+        // Only embed text not already held by another file or an earlier
+        // chunk of this one
+        let content_hashes: Option<Vec<String>> = self
+            .dedupe
+            .then(|| chunks.iter().map(|c| compute_hash(&c.content)).collect());
+        let needs_embedding: Vec<bool> = match &content_hashes {
+            Some(hashes) => {
+                let shared = self
+                    .db
+                    .with_conn(|conn| shared_content_hashes(conn, hashes, &path_str))?;
+                let mut seen = HashSet::new();
+                hashes
+                    .iter()
+                    .map(|hash| !shared.contains(hash) && seen.insert(hash.as_str()))
+                    .collect()
+            }
+            None => vec![true; chunks.len()],
+        };
+        let to_embed: Vec<&CodeChunk> = chunks
+            .iter()
+            .zip(&needs_embedding)
+            .filter_map(|(chunk, &embed)| embed.then_some(chunk))
+            .collect();
+        if to_embed.len() < chunks.len() {
+            tracing::debug!(
+                path = %path.display(),
+                reused = chunks.len() - to_embed.len(),
+                "Reusing embeddings of duplicate chunks"
+            );
+        }
+
         // Generate embeddings
-        let embeddings = self.generate_embeddings(&chunks).await?;
+        let mut embeddings = self
+            .generate_embeddings(&to_embed, &path_str, request.language.as_deref())
+            .await?
+            .into_iter();
 
-        // Store chunks
-        let path_str = path.to_string_lossy().to_string();
-        let mut count = 0;
+        let repo = self.repo_for(request);
+        let mut records = Vec::with_capacity(chunks.len());
 
-        for (chunk, embedding) in chunks.iter().zip(embeddings.iter()) {
+        for (chunk, &embed) in chunks.iter().zip(&needs_embedding) {
             #[allow(clippy::cast_possible_wrap, clippy::cast_possible_truncation)]
             let mut record = ChunkRecord::new(
                 &path_str,
                 chunk.index as i32,
                 chunk.start_line as i32,
                 chunk.end_line as i32,
-                &chunk.content,
+                if self.store_content {
+                    &chunk.content
+                } else {
+                    ""
+                },
                 &file_hash,
             )
             .with_language(request.language.clone().unwrap_or_default());
+            record.repo.clone_from(&repo);
+            if let Some(ref revision) = revision {
+                record = record.with_git_revision(&revision.commit, revision.branch.clone());
+            }
 
             // Only add embedding if we have a real embedding service (not placeholder)
-            if self.embeddings.is_some() {
-                record = record.with_embedding(embedding.clone());
+            if embed {
+                let embedding = embeddings.next().unwrap_or_default();
+                if self.embeddings.is_some() {
+                    record = record.with_embedding(embedding);
+                }
             }
 
-            self.db.with_conn(|conn| {
-                insert_chunk(conn, &record)?;
-                Ok(())
-            })?;
-
-            count += 1;
+            records.push(record);
         }
 
-        // Update file state
-        self.update_file_state(path, &file_hash)?;
+        // Replace the chunks and record the file in one transaction so a
+        // crash can't leave the file state out of step with its chunks
+        let state = file_state(path, &file_hash)?;
+        let count = self.db.with_transaction(|conn| {
+            replace_file_index(conn, &records, content_hashes.as_deref(), &state)
+        })?;
 
         tracing::info!(
             path = %path.display(),
@@ -120,21 +356,152 @@ impl Indexer {
     pub fn delete_file(&self, path: &Path) -> Result<usize> {
         let path_str = path.to_string_lossy().to_string();
 
-        let deleted = self.db.with_conn(|conn| {
-            let count = delete_chunks_by_file(conn, &path_str)?;
-            // Remove file state
-            conn.execute("DELETE FROM file_state WHERE path = ?", [&path_str])
-                .ok();
-            Ok(count)
-        })?;
+        let deleted = self
+            .db
+            .with_conn(|conn| {
+                let count = delete_chunks_by_file(conn, &path_str)?;
+                // Remove file state
+                conn.execute("DELETE FROM file_state WHERE path = ?", [&path_str])
+                    .ok();
+                clear_index_error(conn, &path_str)?;
+                Ok(count)
+            })
+            .map_err(|e| {
+                self.stats.errors.fetch_add(1, Ordering::Relaxed);
+                e
+            })?;
 
         if deleted > 0 {
+            self.stats.files_deleted.fetch_add(1, Ordering::Relaxed);
             tracing::info!(path = %path.display(), chunks = deleted, "Deleted file from index");
         }
 
         Ok(deleted)
     }
 
+    /// Move a file's index entries to a new path if its content is unchanged.
+    ///
+    /// Returns `true` if the rename was applied. Returns `false` when the old
+    /// path isn't indexed or the content differs, in which case the caller
+    /// should fall back to delete + reindex.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database update fails.
+    pub async fn rename_file(&self, from: &Path, to: &Path) -> Result<bool> {
+        let from_str = from.to_string_lossy().to_string();
+        let to_str = to.to_string_lossy().to_string();
+
+        let Some(state) = self.db.with_conn(|conn| get_file_state(conn, &from_str))? else {
+            return Ok(false);
+        };
+        let Ok(content) = tokio::fs::read_to_string(to).await else {
+            return Ok(false);
+        };
+        let hash = compute_hash(&content);
+        if state.hash != hash {
+            return Ok(false);
+        }
+
+        let new_state = file_state(to, &hash)?;
+        let moved = self.db.with_transaction(|conn| {
+            let moved = rename_file_path(conn, &from_str, &to_str)?;
+            upsert_file_state(conn, &new_state)?;
+            Ok(moved)
+        })?;
+
+        tracing::info!(
+            from = %from.display(),
+            to = %to.display(),
+            chunks = moved,
+            "Renamed file in index"
+        );
+        Ok(true)
+    }
+
+    /// Apply renames in a batch without re-embedding unchanged content.
+    ///
+    /// Explicit rename pairs are used when the watcher provides them.
+    /// Otherwise, deleted paths are paired with newly seen paths whose content
+    /// hash matches the deleted file's stored hash. Renames that can't be
+    /// applied are split back into delete + modify so the normal pipeline
+    /// handles them.
+    ///
+    /// Returns the number of renames applied.
+    pub async fn resolve_renames(&self, batch: &mut EventBatch) -> usize {
+        let mut candidates = std::mem::take(&mut batch.renamed);
+        candidates.extend(self.correlate_renames(batch).await);
+
+        let mut applied = 0;
+        for (from, to) in candidates {
+            match self.rename_file(&from, &to).await {
+                Ok(true) => applied += 1,
+                Ok(false) => batch.renamed.push((from, to)),
+                Err(e) => {
+                    tracing::warn!(
+                        from = %from.display(),
+                        to = %to.display(),
+                        error = %e,
+                        "Failed to apply rename, falling back to reindex"
+                    );
+                    batch.renamed.push((from, to));
+                }
+            }
+        }
+
+        batch.split_renames();
+        applied
+    }
+
+    /// Pair deleted and modified paths that look like renames.
+    ///
+    /// Matched paths are removed from `batch.deleted` / `batch.modified`.
+    async fn correlate_renames(&self, batch: &mut EventBatch) -> Vec<(PathBuf, PathBuf)> {
+        if batch.deleted.is_empty() || batch.modified.is_empty() {
+            return Vec::new();
+        }
+
+        // Stored hashes of deleted files
+        let mut deleted_by_hash: HashMap<String, PathBuf> = HashMap::new();
+        for path in &batch.deleted {
+            let path_str = path.to_string_lossy();
+            if let Ok(Some(state)) = self.db.with_conn(|conn| get_file_state(conn, &path_str)) {
+                if !state.hash.is_empty() {
+                    deleted_by_hash.insert(state.hash, path.clone());
+                }
+            }
+        }
+        if deleted_by_hash.is_empty() {
+            return Vec::new();
+        }
+
+        // Only untracked paths can be rename targets
+        let mut pairs = Vec::new();
+        for path in &batch.modified {
+            let path_str = path.to_string_lossy();
+            if !matches!(
+                self.db.with_conn(|conn| get_file_state(conn, &path_str)),
+                Ok(None)
+            ) {
+                continue;
+            }
+            let Ok(content) = tokio::fs::read_to_string(path).await else {
+                continue;
+            };
+            if let Some(from) = deleted_by_hash.remove(&compute_hash(&content)) {
+                pairs.push((from, path.clone()));
+            }
+        }
+
+        batch
+            .deleted
+            .retain(|p| !pairs.iter().any(|(from, _)| from == p));
+        batch
+            .modified
+            .retain(|p| !pairs.iter().any(|(_, to)| to == p));
+        pairs
+    }
+
     /// Check if file is already indexed with same hash.
     fn is_already_indexed(&self, path: &Path, hash: &str) -> Result<bool> {
         let path_str = path.to_string_lossy();
@@ -150,48 +517,38 @@ impl Indexer {
         })
     }
 
-    /// Update file state after indexing.
-    fn update_file_state(&self, path: &Path, hash: &str) -> Result<()> {
-        let metadata = std::fs::metadata(path)?;
-        #[allow(clippy::cast_possible_wrap)]
-        let mtime = metadata
-            .modified()
-            .map(|t| {
-                t.duration_since(std::time::UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_secs() as i64
-            })
-            .unwrap_or(0);
-        #[allow(clippy::cast_possible_wrap)]
-        let size = metadata.len() as i64;
-        let path_str = path.to_string_lossy().to_string();
-        #[allow(clippy::cast_possible_wrap)]
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs() as i64;
+    /// Resolve the repository for a request, detecting it if not given.
+    fn repo_for(&self, request: &IndexRequest) -> Option<String> {
+        if request.repo.is_some() {
+            return request.repo.clone();
+        }
 
-        self.db.with_conn(|conn| {
-            conn.execute(
-                "INSERT OR REPLACE INTO file_state (path, mtime, size, hash, last_indexed) \
-                 VALUES (?, ?, ?, ?, ?)",
-                rusqlite::params![&path_str, mtime, size, hash, now],
-            )
-            .map_err(|e| {
-                crate::Error::Storage(crate::error::StorageError::Database(e.to_string()))
-            })?;
-            Ok(())
-        })
+        let dir = request.path.parent()?;
+        if let Some(cached) = self.repo_cache.lock().get(dir) {
+            return cached.clone();
+        }
+
+        let repo = detect_repo(&request.path);
+        self.repo_cache
+            .lock()
+            .insert(dir.to_path_buf(), repo.clone());
+        repo
     }
 
-    /// Generate embeddings for chunks.
+    /// Generate embeddings for chunks of `file_path`, through the code template.
     async fn generate_embeddings(
         &self,
-        chunks: &[super::chunker::CodeChunk],
+        chunks: &[&CodeChunk],
+        file_path: &str,
+        language: Option<&str>,
     ) -> Result<Vec<Vec<f32>>> {
         if let Some(ref service) = self.embeddings {
             if service.is_initialized() {
-                let texts: Vec<String> = chunks.iter().map(|c| c.content.clone()).collect();
+                let templates = service.templates();
+                let texts: Vec<String> = chunks
+                    .iter()
+                    .map(|c| templates.code(&c.content, language, file_path))
+                    .collect();
                 return service.embed_batch(texts).await;
             }
         }
@@ -202,45 +559,158 @@ impl Indexer {
     }
 
     /// Run the indexer loop processing requests from a channel.
+    ///
+    /// Runs until both channels close or `shutdown` is cancelled. On
+    /// cancellation the file currently being indexed is finished, the
+    /// channels are closed so no new requests are accepted, pending deletes
+    /// are applied, and queued index requests are abandoned (their file state
+    /// is untouched, so they are picked up again on the next start).
+    ///
+    /// Stats are flushed to the database every `STATS_FLUSH_INTERVAL` and
+    /// once more on exit.
     pub async fn run(
         self: Arc<Self>,
-        mut index_rx: mpsc::Receiver<IndexRequest>,
+        index_rx: IndexReceiver,
         mut delete_rx: mpsc::Receiver<std::path::PathBuf>,
+        shutdown: CancellationToken,
     ) {
         tracing::info!("Indexer started");
 
-        loop {
+        let mut index_open = true;
+        let mut delete_open = true;
+        let mut indexed = 0usize;
+        let mut flush_timer = tokio::time::interval_at(
+            tokio::time::Instant::now() + STATS_FLUSH_INTERVAL,
+            STATS_FLUSH_INTERVAL,
+        );
+
+        while index_open || delete_open {
             tokio::select! {
-                Some(request) = index_rx.recv() => {
-                    if let Err(e) = self.index_file(&request).await {
-                        tracing::error!(path = %request.path.display(), error = %e, "Failed to index file");
+                biased;
+                () = shutdown.cancelled() => break,
+                _ = flush_timer.tick() => self.log_flush_stats(),
+                request = index_rx.recv(), if index_open => match request {
+                    Some(request) => {
+                        if let Err(e) = self.index_file(&request).await {
+                            tracing::error!(path = %request.path.display(), error = %e, "Failed to index file");
+                        }
+                        indexed += 1;
                     }
-                }
-                Some(path) = delete_rx.recv() => {
-                    if let Err(e) = self.delete_file(&path) {
-                        tracing::error!(path = %path.display(), error = %e, "Failed to delete file from index");
+                    None => index_open = false,
+                },
+                path = delete_rx.recv(), if delete_open => match path {
+                    Some(path) => {
+                        if let Err(e) = self.delete_file(&path) {
+                            tracing::error!(path = %path.display(), error = %e, "Failed to delete file from index");
+                        }
                     }
-                }
-                else => {
-                    tracing::info!("Indexer channels closed, shutting down");
-                    break;
+                    None => delete_open = false,
+                },
+            }
+        }
+
+        if !shutdown.is_cancelled() {
+            self.log_flush_stats();
+            tracing::info!("Indexer channels closed, shutting down");
+            return;
+        }
+
+        index_rx.close();
+        delete_rx.close();
+
+        let mut deletes_drained = 0usize;
+        while let Ok(path) = delete_rx.try_recv() {
+            match self.delete_file(&path) {
+                Ok(_) => deletes_drained += 1,
+                Err(e) => {
+                    tracing::error!(path = %path.display(), error = %e, "Failed to delete file from index");
                 }
             }
         }
+
+        let mut abandoned = 0usize;
+        while index_rx.try_recv().is_some() {
+            abandoned += 1;
+        }
+
+        self.log_flush_stats();
+        tracing::info!(indexed, deletes_drained, abandoned, "Indexer shut down");
     }
+
+    /// Flush stats, logging rather than returning a failure.
+    fn log_flush_stats(&self) {
+        if let Err(e) = self.flush_stats() {
+            tracing::warn!(error = %e, "Failed to persist indexer stats");
+        }
+    }
+}
+
+/// Detect the repository containing `path`.
+///
+/// Returns the name of the nearest ancestor directory containing `.git`
+/// (a directory, or a file for worktrees and submodules).
+#[must_use]
+pub fn detect_repo(path: &Path) -> Option<String> {
+    let start = if path.is_dir() { path } else { path.parent()? };
+    start
+        .ancestors()
+        .find(|dir| dir.join(".git").exists())
+        .and_then(Path::file_name)
+        .map(|name| name.to_string_lossy().into_owned())
 }
 
 /// Compute blake3 hash of content.
 fn compute_hash(content: &str) -> String {
-    let mut hasher = Hasher::new();
-    hasher.update(content.as_bytes());
-    hasher.finalize().to_hex().to_string()
+    crate::storage::compute_content_hash(content.as_bytes())
+}
+
+/// Read the state to record for `path` after indexing content with `hash`.
+fn file_state(path: &Path, hash: &str) -> Result<FileState> {
+    let metadata = std::fs::metadata(path)?;
+    #[allow(clippy::cast_possible_wrap)]
+    let mtime = metadata
+        .modified()
+        .map(|t| {
+            t.duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs() as i64
+        })
+        .unwrap_or(0);
+    #[allow(clippy::cast_possible_wrap)]
+    let size = metadata.len() as i64;
+
+    Ok(FileState::new(path.to_string_lossy(), mtime, size, hash))
+}
+
+/// Replace a file's chunks with `records` and record its new state.
+///
+/// With `content_hashes` (one per record) the chunks are stored
+/// deduplicated. Run inside a transaction so the chunks and file state
+/// commit together. Returns the number of chunks stored.
+fn replace_file_index(
+    conn: &rusqlite::Connection,
+    records: &[ChunkRecord],
+    content_hashes: Option<&[String]>,
+    state: &FileState,
+) -> Result<usize> {
+    delete_chunks_by_file(conn, &state.path)?;
+    let ids = match content_hashes {
+        Some(hashes) => records
+            .iter()
+            .zip(hashes)
+            .map(|(record, hash)| insert_chunk_deduped(conn, record, hash))
+            .collect::<Result<Vec<_>>>()?,
+        None => insert_chunks_batch(conn, records)?,
+    };
+    upsert_file_state(conn, state)?;
+    Ok(ids.len())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::storage::{migrate, Database};
+    use crate::watcher::{index_queue, OverflowPolicy};
     use std::fs;
     use tempfile::TempDir;
 
@@ -255,6 +725,97 @@ mod tests {
         db
     }
 
+    #[tokio::test]
+    async fn test_index_file_logs_and_clears_failures() {
+        let db = setup_test_db();
+        let indexer = Indexer::new(db.clone(), None);
+
+        let tmp = TempDir::new().unwrap();
+        let file_path = tmp.path().join("legacy.rs");
+        fs::write(&file_path, [0xff, 0xfe, b'f', b'n']).unwrap();
+        let request = IndexRequest {
+            path: file_path.clone(),
+            language: Some("rust".to_string()),
+            repo: None,
+        };
+
+        assert!(indexer.index_file(&request).await.is_err());
+        assert!(indexer.index_file(&request).await.is_err());
+        let errors = db
+            .with_conn(|conn| crate::storage::list_index_errors(conn, None, 10))
+            .unwrap();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].file_path, file_path.to_string_lossy());
+        assert_eq!(errors[0].attempts, 2);
+
+        fs::write(&file_path, "fn fixed() {}\n").unwrap();
+        indexer.index_file(&request).await.unwrap();
+        let remaining = db
+            .with_conn(|conn| crate::storage::count_index_errors(conn, None))
+            .unwrap();
+        assert_eq!(remaining, 0);
+    }
+
+    #[tokio::test]
+    async fn test_replace_file_index_is_atomic() {
+        let db = setup_test_db();
+        let indexer = Indexer::new(db.clone(), None);
+
+        let tmp = TempDir::new().unwrap();
+        let file_path = tmp.path().join("lib.rs");
+        fs::write(&file_path, "fn old() {}\n").unwrap();
+        let path_str = file_path.to_string_lossy().to_string();
+
+        let request = IndexRequest {
+            path: file_path.clone(),
+            language: Some("rust".to_string()),
+            repo: None,
+        };
+        indexer.index_file(&request).await.unwrap();
+        let old_state = db
+            .with_conn(|conn| get_file_state(conn, &path_str))
+            .unwrap()
+            .unwrap();
+
+        // Crash partway through writing the new content's index
+        fs::write(&file_path, "fn new() {}\n").unwrap();
+        let state = file_state(&file_path, "new-hash").unwrap();
+        let records = vec![ChunkRecord::new(
+            &path_str,
+            0,
+            1,
+            1,
+            "fn new() {}",
+            "new-hash",
+        )];
+        let crashed = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            db.with_transaction::<_, ()>(|conn| {
+                replace_file_index(conn, &records, None, &state)?;
+                panic!("simulated crash after writing chunks and file state");
+            })
+        }));
+        assert!(crashed.is_err());
+
+        // Neither the new chunks nor the new file state were committed
+        let chunks = db
+            .with_conn(|conn| crate::storage::get_chunks_by_file(conn, &path_str))
+            .unwrap();
+        assert!(!chunks.is_empty());
+        assert!(chunks.iter().all(|c| c.content.contains("old")));
+        let stored = db
+            .with_conn(|conn| get_file_state(conn, &path_str))
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.hash, old_state.hash);
+
+        // The next index pass picks up the new content
+        assert!(indexer.index_file(&request).await.unwrap() > 0);
+        let chunks = db
+            .with_conn(|conn| crate::storage::get_chunks_by_file(conn, &path_str))
+            .unwrap();
+        assert!(chunks.iter().all(|c| c.content.contains("new")));
+    }
+
     #[tokio::test]
     async fn test_index_file() {
         let db = setup_test_db();
@@ -267,6 +828,7 @@ mod tests {
         let request = IndexRequest {
             path: file_path.clone(),
             language: Some("rust".to_string()),
+            repo: None,
         };
 
         let count = indexer.index_file(&request).await.unwrap();
@@ -282,6 +844,75 @@ mod tests {
         assert!(!chunks.is_empty());
     }
 
+    #[tokio::test]
+    async fn test_index_file_without_content() {
+        let db = setup_test_db();
+        let indexer = Indexer::new(db.clone(), None).with_store_content(false);
+
+        let tmp = TempDir::new().unwrap();
+        let file_path = tmp.path().join("test.rs");
+        fs::write(&file_path, "fn main() {\n    println!(\"hello\");\n}").unwrap();
+
+        let request = IndexRequest {
+            path: file_path.clone(),
+            language: Some("rust".to_string()),
+            repo: None,
+        };
+        assert!(indexer.index_file(&request).await.unwrap() > 0);
+
+        let chunks = db
+            .with_conn(|conn| {
+                crate::storage::get_chunks_by_file(conn, &file_path.to_string_lossy())
+            })
+            .unwrap();
+        assert!(!chunks.is_empty());
+        assert!(chunks.iter().all(|c| c.content.is_empty()));
+        assert_eq!((chunks[0].start_line, chunks[0].end_line), (1, 3));
+    }
+
+    #[tokio::test]
+    async fn test_index_file_dedupes_shared_chunks() {
+        let db = setup_test_db();
+        let indexer = Indexer::new(db.clone(), None).with_dedupe(true);
+
+        let tmp = TempDir::new().unwrap();
+        let header = "// Copyright Example Corp. All rights reserved.\nfn generated() {}\n";
+        for name in ["a.rs", "b.rs"] {
+            let path = tmp.path().join(name);
+            fs::write(&path, header).unwrap();
+            let request = IndexRequest {
+                path,
+                language: Some("rust".to_string()),
+                repo: None,
+            };
+            assert!(indexer.index_file(&request).await.unwrap() > 0);
+        }
+
+        let a = tmp.path().join("a.rs").to_string_lossy().to_string();
+        let duplicates = db
+            .with_conn(|conn| {
+                let ids: Vec<i64> = crate::storage::get_chunks_by_file(conn, &a)?
+                    .iter()
+                    .filter_map(|c| c.id)
+                    .collect();
+                assert!(crate::storage::count_duplicate_chunks(conn)? > 0);
+                crate::storage::get_chunk_duplicates(conn, &ids)
+            })
+            .unwrap();
+        assert!(duplicates
+            .values()
+            .flatten()
+            .all(|d| d.file_path.ends_with("b.rs")));
+
+        // Deleting the first copy leaves the second standing on its own
+        indexer.delete_file(Path::new(&a)).unwrap();
+        assert_eq!(
+            db.with_conn(crate::storage::count_duplicate_chunks)
+                .unwrap(),
+            0
+        );
+    }
+
     #[tokio::test]
     async fn test_reindex_unchanged() {
         let db = setup_test_db();
@@ -294,6 +925,7 @@ mod tests {
         let request = IndexRequest {
             path: file_path.clone(),
             language: Some("rust".to_string()),
+            repo: None,
         };
 
         // First index
@@ -317,12 +949,14 @@ mod tests {
         let request = IndexRequest {
             path: file_path.clone(),
             language: Some("rust".to_string()),
+            repo: None,
         };
 
         indexer.index_file(&request).await.unwrap();
 
         let deleted = indexer.delete_file(&file_path).unwrap();
         assert!(deleted > 0);
+        assert_eq!(indexer.stats().files_deleted, 1);
 
         // Verify empty
         let chunks = db
@@ -334,6 +968,124 @@ mod tests {
         assert!(chunks.is_empty());
     }
 
+    #[tokio::test]
+    async fn test_resolve_renames_moves_unchanged_file() {
+        let db = setup_test_db();
+        let indexer = Indexer::new(db.clone(), None);
+
+        let tmp = TempDir::new().unwrap();
+        let old_path = tmp.path().join("old.rs");
+        let new_path = tmp.path().join("new.rs");
+        fs::write(&old_path, "fn main() {}").unwrap();
+        indexer
+            .index_file(&IndexRequest {
+                path: old_path.clone(),
+                language: Some("rust".to_string()),
+                repo: None,
+            })
+            .await
+            .unwrap();
+        fs::rename(&old_path, &new_path).unwrap();
+
+        // Platform without rename info: reported as delete + modify
+        let mut batch = EventBatch::new();
+        batch.deleted.push(old_path.clone());
+        batch.modified.push(new_path.clone());
+
+        assert_eq!(indexer.resolve_renames(&mut batch).await, 1);
+        assert!(batch.is_empty());
+
+        let (old_chunks, new_chunks) = db
+            .with_conn(|conn| {
+                Ok((
+                    crate::storage::get_chunks_by_file(conn, &old_path.to_string_lossy())?,
+                    crate::storage::get_chunks_by_file(conn, &new_path.to_string_lossy())?,
+                ))
+            })
+            .unwrap();
+        assert!(old_chunks.is_empty());
+        assert!(!new_chunks.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_renames_falls_back_when_content_changed() {
+        let db = setup_test_db();
+        let indexer = Indexer::new(db, None);
+
+        let tmp = TempDir::new().unwrap();
+        let old_path = tmp.path().join("old.rs");
+        let new_path = tmp.path().join("new.rs");
+        fs::write(&old_path, "fn main() {}").unwrap();
+        indexer
+            .index_file(&IndexRequest {
+                path: old_path.clone(),
+                language: Some("rust".to_string()),
+                repo: None,
+            })
+            .await
+            .unwrap();
+        fs::remove_file(&old_path).unwrap();
+        fs::write(&new_path, "fn other() {}").unwrap();
+
+        let mut batch = EventBatch::new();
+        batch.add(crate::watcher::FileEvent::Renamed {
+            from: old_path.clone(),
+            to: new_path.clone(),
+        });
+
+        assert_eq!(indexer.resolve_renames(&mut batch).await, 0);
+        assert!(batch.renamed.is_empty());
+        assert_eq!(batch.deleted, vec![old_path]);
+        assert_eq!(batch.modified, vec![new_path]);
+    }
+
+    #[test]
+    fn test_detect_repo() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("payments");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::create_dir_all(repo.join("src/api")).unwrap();
+        let file = repo.join("src/api/charge.rs");
+        fs::write(&file, "fn charge() {}").unwrap();
+
+        assert_eq!(detect_repo(&file), Some("payments".to_string()));
+        assert_eq!(detect_repo(&repo), Some("payments".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_index_file_sets_repo() {
+        let db = setup_test_db();
+        let indexer = Indexer::new(db.clone(), None);
+
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("payments");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        let file_path = repo.join("lib.rs");
+        fs::write(&file_path, "fn charge() {}").unwrap();
+        let path_str = file_path.to_string_lossy().to_string();
+
+        let mut request = IndexRequest {
+            path: file_path.clone(),
+            language: Some("rust".to_string()),
+            repo: None,
+        };
+        assert!(indexer.index_file(&request).await.unwrap() > 0);
+        let chunks = db
+            .with_conn(|conn| crate::storage::get_chunks_by_file(conn, &path_str))
+            .unwrap();
+        assert!(chunks.iter().all(|c| c.repo.as_deref() == Some("payments")));
+
+        // An explicit repo relabels the file even when its content is unchanged
+        request.repo = Some("payments-service".to_string());
+        assert_eq!(indexer.index_file(&request).await.unwrap(), 0);
+        let chunks = db
+            .with_conn(|conn| crate::storage::get_chunks_by_file(conn, &path_str))
+            .unwrap();
+        assert!(chunks
+            .iter()
+            .all(|c| c.repo.as_deref() == Some("payments-service")));
+    }
+
     #[test]
     fn test_compute_hash() {
         let hash1 = compute_hash("hello");
@@ -344,4 +1096,118 @@ mod tests {
         assert_ne!(hash1, hash3);
         assert_eq!(hash1.len(), 64); // blake3 hex is 64 chars
     }
+
+    #[tokio::test]
+    async fn test_run_exits_when_channels_close() {
+        let indexer = Arc::new(Indexer::new(setup_test_db(), None));
+        let (index_tx, index_rx) = index_queue(10, OverflowPolicy::Block);
+        let (delete_tx, delete_rx) = mpsc::channel(10);
+        drop(index_tx);
+        drop(delete_tx);
+
+        indexer
+            .run(index_rx, delete_rx, CancellationToken::new())
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_run_stops_on_cancel_and_rejects_new_requests() {
+        let indexer = Arc::new(Indexer::new(setup_test_db(), None));
+        let (index_tx, index_rx) = index_queue(10, OverflowPolicy::Block);
+        let (_delete_tx, delete_rx) = mpsc::channel(10);
+        let shutdown = CancellationToken::new();
+        shutdown.cancel();
+
+        indexer.run(index_rx, delete_rx, shutdown).await;
+
+        let request = IndexRequest {
+            path: std::path::PathBuf::from("/tmp/late.rs"),
+            language: None,
+            repo: None,
+        };
+        assert!(index_tx.send(request).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_index_file_records_git_revision() {
+        let db = setup_test_db();
+        let indexer = Indexer::new(db.clone(), None);
+
+        let tmp = TempDir::new().unwrap();
+        let git_dir = tmp.path().join(".git");
+        fs::create_dir_all(git_dir.join("refs/heads")).unwrap();
+        fs::write(git_dir.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        let first = "a".repeat(40);
+        fs::write(git_dir.join("refs/heads/main"), &first).unwrap();
+        let file_path = tmp.path().join("lib.rs");
+        fs::write(&file_path, "fn charge() {}").unwrap();
+        let path_str = file_path.to_string_lossy().to_string();
+
+        let request = IndexRequest {
+            path: file_path.clone(),
+            language: Some("rust".to_string()),
+            repo: None,
+        };
+        assert!(indexer.index_file(&request).await.unwrap() > 0);
+        let chunks = db
+            .with_conn(|conn| crate::storage::get_chunks_by_file(conn, &path_str))
+            .unwrap();
+        assert!(chunks
+            .iter()
+            .all(|c| c.git_commit.as_deref() == Some(first.as_str())
+                && c.git_branch.as_deref() == Some("main")));
+
+        // A new commit moves unchanged files along without re-chunking
+        let second = "b".repeat(40);
+        fs::write(git_dir.join("refs/heads/main"), &second).unwrap();
+        assert_eq!(indexer.index_file(&request).await.unwrap(), 0);
+        let chunks = db
+            .with_conn(|conn| crate::storage::get_chunks_by_file(conn, &path_str))
+            .unwrap();
+        assert!(chunks
+            .iter()
+            .all(|c| c.git_commit.as_deref() == Some(second.as_str())));
+    }
+
+    #[tokio::test]
+    async fn test_flush_stats_adds_deltas() {
+        let db = setup_test_db();
+        let indexer = Indexer::new(db.clone(), None);
+
+        let tmp = TempDir::new().unwrap();
+        let file_path = tmp.path().join("test.rs");
+        fs::write(&file_path, "fn main() {}").unwrap();
+        let request = IndexRequest {
+            path: file_path.clone(),
+            language: Some("rust".to_string()),
+            repo: None,
+        };
+
+        let chunks = indexer.index_file(&request).await.unwrap();
+        // Unchanged files aren't counted
+        indexer.index_file(&request).await.unwrap();
+        let stats = indexer.stats();
+        assert_eq!(stats.files_indexed, 1);
+        assert_eq!(stats.chunks_created, chunks as u64);
+        assert!(stats.last_indexed_at > 0);
+
+        indexer.flush_stats().unwrap();
+        // A second flush with no new activity adds nothing
+        indexer.flush_stats().unwrap();
+        let persisted = db.with_conn(crate::storage::get_stats).unwrap();
+        assert_eq!(persisted[STAT_FILES_INDEXED], 1);
+        assert_eq!(
+            persisted[STAT_CHUNKS_CREATED],
+            i64::try_from(chunks).unwrap()
+        );
+        assert_eq!(persisted[STAT_LAST_INDEXED_AT], stats.last_indexed_at);
+
+        // A new indexer (as after a restart) adds to the lifetime totals
+        let restarted = Indexer::new(db.clone(), None);
+        fs::write(&file_path, "fn main() { changed(); }").unwrap();
+        restarted.index_file(&request).await.unwrap();
+        restarted.flush_stats().unwrap();
+        let persisted = db.with_conn(crate::storage::get_stats).unwrap();
+        assert_eq!(persisted[STAT_FILES_INDEXED], 2);
+    }
 }
@@ -4,13 +4,19 @@
 //! - Directory watching using notify-rs
 //! - Gitignore-aware file filtering
 //! - Incremental indexing of changed files
+//! - Git revision detection for indexed chunks
 //! - Directory scanning for initial indexing
+//! - Startup reconciliation of tracked files
+//! - A bounded index request queue with overflow handling
 
 mod chunker;
 mod events;
 mod filter;
+mod git;
 mod handler;
 mod indexer;
+mod queue;
+mod reconcile;
 mod scanner;
 #[allow(clippy::module_inception)]
 mod watcher;
@@ -18,11 +24,23 @@ mod watcher;
 pub use chunker::{Chunker, ChunkerConfig, CodeChunk};
 pub use events::EventBatch;
 pub use events::FileEvent;
-pub use filter::FileFilter;
+pub use filter::{FileFilter, FileTypes};
+pub use git::{detect_git_revision, GitRevision};
 pub use handler::{EventHandler, HandlerConfig, IndexRequest, WatcherStats, WatcherStatsSnapshot};
-pub use indexer::Indexer;
-pub use scanner::{scan_directory, scan_directory_async, ScanStats, ScanStatsSnapshot};
-pub use watcher::{FileWatcher, WatcherConfig};
+pub use indexer::{detect_repo, Indexer, IndexerStats, IndexerStatsSnapshot, STATS_FLUSH_INTERVAL};
+pub use queue::{
+    index_queue, IndexQueueMonitor, IndexQueueSnapshot, IndexReceiver, IndexSender, OverflowPolicy,
+    SendError,
+};
+pub use reconcile::{
+    reconcile_from_db, ReconcileOptions, ReconcileState, ReconcileStatus, ReconcileStatusSnapshot,
+    DEFAULT_RECONCILE_BATCH_SIZE, DEFAULT_RECONCILE_QUEUE_CAPACITY,
+};
+pub use scanner::{
+    build_walker, is_default_ignored, is_skipped_dir_name, scan_directory, scan_directory_async,
+    ScanStats, ScanStatsSnapshot, NELLIE_IGNORE_FILE, SKIP_DIRS,
+};
+pub use watcher::{FileWatcher, WatcherConfig, DEFAULT_DEBOUNCE_MS};
 
 /// Initialize watcher module.
 pub fn init() {
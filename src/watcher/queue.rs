@@ -0,0 +1,465 @@
+//! Bounded queue of index requests with configurable overflow handling.
+//!
+//! Scans, reconciliation and the watcher all feed files to the indexer
+//! through this queue. When it is full, senders either wait for space
+//! ([`OverflowPolicy::Block`]) or evict the oldest queued request
+//! ([`OverflowPolicy::DropOldest`]). A dropped request leaves the file's
+//! stored state untouched, so the next change to the file or the next
+//! reconciliation picks it up again.
+//!
+//! Unlike a plain channel the queue counts what happens at capacity, so
+//! `get_status` and `/metrics` can show when indexing falls behind.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use tokio::sync::Notify;
+
+use super::handler::IndexRequest;
+
+/// What a sender does when the index queue is full.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Wait for the indexer to make room. Nothing is lost, but producers
+    /// slow to the indexer's pace.
+    #[default]
+    Block,
+    /// Evict the oldest queued request to make room, so producers never
+    /// wait. Evicted files are indexed on their next change or the next
+    /// reconciliation.
+    DropOldest,
+}
+
+impl OverflowPolicy {
+    /// Convert policy to string representation.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Block => "block",
+            Self::DropOldest => "drop_oldest",
+        }
+    }
+}
+
+impl std::str::FromStr for OverflowPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "block" => Ok(Self::Block),
+            "drop_oldest" => Ok(Self::DropOldest),
+            _ => Err(format!(
+                "unknown overflow policy '{s}' (expected 'block' or 'drop_oldest')"
+            )),
+        }
+    }
+}
+
+/// Error returned when sending to a closed queue, holding the request.
+#[derive(Debug)]
+pub struct SendError(pub IndexRequest);
+
+impl std::fmt::Display for SendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "index queue closed")
+    }
+}
+
+impl std::error::Error for SendError {}
+
+/// State shared by the sending and receiving halves.
+struct Shared {
+    queue: Mutex<VecDeque<IndexRequest>>,
+    capacity: usize,
+    policy: OverflowPolicy,
+    senders: AtomicUsize,
+    closed: AtomicBool,
+    /// Signalled when a request is queued or the last sender goes away
+    item_ready: Notify,
+    /// Signalled when a request is taken or the queue closes
+    space_ready: Notify,
+    high_water: AtomicUsize,
+    blocked_sends: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl Shared {
+    fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+        self.item_ready.notify_waiters();
+        self.space_ready.notify_waiters();
+    }
+
+    fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
+
+    /// Queue `request` if there is room or the policy allows evicting.
+    ///
+    /// Gives the request back when the sender has to wait.
+    fn try_push(&self, request: IndexRequest) -> Result<(), TryPush> {
+        let mut queue = self.queue.lock();
+        if self.is_closed() {
+            return Err(TryPush::Closed(request));
+        }
+        if queue.len() >= self.capacity {
+            if self.policy == OverflowPolicy::Block {
+                return Err(TryPush::Full(request));
+            }
+            if let Some(dropped) = queue.pop_front() {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                crate::server::INDEX_QUEUE_DROPPED_TOTAL.inc();
+                tracing::debug!(
+                    path = %dropped.path.display(),
+                    "Index queue full, dropped oldest request"
+                );
+            }
+        }
+        queue.push_back(request);
+        self.high_water.fetch_max(queue.len(), Ordering::Relaxed);
+        drop(queue);
+        self.item_ready.notify_one();
+        Ok(())
+    }
+
+    fn pop(&self) -> Option<IndexRequest> {
+        let request = self.queue.lock().pop_front();
+        if request.is_some() {
+            self.space_ready.notify_one();
+        }
+        request
+    }
+
+    fn snapshot(&self) -> IndexQueueSnapshot {
+        let depth = self.queue.lock().len();
+        IndexQueueSnapshot {
+            depth,
+            capacity: self.capacity,
+            policy: self.policy.as_str(),
+            saturated: depth >= self.capacity,
+            high_water: self.high_water.load(Ordering::Relaxed),
+            blocked_sends: self.blocked_sends.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            closed: self.is_closed(),
+        }
+    }
+}
+
+enum TryPush {
+    Full(IndexRequest),
+    Closed(IndexRequest),
+}
+
+/// Create a bounded index queue.
+///
+/// A capacity of 0 is treated as 1.
+#[must_use]
+pub fn index_queue(capacity: usize, policy: OverflowPolicy) -> (IndexSender, IndexReceiver) {
+    let shared = Arc::new(Shared {
+        queue: Mutex::new(VecDeque::new()),
+        capacity: capacity.max(1),
+        policy,
+        senders: AtomicUsize::new(1),
+        closed: AtomicBool::new(false),
+        item_ready: Notify::new(),
+        space_ready: Notify::new(),
+        high_water: AtomicUsize::new(0),
+        blocked_sends: AtomicU64::new(0),
+        dropped: AtomicU64::new(0),
+    });
+    (
+        IndexSender {
+            shared: Arc::clone(&shared),
+        },
+        IndexReceiver { shared },
+    )
+}
+
+/// Sending half of the index queue.
+///
+/// The queue closes once every sender is dropped and the remaining
+/// requests have been received.
+pub struct IndexSender {
+    shared: Arc<Shared>,
+}
+
+impl IndexSender {
+    /// Queue a request, applying the overflow policy when full.
+    ///
+    /// # Errors
+    ///
+    /// Returns the request if the receiver has closed the queue.
+    pub async fn send(&self, mut request: IndexRequest) -> Result<(), SendError> {
+        let mut blocked = false;
+        loop {
+            // Register for wakeups before checking, so a receive or close
+            // between the check and the wait isn't missed
+            let notified = self.shared.space_ready.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            match self.shared.try_push(request) {
+                Ok(()) => return Ok(()),
+                Err(TryPush::Closed(request)) => return Err(SendError(request)),
+                Err(TryPush::Full(returned)) => request = returned,
+            }
+            if !blocked {
+                blocked = true;
+                self.shared.blocked_sends.fetch_add(1, Ordering::Relaxed);
+                crate::server::INDEX_QUEUE_BLOCKED_SENDS_TOTAL.inc();
+            }
+            notified.await;
+        }
+    }
+
+    /// Queue a request from synchronous code, waiting for space if needed.
+    ///
+    /// Unlike `tokio::sync::mpsc::Sender::blocking_send` this may be called
+    /// from within a runtime, though it blocks the calling thread.
+    ///
+    /// # Errors
+    ///
+    /// Returns the request if the receiver has closed the queue.
+    pub fn blocking_send(&self, request: IndexRequest) -> Result<(), SendError> {
+        // `send` only waits on a `Notify`, which is woken by the receiver
+        // from any thread and needs no runtime to make progress, so a
+        // minimal executor on this thread is enough. Callers are scans on
+        // `spawn_blocking` threads, where parking the thread is fine.
+        futures::executor::block_on(self.send(request))
+    }
+
+    /// Whether everything sent so far has been received.
+    ///
+    /// A closed queue counts as drained.
+    #[must_use]
+    pub fn is_drained(&self) -> bool {
+        self.shared.is_closed() || self.shared.queue.lock().is_empty()
+    }
+
+    /// Whether the receiver has closed the queue.
+    #[must_use]
+    pub fn is_closed(&self) -> bool {
+        self.shared.is_closed()
+    }
+
+    /// Handle for reporting on the queue without keeping it open.
+    #[must_use]
+    pub fn monitor(&self) -> IndexQueueMonitor {
+        IndexQueueMonitor {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl Clone for IndexSender {
+    fn clone(&self) -> Self {
+        self.shared.senders.fetch_add(1, Ordering::Relaxed);
+        Self {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl Drop for IndexSender {
+    fn drop(&mut self) {
+        if self.shared.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.shared.item_ready.notify_waiters();
+        }
+    }
+}
+
+/// Receiving half of the index queue. Dropping it closes the queue.
+pub struct IndexReceiver {
+    shared: Arc<Shared>,
+}
+
+impl IndexReceiver {
+    /// Receive the next request.
+    ///
+    /// Returns `None` once the queue is closed, or every sender is gone and
+    /// the queue is empty.
+    pub async fn recv(&self) -> Option<IndexRequest> {
+        loop {
+            let notified = self.shared.item_ready.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            if self.shared.is_closed() {
+                return None;
+            }
+            if let Some(request) = self.shared.pop() {
+                return Some(request);
+            }
+            if self.shared.senders.load(Ordering::Acquire) == 0 {
+                return None;
+            }
+            notified.await;
+        }
+    }
+
+    /// Take the next request without waiting, even after closing.
+    pub fn try_recv(&self) -> Option<IndexRequest> {
+        self.shared.pop()
+    }
+
+    /// Stop accepting requests. Queued ones can still be taken with
+    /// `try_recv`; waiting senders get their request back.
+    pub fn close(&self) {
+        self.shared.close();
+    }
+
+    /// Handle for reporting on the queue.
+    #[must_use]
+    pub fn monitor(&self) -> IndexQueueMonitor {
+        IndexQueueMonitor {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl Drop for IndexReceiver {
+    fn drop(&mut self) {
+        self.shared.close();
+    }
+}
+
+/// Read-only view of an index queue, as reported by `get_status`.
+#[derive(Clone)]
+pub struct IndexQueueMonitor {
+    shared: Arc<Shared>,
+}
+
+impl IndexQueueMonitor {
+    /// Current depth and overflow counters.
+    #[must_use]
+    pub fn snapshot(&self) -> IndexQueueSnapshot {
+        self.shared.snapshot()
+    }
+}
+
+impl std::fmt::Debug for IndexQueueMonitor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("IndexQueueMonitor")
+            .field(&self.snapshot())
+            .finish()
+    }
+}
+
+/// Snapshot of the index queue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct IndexQueueSnapshot {
+    /// Requests waiting for the indexer
+    pub depth: usize,
+    /// Most requests the queue holds before the overflow policy applies
+    pub capacity: usize,
+    /// Overflow policy, `block` or `drop_oldest`
+    pub policy: &'static str,
+    /// The queue is full, so senders are waiting or evicting
+    pub saturated: bool,
+    /// Deepest the queue has been
+    pub high_water: usize,
+    /// Sends that had to wait for space (`block` policy)
+    pub blocked_sends: u64,
+    /// Requests evicted to make room (`drop_oldest` policy)
+    pub dropped: u64,
+    /// The indexer has stopped accepting requests
+    pub closed: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    fn request(name: &str) -> IndexRequest {
+        IndexRequest {
+            path: PathBuf::from(name),
+            language: None,
+            repo: None,
+        }
+    }
+
+    #[test]
+    fn test_overflow_policy_parse() {
+        assert_eq!("block".parse(), Ok(OverflowPolicy::Block));
+        assert_eq!("DROP_OLDEST".parse(), Ok(OverflowPolicy::DropOldest));
+        assert!("drop_newest".parse::<OverflowPolicy>().is_err());
+        assert_eq!(OverflowPolicy::DropOldest.as_str(), "drop_oldest");
+    }
+
+    #[tokio::test]
+    async fn test_drop_oldest_evicts_when_full() {
+        let (tx, rx) = index_queue(2, OverflowPolicy::DropOldest);
+        for name in ["a.rs", "b.rs", "c.rs"] {
+            tx.send(request(name)).await.unwrap();
+        }
+
+        let snapshot = tx.monitor().snapshot();
+        assert_eq!(snapshot.depth, 2);
+        assert!(snapshot.saturated);
+        assert_eq!(snapshot.dropped, 1);
+        assert_eq!(snapshot.blocked_sends, 0);
+
+        drop(tx);
+        let mut paths = vec![];
+        while let Some(request) = rx.recv().await {
+            paths.push(request.path);
+        }
+        assert_eq!(paths, vec![PathBuf::from("b.rs"), PathBuf::from("c.rs")]);
+    }
+
+    #[tokio::test]
+    async fn test_block_waits_for_space() {
+        let (tx, rx) = index_queue(1, OverflowPolicy::Block);
+        tx.send(request("a.rs")).await.unwrap();
+
+        let sender = tx.clone();
+        let blocked = tokio::spawn(async move { sender.send(request("b.rs")).await });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!blocked.is_finished());
+        assert_eq!(tx.monitor().snapshot().blocked_sends, 1);
+        assert!(!tx.is_drained());
+
+        assert_eq!(rx.recv().await.unwrap().path, PathBuf::from("a.rs"));
+        blocked.await.unwrap().unwrap();
+        assert_eq!(rx.recv().await.unwrap().path, PathBuf::from("b.rs"));
+        assert!(tx.is_drained());
+        assert_eq!(tx.monitor().snapshot().dropped, 0);
+    }
+
+    #[tokio::test]
+    async fn test_close_releases_blocked_senders() {
+        let (tx, rx) = index_queue(1, OverflowPolicy::Block);
+        tx.send(request("a.rs")).await.unwrap();
+
+        let sender = tx.clone();
+        let blocked = tokio::spawn(async move { sender.send(request("b.rs")).await });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        rx.close();
+
+        let returned = blocked.await.unwrap().unwrap_err();
+        assert_eq!(returned.0.path, PathBuf::from("b.rs"));
+        assert!(tx.is_closed());
+        assert!(tx.monitor().snapshot().closed);
+        // Requests queued before the close can still be drained
+        assert_eq!(rx.try_recv().unwrap().path, PathBuf::from("a.rs"));
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[test]
+    fn test_blocking_send_and_disconnect() {
+        let (tx, rx) = index_queue(4, OverflowPolicy::Block);
+        tx.blocking_send(request("a.rs")).unwrap();
+        drop(tx);
+
+        futures::executor::block_on(async {
+            assert_eq!(rx.recv().await.unwrap().path, PathBuf::from("a.rs"));
+            assert!(rx.recv().await.is_none());
+        });
+        assert_eq!(rx.monitor().snapshot().high_water, 1);
+    }
+}
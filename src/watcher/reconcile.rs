@@ -0,0 +1,618 @@
+//! Startup reconciliation of the index against the filesystem.
+//!
+//! Instead of recursively walking watched directories (which hangs on slow
+//! NFS mounts), reconciliation iterates the `file_state` table and checks
+//! each known file's metadata:
+//! - If the file is gone: delete it from the index
+//! - If mtime or size changed: queue it for re-indexing
+//! - If unchanged: skip (fast path)
+//!
+//! When mtime changed but size did not, or `verify_hashes` is set, the file's
+//! content hash is compared against the stored one before re-queueing. New
+//! files are discovered by the watcher, not by reconciliation.
+//!
+//! Files are checked in path order, in batches. After each batch the queued
+//! work is allowed to drain and the last checked path is saved, so a restart
+//! partway through resumes where it stopped instead of starting over.
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use super::filter::FileTypes;
+use super::handler::IndexRequest;
+use super::queue::IndexSender;
+use crate::storage::{
+    delete_meta, get_meta, list_file_paths, needs_reindex_by_hash, set_meta, Database,
+};
+use crate::Result;
+
+/// Default number of files checked per batch before yielding.
+pub const DEFAULT_RECONCILE_BATCH_SIZE: usize = 500;
+
+/// Default capacity of the queue feeding reconciled files to the indexer.
+pub const DEFAULT_RECONCILE_QUEUE_CAPACITY: usize = 1000;
+
+/// Metadata key holding the last path checked by an unfinished reconciliation.
+const RECONCILE_CURSOR_KEY: &str = "reconcile_cursor";
+
+/// How often to check whether queued files have been picked up.
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How many files between progress log lines.
+const PROGRESS_LOG_INTERVAL: u64 = 10_000;
+
+/// Reconciliation settings.
+#[derive(Debug, Clone)]
+pub struct ReconcileOptions {
+    /// Compare content hashes even when mtime and size match.
+    pub verify_hashes: bool,
+    /// Files checked per batch; progress is saved after each batch.
+    pub batch_size: usize,
+    /// Languages changed files are tagged with when re-queued.
+    pub file_types: FileTypes,
+}
+
+impl Default for ReconcileOptions {
+    fn default() -> Self {
+        Self {
+            verify_hashes: false,
+            batch_size: DEFAULT_RECONCILE_BATCH_SIZE,
+            file_types: FileTypes::default(),
+        }
+    }
+}
+
+/// Where a reconciliation run is.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReconcileState {
+    /// No reconciliation has run in this process.
+    #[default]
+    Idle,
+    /// Checking files.
+    Running,
+    /// Every tracked file was checked.
+    Complete,
+    /// Stopped early by shutdown or a closed queue; the next start resumes.
+    Interrupted,
+    /// Failed; the next start resumes from the last saved batch.
+    Failed,
+}
+
+/// Live reconciliation progress, reported by `get_status`.
+#[derive(Debug, Default)]
+pub struct ReconcileStatus {
+    state: Mutex<ReconcileState>,
+    resumed: std::sync::atomic::AtomicBool,
+    pub total: AtomicU64,
+    pub files_checked: AtomicU64,
+    pub unchanged: AtomicU64,
+    pub requeued: AtomicU64,
+    pub deleted: AtomicU64,
+    pub errors: AtomicU64,
+}
+
+impl ReconcileStatus {
+    /// Create a new status tracker.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Current state of the run.
+    #[must_use]
+    pub fn state(&self) -> ReconcileState {
+        *self.state.lock()
+    }
+
+    fn set_state(&self, state: ReconcileState) {
+        *self.state.lock() = state;
+    }
+
+    /// Get a snapshot of current progress.
+    #[must_use]
+    pub fn snapshot(&self) -> ReconcileStatusSnapshot {
+        ReconcileStatusSnapshot {
+            state: self.state(),
+            resumed: self.resumed.load(Ordering::Relaxed),
+            total: self.total.load(Ordering::Relaxed),
+            files_checked: self.files_checked.load(Ordering::Relaxed),
+            unchanged: self.unchanged.load(Ordering::Relaxed),
+            requeued: self.requeued.load(Ordering::Relaxed),
+            deleted: self.deleted.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Snapshot of reconciliation progress.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ReconcileStatusSnapshot {
+    pub state: ReconcileState,
+    /// Whether this run continued an earlier, unfinished one.
+    pub resumed: bool,
+    /// Files this run has to check (excludes those checked before a resume).
+    pub total: u64,
+    pub files_checked: u64,
+    pub unchanged: u64,
+    pub requeued: u64,
+    pub deleted: u64,
+    pub errors: u64,
+}
+
+/// Outcome of checking one tracked file.
+enum Check {
+    Unchanged,
+    Changed,
+    Missing,
+    Error,
+}
+
+/// Reconcile index state from the database.
+///
+/// Changed files are sent to `index_tx` and missing ones to `delete_tx`.
+/// Full queues are handled by the index queue's overflow policy: with
+/// `block` a slow indexer slows reconciliation, with `drop_oldest` requests
+/// are evicted and picked up by a later reconciliation. Stops early,
+/// keeping its saved progress, when `shutdown` fires.
+///
+/// # Errors
+///
+/// Returns an error if tracked files can't be listed, progress can't be
+/// saved, or either queue closes before shutdown (the indexer has stopped).
+pub async fn reconcile_from_db(
+    db: &Database,
+    index_tx: &IndexSender,
+    delete_tx: &mpsc::Sender<PathBuf>,
+    options: ReconcileOptions,
+    status: &ReconcileStatus,
+    shutdown: &CancellationToken,
+) -> Result<()> {
+    status.set_state(ReconcileState::Running);
+    let result = reconcile(db, index_tx, delete_tx, options, status, shutdown).await;
+
+    let snapshot = status.snapshot();
+    let state = match &result {
+        Ok(ReconcileState::Complete) => {
+            tracing::info!(
+                total = snapshot.total,
+                unchanged = snapshot.unchanged,
+                requeued = snapshot.requeued,
+                deleted = snapshot.deleted,
+                errors = snapshot.errors,
+                "Reconciliation complete"
+            );
+            ReconcileState::Complete
+        }
+        Ok(state) => {
+            tracing::warn!(
+                checked = snapshot.files_checked,
+                total = snapshot.total,
+                "Reconciliation interrupted; it will resume on next start"
+            );
+            *state
+        }
+        Err(e) => {
+            tracing::error!(
+                error = %e,
+                checked = snapshot.files_checked,
+                "Reconciliation failed"
+            );
+            ReconcileState::Failed
+        }
+    };
+    status.set_state(state);
+
+    result.map(|_| ())
+}
+
+async fn reconcile(
+    db: &Database,
+    index_tx: &IndexSender,
+    delete_tx: &mpsc::Sender<PathBuf>,
+    options: ReconcileOptions,
+    status: &ReconcileStatus,
+    shutdown: &CancellationToken,
+) -> Result<ReconcileState> {
+    let (paths, cursor) = db.with_conn(|conn| {
+        Ok((
+            list_file_paths(conn)?,
+            get_meta(conn, RECONCILE_CURSOR_KEY)?,
+        ))
+    })?;
+
+    // Paths are listed in order, so everything up to the cursor was checked
+    let start = cursor
+        .as_deref()
+        .map_or(0, |cursor| paths.partition_point(|p| p.as_str() <= cursor));
+    let paths = paths.into_iter().skip(start).collect::<Vec<_>>();
+
+    status.resumed.store(cursor.is_some(), Ordering::Relaxed);
+    status.total.store(paths.len() as u64, Ordering::Relaxed);
+    tracing::info!(
+        verify_hashes = options.verify_hashes,
+        tracked_files = paths.len(),
+        resumed_after = start,
+        "Starting DB-first reconciliation (no filesystem walk)"
+    );
+
+    for batch in paths.chunks(options.batch_size.max(1)) {
+        if shutdown.is_cancelled() {
+            return Ok(ReconcileState::Interrupted);
+        }
+
+        let checks = check_batch(db, batch.to_vec(), options.verify_hashes).await?;
+
+        for (path, check) in checks {
+            let counter = match check {
+                Check::Unchanged => &status.unchanged,
+                Check::Changed => {
+                    let language = options.file_types.detect_language(&path).map(String::from);
+                    let request = IndexRequest {
+                        path,
+                        language,
+                        repo: None,
+                    };
+                    let sent = tokio::select! {
+                        () = shutdown.cancelled() => return Ok(ReconcileState::Interrupted),
+                        sent = index_tx.send(request) => sent.is_ok(),
+                    };
+                    if !sent {
+                        return queue_closed("index", shutdown);
+                    }
+                    &status.requeued
+                }
+                Check::Missing => {
+                    let sent = tokio::select! {
+                        () = shutdown.cancelled() => return Ok(ReconcileState::Interrupted),
+                        sent = delete_tx.send(path) => sent.is_ok(),
+                    };
+                    if !sent {
+                        return queue_closed("delete", shutdown);
+                    }
+                    &status.deleted
+                }
+                Check::Error => &status.errors,
+            };
+            counter.fetch_add(1, Ordering::Relaxed);
+        }
+
+        // Only save progress once the batch's work has left the queues, so
+        // a restart can't skip files that were queued but never indexed
+        let index_drained = || index_tx.is_drained();
+        let delete_drained =
+            || delete_tx.capacity() == delete_tx.max_capacity() || delete_tx.is_closed();
+        if !wait_for_drain(index_drained, shutdown).await
+            || !wait_for_drain(delete_drained, shutdown).await
+        {
+            return Ok(ReconcileState::Interrupted);
+        }
+        if let Some(last) = batch.last() {
+            db.with_conn(|conn| set_meta(conn, RECONCILE_CURSOR_KEY, last))?;
+        }
+
+        let before = status
+            .files_checked
+            .fetch_add(batch.len() as u64, Ordering::Relaxed);
+        let checked = before + batch.len() as u64;
+        if checked / PROGRESS_LOG_INTERVAL > before / PROGRESS_LOG_INTERVAL {
+            let snapshot = status.snapshot();
+            tracing::info!(
+                progress = checked,
+                total = snapshot.total,
+                unchanged = snapshot.unchanged,
+                requeued = snapshot.requeued,
+                deleted = snapshot.deleted,
+                errors = snapshot.errors,
+                "Reconciliation progress..."
+            );
+        }
+
+        tokio::task::yield_now().await;
+    }
+
+    db.with_conn(|conn| delete_meta(conn, RECONCILE_CURSOR_KEY))?;
+    Ok(ReconcileState::Complete)
+}
+
+/// Stat a batch of tracked files on a blocking thread.
+async fn check_batch(
+    db: &Database,
+    paths: Vec<String>,
+    verify_hashes: bool,
+) -> Result<Vec<(PathBuf, Check)>> {
+    let db = db.clone();
+    tokio::task::spawn_blocking(move || {
+        paths
+            .into_iter()
+            .map(|path_str| {
+                let check = check_file(&db, &path_str, verify_hashes);
+                (PathBuf::from(path_str), check)
+            })
+            .collect()
+    })
+    .await
+    .map_err(|e| crate::Error::internal(format!("Reconciliation task failed: {e}")))
+}
+
+fn check_file(db: &Database, path_str: &str, verify_hashes: bool) -> Check {
+    match std::fs::metadata(path_str) {
+        Ok(metadata) => {
+            #[allow(clippy::cast_possible_wrap)]
+            let mtime = metadata
+                .modified()
+                .map(|t| {
+                    t.duration_since(std::time::UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs() as i64
+                })
+                .unwrap_or(0);
+            #[allow(clippy::cast_possible_wrap)]
+            let size = metadata.len() as i64;
+
+            let needs_index =
+                needs_reindex_by_hash(db, path_str, mtime, size, verify_hashes).unwrap_or(true);
+
+            if needs_index {
+                Check::Changed
+            } else {
+                Check::Unchanged
+            }
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Check::Missing,
+        Err(_) => Check::Error,
+    }
+}
+
+/// Outcome of a send failing because the indexer dropped its queue.
+///
+/// During shutdown that's expected; otherwise the indexer stopped early and
+/// reconciliation fails loudly rather than passing for an interruption.
+fn queue_closed(queue: &str, shutdown: &CancellationToken) -> Result<ReconcileState> {
+    if shutdown.is_cancelled() {
+        return Ok(ReconcileState::Interrupted);
+    }
+    Err(crate::Error::internal(format!(
+        "{queue} queue closed during reconciliation; the indexer has stopped"
+    )))
+}
+
+/// Wait until everything sent to a queue has been received.
+///
+/// Returns `false` if shutdown fired first. A closed queue counts as drained;
+/// the next send notices it.
+async fn wait_for_drain(is_drained: impl Fn() -> bool, shutdown: &CancellationToken) -> bool {
+    while !is_drained() {
+        tokio::select! {
+            () = shutdown.cancelled() => return false,
+            () = tokio::time::sleep(DRAIN_POLL_INTERVAL) => {}
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{migrate, upsert_file_state, FileState};
+    use crate::watcher::{index_queue, OverflowPolicy};
+    use tempfile::TempDir;
+
+    fn setup_test_db() -> Database {
+        let db = Database::open_in_memory().unwrap();
+        db.with_conn(|conn| migrate(conn)).unwrap();
+        db
+    }
+
+    /// Track `path` with its current metadata, or stale metadata if `stale`.
+    fn track(db: &Database, path: &std::path::Path, stale: bool) {
+        let (mtime, size) = match std::fs::metadata(path) {
+            Ok(metadata) => {
+                let mtime = metadata
+                    .modified()
+                    .unwrap()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs();
+                let size = i64::try_from(metadata.len()).unwrap();
+                (i64::try_from(mtime).unwrap(), size)
+            }
+            Err(_) => (0, 0),
+        };
+        let size = if stale { size + 1 } else { size };
+        db.with_conn(|conn| {
+            upsert_file_state(
+                conn,
+                &FileState::new(path.to_string_lossy(), mtime, size, "hash"),
+            )
+        })
+        .unwrap();
+    }
+
+    /// Channels whose receivers are drained in the background.
+    fn channels() -> (
+        IndexSender,
+        mpsc::Sender<PathBuf>,
+        tokio::task::JoinHandle<(Vec<PathBuf>, Vec<PathBuf>)>,
+    ) {
+        let (index_tx, index_rx) = index_queue(2, OverflowPolicy::Block);
+        let (delete_tx, mut delete_rx) = mpsc::channel::<PathBuf>(2);
+        let handle = tokio::spawn(async move {
+            let (mut indexed, mut deleted) = (Vec::new(), Vec::new());
+            loop {
+                tokio::select! {
+                    Some(request) = index_rx.recv() => indexed.push(request.path),
+                    Some(path) = delete_rx.recv() => deleted.push(path),
+                    else => break,
+                }
+            }
+            (indexed, deleted)
+        });
+        (index_tx, delete_tx, handle)
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_requeues_changed_and_deletes_missing() {
+        let db = setup_test_db();
+        let tmp = TempDir::new().unwrap();
+        let unchanged = tmp.path().join("a.rs");
+        let changed = tmp.path().join("b.rs");
+        let missing = tmp.path().join("c.rs");
+        std::fs::write(&unchanged, "fn a() {}").unwrap();
+        std::fs::write(&changed, "fn b() {}").unwrap();
+        track(&db, &unchanged, false);
+        track(&db, &changed, true);
+        track(&db, &missing, false);
+
+        let (index_tx, delete_tx, receiver) = channels();
+        let status = ReconcileStatus::new();
+        let options = ReconcileOptions {
+            verify_hashes: false,
+            batch_size: 2,
+            ..ReconcileOptions::default()
+        };
+        reconcile_from_db(
+            &db,
+            &index_tx,
+            &delete_tx,
+            options,
+            &status,
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+        drop((index_tx, delete_tx));
+        let (indexed, deleted) = receiver.await.unwrap();
+
+        assert_eq!(indexed, vec![changed]);
+        assert_eq!(deleted, vec![missing]);
+        let snapshot = status.snapshot();
+        assert_eq!(snapshot.state, ReconcileState::Complete);
+        assert!(!snapshot.resumed);
+        assert_eq!(snapshot.total, 3);
+        assert_eq!(snapshot.files_checked, 3);
+        assert_eq!(snapshot.unchanged, 1);
+        assert_eq!(snapshot.requeued, 1);
+        assert_eq!(snapshot.deleted, 1);
+
+        // A finished run leaves nothing to resume
+        let cursor = db
+            .with_conn(|conn| get_meta(conn, RECONCILE_CURSOR_KEY))
+            .unwrap();
+        assert_eq!(cursor, None);
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_resumes_after_saved_progress() {
+        let db = setup_test_db();
+        let tmp = TempDir::new().unwrap();
+        let paths: Vec<PathBuf> = ["a.rs", "b.rs", "c.rs", "d.rs"]
+            .iter()
+            .map(|name| tmp.path().join(name))
+            .collect();
+        for path in &paths {
+            std::fs::write(path, "fn f() {}").unwrap();
+            track(&db, path, true);
+        }
+
+        // A previous run got through the first two files
+        db.with_conn(|conn| set_meta(conn, RECONCILE_CURSOR_KEY, &paths[1].to_string_lossy()))
+            .unwrap();
+
+        let (index_tx, delete_tx, receiver) = channels();
+        let status = ReconcileStatus::new();
+        reconcile_from_db(
+            &db,
+            &index_tx,
+            &delete_tx,
+            ReconcileOptions::default(),
+            &status,
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+        drop((index_tx, delete_tx));
+        let (indexed, _) = receiver.await.unwrap();
+
+        assert_eq!(indexed, paths[2..].to_vec());
+        let snapshot = status.snapshot();
+        assert!(snapshot.resumed);
+        assert_eq!(snapshot.total, 2);
+        assert_eq!(snapshot.requeued, 2);
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_shutdown_before_drain_saves_no_progress() {
+        let db = setup_test_db();
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("a.rs");
+        std::fs::write(&path, "fn a() {}").unwrap();
+        track(&db, &path, true);
+
+        // Nobody reads the queue, so the first batch never drains
+        let (index_tx, _index_rx) = index_queue(10, OverflowPolicy::Block);
+        let (delete_tx, _delete_rx) = mpsc::channel(10);
+        let status = ReconcileStatus::new();
+        let shutdown = CancellationToken::new();
+        let cancel = shutdown.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            cancel.cancel();
+        });
+
+        reconcile_from_db(
+            &db,
+            &index_tx,
+            &delete_tx,
+            ReconcileOptions::default(),
+            &status,
+            &shutdown,
+        )
+        .await
+        .unwrap();
+
+        let snapshot = status.snapshot();
+        assert_eq!(snapshot.state, ReconcileState::Interrupted);
+        assert_eq!(snapshot.requeued, 1);
+        assert_eq!(snapshot.files_checked, 0);
+        let cursor = db
+            .with_conn(|conn| get_meta(conn, RECONCILE_CURSOR_KEY))
+            .unwrap();
+        assert_eq!(cursor, None);
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_fails_when_indexer_stops() {
+        let db = setup_test_db();
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("a.rs");
+        std::fs::write(&path, "fn a() {}").unwrap();
+        track(&db, &path, true);
+
+        // The indexer has gone away without a shutdown
+        let (index_tx, index_rx) = index_queue(10, OverflowPolicy::Block);
+        drop(index_rx);
+        let (delete_tx, _delete_rx) = mpsc::channel(10);
+        let status = ReconcileStatus::new();
+
+        let err = reconcile_from_db(
+            &db,
+            &index_tx,
+            &delete_tx,
+            ReconcileOptions::default(),
+            &status,
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap_err();
+
+        assert!(err.to_string().contains("index queue closed"));
+        assert_eq!(status.snapshot().state, ReconcileState::Failed);
+    }
+}
@@ -1,23 +1,105 @@
 //! Directory scanner for initial indexing.
 //!
 //! Walks directories respecting .gitignore and sends files for indexing.
+//! `build_walker` is the single source of truth for which files get walked;
+//! the initial scan, the watcher and the indexing tools all go through it.
 
 use std::path::Path;
 use std::sync::atomic::{AtomicU64, Ordering};
 
 use ignore::WalkBuilder;
-use tokio::sync::mpsc;
+use serde::Serialize;
 
 use super::filter::FileFilter;
 use super::handler::IndexRequest;
+use super::queue::IndexSender;
+use super::watcher::WatcherConfig;
 use crate::Result;
 
+/// Per-directory ignore file for excluding paths from Nellie only.
+///
+/// Uses gitignore syntax.
+pub const NELLIE_IGNORE_FILE: &str = ".nellieignore";
+
+/// Directories always skipped when walking, regardless of ignore files.
+///
+/// Entries starting with `*` match as a suffix (e.g. `*.egg-info`).
+pub const SKIP_DIRS: &[&str] = &[
+    ".git",
+    "node_modules",
+    "__pycache__",
+    ".venv",
+    "venv",
+    "target",
+    "build",
+    "dist",
+    ".next",
+    ".nuxt",
+    "vendor",
+    ".cargo",
+    ".rustup",
+    "Pods",
+    ".gradle",
+    ".idea",
+    ".vs",
+    ".vscode",
+    "coverage",
+    ".pytest_cache",
+    ".mypy_cache",
+    ".tox",
+    "eggs",
+    "*.egg-info",
+    ".sass-cache",
+    "bower_components",
+    "site-packages",
+    "obj",
+    ".cache",
+    ".parcel-cache",
+];
+
+/// Check if a directory name is in a skip list.
+#[must_use]
+pub fn is_skipped_dir_name(skip_dirs: &[String], name: &str) -> bool {
+    skip_dirs.iter().any(|skip| match skip.strip_prefix('*') {
+        Some(suffix) => name.ends_with(suffix),
+        None => name == skip,
+    })
+}
+
+/// Build a directory walker honoring all of Nellie's ignore rules.
+///
+/// Respects `.gitignore` (even outside a git repository), global git
+/// excludes, `.ignore` and `.nellieignore` files, skips hidden entries, and
+/// prunes the directories in `config.skip_dirs`.
+#[must_use]
+pub fn build_walker(root: &Path, config: &WatcherConfig) -> ignore::Walk {
+    let skip_dirs = config.skip_dirs.clone();
+
+    WalkBuilder::new(root)
+        .hidden(true) // Respect hidden files/dirs
+        .git_ignore(true) // Respect .gitignore
+        .git_global(true) // Respect global gitignore
+        .git_exclude(true) // Respect .git/info/exclude
+        .ignore(true) // Respect .ignore files
+        .parents(true) // Check parent directories for ignore files
+        .require_git(false) // Watch dirs aren't always repositories
+        .add_custom_ignore_filename(NELLIE_IGNORE_FILE)
+        .filter_entry(move |entry| {
+            entry.depth() == 0
+                || !entry.file_type().is_some_and(|ft| ft.is_dir())
+                || !is_skipped_dir_name(&skip_dirs, &entry.file_name().to_string_lossy())
+        })
+        .build()
+}
+
 /// Scan statistics.
 #[derive(Debug, Default)]
 pub struct ScanStats {
     pub files_found: AtomicU64,
     pub files_queued: AtomicU64,
     pub files_skipped: AtomicU64,
+    /// Lockfiles and minified assets left out of the index.
+    pub files_skipped_noise: AtomicU64,
     pub errors: AtomicU64,
 }
 
@@ -35,42 +117,43 @@ impl ScanStats {
             files_found: self.files_found.load(Ordering::Relaxed),
             files_queued: self.files_queued.load(Ordering::Relaxed),
             files_skipped: self.files_skipped.load(Ordering::Relaxed),
+            files_skipped_noise: self.files_skipped_noise.load(Ordering::Relaxed),
             errors: self.errors.load(Ordering::Relaxed),
         }
     }
 }
 
 /// Snapshot of scan stats.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, utoipa::ToSchema)]
 pub struct ScanStatsSnapshot {
     pub files_found: u64,
     pub files_queued: u64,
     pub files_skipped: u64,
+    pub files_skipped_noise: u64,
     pub errors: u64,
 }
 
 /// Scan a directory and queue files for indexing.
 ///
 /// Uses the `ignore` crate to respect .gitignore patterns.
-/// Filters to code files only using `FileFilter`.
+/// Filters to the code files in `config.file_types`.
 ///
 /// Returns statistics about the scan.
+///
+/// # Errors
+///
+/// Currently never fails: walk errors are logged and counted in the stats,
+/// and a closed index queue ends the scan early.
 pub fn scan_directory(
     path: &Path,
-    index_tx: &mpsc::Sender<IndexRequest>,
+    config: &WatcherConfig,
+    index_tx: &IndexSender,
 ) -> Result<ScanStatsSnapshot> {
     let stats = ScanStats::new();
 
     tracing::info!(path = %path.display(), "Starting directory scan");
 
-    let walker = WalkBuilder::new(path)
-        .hidden(true) // Respect hidden files/dirs
-        .git_ignore(true) // Respect .gitignore
-        .git_global(true) // Respect global gitignore
-        .git_exclude(true) // Respect .git/info/exclude
-        .ignore(true) // Respect .ignore files
-        .parents(true) // Check parent directories for ignore files
-        .build();
+    let walker = build_walker(path, config);
 
     for entry in walker {
         match entry {
@@ -85,26 +168,29 @@ pub fn scan_directory(
                 stats.files_found.fetch_add(1, Ordering::Relaxed);
 
                 // Check if it's a code file we should index
-                if !FileFilter::is_code_file(entry_path) {
+                if !config.file_types.is_code_file(entry_path) {
                     stats.files_skipped.fetch_add(1, Ordering::Relaxed);
                     continue;
                 }
 
-                // Check additional ignore patterns
-                if is_default_ignored(entry_path) {
-                    stats.files_skipped.fetch_add(1, Ordering::Relaxed);
+                if config.skip_noise_files && FileFilter::is_noise_file(entry_path) {
+                    stats.files_skipped_noise.fetch_add(1, Ordering::Relaxed);
                     continue;
                 }
 
                 // Detect language and queue for indexing
-                let language = FileFilter::detect_language(entry_path).map(String::from);
+                let language = config
+                    .file_types
+                    .detect_language(entry_path)
+                    .map(String::from);
                 let request = IndexRequest {
                     path: entry_path.to_path_buf(),
                     language,
+                    repo: None,
                 };
 
                 if index_tx.blocking_send(request).is_err() {
-                    tracing::warn!("Index channel closed during scan");
+                    tracing::warn!("Index queue closed during scan; indexer has stopped");
                     break;
                 }
 
@@ -123,6 +209,7 @@ pub fn scan_directory(
         found = snapshot.files_found,
         queued = snapshot.files_queued,
         skipped = snapshot.files_skipped,
+        skipped_noise = snapshot.files_skipped_noise,
         errors = snapshot.errors,
         "Directory scan complete"
     );
@@ -131,57 +218,58 @@ pub fn scan_directory(
 }
 
 /// Async version of directory scan.
+///
+/// # Errors
+///
+/// Returns an error if the blocking scan task panics or is cancelled.
 pub async fn scan_directory_async(
     path: &Path,
-    index_tx: &mpsc::Sender<IndexRequest>,
+    config: &WatcherConfig,
+    index_tx: &IndexSender,
 ) -> Result<ScanStatsSnapshot> {
     let path = path.to_path_buf();
+    let config = config.clone();
     let tx = index_tx.clone();
 
-    tokio::task::spawn_blocking(move || scan_directory(&path, &tx))
+    tokio::task::spawn_blocking(move || scan_directory(&path, &config, &tx))
         .await
         .map_err(|e| crate::Error::internal(format!("Scan task failed: {e}")))?
 }
 
 /// Check if a path should be ignored (beyond .gitignore).
-fn is_default_ignored(path: &Path) -> bool {
-    let path_str = path.to_string_lossy();
-
-    // Skip dotdirs (except .github)
-    for component in path.components() {
-        if let std::path::Component::Normal(name) = component {
-            let name_str = name.to_string_lossy();
-            if name_str.starts_with('.')
-                && name_str.len() > 1
-                && name_str != ".github"
-            {
-                return true;
-            }
-        }
-    }
-
-    // Common build/dependency directories
-    let ignored_dirs = [
-        "node_modules", "target", "build", "dist", "__pycache__",
-        "venv", ".venv", "vendor", "obj", "bin", "coverage",
-        "egg-info", ".egg-info", "site-packages",
-    ];
-
-    for component in path.components() {
-        if let std::path::Component::Normal(name) = component {
-            let name_str = name.to_string_lossy();
-            if ignored_dirs.iter().any(|&d| name_str == d || name_str.ends_with(d)) {
-                return true;
-            }
-        }
-    }
+///
+/// Used for paths that don't come from `build_walker`, such as watcher
+/// events. Pass the path relative to the watched root so that hidden
+/// directories above the root don't exclude everything.
+#[must_use]
+pub fn is_default_ignored(path: &Path) -> bool {
+    is_ignored_path(path, &WatcherConfig::default().skip_dirs)
+}
 
-    false
+/// Check if a path lies under a hidden directory or one in `skip_dirs`, or
+/// is itself hidden, mirroring what `build_walker` prunes.
+///
+/// As with [`is_default_ignored`], pass the path relative to the watched
+/// root.
+#[must_use]
+pub fn is_ignored_path(path: &Path, skip_dirs: &[String]) -> bool {
+    path.components().any(|component| {
+        let std::path::Component::Normal(name) = component else {
+            return false;
+        };
+        let name = name.to_string_lossy();
+
+        // Skip dotdirs (except .github)
+        (name.starts_with('.') && name.len() > 1 && name != ".github")
+            || is_skipped_dir_name(skip_dirs, &name)
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::watcher::filter::{FileTypes, DOC_LANGUAGE};
+    use crate::watcher::{index_queue, OverflowPolicy};
     use std::fs;
     use tempfile::TempDir;
 
@@ -210,9 +298,9 @@ mod tests {
         fs::create_dir(&node_modules).unwrap();
         fs::write(node_modules.join("pkg.js"), "module.exports = {};").unwrap();
 
-        let (tx, mut rx) = mpsc::channel(100);
+        let (tx, rx) = index_queue(100, OverflowPolicy::Block);
 
-        let stats = scan_directory(tmp.path(), &tx).unwrap();
+        let stats = scan_directory(tmp.path(), &WatcherConfig::default(), &tx).unwrap();
 
         // Should have found and queued the .rs files
         assert!(stats.files_queued >= 2);
@@ -229,4 +317,93 @@ mod tests {
         // node_modules should be ignored
         assert!(!paths.iter().any(|p| p.to_string_lossy().contains("node_modules")));
     }
+
+    #[test]
+    fn test_build_walker_respects_ignore_files() {
+        let tmp = TempDir::new().unwrap();
+        fs::write(tmp.path().join(".gitignore"), "generated/\n").unwrap();
+        fs::write(tmp.path().join(NELLIE_IGNORE_FILE), "secret.rs\n").unwrap();
+        fs::write(tmp.path().join("main.rs"), "fn main() {}").unwrap();
+        fs::write(tmp.path().join("secret.rs"), "fn secret() {}").unwrap();
+        fs::create_dir(tmp.path().join("generated")).unwrap();
+        fs::write(tmp.path().join("generated/out.rs"), "fn out() {}").unwrap();
+        fs::create_dir(tmp.path().join("node_modules")).unwrap();
+        fs::write(tmp.path().join("node_modules/pkg.js"), "x").unwrap();
+
+        let files: Vec<_> = build_walker(tmp.path(), &WatcherConfig::default())
+            .filter_map(std::result::Result::ok)
+            .filter(|e| e.path().is_file())
+            .map(|e| e.path().to_path_buf())
+            .collect();
+
+        assert!(files.iter().any(|p| p.ends_with("main.rs")));
+        assert!(!files.iter().any(|p| p.ends_with("secret.rs")));
+        assert!(!files.iter().any(|p| p.ends_with("out.rs")));
+        assert!(!files.iter().any(|p| p.ends_with("pkg.js")));
+    }
+
+    #[tokio::test]
+    async fn test_scan_directory_respects_gitignore() {
+        let tmp = TempDir::new().unwrap();
+        fs::write(tmp.path().join(".gitignore"), "ignored.rs\n").unwrap();
+        fs::write(tmp.path().join("kept.rs"), "fn kept() {}").unwrap();
+        fs::write(tmp.path().join("ignored.rs"), "fn ignored() {}").unwrap();
+
+        let (tx, rx) = index_queue(100, OverflowPolicy::Block);
+        scan_directory(tmp.path(), &WatcherConfig::default(), &tx).unwrap();
+        drop(tx);
+
+        let mut paths = vec![];
+        while let Some(req) = rx.recv().await {
+            paths.push(req.path);
+        }
+
+        assert!(paths.iter().any(|p| p.ends_with("kept.rs")));
+        assert!(!paths.iter().any(|p| p.ends_with("ignored.rs")));
+    }
+
+    #[tokio::test]
+    async fn test_scan_directory_skips_noise_files() {
+        let tmp = TempDir::new().unwrap();
+        fs::write(tmp.path().join("index.js"), "export {};").unwrap();
+        fs::write(tmp.path().join("package-lock.json"), "{}").unwrap();
+        fs::write(tmp.path().join("pnpm-lock.yaml"), "lockfileVersion: 6").unwrap();
+        fs::write(tmp.path().join("vendor.min.js"), "var a=1;").unwrap();
+
+        let (tx, rx) = index_queue(100, OverflowPolicy::Block);
+        let stats = scan_directory(tmp.path(), &WatcherConfig::default(), &tx).unwrap();
+        drop(tx);
+
+        let mut paths = vec![];
+        while let Some(req) = rx.recv().await {
+            paths.push(req.path);
+        }
+
+        assert_eq!(paths.len(), 1);
+        assert!(paths[0].ends_with("index.js"));
+        assert_eq!(stats.files_skipped_noise, 3);
+    }
+
+    #[tokio::test]
+    async fn test_scan_directory_uses_file_types() {
+        let tmp = TempDir::new().unwrap();
+        fs::write(tmp.path().join("main.rs"), "fn main() {}").unwrap();
+        fs::write(tmp.path().join("notes.rst"), "Notes\n=====").unwrap();
+
+        let config = WatcherConfig::default().with_file_types(FileTypes::default().with_docs(true));
+        let (tx, rx) = index_queue(100, OverflowPolicy::Block);
+        scan_directory(tmp.path(), &config, &tx).unwrap();
+        drop(tx);
+
+        let mut requests = vec![];
+        while let Some(req) = rx.recv().await {
+            requests.push(req);
+        }
+        requests.sort_by(|a, b| a.path.cmp(&b.path));
+
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].path.ends_with("main.rs"));
+        assert!(requests[1].path.ends_with("notes.rst"));
+        assert_eq!(requests[1].language.as_deref(), Some(DOC_LANGUAGE));
+    }
 }
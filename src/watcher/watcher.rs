@@ -12,34 +12,20 @@ use parking_lot::Mutex;
 use tokio::sync::mpsc;
 
 use super::events::{EventBatch, FileEvent};
+use super::filter::FileTypes;
+use super::scanner::{is_skipped_dir_name, SKIP_DIRS};
 use crate::error::WatcherError;
 use crate::Result;
 
+/// Default debounce window for file events, in milliseconds.
+pub const DEFAULT_DEBOUNCE_MS: u64 = 500;
+
 /// Debounce duration for file events.
-const DEBOUNCE_DURATION: Duration = Duration::from_millis(500);
+const DEBOUNCE_DURATION: Duration = Duration::from_millis(DEFAULT_DEBOUNCE_MS);
 
-/// Directories to skip when setting up watches.
-/// (Used by tests; handler's FileFilter handles event filtering)
-#[allow(dead_code)]
-const SKIP_DIRS: &[&str] = &[
-    "node_modules",
-    ".git",
-    "target",
-    "build",
-    "dist",
-    "__pycache__",
-    ".venv",
-    "venv",
-    ".idea",
-    ".vscode",
-    "vendor",
-    ".next",
-    ".nuxt",
-    "coverage",
-    ".cache",
-    ".parcel-cache",
-    "bower_components",
-];
+/// Maximum number of debounce windows to coalesce into one batch, so a
+/// continuous stream of changes can't delay indexing indefinitely.
+const MAX_COALESCE_WINDOWS: u32 = 10;
 
 /// File watcher configuration.
 #[derive(Debug, Clone)]
@@ -47,7 +33,39 @@ pub struct WatcherConfig {
     /// Directories to watch.
     pub watch_dirs: Vec<PathBuf>,
     /// Debounce duration.
+    ///
+    /// Events for the same path within this window collapse into one, and
+    /// batches arriving within a window of each other are merged.
     pub debounce: Duration,
+    /// Directory names pruned from walks (`*` prefix matches a suffix).
+    pub skip_dirs: Vec<String>,
+    /// Skip dependency lockfiles and minified assets when indexing.
+    pub skip_noise_files: bool,
+    /// Which extensions are indexed and their languages.
+    pub file_types: FileTypes,
+}
+
+impl WatcherConfig {
+    /// Set the debounce window in milliseconds.
+    #[must_use]
+    pub const fn with_debounce_ms(mut self, debounce_ms: u64) -> Self {
+        self.debounce = Duration::from_millis(debounce_ms);
+        self
+    }
+
+    /// Set whether lockfiles and minified assets are skipped.
+    #[must_use]
+    pub const fn with_skip_noise_files(mut self, skip: bool) -> Self {
+        self.skip_noise_files = skip;
+        self
+    }
+
+    /// Set which extensions are indexed.
+    #[must_use]
+    pub fn with_file_types(mut self, file_types: FileTypes) -> Self {
+        self.file_types = file_types;
+        self
+    }
 }
 
 impl Default for WatcherConfig {
@@ -55,6 +73,9 @@ impl Default for WatcherConfig {
         Self {
             watch_dirs: Vec::new(),
             debounce: DEBOUNCE_DURATION,
+            skip_dirs: SKIP_DIRS.iter().map(|&d| d.to_string()).collect(),
+            skip_noise_files: true,
+            file_types: FileTypes::default(),
         }
     }
 }
@@ -63,6 +84,7 @@ impl Default for WatcherConfig {
 pub struct FileWatcher {
     _debouncer: Debouncer<RecommendedWatcher>,
     event_rx: mpsc::Receiver<EventBatch>,
+    debounce: Duration,
     watched_dirs: Arc<Mutex<Vec<PathBuf>>>,
 }
 
@@ -86,9 +108,16 @@ impl FileWatcher {
                 match result {
                     Ok(events) => {
                         let mut batch = EventBatch::new();
+                        // The debouncer doesn't report renames; they surface as a
+                        // vanished path plus a new one, which the indexer pairs
+                        // up by content hash.
                         for event in events {
                             if matches!(event.kind, DebouncedEventKind::Any) {
-                                batch.add(FileEvent::Modified(event.path));
+                                if event.path.exists() {
+                                    batch.add(FileEvent::Modified(event.path));
+                                } else {
+                                    batch.add(FileEvent::Deleted(event.path));
+                                }
                             }
                         }
 
@@ -97,6 +126,7 @@ impl FileWatcher {
                             let dirs = watched_dirs_clone.lock();
                             batch.modified.retain(|p| is_under_watched(&dirs, p));
                             batch.deleted.retain(|p| is_under_watched(&dirs, p));
+                            batch.renamed.retain(|(_, to)| is_under_watched(&dirs, to));
 
                             if !batch.is_empty() {
                                 let _ = batch_tx.blocking_send(batch);
@@ -117,6 +147,7 @@ impl FileWatcher {
         let mut watcher = Self {
             _debouncer: debouncer,
             event_rx,
+            debounce: config.debounce,
             watched_dirs,
         };
 
@@ -190,9 +221,25 @@ impl FileWatcher {
 
     /// Receive the next batch of events.
     ///
+    /// Batches that arrive within one debounce window of each other are
+    /// merged (up to `MAX_COALESCE_WINDOWS`), so bursts like formatter runs or
+    /// git checkouts produce a single index request per file.
+    ///
     /// Returns `None` if the watcher has been dropped.
     pub async fn recv(&mut self) -> Option<EventBatch> {
-        self.event_rx.recv().await
+        let mut batch = self.event_rx.recv().await?;
+        if self.debounce.is_zero() {
+            return Some(batch);
+        }
+
+        let deadline = tokio::time::Instant::now() + self.debounce * MAX_COALESCE_WINDOWS;
+        loop {
+            let window = tokio::time::Instant::now() + self.debounce;
+            match tokio::time::timeout_at(window.min(deadline), self.event_rx.recv()).await {
+                Ok(Some(next)) => batch.merge(next),
+                Ok(None) | Err(_) => return Some(batch),
+            }
+        }
     }
 
     /// Get list of watched directories.
@@ -211,9 +258,10 @@ fn is_under_watched(watched: &[PathBuf], path: &Path) -> bool {
 /// (Used by tests; handler's FileFilter handles event filtering)
 #[allow(dead_code)]
 fn should_skip_dir(path: &Path) -> bool {
+    let skip_dirs = WatcherConfig::default().skip_dirs;
     path.file_name()
         .and_then(|n| n.to_str())
-        .is_some_and(|name| SKIP_DIRS.contains(&name))
+        .is_some_and(|name| is_skipped_dir_name(&skip_dirs, name))
 }
 
 #[cfg(test)]
@@ -226,6 +274,10 @@ mod tests {
         let config = WatcherConfig::default();
         assert!(config.watch_dirs.is_empty());
         assert_eq!(config.debounce, DEBOUNCE_DURATION);
+        assert_eq!(
+            config.with_debounce_ms(250).debounce,
+            Duration::from_millis(250)
+        );
     }
 
     #[test]
@@ -59,6 +59,7 @@ mod tests {
             let request = nellie::watcher::IndexRequest {
                 path: path.to_path_buf(),
                 language: Some("rust".to_string()),
+                repo: None,
             };
             indexer.index_file(&request).await.unwrap();
         }